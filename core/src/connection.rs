@@ -94,6 +94,17 @@ impl Endpoint {
     }
 }
 
+/// The protocols negotiated while upgrading a connection via
+/// [`transport::upgrade`](crate::transport::upgrade).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NegotiatedProtocols {
+    /// The name of the security protocol, e.g. `/noise`, if known.
+    pub security: Option<String>,
+    /// The name of the stream multiplexer protocol, e.g. `/yamux/1.0.0`, if
+    /// known.
+    pub muxer: Option<String>,
+}
+
 /// The endpoint roles associated with a pending peer-to-peer connection.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PendingPoint {
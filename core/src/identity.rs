@@ -31,10 +31,17 @@
 //! binary representation of the specific key type
 //! (e.g. [ed25519 binary format](https://datatracker.ietf.org/doc/html/rfc8032#section-5.1.5)).
 //! All key types have functions to enable conversion to/from their binary representations.
+//!
+//! Keypairs of all key types can moreover be converted to/from PKCS#8 and
+//! public keys to/from X.509 SubjectPublicKeyInfo structures, both DER- and
+//! PEM-encoded, for interoperability with standard tooling like OpenSSL.
 
+mod asn1;
 #[cfg(feature = "ecdsa")]
 pub mod ecdsa;
 pub mod ed25519;
+#[cfg(feature = "keystore")]
+pub mod keystore;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod rsa;
 #[cfg(feature = "secp256k1")]
@@ -48,19 +55,20 @@ use std::convert::{TryFrom, TryInto};
 
 /// Identity keypair of a node.
 ///
-/// # Example: Generating RSA keys with OpenSSL
+/// # Example: Generating keys with OpenSSL
 ///
 /// ```text
-/// openssl genrsa -out private.pem 2048
-/// openssl pkcs8 -in private.pem -inform PEM -topk8 -out private.pk8 -outform DER -nocrypt
-/// rm private.pem      # optional
+/// openssl genpkey -algorithm ed25519 -out private.pem
+/// openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:secp256k1 -out private.pem
+/// openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out private.pem
+/// openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out private.pem
 /// ```
 ///
 /// Loading the keys:
 ///
 /// ```text
-/// let mut bytes = std::fs::read("private.pk8").unwrap();
-/// let keypair = Keypair::rsa_from_pkcs8(&mut bytes);
+/// let pem = std::fs::read_to_string("private.pem").unwrap();
+/// let keypair = Keypair::from_pkcs8_pem(&pem);
 /// ```
 ///
 #[derive(Debug, Clone)]
@@ -115,6 +123,70 @@ impl Keypair {
             .map(|sk| Keypair::Secp256k1(secp256k1::Keypair::from(sk)))
     }
 
+    /// Encode the keypair into a DER-encoded PKCS#8 PrivateKeyInfo structure
+    /// as defined in [RFC5208].
+    ///
+    /// [RFC5208]: https://tools.ietf.org/html/rfc5208#section-5
+    pub fn to_pkcs8_der(&self) -> Vec<u8> {
+        use Keypair::*;
+        match self {
+            Ed25519(pair) => pair.to_pkcs8_der(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Rsa(pair) => pair.to_pkcs8_der(),
+            #[cfg(feature = "secp256k1")]
+            Secp256k1(pair) => pair.to_pkcs8_der(),
+            #[cfg(feature = "ecdsa")]
+            Ecdsa(pair) => pair.to_pkcs8_der(),
+        }
+    }
+
+    /// Decode a keypair of any key type from a DER-encoded PKCS#8
+    /// PrivateKeyInfo structure (i.e. unencrypted), zeroing the input on
+    /// success.
+    pub fn from_pkcs8_der(der: &mut [u8]) -> Result<Keypair, DecodingError> {
+        let algorithm = asn1::pkcs8_algorithm(der)?;
+
+        match algorithm.oid {
+            oid if oid == asn1::ED25519_OID => {
+                ed25519::Keypair::from_pkcs8_der(der).map(Keypair::Ed25519)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            oid if oid == asn1::RSA_ENCRYPTION_OID => {
+                rsa::Keypair::from_pkcs8_der(der).map(Keypair::Rsa)
+            }
+            #[cfg(feature = "secp256k1")]
+            oid if oid == asn1::EC_PUBLIC_KEY_OID
+                && algorithm.curve == Some(asn1::SECP256K1_OID) =>
+            {
+                secp256k1::Keypair::from_pkcs8_der(der).map(Keypair::Secp256k1)
+            }
+            #[cfg(feature = "ecdsa")]
+            oid if oid == asn1::EC_PUBLIC_KEY_OID
+                && algorithm.curve == Some(asn1::SECP256R1_OID) =>
+            {
+                ecdsa::Keypair::from_pkcs8_der(der).map(Keypair::Ecdsa)
+            }
+            oid => Err(DecodingError::new(format!(
+                "unsupported PKCS#8 key algorithm {}",
+                oid
+            ))),
+        }
+    }
+
+    /// Encode the keypair into a PEM-encoded PKCS#8 PrivateKeyInfo structure,
+    /// i.e. a `PRIVATE KEY` PEM document.
+    pub fn to_pkcs8_pem(&self) -> String {
+        let der = zeroize::Zeroizing::new(self.to_pkcs8_der());
+        asn1::pkcs8_to_pem(&der)
+    }
+
+    /// Decode a keypair of any key type from a PEM-encoded PKCS#8
+    /// PrivateKeyInfo structure, i.e. a `PRIVATE KEY` PEM document.
+    pub fn from_pkcs8_pem(pem: &str) -> Result<Keypair, DecodingError> {
+        let mut der = asn1::pkcs8_from_pem(pem)?;
+        Keypair::from_pkcs8_der(&mut der)
+    }
+
     /// Sign a message using the private key of this keypair, producing
     /// a signature that can be verified using the corresponding public key.
     pub fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SigningError> {
@@ -272,6 +344,67 @@ impl PublicKey {
         pubkey.try_into()
     }
 
+    /// Encode the public key in DER as a X.509 SubjectPublicKeyInfo
+    /// structure, as defined in [RFC5280].
+    ///
+    /// [RFC5280]: https://tools.ietf.org/html/rfc5280#section-4.1
+    pub fn to_spki_der(&self) -> Vec<u8> {
+        use PublicKey::*;
+        match self {
+            Ed25519(pk) => pk.encode_x509(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Rsa(pk) => pk.encode_x509(),
+            #[cfg(feature = "secp256k1")]
+            Secp256k1(pk) => pk.encode_x509(),
+            #[cfg(feature = "ecdsa")]
+            Ecdsa(pk) => pk.encode_der(),
+        }
+    }
+
+    /// Decode a public key of any key type from a DER-encoded X.509
+    /// SubjectPublicKeyInfo structure.
+    pub fn from_spki_der(der: &[u8]) -> Result<PublicKey, DecodingError> {
+        let algorithm = asn1::spki_algorithm(der)?;
+
+        match algorithm.oid {
+            oid if oid == asn1::ED25519_OID => {
+                ed25519::PublicKey::decode_x509(der).map(PublicKey::Ed25519)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            oid if oid == asn1::RSA_ENCRYPTION_OID => {
+                rsa::PublicKey::decode_x509(der).map(PublicKey::Rsa)
+            }
+            #[cfg(feature = "secp256k1")]
+            oid if oid == asn1::EC_PUBLIC_KEY_OID
+                && algorithm.curve == Some(asn1::SECP256K1_OID) =>
+            {
+                secp256k1::PublicKey::decode_x509(der).map(PublicKey::Secp256k1)
+            }
+            #[cfg(feature = "ecdsa")]
+            oid if oid == asn1::EC_PUBLIC_KEY_OID
+                && algorithm.curve == Some(asn1::SECP256R1_OID) =>
+            {
+                ecdsa::PublicKey::decode_der(der).map(PublicKey::Ecdsa)
+            }
+            oid => Err(DecodingError::new(format!(
+                "unsupported X.509 public key algorithm {}",
+                oid
+            ))),
+        }
+    }
+
+    /// Encode the public key into a PEM-encoded X.509 SubjectPublicKeyInfo
+    /// structure, i.e. a `PUBLIC KEY` PEM document.
+    pub fn to_spki_pem(&self) -> String {
+        asn1::spki_to_pem(&self.to_spki_der())
+    }
+
+    /// Decode a public key of any key type from a PEM-encoded X.509
+    /// SubjectPublicKeyInfo structure, i.e. a `PUBLIC KEY` PEM document.
+    pub fn from_spki_pem(pem: &str) -> Result<PublicKey, DecodingError> {
+        PublicKey::from_spki_der(&asn1::spki_from_pem(pem)?)
+    }
+
     /// Convert the `PublicKey` into the corresponding `PeerId`.
     pub fn to_peer_id(&self) -> PeerId {
         self.into()
@@ -378,4 +511,79 @@ mod tests {
 
         assert_eq!(expected_peer_id, peer_id);
     }
+
+    /// PEM-encoded PKCS#8 keypairs and their X.509 public keys, generated with
+    /// `openssl genpkey` and `openssl pkey -pubout`.
+    fn openssl_fixtures() -> Vec<(&'static str, &'static str)> {
+        vec![
+            (
+                include_str!("identity/test/ed25519.pem"),
+                include_str!("identity/test/ed25519.pub.pem"),
+            ),
+            #[cfg(not(target_arch = "wasm32"))]
+            (
+                include_str!("identity/test/rsa-2048.pem"),
+                include_str!("identity/test/rsa-2048.pub.pem"),
+            ),
+            #[cfg(feature = "secp256k1")]
+            (
+                include_str!("identity/test/secp256k1.pem"),
+                include_str!("identity/test/secp256k1.pub.pem"),
+            ),
+            #[cfg(feature = "ecdsa")]
+            (
+                include_str!("identity/test/ecdsa-p256.pem"),
+                include_str!("identity/test/ecdsa-p256.pub.pem"),
+            ),
+        ]
+    }
+
+    #[test]
+    fn keypair_pkcs8_pem_openssl_roundtrip() {
+        for (private_pem, public_pem) in openssl_fixtures() {
+            let keypair = Keypair::from_pkcs8_pem(private_pem).unwrap();
+            let public_key = PublicKey::from_spki_pem(public_pem).unwrap();
+
+            assert_eq!(keypair.public(), public_key);
+            assert_eq!(keypair.to_pkcs8_pem(), private_pem);
+            assert_eq!(public_key.to_spki_pem(), public_pem);
+
+            let msg = b"hello world";
+            let sig = keypair.sign(msg).unwrap();
+            assert!(public_key.verify(msg, &sig));
+        }
+    }
+
+    #[test]
+    fn keypair_pkcs8_der_roundtrip() {
+        let keypairs = vec![
+            Keypair::generate_ed25519(),
+            #[cfg(feature = "secp256k1")]
+            Keypair::generate_secp256k1(),
+            #[cfg(feature = "ecdsa")]
+            Keypair::generate_ecdsa(),
+        ];
+
+        for expected_keypair in keypairs {
+            let mut der = expected_keypair.to_pkcs8_der();
+            let keypair = Keypair::from_pkcs8_der(&mut der).unwrap();
+
+            assert_eq!(expected_keypair.public(), keypair.public());
+            assert!(der.iter().all(|b| *b == 0));
+
+            let public_key =
+                PublicKey::from_spki_der(&expected_keypair.public().to_spki_der()).unwrap();
+            assert_eq!(expected_keypair.public(), public_key);
+        }
+    }
+
+    #[test]
+    fn pkcs8_pem_with_wrong_label_is_rejected() {
+        let (_, public_pem) = openssl_fixtures()[0];
+
+        assert!(Keypair::from_pkcs8_pem(public_pem).is_err());
+        assert!(
+            PublicKey::from_spki_pem(&public_pem.replace("PUBLIC KEY", "PRIVATE KEY")).is_err()
+        );
+    }
 }
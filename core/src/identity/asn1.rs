@@ -0,0 +1,250 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! PKCS#8 PrivateKeyInfo and X.509 SubjectPublicKeyInfo structures shared
+//! by the key types.
+//!
+//! Primer: http://luca.ntop.org/Teaching/Appunti/asn1.html
+//! Playground: https://lapo.it/asn1js/
+
+use super::error::DecodingError;
+use pkcs8::der::{self, Decodable, Encodable};
+use pkcs8::{
+    AlgorithmIdentifier, LineEnding, ObjectIdentifier, PrivateKeyInfo, SubjectPublicKeyInfo,
+};
+use zeroize::Zeroizing;
+
+/// The object identifier of Ed25519 keys as defined in [RFC8410].
+///
+/// [RFC8410]: https://tools.ietf.org/html/rfc8410#section-3
+pub(crate) const ED25519_OID: ObjectIdentifier = ObjectIdentifier::new("1.3.101.112");
+
+/// The object identifier of RSA keys as defined in [RFC3279].
+///
+/// [RFC3279]: https://tools.ietf.org/html/rfc3279#section-2.3.1
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const RSA_ENCRYPTION_OID: ObjectIdentifier =
+    ObjectIdentifier::new("1.2.840.113549.1.1.1");
+
+/// The object identifier of elliptic curve keys as defined in [RFC5480].
+///
+/// [RFC5480]: https://tools.ietf.org/html/rfc5480#section-2.1.1
+#[cfg(any(feature = "secp256k1", feature = "ecdsa"))]
+pub(crate) const EC_PUBLIC_KEY_OID: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.2.1");
+
+/// The object identifier of the secp256k1 curve as defined in [SEC2].
+///
+/// [SEC2]: https://www.secg.org/sec2-v2.pdf
+#[cfg(feature = "secp256k1")]
+pub(crate) const SECP256K1_OID: ObjectIdentifier = ObjectIdentifier::new("1.3.132.0.10");
+
+/// The object identifier of the secp256r1 curve, a.k.a. P-256, as defined in
+/// [RFC5480].
+///
+/// [RFC5480]: https://tools.ietf.org/html/rfc5480#section-2.1.1.1
+#[cfg(feature = "ecdsa")]
+pub(crate) const SECP256R1_OID: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.3.1.7");
+
+/// The PEM label of PKCS#8 PrivateKeyInfo documents.
+const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+
+/// The PEM label of X.509 SubjectPublicKeyInfo documents.
+const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+
+/// The algorithm and, for elliptic curve keys, the curve of a key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Algorithm {
+    pub(crate) oid: ObjectIdentifier,
+    pub(crate) curve: Option<ObjectIdentifier>,
+}
+
+impl Algorithm {
+    pub(crate) const fn new(oid: ObjectIdentifier, curve: Option<ObjectIdentifier>) -> Self {
+        Algorithm { oid, curve }
+    }
+
+    fn identifier(&self) -> AlgorithmIdentifier<'_> {
+        AlgorithmIdentifier {
+            oid: self.oid,
+            parameters: self.curve.as_ref().map(Into::into),
+        }
+    }
+
+    fn of(identifier: &AlgorithmIdentifier<'_>) -> Self {
+        Algorithm {
+            oid: identifier.oid,
+            curve: identifier.parameters_oid().ok(),
+        }
+    }
+}
+
+/// Returns the algorithm of a DER-encoded PKCS#8 PrivateKeyInfo structure.
+pub(crate) fn pkcs8_algorithm(der: &[u8]) -> Result<Algorithm, DecodingError> {
+    PrivateKeyInfo::from_der(der)
+        .map(|info| Algorithm::of(&info.algorithm))
+        .map_err(|e| DecodingError::new("PKCS#8 PrivateKeyInfo").source(e))
+}
+
+/// Encodes a secret key of the given algorithm in a PKCS#8 PrivateKeyInfo
+/// structure as defined in [RFC5208].
+///
+/// [RFC5208]: https://tools.ietf.org/html/rfc5208#section-5
+pub(crate) fn encode_pkcs8(algorithm: Algorithm, private_key: &[u8]) -> Vec<u8> {
+    let document = PrivateKeyInfo::new(algorithm.identifier(), private_key)
+        .to_der()
+        .expect("PKCS#8 PrivateKeyInfo encoding failed.");
+    AsRef::<[u8]>::as_ref(&document).to_vec()
+}
+
+/// Decodes the secret key of a PKCS#8 PrivateKeyInfo structure, checking
+/// that it is of the expected algorithm.
+///
+/// The returned key borrows from the input, which the caller is expected to
+/// zero once the key is decoded.
+pub(crate) fn decode_pkcs8(der: &[u8], algorithm: Algorithm) -> Result<&[u8], DecodingError> {
+    let info = PrivateKeyInfo::from_der(der)
+        .map_err(|e| DecodingError::new("PKCS#8 PrivateKeyInfo").source(e))?;
+    if Algorithm::of(&info.algorithm) != algorithm {
+        return Err(DecodingError::new(format!(
+            "PKCS#8 PrivateKeyInfo: unexpected algorithm {}",
+            info.algorithm.oid
+        )));
+    }
+    Ok(info.private_key)
+}
+
+/// Encodes an elliptic curve secret key in a PKCS#8 PrivateKeyInfo
+/// structure, wrapping an ECPrivateKey structure as defined in [RFC5915].
+///
+/// Like OpenSSL, the curve is only named by the outer structure, whereas the
+/// inner structure includes the uncompressed public key.
+///
+/// [RFC5915]: https://tools.ietf.org/html/rfc5915
+#[cfg(any(feature = "secp256k1", feature = "ecdsa"))]
+pub(crate) fn encode_ec_pkcs8(curve: ObjectIdentifier, secret: &[u8], public: &[u8]) -> Vec<u8> {
+    let ec_private_key = sec1::EcPrivateKey {
+        private_key: secret,
+        parameters: None,
+        public_key: Some(public),
+    };
+    let inner = Zeroizing::new(
+        ec_private_key
+            .to_vec()
+            .expect("SEC1 ECPrivateKey encoding failed."),
+    );
+    encode_pkcs8(Algorithm::new(EC_PUBLIC_KEY_OID, Some(curve)), &inner)
+}
+
+/// The secret key of an elliptic curve keypair and its encoded public key,
+/// if included.
+#[cfg(any(feature = "secp256k1", feature = "ecdsa"))]
+pub(crate) type EcKeys = (Zeroizing<Vec<u8>>, Option<Vec<u8>>);
+
+/// Decodes the keys of an elliptic curve keypair on the given curve from a
+/// PKCS#8 PrivateKeyInfo structure, see [`encode_ec_pkcs8`].
+#[cfg(any(feature = "secp256k1", feature = "ecdsa"))]
+pub(crate) fn decode_ec_pkcs8(
+    der: &[u8],
+    curve: ObjectIdentifier,
+) -> Result<EcKeys, DecodingError> {
+    let inner = decode_pkcs8(der, Algorithm::new(EC_PUBLIC_KEY_OID, Some(curve)))?;
+    let ec_private_key = sec1::EcPrivateKey::from_der(inner)
+        .map_err(|e| DecodingError::new("SEC1 ECPrivateKey").source(e))?;
+    match ec_private_key.parameters {
+        Some(sec1::EcParameters::NamedCurve(oid)) if oid != curve => Err(DecodingError::new(
+            format!("SEC1 ECPrivateKey: unexpected curve {}", oid),
+        )),
+        _ => Ok((
+            Zeroizing::new(ec_private_key.private_key.to_vec()),
+            ec_private_key.public_key.map(<[u8]>::to_vec),
+        )),
+    }
+}
+
+/// Returns the algorithm of a DER-encoded X.509 SubjectPublicKeyInfo
+/// structure.
+pub(crate) fn spki_algorithm(der: &[u8]) -> Result<Algorithm, DecodingError> {
+    SubjectPublicKeyInfo::from_der(der)
+        .map(|info| Algorithm::of(&info.algorithm))
+        .map_err(|e| DecodingError::new("X.509 SubjectPublicKeyInfo").source(e))
+}
+
+/// Encodes a public key of the given algorithm in an X.509
+/// SubjectPublicKeyInfo structure as defined in [RFC5280].
+///
+/// [RFC5280]: https://tools.ietf.org/html/rfc5280#section-4.1
+pub(crate) fn encode_spki(algorithm: Algorithm, public_key: &[u8]) -> Vec<u8> {
+    SubjectPublicKeyInfo {
+        algorithm: algorithm.identifier(),
+        subject_public_key: public_key,
+    }
+    .to_vec()
+    .expect("X.509 SubjectPublicKeyInfo encoding failed.")
+}
+
+/// Decodes the public key of an X.509 SubjectPublicKeyInfo structure,
+/// checking that it is of the expected algorithm.
+pub(crate) fn decode_spki(der: &[u8], algorithm: Algorithm) -> Result<&[u8], DecodingError> {
+    let info = SubjectPublicKeyInfo::from_der(der)
+        .map_err(|e| DecodingError::new("X.509 SubjectPublicKeyInfo").source(e))?;
+    if Algorithm::of(&info.algorithm) != algorithm {
+        return Err(DecodingError::new(format!(
+            "X.509 SubjectPublicKeyInfo: unexpected algorithm {}",
+            info.algorithm.oid
+        )));
+    }
+    Ok(info.subject_public_key)
+}
+
+/// Wraps a DER-encoded PKCS#8 PrivateKeyInfo structure in a PEM document
+/// with the `PRIVATE KEY` label.
+pub(crate) fn pkcs8_to_pem(der: &[u8]) -> String {
+    der::pem::encode_string(PRIVATE_KEY_LABEL, LineEnding::LF, der).expect("PEM encoding failed.")
+}
+
+/// Unwraps a DER-encoded PKCS#8 PrivateKeyInfo structure from a PEM
+/// document with the `PRIVATE KEY` label.
+pub(crate) fn pkcs8_from_pem(pem: &str) -> Result<Zeroizing<Vec<u8>>, DecodingError> {
+    decode_pem(pem, PRIVATE_KEY_LABEL).map(Zeroizing::new)
+}
+
+/// Wraps a DER-encoded X.509 SubjectPublicKeyInfo structure in a PEM
+/// document with the `PUBLIC KEY` label.
+pub(crate) fn spki_to_pem(der: &[u8]) -> String {
+    der::pem::encode_string(PUBLIC_KEY_LABEL, LineEnding::LF, der).expect("PEM encoding failed.")
+}
+
+/// Unwraps a DER-encoded X.509 SubjectPublicKeyInfo structure from a PEM
+/// document with the `PUBLIC KEY` label.
+pub(crate) fn spki_from_pem(pem: &str) -> Result<Vec<u8>, DecodingError> {
+    decode_pem(pem, PUBLIC_KEY_LABEL)
+}
+
+fn decode_pem(pem: &str, expected_label: &str) -> Result<Vec<u8>, DecodingError> {
+    let (label, der) = der::pem::decode_vec(pem.trim().as_bytes())
+        .map_err(|e| DecodingError::new("PEM").source(der::Error::from(e)))?;
+    if label != expected_label {
+        return Err(DecodingError::new(format!(
+            "PEM: expected label {}, got {}",
+            expected_label, label
+        )));
+    }
+    Ok(der)
+}
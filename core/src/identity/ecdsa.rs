@@ -20,6 +20,7 @@
 
 //! ECDSA keys with secp256r1 curve support.
 
+use super::asn1;
 use super::error::DecodingError;
 use core::fmt;
 use p256::{
@@ -29,6 +30,7 @@ use p256::{
     },
     EncodedPoint,
 };
+use zeroize::{Zeroize, Zeroizing};
 
 /// An ECDSA keypair.
 #[derive(Clone)]
@@ -48,6 +50,35 @@ impl Keypair {
         self.secret.sign(msg)
     }
 
+    /// Encode the keypair into a DER-encoded PKCS#8 PrivateKeyInfo structure
+    /// wrapping an ECPrivateKey structure as defined in [RFC5915], e.g. as
+    /// produced by `openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -outform DER`.
+    ///
+    /// [RFC5915]: https://tools.ietf.org/html/rfc5915#section-3
+    pub fn to_pkcs8_der(&self) -> Vec<u8> {
+        let secret = Zeroizing::new(self.secret.to_bytes());
+        asn1::encode_ec_pkcs8(asn1::SECP256R1_OID, &secret, &self.public.to_bytes())
+    }
+
+    /// Decode a keypair from a DER-encoded PKCS#8 PrivateKeyInfo structure
+    /// as produced by [`Keypair::to_pkcs8_der`], zeroing the input on success.
+    ///
+    /// Fails if the structure includes a public key that does not belong to
+    /// the secret key.
+    pub fn from_pkcs8_der(der: &mut [u8]) -> Result<Keypair, DecodingError> {
+        let (secret, public) = asn1::decode_ec_pkcs8(der, asn1::SECP256R1_OID)?;
+        let keypair = Keypair::from(SecretKey::from_bytes(&secret)?);
+        if let Some(public) = public {
+            if PublicKey::from_bytes(&public)? != keypair.public {
+                return Err(DecodingError::new(
+                    "ECDSA PKCS#8: public key does not match secret key",
+                ));
+            }
+        }
+        der.zeroize();
+        Ok(keypair)
+    }
+
     /// Get the public key of this keypair.
     pub fn public(&self) -> &PublicKey {
         &self.public
@@ -242,4 +273,14 @@ mod tests {
         let invalid_msg = "h3ll0 w0rld".as_bytes();
         assert!(!pk.verify(invalid_msg, &sig));
     }
+
+    #[test]
+    fn pkcs8_with_mismatched_public_key_is_rejected() {
+        let pair = Keypair::generate();
+        let other = Keypair::generate();
+        let secret = Zeroizing::new(pair.secret.to_bytes());
+        let mut der = asn1::encode_ec_pkcs8(asn1::SECP256R1_OID, &secret, &other.public.to_bytes());
+
+        assert!(Keypair::from_pkcs8_der(&mut der).is_err());
+    }
 }
@@ -20,12 +20,16 @@
 
 //! Ed25519 keys.
 
+use super::asn1::{self, Algorithm};
 use super::error::DecodingError;
 use core::fmt;
 use ed25519_dalek::{self as ed25519, Signer as _, Verifier as _};
 use rand::RngCore;
 use std::convert::TryFrom;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
+
+/// The algorithm of Ed25519 keys in PKCS#8 and X.509 structures.
+const ALGORITHM: Algorithm = Algorithm::new(asn1::ED25519_OID, None);
 
 /// An Ed25519 keypair.
 pub struct Keypair(ed25519::Keypair);
@@ -56,6 +60,40 @@ impl Keypair {
             .map_err(|e| DecodingError::new("Ed25519 keypair").source(e))
     }
 
+    /// Encode the keypair into a DER-encoded PKCS#8 PrivateKeyInfo structure
+    /// as defined in [RFC8410], e.g. as produced by
+    /// `openssl genpkey -algorithm ed25519 -outform DER`.
+    ///
+    /// [RFC8410]: https://tools.ietf.org/html/rfc8410#section-7
+    pub fn to_pkcs8_der(&self) -> Vec<u8> {
+        use pkcs8::der::{asn1::OctetString, Encodable};
+
+        let secret = Zeroizing::new(self.0.secret.to_bytes());
+        // The secret key is wrapped in a CurvePrivateKey OCTET STRING.
+        let private_key = Zeroizing::new(
+            OctetString::new(&*secret)
+                .and_then(|s| s.to_vec())
+                .expect("Ed25519 CurvePrivateKey encoding failed."),
+        );
+        asn1::encode_pkcs8(ALGORITHM, &private_key)
+    }
+
+    /// Decode a keypair from a DER-encoded PKCS#8 PrivateKeyInfo structure
+    /// as produced by [`Keypair::to_pkcs8_der`], zeroing the input on success.
+    pub fn from_pkcs8_der(der: &mut [u8]) -> Result<Keypair, DecodingError> {
+        use pkcs8::der::{asn1::OctetString, Decodable};
+
+        let mut secret = {
+            let private_key = asn1::decode_pkcs8(der, ALGORITHM)?;
+            let secret = OctetString::from_der(private_key)
+                .map_err(|e| DecodingError::new("Ed25519 CurvePrivateKey").source(e))?;
+            Zeroizing::new(secret.as_bytes().to_vec())
+        };
+        let secret = SecretKey::from_bytes(&mut *secret)?;
+        der.zeroize();
+        Ok(Keypair::from(secret))
+    }
+
     /// Sign a message using the private key of this keypair.
     pub fn sign(&self, msg: &[u8]) -> Vec<u8> {
         self.0.sign(msg).to_bytes().to_vec()
@@ -146,6 +184,20 @@ impl PublicKey {
             .map_err(|e| DecodingError::new("Ed25519 public key").source(e))
             .map(PublicKey)
     }
+
+    /// Encode the public key in DER as a X.509 SubjectPublicKeyInfo structure,
+    /// as defined in [RFC8410].
+    ///
+    /// [RFC8410]: https://tools.ietf.org/html/rfc8410#section-4
+    pub fn encode_x509(&self) -> Vec<u8> {
+        asn1::encode_spki(ALGORITHM, &self.encode())
+    }
+
+    /// Decode a public key from a DER-encoded X.509 SubjectPublicKeyInfo
+    /// structure. See also `encode_x509`.
+    pub fn decode_x509(pk: &[u8]) -> Result<PublicKey, DecodingError> {
+        asn1::decode_spki(pk, ALGORITHM).and_then(PublicKey::decode)
+    }
 }
 
 /// An Ed25519 secret key.
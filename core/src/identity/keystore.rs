@@ -0,0 +1,543 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Encrypted on-disk storage of identity keypairs.
+//!
+//! A [`Keystore`] saves an identity [`Keypair`] to a file, encrypted with a
+//! key derived from a passphrase via Argon2id, and loads it back. The keypair
+//! is stored in its PKCS#8 encoding, encrypted and authenticated with
+//! ChaCha20-Poly1305, thus all key types are supported.
+//!
+//! Files are written atomically and, on Unix, are only readable and writable
+//! by their owner. Loading a file that is a symbolic link, is owned by another
+//! user or is accessible by other users fails.
+//!
+//! ```no_run
+//! use libp2p_core::identity::keystore::{KeyType, Keystore};
+//!
+//! let keystore = Keystore::new("correct horse battery staple");
+//! let keypair = keystore.load_or_generate("node.key", KeyType::Ed25519).unwrap();
+//! ```
+
+use super::{error::DecodingError, Keypair};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// The magic bytes at the start of every keystore file.
+const MAGIC: &[u8; 8] = b"libp2pks";
+
+/// The version of the keystore file format.
+const VERSION: u8 = 1;
+
+/// The maximum Argon2 memory cost in KiB, i.e. 1 GiB.
+const MAX_MEMORY_COST: u32 = 1024 * 1024;
+
+/// The maximum number of Argon2 iterations.
+const MAX_TIME_COST: u32 = 16;
+
+/// The maximum Argon2 degree of parallelism.
+const MAX_PARALLELISM: u32 = 16;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// The length of the header of a keystore file, i.e. the magic bytes, the
+/// version, the Argon2 parameters, the salt and the nonce.
+///
+/// The header is authenticated as associated data of the ciphertext.
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN + NONCE_LEN;
+
+/// The type of keypair to generate with [`Keystore::load_or_generate`].
+///
+/// RSA keypairs cannot be generated, but can be saved and loaded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyType {
+    /// An Ed25519 keypair.
+    Ed25519,
+    /// A Secp256k1 keypair.
+    #[cfg(feature = "secp256k1")]
+    Secp256k1,
+    /// An ECDSA keypair.
+    #[cfg(feature = "ecdsa")]
+    Ecdsa,
+}
+
+impl KeyType {
+    fn generate(self) -> Keypair {
+        match self {
+            KeyType::Ed25519 => Keypair::generate_ed25519(),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => Keypair::generate_secp256k1(),
+            #[cfg(feature = "ecdsa")]
+            KeyType::Ecdsa => Keypair::generate_ecdsa(),
+        }
+    }
+}
+
+/// Saves and loads identity keypairs to and from files encrypted with a
+/// passphrase.
+#[derive(Clone)]
+pub struct Keystore {
+    passphrase: Zeroizing<Vec<u8>>,
+    /// Argon2 memory cost in KiB.
+    memory_cost: u32,
+    /// Argon2 number of iterations.
+    time_cost: u32,
+    /// Argon2 degree of parallelism.
+    parallelism: u32,
+}
+
+impl Keystore {
+    /// Creates a keystore encrypting keypairs with the given passphrase.
+    ///
+    /// Keys are derived with Argon2id using 19 MiB of memory, 2 iterations
+    /// and a parallelism of 1 by default.
+    pub fn new(passphrase: impl AsRef<[u8]>) -> Self {
+        Keystore {
+            passphrase: Zeroizing::new(passphrase.as_ref().to_vec()),
+            memory_cost: 19 * 1024,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+
+    /// Configures the Argon2 parameters used to derive the keys of saved
+    /// files, i.e. the memory cost in KiB, the number of iterations and the
+    /// degree of parallelism.
+    ///
+    /// The parameters are stored in the file, thus loading a file always
+    /// uses the parameters it was saved with. To bound the cost of loading
+    /// untrusted files, the memory cost is limited to 1 GiB and both the
+    /// number of iterations and the parallelism to 16. Saving and loading
+    /// fail with [`KeystoreError::InvalidKdfParams`] beyond these limits.
+    pub fn with_kdf_params(mut self, memory_cost: u32, time_cost: u32, parallelism: u32) -> Self {
+        self.memory_cost = memory_cost;
+        self.time_cost = time_cost;
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// Encrypts the keypair and writes it to the given path, replacing any
+    /// existing file atomically.
+    pub fn save(&self, path: impl AsRef<Path>, keypair: &Keypair) -> Result<(), KeystoreError> {
+        let contents = self.encrypt(keypair)?;
+        write_atomically(path.as_ref(), &contents, true)?;
+        Ok(())
+    }
+
+    /// Encrypts the keypair into the contents of a keystore file.
+    fn encrypt(&self, keypair: &Keypair) -> Result<Vec<u8>, KeystoreError> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        header.extend_from_slice(&self.memory_cost.to_be_bytes());
+        header.extend_from_slice(&self.time_cost.to_be_bytes());
+        header.extend_from_slice(&self.parallelism.to_be_bytes());
+
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        header.extend_from_slice(&salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        header.extend_from_slice(&nonce);
+
+        let key = self.derive_key(self.memory_cost, self.time_cost, self.parallelism, &salt)?;
+        let plaintext = Zeroizing::new(keypair.to_pkcs8_der());
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&*key))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| KeystoreError::Encryption)?;
+
+        let mut contents = header;
+        contents.extend_from_slice(&ciphertext);
+        Ok(contents)
+    }
+
+    /// Reads the file at the given path and decrypts the keypair it
+    /// contains.
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<Keypair, KeystoreError> {
+        let mut file = open_checked(path.as_ref())?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        if contents.len() < HEADER_LEN || !contents.starts_with(MAGIC) {
+            return Err(KeystoreError::InvalidFormat);
+        }
+        let (header, ciphertext) = contents.split_at(HEADER_LEN);
+        let version = header[MAGIC.len()];
+        if version != VERSION {
+            return Err(KeystoreError::UnsupportedVersion(version));
+        }
+        let params = &header[MAGIC.len() + 1..];
+        let u32_at = |i: usize| u32::from_be_bytes(params[4 * i..4 * i + 4].try_into().unwrap());
+        let salt = &params[12..12 + SALT_LEN];
+        let nonce = &params[12 + SALT_LEN..];
+
+        let key = self.derive_key(u32_at(0), u32_at(1), u32_at(2), salt)?;
+        let mut plaintext = ChaCha20Poly1305::new(Key::from_slice(&*key))
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| KeystoreError::Decryption)?;
+
+        Ok(Keypair::from_pkcs8_der(&mut plaintext)?)
+    }
+
+    /// Loads the keypair at the given path or, if there is no such file,
+    /// generates a keypair of the given type and saves it at the path.
+    ///
+    /// The generated keypair is only saved if the file still does not exist,
+    /// thus concurrent calls for the same path return the same keypair.
+    pub fn load_or_generate(
+        &self,
+        path: impl AsRef<Path>,
+        key_type: KeyType,
+    ) -> Result<Keypair, KeystoreError> {
+        let path = path.as_ref();
+        match self.load(path) {
+            Err(KeystoreError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                let keypair = key_type.generate();
+                match write_atomically(path, &self.encrypt(&keypair)?, false) {
+                    Ok(()) => Ok(keypair),
+                    // Another process saved a keypair in the meantime.
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => self.load(path),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            result => result,
+        }
+    }
+
+    fn derive_key(
+        &self,
+        memory_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+        if memory_cost > MAX_MEMORY_COST
+            || time_cost > MAX_TIME_COST
+            || parallelism > MAX_PARALLELISM
+        {
+            return Err(KeystoreError::InvalidKdfParams);
+        }
+        let params = argon2::Params::new(memory_cost, time_cost, parallelism, Some(32))
+            .map_err(|_| KeystoreError::InvalidKdfParams)?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password_into(&self.passphrase, salt, &mut *key)
+            .map_err(|_| KeystoreError::InvalidKdfParams)?;
+        Ok(key)
+    }
+}
+
+/// Writes the contents to a temporary file next to the given path, only
+/// accessible by the current user, and moves it to the path.
+///
+/// If `replace` is false, the file is hard-linked to the path instead of
+/// renamed, failing with [`io::ErrorKind::AlreadyExists`] if the path exists.
+fn write_atomically(path: &Path, contents: &[u8], replace: bool) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path is not a file"))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{:016x}.tmp",
+        file_name.to_string_lossy(),
+        rand::random::<u64>()
+    ));
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let result = options.open(&tmp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if replace {
+            fs::rename(&tmp_path, path)
+        } else {
+            fs::hard_link(&tmp_path, path)
+        }
+    });
+    if result.is_err() || !replace {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result?;
+
+    // Persist the rename, if supported by the platform.
+    if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens the file at the given path for reading, without following symbolic
+/// links, and ensures that it is owned by the current user and not accessible
+/// by other users.
+#[cfg(unix)]
+fn open_checked(path: &Path) -> Result<File, KeystoreError> {
+    use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?;
+    // Check the opened file rather than the path, which may have changed.
+    let metadata = file.metadata()?;
+    // Safe as `geteuid` cannot fail.
+    let euid = unsafe { libc::geteuid() };
+    if metadata.uid() != euid {
+        return Err(KeystoreError::InsecureOwner {
+            uid: metadata.uid(),
+        });
+    }
+    let mode = metadata.mode();
+    if mode & 0o077 != 0 {
+        return Err(KeystoreError::InsecurePermissions { mode: mode & 0o777 });
+    }
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn open_checked(path: &Path) -> Result<File, KeystoreError> {
+    Ok(File::open(path)?)
+}
+
+/// An error of a [`Keystore`].
+#[derive(Debug, thiserror::Error)]
+pub enum KeystoreError {
+    /// Reading or writing the file failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// The file is owned by another user.
+    #[error("Keystore file is owned by another user (uid {uid})")]
+    InsecureOwner { uid: u32 },
+    /// The file is accessible by users other than its owner.
+    #[error("Keystore file is accessible by other users (mode {mode:o})")]
+    InsecurePermissions { mode: u32 },
+    /// The file is not a keystore file.
+    #[error("Not a keystore file")]
+    InvalidFormat,
+    /// The file has been written by an unsupported version.
+    #[error("Unsupported keystore version {0}")]
+    UnsupportedVersion(u8),
+    /// The Argon2 parameters are out of range.
+    #[error("Invalid key derivation parameters")]
+    InvalidKdfParams,
+    /// Encrypting the keypair failed.
+    #[error("Failed to encrypt keypair")]
+    Encryption,
+    /// The passphrase is wrong or the file has been tampered with.
+    #[error("Wrong passphrase or corrupted keystore file")]
+    Decryption,
+    /// The decrypted keypair could not be decoded.
+    #[error("Failed to decode keypair: {0}")]
+    Decoding(#[from] DecodingError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn keystore(passphrase: &str) -> Keystore {
+        // Cheap parameters, to keep the tests fast.
+        Keystore::new(passphrase).with_kdf_params(64, 1, 1)
+    }
+
+    fn tmp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("libp2p-keystore-{:016x}", rand::random::<u64>()))
+    }
+
+    #[test]
+    fn save_load_roundtrip() {
+        let path = tmp_path();
+        let keypairs = vec![
+            Keypair::generate_ed25519(),
+            #[cfg(feature = "secp256k1")]
+            Keypair::generate_secp256k1(),
+            #[cfg(feature = "ecdsa")]
+            Keypair::generate_ecdsa(),
+        ];
+
+        for keypair in keypairs {
+            keystore("passphrase").save(&path, &keypair).unwrap();
+            let loaded = keystore("passphrase").load(&path).unwrap();
+            assert_eq!(keypair.public(), loaded.public());
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase_fails() {
+        let path = tmp_path();
+        keystore("passphrase")
+            .save(&path, &Keypair::generate_ed25519())
+            .unwrap();
+
+        assert!(matches!(
+            keystore("wrong").load(&path),
+            Err(KeystoreError::Decryption)
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tampered_header_fails() {
+        let path = tmp_path();
+        keystore("passphrase")
+            .save(&path, &Keypair::generate_ed25519())
+            .unwrap();
+
+        // Flip a bit of the salt, which is authenticated as associated data.
+        let mut contents = fs::read(&path).unwrap();
+        contents[HEADER_LEN - NONCE_LEN - 1] ^= 1;
+        write_atomically(&path, &contents, true).unwrap();
+
+        assert!(matches!(
+            keystore("passphrase").load(&path),
+            Err(KeystoreError::Decryption)
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_or_generate_persists_keypair() {
+        let path = tmp_path();
+
+        let generated = keystore("passphrase")
+            .load_or_generate(&path, KeyType::Ed25519)
+            .unwrap();
+        let loaded = keystore("passphrase")
+            .load_or_generate(&path, KeyType::Ed25519)
+            .unwrap();
+        assert_eq!(generated.public(), loaded.public());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn insecure_permissions_are_rejected() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = tmp_path();
+        keystore("passphrase")
+            .save(&path, &Keypair::generate_ed25519())
+            .unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(matches!(
+            keystore("passphrase").load(&path),
+            Err(KeystoreError::InsecurePermissions { mode: 0o644 })
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_or_generate_keeps_existing_file() {
+        let path = tmp_path();
+        let saved = Keypair::generate_ed25519();
+        keystore("passphrase").save(&path, &saved).unwrap();
+
+        // Saving a generated keypair must not replace a file created
+        // concurrently.
+        let contents = keystore("passphrase")
+            .encrypt(&Keypair::generate_ed25519())
+            .unwrap();
+        let error = write_atomically(&path, &contents, false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+
+        let loaded = keystore("passphrase")
+            .load_or_generate(&path, KeyType::Ed25519)
+            .unwrap();
+        assert_eq!(saved.public(), loaded.public());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn excessive_kdf_params_are_rejected() {
+        let path = tmp_path();
+        keystore("passphrase")
+            .save(&path, &Keypair::generate_ed25519())
+            .unwrap();
+
+        // Raise the memory cost stored in the header beyond the limit.
+        let mut contents = fs::read(&path).unwrap();
+        let offset = MAGIC.len() + 1;
+        contents[offset..offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        write_atomically(&path, &contents, true).unwrap();
+
+        assert!(matches!(
+            keystore("passphrase").load(&path),
+            Err(KeystoreError::InvalidKdfParams)
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_not_followed() {
+        let path = tmp_path();
+        let link = tmp_path();
+        keystore("passphrase")
+            .save(&path, &Keypair::generate_ed25519())
+            .unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        assert!(matches!(
+            keystore("passphrase").load(&link),
+            Err(KeystoreError::Io(_))
+        ));
+
+        fs::remove_file(link).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+}
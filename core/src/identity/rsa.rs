@@ -27,16 +27,26 @@ use ring::rand::SystemRandom;
 use ring::signature::KeyPair;
 use ring::signature::{self, RsaKeyPair, RSA_PKCS1_2048_8192_SHA256, RSA_PKCS1_SHA256};
 use std::{fmt, sync::Arc};
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 /// An RSA keypair.
 #[derive(Clone)]
-pub struct Keypair(Arc<RsaKeyPair>);
+pub struct Keypair {
+    inner: Arc<RsaKeyPair>,
+    /// The PKCS#8 encoding the keypair was decoded from.
+    ///
+    /// `ring` neither encodes keypairs nor exposes their private components,
+    /// so the keypair cannot be re-encoded from `inner`. Keeping this second
+    /// copy of the private key in memory is the price of supporting
+    /// [`Keypair::to_pkcs8_der`]. It is shared by all clones of the keypair and
+    /// zeroized once the last of them is dropped.
+    pkcs8: Arc<Zeroizing<Vec<u8>>>,
+}
 
 impl std::fmt::Debug for Keypair {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("Keypair")
-            .field("public", self.0.public_key())
+            .field("public", self.inner.public_key())
             .finish()
     }
 }
@@ -49,20 +59,45 @@ impl Keypair {
     pub fn from_pkcs8(der: &mut [u8]) -> Result<Keypair, DecodingError> {
         let kp = RsaKeyPair::from_pkcs8(der)
             .map_err(|e| DecodingError::new("RSA PKCS#8 PrivateKeyInfo").source(e))?;
+        let pkcs8 = Zeroizing::new(der.to_vec());
         der.zeroize();
-        Ok(Keypair(Arc::new(kp)))
+        Ok(Keypair {
+            inner: Arc::new(kp),
+            pkcs8: Arc::new(pkcs8),
+        })
+    }
+
+    /// Decode an RSA keypair from a DER-encoded PKCS#8 PrivateKeyInfo
+    /// structure, zeroing the input on success.
+    ///
+    /// Equivalent to [`Keypair::from_pkcs8`], named like the functions of
+    /// the other key types.
+    pub fn from_pkcs8_der(der: &mut [u8]) -> Result<Keypair, DecodingError> {
+        Keypair::from_pkcs8(der)
+    }
+
+    /// Encode the keypair into a DER-encoded PKCS#8 PrivateKeyInfo structure,
+    /// i.e. the structure it has been decoded from by [`Keypair::from_pkcs8_der`].
+    ///
+    /// Since `ring` cannot encode RSA keypairs, this returns a copy of the
+    /// original encoding, which the keypair retains for its whole lifetime.
+    pub fn to_pkcs8_der(&self) -> Vec<u8> {
+        self.pkcs8.to_vec()
     }
 
     /// Get the public key from the keypair.
     pub fn public(&self) -> PublicKey {
-        PublicKey(self.0.public_key().as_ref().to_vec())
+        PublicKey(self.inner.public_key().as_ref().to_vec())
     }
 
     /// Sign a message with this keypair.
     pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SigningError> {
-        let mut signature = vec![0; self.0.public_modulus_len()];
+        let mut signature = vec![0; self.inner.public_modulus_len()];
         let rng = SystemRandom::new();
-        match self.0.sign(&RSA_PKCS1_SHA256, &rng, data, &mut signature) {
+        match self
+            .inner
+            .sign(&RSA_PKCS1_SHA256, &rng, data, &mut signature)
+        {
             Ok(()) => Ok(signature),
             Err(e) => Err(SigningError::new("RSA").source(e)),
         }
@@ -263,7 +298,7 @@ impl DerDecodable<'_> for Asn1SubjectPublicKey {
             )));
         }
 
-        let pk_der: Vec<u8> = object.value().into_iter().skip(1).cloned().collect();
+        let pk_der: Vec<u8> = object.value().iter().skip(1).cloned().collect();
         // We don't parse pk_der further as an ASN.1 RsaPublicKey, since
         // we only need the DER encoding for `verify`.
         Ok(Self(PublicKey(pk_der)))
@@ -336,6 +371,15 @@ mod tests {
         assert!(Keypair::from_pkcs8(&mut KEY3.to_vec()).is_ok());
     }
 
+    #[test]
+    fn rsa_pkcs8_der_roundtrip() {
+        let kp = Keypair::from_pkcs8_der(&mut KEY1.to_vec()).unwrap();
+        assert_eq!(kp.to_pkcs8_der(), KEY1);
+
+        let decoded = Keypair::from_pkcs8_der(&mut kp.to_pkcs8_der()).unwrap();
+        assert_eq!(decoded.public(), kp.public());
+    }
+
     #[test]
     fn rsa_x509_encode_decode() {
         fn prop(SomeKeypair(kp): SomeKeypair) -> Result<bool, String> {
@@ -20,6 +20,7 @@
 
 //! Secp256k1 keys.
 
+use super::asn1::{self, Algorithm};
 use super::error::{DecodingError, SigningError};
 use asn1_der::typed::{DerDecodable, Sequence};
 use core::fmt;
@@ -27,6 +28,9 @@ use libsecp256k1::{Message, Signature};
 use sha2::{Digest as ShaDigestTrait, Sha256};
 use zeroize::Zeroize;
 
+/// The algorithm of Secp256k1 keys in PKCS#8 and X.509 structures.
+const ALGORITHM: Algorithm = Algorithm::new(asn1::EC_PUBLIC_KEY_OID, Some(asn1::SECP256K1_OID));
+
 /// A Secp256k1 keypair.
 #[derive(Clone)]
 pub struct Keypair {
@@ -40,6 +44,41 @@ impl Keypair {
         Keypair::from(SecretKey::generate())
     }
 
+    /// Encode the keypair into a DER-encoded PKCS#8 PrivateKeyInfo structure
+    /// wrapping an ECPrivateKey structure as defined in [RFC5915], e.g. as
+    /// produced by `openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:secp256k1 -outform DER`.
+    ///
+    /// [RFC5915]: https://tools.ietf.org/html/rfc5915#section-3
+    pub fn to_pkcs8_der(&self) -> Vec<u8> {
+        let secret = zeroize::Zeroizing::new(self.secret.to_bytes());
+        asn1::encode_ec_pkcs8(
+            asn1::SECP256K1_OID,
+            &*secret,
+            &self.public.encode_uncompressed(),
+        )
+    }
+
+    /// Decode a keypair from a DER-encoded PKCS#8 PrivateKeyInfo structure
+    /// as produced by [`Keypair::to_pkcs8_der`], zeroing the input on success.
+    ///
+    /// Fails if the structure includes a public key that does not belong to
+    /// the secret key.
+    pub fn from_pkcs8_der(der: &mut [u8]) -> Result<Keypair, DecodingError> {
+        let (mut secret, public) = asn1::decode_ec_pkcs8(der, asn1::SECP256K1_OID)?;
+        let keypair = Keypair::from(SecretKey::from_bytes(&mut *secret)?);
+        if let Some(public) = public {
+            let public = libsecp256k1::PublicKey::parse_slice(&public, None)
+                .map_err(|_| DecodingError::new("failed to parse secp256k1 public key"))?;
+            if PublicKey(public) != keypair.public {
+                return Err(DecodingError::new(
+                    "Secp256k1 PKCS#8: public key does not match secret key",
+                ));
+            }
+        }
+        der.zeroize();
+        Ok(keypair)
+    }
+
     /// Get the public key of this keypair.
     pub fn public(&self) -> &PublicKey {
         &self.public
@@ -194,6 +233,24 @@ impl PublicKey {
             .map_err(|_| DecodingError::new("failed to parse secp256k1 public key"))
             .map(PublicKey)
     }
+
+    /// Encode the public key in DER as a X.509 SubjectPublicKeyInfo structure
+    /// with the uncompressed point, as defined in [RFC5480].
+    ///
+    /// [RFC5480]: https://tools.ietf.org/html/rfc5480#section-2
+    pub fn encode_x509(&self) -> Vec<u8> {
+        asn1::encode_spki(ALGORITHM, &self.encode_uncompressed())
+    }
+
+    /// Decode a public key from a DER-encoded X.509 SubjectPublicKeyInfo
+    /// structure with either a compressed or an uncompressed point.
+    /// See also `encode_x509`.
+    pub fn decode_x509(pk: &[u8]) -> Result<PublicKey, DecodingError> {
+        let point = asn1::decode_spki(pk, ALGORITHM)?;
+        libsecp256k1::PublicKey::parse_slice(point, None)
+            .map_err(|_| DecodingError::new("failed to parse secp256k1 public key"))
+            .map(PublicKey)
+    }
 }
 
 #[cfg(test)]
@@ -209,4 +266,17 @@ mod tests {
         assert_eq!(sk1.0.serialize(), sk2.0.serialize());
         assert_eq!(sk_bytes, [0; 32]);
     }
+
+    #[test]
+    fn pkcs8_with_mismatched_public_key_is_rejected() {
+        let pair = Keypair::generate();
+        let other = Keypair::generate();
+        let mut der = asn1::encode_ec_pkcs8(
+            asn1::SECP256K1_OID,
+            &pair.secret.to_bytes(),
+            &other.public.encode_uncompressed(),
+        );
+
+        assert!(Keypair::from_pkcs8_der(&mut der).is_err());
+    }
 }
@@ -63,12 +63,14 @@ pub mod identity;
 pub mod muxing;
 pub mod peer_record;
 pub mod signed_envelope;
+pub mod time;
 pub mod transport;
 pub mod upgrade;
 
-pub use connection::{ConnectedPoint, Endpoint};
+pub use connection::{ConnectedPoint, Endpoint, NegotiatedProtocols};
 pub use identity::PublicKey;
 pub use multiaddr::Multiaddr;
+pub use multibase;
 pub use multihash;
 pub use muxing::StreamMuxer;
 pub use peer_id::PeerId;
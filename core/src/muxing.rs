@@ -51,6 +51,7 @@
 //! The upgrade process will take ownership of the connection, which makes it possible for the
 //! implementation of `StreamMuxer` to control everything that happens on the wire.
 
+use crate::connection::NegotiatedProtocols;
 use fnv::FnvHashMap;
 use futures::{future, prelude::*, task::Context, task::Poll};
 use multiaddr::Multiaddr;
@@ -517,6 +518,7 @@ pub struct StreamMuxerBox {
             + Send
             + Sync,
     >,
+    protocols: NegotiatedProtocols,
 }
 
 impl StreamMuxerBox {
@@ -537,8 +539,22 @@ impl StreamMuxerBox {
 
         StreamMuxerBox {
             inner: Box::new(wrap),
+            protocols: NegotiatedProtocols::default(),
         }
     }
+
+    /// Sets the protocols negotiated while upgrading the connection of the
+    /// muxer.
+    pub fn with_negotiated_protocols(mut self, protocols: NegotiatedProtocols) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    /// Returns the protocols negotiated while upgrading the connection of the
+    /// muxer, as far as known.
+    pub fn negotiated_protocols(&self) -> &NegotiatedProtocols {
+        &self.protocols
+    }
 }
 
 impl StreamMuxer for StreamMuxerBox {
@@ -19,6 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::PublicKey;
+use multibase::Base;
 use multihash::{Code, Error, Multihash, MultihashDigest};
 use rand::Rng;
 use std::{convert::TryFrom, fmt, str::FromStr};
@@ -31,6 +32,12 @@ use serde::{Deserialize, Serialize};
 /// automatically used as the peer id using an identity multihash.
 const MAX_INLINE_KEY_LENGTH: usize = 42;
 
+/// The version of CIDs encoding peer IDs.
+const CID_VERSION: u64 = 1;
+
+/// The `libp2p-key` multicodec of CIDs encoding peer IDs.
+const LIBP2P_KEY_CODEC: u64 = 0x72;
+
 /// Identifier of a peer of the network.
 ///
 /// The data is a multihash of the public key of the peer.
@@ -109,6 +116,45 @@ impl PeerId {
         bs58::encode(self.to_bytes()).into_string()
     }
 
+    /// Returns this `PeerId` encoded as a CIDv1 with the `libp2p-key`
+    /// multicodec, in the given multibase, e.g. [`Base::Base32Lower`]
+    /// (`bafz...`) or [`Base::Base36Lower`] (`k51...`).
+    ///
+    /// See the [peer ID specification](https://github.com/libp2p/specs/blob/master/peer-ids/peer-ids.md#string-representation).
+    pub fn to_cid_string(&self, base: Base) -> String {
+        let mut version_buf = unsigned_varint::encode::u64_buffer();
+        let mut codec_buf = unsigned_varint::encode::u64_buffer();
+
+        let mut cid = Vec::new();
+        cid.extend_from_slice(unsigned_varint::encode::u64(CID_VERSION, &mut version_buf));
+        cid.extend_from_slice(unsigned_varint::encode::u64(
+            LIBP2P_KEY_CODEC,
+            &mut codec_buf,
+        ));
+        cid.extend_from_slice(&self.to_bytes());
+
+        multibase::encode(base, cid)
+    }
+
+    /// Parses a `PeerId` from a multibase-encoded CIDv1 with the `libp2p-key`
+    /// multicodec, as produced by [`PeerId::to_cid_string`].
+    fn from_cid_str(s: &str) -> Result<PeerId, ParseError> {
+        let (_, bytes) = multibase::decode(s)?;
+
+        let (version, rest) =
+            unsigned_varint::decode::u64(&bytes).map_err(|_| ParseError::InvalidCid)?;
+        if version != CID_VERSION {
+            return Err(ParseError::UnsupportedCidVersion(version));
+        }
+        let (codec, multihash) =
+            unsigned_varint::decode::u64(rest).map_err(|_| ParseError::InvalidCid)?;
+        if codec != LIBP2P_KEY_CODEC {
+            return Err(ParseError::UnsupportedCodec(codec));
+        }
+
+        PeerId::from_bytes(multihash).map_err(|_| ParseError::MultiHash)
+    }
+
     /// Checks whether the public key passed as parameter matches the public key of this `PeerId`.
     ///
     /// Returns `None` if this `PeerId`s hash algorithm is not supported when encoding the
@@ -225,6 +271,14 @@ impl<'de> Deserialize<'de> for PeerId {
 pub enum ParseError {
     #[error("base-58 decode error: {0}")]
     B58(#[from] bs58::decode::Error),
+    #[error("multibase decode error: {0}")]
+    MultiBase(#[from] multibase::Error),
+    #[error("decoding CID failed")]
+    InvalidCid,
+    #[error("unsupported CID version {0}")]
+    UnsupportedCidVersion(u64),
+    #[error("unsupported CID multicodec {0:#x}, expected libp2p-key")]
+    UnsupportedCodec(u64),
     #[error("decoding multihash failed")]
     MultiHash,
 }
@@ -232,16 +286,24 @@ pub enum ParseError {
 impl FromStr for PeerId {
     type Err = ParseError;
 
+    /// Parses a `PeerId` from either a base58btc-encoded multihash, i.e.
+    /// starting with `1` or `Qm`, or a multibase-encoded CIDv1 with the
+    /// `libp2p-key` multicodec.
     #[inline]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = bs58::decode(s).into_vec()?;
-        PeerId::from_bytes(&bytes).map_err(|_| ParseError::MultiHash)
+        if s.starts_with('1') || s.starts_with("Qm") {
+            let bytes = bs58::decode(s).into_vec()?;
+            PeerId::from_bytes(&bytes).map_err(|_| ParseError::MultiHash)
+        } else {
+            PeerId::from_cid_str(s)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{identity, PeerId};
+    use multibase::Base;
 
     #[test]
     fn peer_id_is_public_key() {
@@ -271,4 +333,37 @@ mod tests {
             assert_eq!(peer_id, PeerId::from_bytes(&peer_id.to_bytes()).unwrap());
         }
     }
+
+    #[test]
+    fn peer_id_from_cid_string() {
+        // Example of the peer ID specification.
+        let expected: PeerId = "QmYyQSo1c1Ym7orWxLYvCrM2EmxFTANf8wXmmE7DWjhx5N"
+            .parse()
+            .unwrap();
+        let peer_id: PeerId = "bafzbeie5745rpv2m6tjyuugywy4d5ewrqgqqhfnf445he3omzpjbx5xqxe"
+            .parse()
+            .unwrap();
+        assert_eq!(peer_id, expected);
+    }
+
+    #[test]
+    fn peer_id_to_cid_string_then_back() {
+        let peer_id = identity::Keypair::generate_ed25519().public().to_peer_id();
+
+        for base in [Base::Base32Lower, Base::Base36Lower, Base::Base58Btc] {
+            let second: PeerId = peer_id.to_cid_string(base).parse().unwrap();
+            assert_eq!(peer_id, second);
+        }
+
+        assert!(peer_id.to_cid_string(Base::Base36Lower).starts_with("k51"));
+    }
+
+    #[test]
+    fn cid_with_other_codec_is_rejected() {
+        // CIDv1 of an empty raw (0x55) block.
+        assert!(matches!(
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku".parse::<PeerId>(),
+            Err(super::ParseError::UnsupportedCodec(0x55))
+        ));
+    }
 }
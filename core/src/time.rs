@@ -0,0 +1,233 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The source of time of libp2p.
+//!
+//! The `Swarm`, the connection handlers and the protocols take the current
+//! time from [`now`] and wait via [`Delay`] and [`Interval`], instead of
+//! using `instant` and `futures-timer` directly. By default, these follow the
+//! wall clock. A [`Clock`] set for the current thread via [`set_clock`]
+//! replaces the wall clock for all calls to [`now`] and all [`Delay`]s and
+//! [`Interval`]s created on that thread, e.g. the virtual clock of a
+//! [`SimNetwork`](crate::transport::simulation::SimNetwork).
+
+use futures::prelude::*;
+use std::{
+    cell::RefCell,
+    fmt,
+    marker::PhantomData,
+    pin::Pin,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+pub use instant::Instant;
+
+thread_local! {
+    static CLOCK: RefCell<Option<Arc<dyn Clock>>> = RefCell::new(None);
+}
+
+/// A source of time replacing the wall clock, see [`set_clock`].
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current time of the clock.
+    fn now(&self) -> Instant;
+
+    /// Returns [`Poll::Ready`] if the clock reached `deadline`, otherwise
+    /// arranges for the current task to be woken up once it does.
+    fn poll_deadline(&self, deadline: Instant, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+/// Makes `clock` the source of time of the current thread until the returned
+/// [`ClockGuard`] is dropped.
+pub fn set_clock(clock: Arc<dyn Clock>) -> ClockGuard {
+    let previous = CLOCK.with(|c| c.borrow_mut().replace(clock));
+    ClockGuard {
+        previous,
+        _not_send: PhantomData,
+    }
+}
+
+/// Restores the previous source of time of the current thread when dropped,
+/// see [`set_clock`].
+#[must_use = "The clock is unset when the guard is dropped."]
+pub struct ClockGuard {
+    previous: Option<Arc<dyn Clock>>,
+    _not_send: PhantomData<Rc<()>>,
+}
+
+impl Drop for ClockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CLOCK.with(|c| *c.borrow_mut() = previous);
+    }
+}
+
+impl fmt::Debug for ClockGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClockGuard").finish()
+    }
+}
+
+fn current_clock() -> Option<Arc<dyn Clock>> {
+    CLOCK.with(|c| c.borrow().clone())
+}
+
+/// Returns the current time of the [`Clock`] of the current thread, if any,
+/// and of the wall clock otherwise.
+pub fn now() -> Instant {
+    match current_clock() {
+        Some(clock) => clock.now(),
+        None => Instant::now(),
+    }
+}
+
+/// A future resolving once a given duration elapsed on the [`Clock`] of the
+/// thread it was created on, if any, and on the wall clock otherwise.
+pub struct Delay {
+    inner: DelayInner,
+}
+
+enum DelayInner {
+    Wall(futures_timer::Delay),
+    Clock {
+        clock: Arc<dyn Clock>,
+        deadline: Instant,
+    },
+}
+
+impl Delay {
+    /// Creates a new [`Delay`] resolving after `duration`.
+    pub fn new(duration: Duration) -> Delay {
+        let inner = match current_clock() {
+            Some(clock) => DelayInner::Clock {
+                deadline: clock.now() + duration,
+                clock,
+            },
+            None => DelayInner::Wall(futures_timer::Delay::new(duration)),
+        };
+        Delay { inner }
+    }
+
+    /// Resets the [`Delay`] to resolve after `duration` from now.
+    pub fn reset(&mut self, duration: Duration) {
+        match &mut self.inner {
+            DelayInner::Wall(delay) => delay.reset(duration),
+            DelayInner::Clock { clock, deadline } => *deadline = clock.now() + duration,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &mut self.inner {
+            DelayInner::Wall(delay) => delay.poll_unpin(cx),
+            DelayInner::Clock { clock, deadline } => clock.poll_deadline(*deadline, cx),
+        }
+    }
+}
+
+impl fmt::Debug for Delay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.inner {
+            DelayInner::Wall(delay) => f.debug_tuple("Delay").field(delay).finish(),
+            DelayInner::Clock { deadline, .. } => {
+                f.debug_struct("Delay").field("deadline", deadline).finish()
+            }
+        }
+    }
+}
+
+/// A stream yielding once per period, following the same clock as [`Delay`].
+#[derive(Debug)]
+pub struct Interval {
+    delay: Delay,
+    period: Duration,
+}
+
+impl Interval {
+    /// Creates a new [`Interval`] yielding first after `period`.
+    pub fn new(period: Duration) -> Interval {
+        Interval {
+            delay: Delay::new(period),
+            period,
+        }
+    }
+
+    /// Creates a new [`Interval`] yielding first at `start`.
+    pub fn new_at(start: Instant, period: Duration) -> Interval {
+        Interval {
+            delay: Delay::new(start.saturating_duration_since(now())),
+            period,
+        }
+    }
+}
+
+impl Stream for Interval {
+    type Item = ();
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        futures::ready!(self.delay.poll_unpin(cx));
+        let period = self.period;
+        self.delay.reset(period);
+        Poll::Ready(Some(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    /// A [`Clock`] only moving when set explicitly.
+    struct ManualClock(Mutex<Instant>);
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock()
+        }
+
+        fn poll_deadline(&self, deadline: Instant, _: &mut Context<'_>) -> Poll<()> {
+            if *self.0.lock() >= deadline {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn delay_follows_clock_of_creating_thread() {
+        let start = Instant::now();
+        let clock = Arc::new(ManualClock(Mutex::new(start)));
+        let guard = set_clock(clock.clone());
+        assert_eq!(now(), start);
+        let mut delay = Delay::new(Duration::from_secs(3600));
+        drop(guard);
+
+        assert!((&mut delay).now_or_never().is_none());
+        *clock.0.lock() += Duration::from_secs(3600);
+        assert!(delay.now_or_never().is_some());
+        assert_ne!(now(), start + Duration::from_secs(3600));
+    }
+}
@@ -36,6 +36,7 @@ pub mod dummy;
 pub mod map;
 pub mod map_err;
 pub mod memory;
+pub mod simulation;
 pub mod timeout;
 pub mod upgrade;
 
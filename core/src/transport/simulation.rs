@@ -0,0 +1,1050 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Deterministic, simulated network for multi-node tests.
+//!
+//! A [`SimNetwork`] connects any number of [`SimTransport`]s, one per
+//! simulated node, using `/memory/N` addresses like the
+//! [`MemoryTransport`](super::MemoryTransport). Unlike the latter, data
+//! written to a [`SimStream`] is only delivered once the virtual clock of the
+//! [`SimNetwork`] reached its delivery time, which is derived from the
+//! [`LinkConfig`] between the two nodes:
+//!
+//!   - the latency of the link delays every write as well as connection
+//!     establishment,
+//!   - the bandwidth of the link delays writes by their transmission time,
+//!     queueing them behind earlier writes on the same connection,
+//!   - lost writes are retransmitted after a retransmission timeout, i.e.
+//!     loss manifests as additional delay while streams stay reliable and
+//!     ordered.
+//!
+//! Nodes can be split into partitions, see [`SimNetwork::partition`]. Dials
+//! across partitions fail and data in flight between partitions is held back
+//! until the partition is healed.
+//!
+//! The virtual clock only moves when advanced explicitly, via
+//! [`SimNetwork::advance`] or [`SimNetwork::advance_to_next_deadline`]. All
+//! random decisions are drawn from a generator seeded on construction. Thus a
+//! run is reproducible from its seed, as long as the simulated nodes are
+//! polled in the same order, e.g. from a single task.
+//!
+//! # Scope
+//!
+//! Besides the simulated network itself, i.e. the establishment of
+//! connections and the delivery of data, and [`SimNetwork::sleep`], the
+//! virtual clock drives [`time`](crate::time) on the thread on which
+//! [`SimNetwork::enter`] was called. The `Swarm`, connection handlers and
+//! protocols take the current time and create their timers via
+//! [`time`](crate::time), e.g. for the idle connection timeout, the substream
+//! upgrade timeout or periodic protocol tasks. Their timers thus fire once the
+//! virtual clock reached them, and [`SimNetwork::next_deadline`] takes them
+//! into account. Timers of other crates, e.g. of a stream multiplexer, keep
+//! running on the wall clock.
+//!
+//! All nodes of a simulation should be created and polled from the thread
+//! that entered the virtual clock, including the background tasks of their
+//! connections, and the virtual clock only be advanced once none of them can
+//! make progress. Otherwise timers may follow the wall clock, or data may be
+//! delivered before the receiving node processed earlier data, breaking
+//! reproducibility.
+
+use crate::{
+    time::{self, Clock, ClockGuard, Instant},
+    transport::{ListenerEvent, TransportError},
+    Transport,
+};
+use fnv::FnvHashMap;
+use futures::{
+    channel::mpsc,
+    future::{self, Ready},
+    prelude::*,
+    task::{Context, Poll, Waker},
+};
+use multiaddr::{Multiaddr, Protocol};
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    cmp,
+    collections::{BTreeSet, VecDeque},
+    error, fmt, io,
+    num::NonZeroU64,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+/// Lower bound of the time after which a lost write is retransmitted.
+const MIN_RETRANSMISSION_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Identifier of a simulated node, see [`SimNetwork::node`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u64);
+
+/// Properties of the link between two simulated nodes.
+///
+/// The default link delivers data instantly, without loss.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LinkConfig {
+    latency: Duration,
+    bandwidth: Option<NonZeroU64>,
+    loss: f64,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        LinkConfig {
+            latency: Duration::ZERO,
+            bandwidth: None,
+            loss: 0.0,
+        }
+    }
+}
+
+impl LinkConfig {
+    /// Sets the one-way latency of the link.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Sets the bandwidth of the link in bytes per second, per connection and
+    /// direction.
+    pub fn with_bandwidth(mut self, bytes_per_second: NonZeroU64) -> Self {
+        self.bandwidth = Some(bytes_per_second);
+        self
+    }
+
+    /// Sets the probability with which a write is lost and needs to be
+    /// retransmitted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `loss` is not within `[0, 1)`.
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        assert!(
+            (0.0..1.0).contains(&loss),
+            "Loss must be within [0, 1), got {}.",
+            loss
+        );
+        self.loss = loss;
+        self
+    }
+
+    /// Time it takes to put `len` bytes onto the link.
+    fn transmission_time(&self, len: usize) -> Duration {
+        self.bandwidth
+            .map(|bandwidth| Duration::from_secs_f64(len as f64 / bandwidth.get() as f64))
+            .unwrap_or_default()
+    }
+
+    /// Time it takes for a transmitted message to arrive, including
+    /// retransmissions.
+    fn delay(&self, rng: &mut StdRng) -> Duration {
+        let mut delay = self.latency;
+        if self.loss > 0.0 {
+            let retransmission_timeout = cmp::max(self.latency * 2, MIN_RETRANSMISSION_TIMEOUT);
+            while rng.gen_bool(self.loss) {
+                delay += retransmission_timeout;
+            }
+        }
+        delay
+    }
+}
+
+/// A simulated network with a virtual clock.
+///
+/// Cloning a [`SimNetwork`] yields a handle to the same network.
+#[derive(Clone)]
+pub struct SimNetwork {
+    inner: Arc<Mutex<Network>>,
+}
+
+impl SimNetwork {
+    /// Creates a new network drawing all random decisions from `seed`.
+    pub fn new(seed: u64) -> Self {
+        SimNetwork {
+            inner: Arc::new(Mutex::new(Network {
+                start: Instant::now(),
+                now: Duration::ZERO,
+                rng: StdRng::seed_from_u64(seed),
+                next_node: 0,
+                next_port: 0,
+                next_connection: 0,
+                default_link: LinkConfig::default(),
+                links: FnvHashMap::default(),
+                partitions: FnvHashMap::default(),
+                listeners: FnvHashMap::default(),
+                connections: FnvHashMap::default(),
+                timers: BTreeSet::new(),
+                wakers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Adds a new node to the network, returning its transport.
+    pub fn node(&self) -> SimTransport {
+        let mut network = self.inner.lock();
+        let node = NodeId(network.next_node);
+        network.next_node += 1;
+
+        SimTransport {
+            network: self.clone(),
+            node,
+        }
+    }
+
+    /// Sets the [`LinkConfig`] of all links without a dedicated one.
+    ///
+    /// Only affects data written and connections dialed afterwards.
+    pub fn set_default_link(&self, link: LinkConfig) {
+        self.inner.lock().default_link = link;
+    }
+
+    /// Sets the [`LinkConfig`] of the link between `a` and `b`.
+    ///
+    /// Only affects data written and connections dialed afterwards.
+    pub fn set_link(&self, a: NodeId, b: NodeId, link: LinkConfig) {
+        self.inner.lock().links.insert(ordered(a, b), link);
+    }
+
+    /// Splits the network into the given groups of nodes, replacing any
+    /// previous partitioning.
+    ///
+    /// Nodes can only communicate with nodes of the same group. Nodes not
+    /// listed in any group form an implicit group of their own.
+    pub fn partition<I>(&self, groups: I)
+    where
+        I: IntoIterator,
+        I::Item: IntoIterator<Item = NodeId>,
+    {
+        let mut network = self.inner.lock();
+        network.partitions.clear();
+        for (index, group) in groups.into_iter().enumerate() {
+            for node in group {
+                network.partitions.insert(node, index);
+            }
+        }
+        network.wake_all();
+    }
+
+    /// Removes any partitioning, releasing the data held back in the
+    /// meantime.
+    pub fn heal(&self) {
+        let mut network = self.inner.lock();
+        network.partitions.clear();
+        network.wake_all();
+    }
+
+    /// Returns the time elapsed on the virtual clock since the network was
+    /// created.
+    pub fn now(&self) -> Duration {
+        self.inner.lock().now
+    }
+
+    /// Advances the virtual clock by `duration`, waking up all tasks waiting
+    /// for data or a timer that became due.
+    pub fn advance(&self, duration: Duration) {
+        let mut network = self.inner.lock();
+        network.now += duration;
+        let now = network.now;
+        network.timers.retain(|deadline| *deadline > now);
+        network.wake_all();
+    }
+
+    /// Returns the time on the virtual clock at which the next pending
+    /// delivery, connection attempt or timer becomes due.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.inner.lock().timers.iter().next().copied()
+    }
+
+    /// Advances the virtual clock to [`SimNetwork::next_deadline`].
+    ///
+    /// Returns `false` if there is no pending deadline.
+    pub fn advance_to_next_deadline(&self) -> bool {
+        match self.next_deadline() {
+            Some(deadline) => {
+                self.advance(deadline - self.now());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes the virtual clock the source of [`time`](crate::time) on the
+    /// current thread until the returned [`ClockGuard`] is dropped.
+    pub fn enter(&self) -> ClockGuard {
+        time::set_clock(Arc::new(SimClock(self.clone())))
+    }
+
+    /// Returns a future resolving once the virtual clock advanced by
+    /// `duration`.
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        let mut network = self.inner.lock();
+        let deadline = network.now + duration;
+        network.schedule(deadline);
+
+        Sleep {
+            network: self.clone(),
+            deadline,
+        }
+    }
+}
+
+impl fmt::Debug for SimNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let network = self.inner.lock();
+        f.debug_struct("SimNetwork")
+            .field("now", &network.now)
+            .field("nodes", &network.next_node)
+            .field("connections", &network.connections.len())
+            .finish()
+    }
+}
+
+struct Network {
+    /// The [`Instant`] corresponding to the start of the virtual clock.
+    start: Instant,
+    /// Time elapsed on the virtual clock.
+    now: Duration,
+    rng: StdRng,
+    next_node: u64,
+    next_port: u64,
+    next_connection: u64,
+    default_link: LinkConfig,
+    links: FnvHashMap<(NodeId, NodeId), LinkConfig>,
+    /// The group of each partitioned node.
+    partitions: FnvHashMap<NodeId, usize>,
+    listeners: FnvHashMap<NonZeroU64, (NodeId, ConnectionSender)>,
+    connections: FnvHashMap<u64, Connection>,
+    /// Points in time after [`Network::now`] at which some task may make
+    /// progress.
+    timers: BTreeSet<Duration>,
+    /// Tasks to wake up once the clock advanced or the partitions changed.
+    wakers: Vec<Waker>,
+}
+
+impl Network {
+    fn link(&self, a: NodeId, b: NodeId) -> LinkConfig {
+        self.links
+            .get(&ordered(a, b))
+            .copied()
+            .unwrap_or(self.default_link)
+    }
+
+    fn is_partitioned(&self, a: NodeId, b: NodeId) -> bool {
+        self.partitions.get(&a) != self.partitions.get(&b)
+    }
+
+    fn allocate_port(&mut self) -> NonZeroU64 {
+        loop {
+            self.next_port += 1;
+            let port = NonZeroU64::new(self.next_port).expect("incremented port to be non-zero");
+            if !self.listeners.contains_key(&port) {
+                return port;
+            }
+        }
+    }
+
+    fn schedule(&mut self, deadline: Duration) {
+        if deadline > self.now {
+            self.timers.insert(deadline);
+        }
+    }
+
+    /// Registers the current task to be woken up on the next change of the
+    /// clock or the partitions.
+    fn register(&mut self, cx: &Context<'_>) {
+        if !self.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            self.wakers.push(cx.waker().clone());
+        }
+    }
+
+    fn wake_all(&mut self) {
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// A [`mpsc::UnboundedSender`] enabling a [`SimDial`] to send a [`SimStream`]
+/// and the port of the dialer to a [`SimListener`].
+type ConnectionSender = mpsc::UnboundedSender<(SimStream, NonZeroU64)>;
+
+/// State of a connection between two nodes.
+struct Connection {
+    /// The data written by the dialer and the listener respectively.
+    pipes: [Pipe; 2],
+    /// Whether the stream of the dialer and the listener respectively has
+    /// been dropped.
+    dropped: [bool; 2],
+    rng: StdRng,
+}
+
+/// One direction of a [`Connection`].
+#[derive(Default)]
+struct Pipe {
+    /// Written data along with the time of its delivery.
+    chunks: VecDeque<(Duration, Vec<u8>)>,
+    /// Time until which the link is busy transmitting earlier writes.
+    busy_until: Duration,
+    /// Time at which the latest write is delivered.
+    last_delivery: Duration,
+    /// Time at which the closing of the writing side is delivered.
+    closed_at: Option<Duration>,
+    reader_dropped: bool,
+    reader_waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn close(&mut self, now: Duration, link: &LinkConfig) -> Duration {
+        let closed_at = cmp::max(now + link.latency, self.last_delivery);
+        self.closed_at = Some(closed_at);
+        if let Some(waker) = self.reader_waker.take() {
+            waker.wake();
+        }
+        closed_at
+    }
+}
+
+/// Transport of a single node of a [`SimNetwork`], supporting `/memory/N`
+/// multiaddresses.
+#[derive(Debug, Clone)]
+pub struct SimTransport {
+    network: SimNetwork,
+    node: NodeId,
+}
+
+impl SimTransport {
+    /// Returns the identifier of the node of this transport.
+    pub fn node_id(&self) -> NodeId {
+        self.node
+    }
+}
+
+impl Transport for SimTransport {
+    type Output = SimStream;
+    type Error = SimTransportError;
+    type Listener = SimListener;
+    type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
+    type Dial = SimDial;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let port = match parse_memory_addr(&addr) {
+            Some(port) => port,
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        let (tx, rx) = mpsc::unbounded();
+        let port = {
+            let mut network = self.network.inner.lock();
+            let port = match NonZeroU64::new(port) {
+                Some(port) if network.listeners.contains_key(&port) => {
+                    return Err(TransportError::Other(SimTransportError::AlreadyInUse))
+                }
+                Some(port) => port,
+                None => network.allocate_port(),
+            };
+            network.listeners.insert(port, (self.node, tx));
+            port
+        };
+
+        Ok(SimListener {
+            network: self.network,
+            port,
+            addr: Protocol::Memory(port.get()).into(),
+            receiver: rx,
+            tell_listen_addr: true,
+        })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<SimDial, TransportError<Self::Error>> {
+        let port = match parse_memory_addr(&addr) {
+            Some(port) => NonZeroU64::new(port)
+                .ok_or(TransportError::Other(SimTransportError::Unreachable))?,
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        let mut network = self.network.inner.lock();
+        let listener = match network.listeners.get(&port) {
+            Some((listener, _)) => *listener,
+            None => return Err(TransportError::Other(SimTransportError::Unreachable)),
+        };
+
+        // Connection establishment takes one round trip.
+        let link = network.link(self.node, listener);
+        let connect_at = network.now + link.delay(&mut network.rng) + link.delay(&mut network.rng);
+        network.schedule(connect_at);
+        drop(network);
+
+        Ok(SimDial {
+            network: self.network,
+            dialer: self.node,
+            port,
+            connect_at,
+        })
+    }
+
+    fn dial_as_listener(self, addr: Multiaddr) -> Result<SimDial, TransportError<Self::Error>> {
+        self.dial(addr)
+    }
+
+    fn address_translation(&self, _server: &Multiaddr, _observed: &Multiaddr) -> Option<Multiaddr> {
+        None
+    }
+}
+
+/// Error that can be produced from the [`SimTransport`].
+#[derive(Debug, Copy, Clone)]
+pub enum SimTransportError {
+    /// There's no listener on the given port or it is in another partition.
+    Unreachable,
+    /// Tries to listen on a port that is already in use.
+    AlreadyInUse,
+}
+
+impl fmt::Display for SimTransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SimTransportError::Unreachable => write!(f, "No reachable listener on the given port."),
+            SimTransportError::AlreadyInUse => write!(f, "Port already occupied."),
+        }
+    }
+}
+
+impl error::Error for SimTransportError {}
+
+/// Connection to a [`SimTransport`] currently being opened.
+pub struct SimDial {
+    network: SimNetwork,
+    dialer: NodeId,
+    port: NonZeroU64,
+    connect_at: Duration,
+}
+
+impl Future for SimDial {
+    type Output = Result<SimStream, SimTransportError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let (dialer_stream, listener_stream, dial_port, sender) = {
+            let mut network = self.network.inner.lock();
+            if network.now < self.connect_at {
+                network.register(cx);
+                return Poll::Pending;
+            }
+
+            let (listener, sender) = match network.listeners.get(&self.port) {
+                Some((listener, sender)) => (*listener, sender.clone()),
+                None => return Poll::Ready(Err(SimTransportError::Unreachable)),
+            };
+            if network.is_partitioned(self.dialer, listener) {
+                return Poll::Ready(Err(SimTransportError::Unreachable));
+            }
+
+            let id = network.next_connection;
+            network.next_connection += 1;
+            let rng = StdRng::seed_from_u64(network.rng.gen());
+            network.connections.insert(
+                id,
+                Connection {
+                    pipes: Default::default(),
+                    dropped: [false; 2],
+                    rng,
+                },
+            );
+            let dial_port = network.allocate_port();
+
+            let stream = |side| SimStream {
+                network: self.network.clone(),
+                connection: id,
+                side,
+                nodes: [self.dialer, listener],
+            };
+            (stream(0), stream(1), dial_port, sender)
+        };
+
+        // Send outside of the lock, as a failed send drops the stream.
+        if sender.unbounded_send((listener_stream, dial_port)).is_err() {
+            return Poll::Ready(Err(SimTransportError::Unreachable));
+        }
+
+        Poll::Ready(Ok(dialer_stream))
+    }
+}
+
+/// Listener for simulated connections.
+pub struct SimListener {
+    network: SimNetwork,
+    /// Port we're listening on.
+    port: NonZeroU64,
+    /// The address we are listening on.
+    addr: Multiaddr,
+    /// Receives incoming connections.
+    receiver: mpsc::UnboundedReceiver<(SimStream, NonZeroU64)>,
+    /// Generate `ListenerEvent::NewAddress` to inform about our listen address.
+    tell_listen_addr: bool,
+}
+
+impl Stream for SimListener {
+    type Item = Result<
+        ListenerEvent<Ready<Result<SimStream, SimTransportError>>, SimTransportError>,
+        SimTransportError,
+    >;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.tell_listen_addr {
+            self.tell_listen_addr = false;
+            return Poll::Ready(Some(Ok(ListenerEvent::NewAddress(self.addr.clone()))));
+        }
+
+        let (stream, dial_port) = match self.receiver.poll_next_unpin(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(None) => panic!("Alive listeners always have a sender."),
+            Poll::Ready(Some(v)) => v,
+        };
+
+        let event = ListenerEvent::Upgrade {
+            upgrade: future::ready(Ok(stream)),
+            local_addr: self.addr.clone(),
+            remote_addr: Protocol::Memory(dial_port.get()).into(),
+        };
+
+        Poll::Ready(Some(Ok(event)))
+    }
+}
+
+impl Drop for SimListener {
+    fn drop(&mut self) {
+        self.network.inner.lock().listeners.remove(&self.port);
+    }
+}
+
+/// An established, simulated connection between two nodes.
+///
+/// Implements `AsyncRead` and `AsyncWrite`.
+pub struct SimStream {
+    network: SimNetwork,
+    connection: u64,
+    /// Index of the local node in [`Connection::nodes`].
+    side: usize,
+    nodes: [NodeId; 2],
+}
+
+impl SimStream {
+    /// Returns the identifier of the local node.
+    pub fn local_node(&self) -> NodeId {
+        self.nodes[self.side]
+    }
+
+    /// Returns the identifier of the remote node.
+    pub fn remote_node(&self) -> NodeId {
+        self.nodes[1 - self.side]
+    }
+}
+
+impl AsyncRead for SimStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut network = self.network.inner.lock();
+        let network = &mut *network;
+        if network.is_partitioned(self.nodes[0], self.nodes[1]) {
+            network.register(cx);
+            return Poll::Pending;
+        }
+
+        let now = network.now;
+        let pipe = &mut network
+            .connections
+            .get_mut(&self.connection)
+            .expect("Connection to exist while one of its streams is alive.")
+            .pipes[1 - self.side];
+
+        match pipe.chunks.front_mut() {
+            Some((deliver_at, data)) if *deliver_at <= now => {
+                let n = cmp::min(buf.len(), data.len());
+                buf[..n].copy_from_slice(&data[..n]);
+                data.drain(..n);
+                if data.is_empty() {
+                    pipe.chunks.pop_front();
+                }
+                Poll::Ready(Ok(n))
+            }
+            Some(_) => {
+                network.register(cx);
+                Poll::Pending
+            }
+            None => match pipe.closed_at {
+                Some(closed_at) if closed_at <= now => Poll::Ready(Ok(0)),
+                Some(_) => {
+                    network.register(cx);
+                    Poll::Pending
+                }
+                None => {
+                    pipe.reader_waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            },
+        }
+    }
+}
+
+impl AsyncWrite for SimStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let mut network = self.network.inner.lock();
+        let network = &mut *network;
+        let now = network.now;
+        let link = network.link(self.nodes[0], self.nodes[1]);
+        let Connection { pipes, rng, .. } = network
+            .connections
+            .get_mut(&self.connection)
+            .expect("Connection to exist while one of its streams is alive.");
+        let pipe = &mut pipes[self.side];
+
+        if pipe.reader_dropped || pipe.closed_at.is_some() {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        let sent_at = cmp::max(now, pipe.busy_until) + link.transmission_time(buf.len());
+        pipe.busy_until = sent_at;
+        let deliver_at = cmp::max(sent_at + link.delay(rng), pipe.last_delivery);
+        pipe.last_delivery = deliver_at;
+        pipe.chunks.push_back((deliver_at, buf.to_vec()));
+        if let Some(waker) = pipe.reader_waker.take() {
+            waker.wake();
+        }
+
+        network.schedule(deliver_at);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut network = self.network.inner.lock();
+        let network = &mut *network;
+        let now = network.now;
+        let link = network.link(self.nodes[0], self.nodes[1]);
+        let pipe = &mut network
+            .connections
+            .get_mut(&self.connection)
+            .expect("Connection to exist while one of its streams is alive.")
+            .pipes[self.side];
+
+        if pipe.closed_at.is_none() {
+            let closed_at = pipe.close(now, &link);
+            network.schedule(closed_at);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for SimStream {
+    fn drop(&mut self) {
+        let mut network = self.network.inner.lock();
+        let network = &mut *network;
+        let now = network.now;
+        let link = network.link(self.nodes[0], self.nodes[1]);
+        let connection = match network.connections.get_mut(&self.connection) {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        let closed_at = match connection.pipes[self.side].closed_at {
+            Some(_) => None,
+            None => Some(connection.pipes[self.side].close(now, &link)),
+        };
+        connection.pipes[1 - self.side].reader_dropped = true;
+        connection.dropped[self.side] = true;
+        if connection.dropped.iter().all(|dropped| *dropped) {
+            network.connections.remove(&self.connection);
+        }
+
+        if let Some(closed_at) = closed_at {
+            network.schedule(closed_at);
+        }
+    }
+}
+
+/// Future resolving once the virtual clock of a [`SimNetwork`] reached a
+/// given point in time.
+pub struct Sleep {
+    network: SimNetwork,
+    deadline: Duration,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut network = self.network.inner.lock();
+        if network.now >= self.deadline {
+            return Poll::Ready(());
+        }
+        network.register(cx);
+        Poll::Pending
+    }
+}
+
+/// The virtual clock of a [`SimNetwork`] as a [`Clock`], see [`SimNetwork::enter`].
+struct SimClock(SimNetwork);
+
+impl Clock for SimClock {
+    fn now(&self) -> Instant {
+        let network = self.0.inner.lock();
+        network.start + network.now
+    }
+
+    fn poll_deadline(&self, deadline: Instant, cx: &mut Context<'_>) -> Poll<()> {
+        let mut network = self.0.inner.lock();
+        let deadline = deadline.saturating_duration_since(network.start);
+        if network.now >= deadline {
+            return Poll::Ready(());
+        }
+        network.schedule(deadline);
+        network.register(cx);
+        Poll::Pending
+    }
+}
+
+fn ordered(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+    (cmp::min(a, b), cmp::max(a, b))
+}
+
+/// If the address is `/memory/n`, returns the value of `n`.
+fn parse_memory_addr(a: &Multiaddr) -> Option<u64> {
+    let mut protocols = a.iter();
+    match protocols.next() {
+        Some(Protocol::Memory(port)) => match protocols.next() {
+            None | Some(Protocol::P2p(_)) => Some(port),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connects `dialer` to `listener`, advancing the clock as needed.
+    fn connect(
+        network: &SimNetwork,
+        dialer: &SimTransport,
+        listener: &SimTransport,
+    ) -> (SimStream, SimStream, SimListener) {
+        let mut listener = listener
+            .clone()
+            .listen_on("/memory/0".parse().unwrap())
+            .unwrap();
+        let addr = match listener.next().now_or_never() {
+            Some(Some(Ok(ListenerEvent::NewAddress(addr)))) => addr,
+            _ => panic!("Expect listener to report its address."),
+        };
+
+        let mut dial = dialer.clone().dial(addr).unwrap();
+        let dialer_stream = loop {
+            if let Some(result) = (&mut dial).now_or_never() {
+                break result.unwrap();
+            }
+            assert!(network.advance_to_next_deadline());
+        };
+        let listener_stream = match listener.next().now_or_never() {
+            Some(Some(Ok(ListenerEvent::Upgrade { upgrade, .. }))) => {
+                upgrade.now_or_never().unwrap().unwrap()
+            }
+            _ => panic!("Expect listener to report the connection."),
+        };
+
+        (dialer_stream, listener_stream, listener)
+    }
+
+    fn try_read(stream: &mut SimStream, len: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0; len];
+        let n = stream.read(&mut buf).now_or_never()?.unwrap();
+        buf.truncate(n);
+        Some(buf)
+    }
+
+    #[test]
+    fn latency_applies_to_dials_and_writes() {
+        let latency = Duration::from_millis(100);
+        let network = SimNetwork::new(0);
+        network.set_default_link(LinkConfig::default().with_latency(latency));
+        let (a, b) = (network.node(), network.node());
+
+        let (mut dialer, mut listener, _l) = connect(&network, &a, &b);
+        assert_eq!(network.now(), latency * 2);
+
+        dialer
+            .write_all(&[1, 2, 3])
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(try_read(&mut listener, 3), None);
+
+        network.advance(latency - Duration::from_millis(1));
+        assert_eq!(try_read(&mut listener, 3), None);
+
+        network.advance(Duration::from_millis(1));
+        assert_eq!(try_read(&mut listener, 3), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn bandwidth_queues_writes() {
+        let network = SimNetwork::new(0);
+        let (a, b) = (network.node(), network.node());
+        network.set_link(
+            a.node_id(),
+            b.node_id(),
+            LinkConfig::default().with_bandwidth(NonZeroU64::new(1000).unwrap()),
+        );
+
+        let (mut dialer, mut listener, _l) = connect(&network, &a, &b);
+
+        dialer.write_all(&[0; 500]).now_or_never().unwrap().unwrap();
+        dialer.write_all(&[1; 500]).now_or_never().unwrap().unwrap();
+
+        network.advance(Duration::from_millis(499));
+        assert_eq!(try_read(&mut listener, 1000), None);
+
+        network.advance(Duration::from_millis(1));
+        assert_eq!(try_read(&mut listener, 1000), Some(vec![0; 500]));
+        assert_eq!(try_read(&mut listener, 1000), None);
+
+        assert!(network.advance_to_next_deadline());
+        assert_eq!(network.now(), Duration::from_secs(1));
+        assert_eq!(try_read(&mut listener, 1000), Some(vec![1; 500]));
+    }
+
+    #[test]
+    fn partition_refuses_dials_and_holds_back_data() {
+        let network = SimNetwork::new(0);
+        let (a, b, c) = (network.node(), network.node(), network.node());
+
+        let (mut dialer, mut listener, _l) = connect(&network, &a, &b);
+
+        network.partition([vec![a.node_id()], vec![b.node_id()]]);
+        dialer.write_all(&[1]).now_or_never().unwrap().unwrap();
+        assert_eq!(try_read(&mut listener, 1), None);
+
+        let mut c_listener = c.listen_on("/memory/0".parse().unwrap()).unwrap();
+        let c_addr = match c_listener.next().now_or_never() {
+            Some(Some(Ok(ListenerEvent::NewAddress(addr)))) => addr,
+            _ => panic!("Expect listener to report its address."),
+        };
+        assert!(matches!(
+            a.clone().dial(c_addr).unwrap().now_or_never(),
+            Some(Err(SimTransportError::Unreachable))
+        ));
+
+        network.heal();
+        assert_eq!(try_read(&mut listener, 1), Some(vec![1]));
+    }
+
+    #[test]
+    fn close_is_delivered_after_data() {
+        let latency = Duration::from_millis(10);
+        let network = SimNetwork::new(0);
+        network.set_default_link(LinkConfig::default().with_latency(latency));
+        let (a, b) = (network.node(), network.node());
+
+        let (mut dialer, mut listener, _l) = connect(&network, &a, &b);
+
+        dialer.write_all(&[1]).now_or_never().unwrap().unwrap();
+        drop(dialer);
+
+        network.advance(latency);
+        assert_eq!(try_read(&mut listener, 2), Some(vec![1]));
+        assert_eq!(try_read(&mut listener, 2), Some(vec![]));
+        assert!(listener.write_all(&[1]).now_or_never().unwrap().is_err());
+    }
+
+    #[test]
+    fn loss_is_reproducible_from_seed() {
+        fn delivery_times(seed: u64) -> Vec<Duration> {
+            let network = SimNetwork::new(seed);
+            network.set_default_link(
+                LinkConfig::default()
+                    .with_latency(Duration::from_millis(10))
+                    .with_loss(0.5),
+            );
+            let (a, b) = (network.node(), network.node());
+            let (mut dialer, mut listener, _l) = connect(&network, &a, &b);
+
+            for i in 0..50 {
+                dialer.write_all(&[i]).now_or_never().unwrap().unwrap();
+            }
+
+            let mut times = Vec::new();
+            while times.len() < 50 {
+                assert!(network.advance_to_next_deadline());
+                while let Some(data) = try_read(&mut listener, 1) {
+                    times.push(network.now());
+                    assert_eq!(data, vec![times.len() as u8 - 1]);
+                }
+            }
+            times
+        }
+
+        let times = delivery_times(42);
+        assert_eq!(times, delivery_times(42));
+        assert!(times
+            .iter()
+            .any(|t| *t > times[0] + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn sleep_follows_virtual_clock() {
+        let network = SimNetwork::new(0);
+        let mut sleep = network.sleep(Duration::from_secs(60));
+
+        assert!((&mut sleep).now_or_never().is_none());
+        assert_eq!(network.next_deadline(), Some(Duration::from_secs(60)));
+        assert!(network.advance_to_next_deadline());
+        assert!(sleep.now_or_never().is_some());
+    }
+
+    #[test]
+    fn entered_clock_drives_time() {
+        let network = SimNetwork::new(0);
+        let _clock = network.enter();
+        let start = time::now();
+        let mut delay = time::Delay::new(Duration::from_secs(60));
+
+        assert!((&mut delay).now_or_never().is_none());
+        assert_eq!(network.next_deadline(), Some(Duration::from_secs(60)));
+        assert!(network.advance_to_next_deadline());
+        assert!(delay.now_or_never().is_some());
+        assert_eq!(time::now() - start, Duration::from_secs(60));
+    }
+}
@@ -24,12 +24,12 @@
 //! underlying `Transport`.
 // TODO: add example
 
+use crate::time::Delay;
 use crate::{
     transport::{ListenerEvent, TransportError},
     Multiaddr, Transport,
 };
 use futures::prelude::*;
-use futures_timer::Delay;
 use std::{error, fmt, io, pin::Pin, task::Context, task::Poll, time::Duration};
 
 /// A `TransportTimeout` is a `Transport` that wraps another `Transport` and adds
@@ -23,15 +23,15 @@
 pub use crate::upgrade::Version;
 
 use crate::{
-    connection::ConnectedPoint,
-    muxing::{StreamMuxer, StreamMuxerBox},
+    connection::{ConnectedPoint, NegotiatedProtocols},
+    muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent},
     transport::{
         and_then::AndThen, boxed::boxed, timeout::TransportTimeout, ListenerEvent, Transport,
         TransportError,
     },
     upgrade::{
         self, apply_inbound, apply_outbound, InboundUpgrade, InboundUpgradeApply, OutboundUpgrade,
-        OutboundUpgradeApply, UpgradeError,
+        OutboundUpgradeApply, ProtocolName, UpgradeError, UpgradeInfo,
     },
     Negotiated, PeerId,
 };
@@ -64,6 +64,10 @@ use std::{
 ///   4. The [`Transport::Output`] conforms to the requirements of a `Swarm`,
 ///      namely a tuple of a [`PeerId`] (from the authentication upgrade) and a
 ///      [`StreamMuxer`] (from the multiplexing upgrade).
+///
+/// The names of the negotiated security and multiplexing protocols are
+/// recorded along the way, see [`Upgraded::negotiated_protocols`] and
+/// [`StreamMuxerBox::negotiated_protocols`].
 #[derive(Clone)]
 pub struct Builder<T> {
     inner: T,
@@ -91,11 +95,11 @@ where
     /// ## Transitions
     ///
     ///   * I/O upgrade: `C -> (PeerId, D)`.
-    ///   * Transport output: `C -> (PeerId, D)`
+    ///   * Transport output: `C -> (PeerId, Secured<D>)`
     pub fn authenticate<C, D, U, E>(
         self,
         upgrade: U,
-    ) -> Authenticated<AndThen<T, impl FnOnce(C, ConnectedPoint) -> Authenticate<C, U> + Clone>>
+    ) -> AuthenticatedAndThen<T, impl FnOnce(C, ConnectedPoint) -> Authenticate<C, U> + Clone>
     where
         T: Transport<Output = C>,
         C: AsyncRead + AsyncWrite + Unpin,
@@ -107,13 +111,47 @@ where
         let version = self.version;
         Authenticated(Builder::new(
             self.inner.and_then(move |conn, endpoint| Authenticate {
-                inner: upgrade::apply(conn, upgrade, endpoint, version),
+                inner: upgrade::apply(conn, RecordProtocol(upgrade), endpoint, version),
             }),
             version,
         ))
     }
 }
 
+/// The I/O resource of an authenticated connection, together with the name
+/// of the negotiated security protocol.
+///
+/// Produced by the transport of [`Authenticated`] and consumed by
+/// [`Authenticated::apply`] and [`Authenticated::multiplex`].
+pub struct Secured<C> {
+    io: C,
+    security: String,
+}
+
+impl<C> Secured<C> {
+    /// Returns the name of the negotiated security protocol.
+    pub fn security(&self) -> &str {
+        &self.security
+    }
+
+    /// Returns the wrapped I/O resource.
+    pub fn into_inner(self) -> C {
+        self.io
+    }
+}
+
+/// The output of the transport of [`Authenticated`].
+type SecuredOutput<C> = (PeerId, Secured<C>);
+
+/// The future returned for [`Authenticated::intercept`].
+type Intercept<C, E> = future::Ready<Result<SecuredOutput<C>, E>>;
+
+/// The transport of [`Authenticated`] with a further upgrade applied.
+type AuthenticatedAndThen<T, F> = Authenticated<AndThen<T, F>>;
+
+/// The transport of [`Multiplexed`] obtained from an [`Authenticated`] one.
+type MultiplexedAndThen<T, F> = Multiplexed<AndThen<T, F>>;
+
 /// An upgrade that authenticates the remote peer, typically
 /// in the context of negotiating a secure channel.
 ///
@@ -125,24 +163,59 @@ where
     U: InboundUpgrade<Negotiated<C>> + OutboundUpgrade<Negotiated<C>>,
 {
     #[pin]
-    inner: EitherUpgrade<C, U>,
+    inner: EitherUpgrade<C, RecordProtocol<U>>,
 }
 
-impl<C, U> Future for Authenticate<C, U>
+impl<C, D, U, E> Future for Authenticate<C, U>
 where
     C: AsyncRead + AsyncWrite + Unpin,
-    U: InboundUpgrade<Negotiated<C>>
-        + OutboundUpgrade<
-            Negotiated<C>,
-            Output = <U as InboundUpgrade<Negotiated<C>>>::Output,
-            Error = <U as InboundUpgrade<Negotiated<C>>>::Error,
-        >,
+    U: InboundUpgrade<Negotiated<C>, Output = (PeerId, D), Error = E>,
+    U: OutboundUpgrade<Negotiated<C>, Output = (PeerId, D), Error = E>,
 {
-    type Output = <EitherUpgrade<C, U> as Future>::Output;
+    type Output = Result<(PeerId, Secured<D>), UpgradeError<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        Future::poll(this.inner, cx)
+        let ((i, io), security) = ready!(Future::poll(this.inner, cx))?;
+        Poll::Ready(Ok((i, Secured { io, security })))
+    }
+}
+
+/// An upgrade applied on top of an authenticated transport.
+///
+/// Configured through [`Authenticated::apply`].
+#[pin_project::pin_project]
+pub struct Apply<C, U>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: InboundUpgrade<Negotiated<C>> + OutboundUpgrade<Negotiated<C>>,
+{
+    peer_id: Option<PeerId>,
+    security: Option<String>,
+    #[pin]
+    upgrade: EitherUpgrade<C, U>,
+}
+
+impl<C, U, D, E> Future for Apply<C, U>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: InboundUpgrade<Negotiated<C>, Output = D, Error = E>,
+    U: OutboundUpgrade<Negotiated<C>, Output = D, Error = E>,
+{
+    type Output = Result<(PeerId, Secured<D>), UpgradeError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let io = ready!(Future::poll(this.upgrade, cx))?;
+        let i = this
+            .peer_id
+            .take()
+            .expect("Apply future polled after completion.");
+        let security = this
+            .security
+            .take()
+            .expect("Apply future polled after completion.");
+        Poll::Ready(Ok((i, Secured { io, security })))
     }
 }
 
@@ -157,8 +230,9 @@ where
     U: InboundUpgrade<Negotiated<C>> + OutboundUpgrade<Negotiated<C>>,
 {
     peer_id: Option<PeerId>,
+    security: Option<String>,
     #[pin]
-    upgrade: EitherUpgrade<C, U>,
+    upgrade: EitherUpgrade<C, RecordProtocol<U>>,
 }
 
 impl<C, U, M, E> Future for Multiplex<C, U>
@@ -167,11 +241,11 @@ where
     U: InboundUpgrade<Negotiated<C>, Output = M, Error = E>,
     U: OutboundUpgrade<Negotiated<C>, Output = M, Error = E>,
 {
-    type Output = Result<(PeerId, M), UpgradeError<E>>;
+    type Output = Result<(PeerId, Upgraded<M>), UpgradeError<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        let m = match ready!(Future::poll(this.upgrade, cx)) {
+        let (muxer, muxer_protocol) = match ready!(Future::poll(this.upgrade, cx)) {
             Ok(m) => m,
             Err(err) => return Poll::Ready(Err(err)),
         };
@@ -179,7 +253,11 @@ where
             .peer_id
             .take()
             .expect("Multiplex future polled after completion.");
-        Poll::Ready(Ok((i, m)))
+        let protocols = NegotiatedProtocols {
+            security: this.security.take(),
+            muxer: Some(muxer_protocol),
+        };
+        Poll::Ready(Ok((i, Upgraded { muxer, protocols })))
     }
 }
 
@@ -201,19 +279,57 @@ where
     /// ## Transitions
     ///
     ///   * I/O upgrade: `C -> D`.
-    ///   * Transport output: `(PeerId, C) -> (PeerId, D)`.
-    pub fn apply<C, D, U, E>(self, upgrade: U) -> Authenticated<Upgrade<T, U>>
+    ///   * Transport output: `(PeerId, Secured<C>) -> (PeerId, Secured<D>)`.
+    pub fn apply<C, D, U, E>(
+        self,
+        upgrade: U,
+    ) -> AuthenticatedAndThen<T, impl FnOnce(SecuredOutput<C>, ConnectedPoint) -> Apply<C, U> + Clone>
     where
-        T: Transport<Output = (PeerId, C)>,
+        T: Transport<Output = (PeerId, Secured<C>)>,
         C: AsyncRead + AsyncWrite + Unpin,
         D: AsyncRead + AsyncWrite + Unpin,
         U: InboundUpgrade<Negotiated<C>, Output = D, Error = E>,
         U: OutboundUpgrade<Negotiated<C>, Output = D, Error = E> + Clone,
         E: Error + 'static,
     {
+        let version = self.0.version;
+        Authenticated(Builder::new(
+            self.0.inner.and_then(move |(i, c), endpoint| Apply {
+                peer_id: Some(i),
+                security: Some(c.security),
+                upgrade: upgrade::apply(c.io, upgrade, endpoint, version),
+            }),
+            version,
+        ))
+    }
+
+    /// Consults the given function once the remote has been authenticated,
+    /// before any further upgrade is applied.
+    ///
+    /// An error returned by the function aborts the upgrade of the
+    /// connection and is reported as the transport error of the connection.
+    ///
+    /// ## Transitions
+    ///
+    ///   * Transport output: `(PeerId, Secured<C>) -> (PeerId, Secured<C>)`.
+    pub fn intercept<C, F, E>(
+        self,
+        f: F,
+    ) -> AuthenticatedAndThen<
+        T,
+        impl FnOnce(SecuredOutput<C>, ConnectedPoint) -> Intercept<C, E> + Clone,
+    >
+    where
+        T: Transport<Output = (PeerId, Secured<C>)>,
+        F: FnOnce(&PeerId, &ConnectedPoint) -> Result<(), E> + Clone,
+        E: Error + 'static,
+    {
+        let version = self.0.version;
         Authenticated(Builder::new(
-            Upgrade::new(self.0.inner, upgrade),
-            self.0.version,
+            self.0
+                .inner
+                .and_then(move |(i, c), endpoint| future::ready(f(&i, &endpoint).map(|()| (i, c)))),
+            version,
         ))
     }
 
@@ -226,13 +342,16 @@ where
     /// ## Transitions
     ///
     ///   * I/O upgrade: `C -> M`.
-    ///   * Transport output: `(PeerId, C) -> (PeerId, M)`.
+    ///   * Transport output: `(PeerId, Secured<C>) -> (PeerId, Upgraded<M>)`.
     pub fn multiplex<C, M, U, E>(
         self,
         upgrade: U,
-    ) -> Multiplexed<AndThen<T, impl FnOnce((PeerId, C), ConnectedPoint) -> Multiplex<C, U> + Clone>>
+    ) -> MultiplexedAndThen<
+        T,
+        impl FnOnce(SecuredOutput<C>, ConnectedPoint) -> Multiplex<C, U> + Clone,
+    >
     where
-        T: Transport<Output = (PeerId, C)>,
+        T: Transport<Output = (PeerId, Secured<C>)>,
         C: AsyncRead + AsyncWrite + Unpin,
         M: StreamMuxer,
         U: InboundUpgrade<Negotiated<C>, Output = M, Error = E>,
@@ -241,9 +360,10 @@ where
     {
         let version = self.0.version;
         Multiplexed(self.0.inner.and_then(move |(i, c), endpoint| {
-            let upgrade = upgrade::apply(c, upgrade, endpoint, version);
+            let upgrade = upgrade::apply(c.io, RecordProtocol(upgrade), endpoint, version);
             Multiplex {
                 peer_id: Some(i),
+                security: Some(c.security),
                 upgrade,
             }
         }))
@@ -259,13 +379,16 @@ where
     /// ## Transitions
     ///
     ///   * I/O upgrade: `C -> M`.
-    ///   * Transport output: `(PeerId, C) -> (PeerId, M)`.
+    ///   * Transport output: `(PeerId, Secured<C>) -> (PeerId, Upgraded<M>)`.
     pub fn multiplex_ext<C, M, U, E, F>(
         self,
         up: F,
-    ) -> Multiplexed<AndThen<T, impl FnOnce((PeerId, C), ConnectedPoint) -> Multiplex<C, U> + Clone>>
+    ) -> MultiplexedAndThen<
+        T,
+        impl FnOnce(SecuredOutput<C>, ConnectedPoint) -> Multiplex<C, U> + Clone,
+    >
     where
-        T: Transport<Output = (PeerId, C)>,
+        T: Transport<Output = (PeerId, Secured<C>)>,
         C: AsyncRead + AsyncWrite + Unpin,
         M: StreamMuxer,
         U: InboundUpgrade<Negotiated<C>, Output = M, Error = E>,
@@ -275,9 +398,11 @@ where
     {
         let version = self.0.version;
         Multiplexed(self.0.inner.and_then(move |(peer_id, c), endpoint| {
-            let upgrade = upgrade::apply(c, up(&peer_id, &endpoint), endpoint, version);
+            let upgrade = RecordProtocol(up(&peer_id, &endpoint));
+            let upgrade = upgrade::apply(c.io, upgrade, endpoint, version);
             Multiplex {
                 peer_id: Some(peer_id),
+                security: Some(c.security),
                 upgrade,
             }
         }))
@@ -294,7 +419,7 @@ impl<T> Multiplexed<T> {
     /// the [`StreamMuxer`] and custom transport errors.
     pub fn boxed<M>(self) -> super::Boxed<(PeerId, StreamMuxerBox)>
     where
-        T: Transport<Output = (PeerId, M)> + Sized + Clone + Send + Sync + 'static,
+        T: Transport<Output = (PeerId, Upgraded<M>)> + Sized + Clone + Send + Sync + 'static,
         T::Dial: Send + 'static,
         T::Listener: Send + 'static,
         T::ListenerUpgrade: Send + 'static,
@@ -303,7 +428,10 @@ impl<T> Multiplexed<T> {
         M::Substream: Send + 'static,
         M::OutboundSubstream: Send + 'static,
     {
-        boxed(self.map(|(i, m), _| (i, StreamMuxerBox::new(m))))
+        boxed(self.map(|(i, m), _| {
+            let muxer = StreamMuxerBox::new(m.muxer).with_negotiated_protocols(m.protocols);
+            (i, muxer)
+        }))
     }
 
     /// Adds a timeout to the setup and protocol upgrade process for all
@@ -352,6 +480,164 @@ where
     }
 }
 
+/// A [`StreamMuxer`] produced by a [`Multiplexed`] transport, together with the
+/// protocols negotiated while upgrading its connection.
+pub struct Upgraded<M> {
+    muxer: M,
+    protocols: NegotiatedProtocols,
+}
+
+impl<M> Upgraded<M> {
+    /// Returns the protocols negotiated while upgrading the connection.
+    pub fn negotiated_protocols(&self) -> &NegotiatedProtocols {
+        &self.protocols
+    }
+
+    /// Returns the wrapped [`StreamMuxer`].
+    pub fn into_inner(self) -> M {
+        self.muxer
+    }
+}
+
+impl<M: StreamMuxer> StreamMuxer for Upgraded<M> {
+    type Substream = M::Substream;
+    type OutboundSubstream = M::OutboundSubstream;
+    type Error = M::Error;
+
+    fn poll_event(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent<Self::Substream>, Self::Error>> {
+        self.muxer.poll_event(cx)
+    }
+
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.muxer.open_outbound()
+    }
+
+    fn poll_outbound(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::OutboundSubstream,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        self.muxer.poll_outbound(cx, s)
+    }
+
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.muxer.destroy_outbound(s)
+    }
+
+    fn read_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        self.muxer.read_substream(cx, s, buf)
+    }
+
+    fn write_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        self.muxer.write_substream(cx, s, buf)
+    }
+
+    fn flush_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.muxer.flush_substream(cx, s)
+    }
+
+    fn shutdown_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.muxer.shutdown_substream(cx, s)
+    }
+
+    fn destroy_substream(&self, s: Self::Substream) {
+        self.muxer.destroy_substream(s)
+    }
+
+    fn close(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.muxer.close(cx)
+    }
+
+    fn flush_all(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.muxer.flush_all(cx)
+    }
+}
+
+/// An upgrade recording the name of the protocol it negotiated alongside the
+/// output of the wrapped upgrade.
+#[derive(Clone)]
+struct RecordProtocol<U>(U);
+
+impl<U: UpgradeInfo> UpgradeInfo for RecordProtocol<U> {
+    type Info = U::Info;
+    type InfoIter = U::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.0.protocol_info()
+    }
+}
+
+impl<C, U: InboundUpgrade<C>> InboundUpgrade<C> for RecordProtocol<U> {
+    type Output = (U::Output, String);
+    type Error = U::Error;
+    type Future = RecordProtocolFuture<U::Future>;
+
+    fn upgrade_inbound(self, socket: C, info: Self::Info) -> Self::Future {
+        RecordProtocolFuture {
+            protocol: Some(String::from_utf8_lossy(info.protocol_name()).into_owned()),
+            inner: self.0.upgrade_inbound(socket, info),
+        }
+    }
+}
+
+impl<C, U: OutboundUpgrade<C>> OutboundUpgrade<C> for RecordProtocol<U> {
+    type Output = (U::Output, String);
+    type Error = U::Error;
+    type Future = RecordProtocolFuture<U::Future>;
+
+    fn upgrade_outbound(self, socket: C, info: Self::Info) -> Self::Future {
+        RecordProtocolFuture {
+            protocol: Some(String::from_utf8_lossy(info.protocol_name()).into_owned()),
+            inner: self.0.upgrade_outbound(socket, info),
+        }
+    }
+}
+
+#[pin_project::pin_project]
+struct RecordProtocolFuture<F> {
+    #[pin]
+    inner: F,
+    protocol: Option<String>,
+}
+
+impl<F, O, E> Future for RecordProtocolFuture<F>
+where
+    F: Future<Output = Result<O, E>>,
+{
+    type Output = Result<(O, String), E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = ready!(this.inner.poll(cx))?;
+        let protocol = this
+            .protocol
+            .take()
+            .expect("RecordProtocolFuture polled after completion.");
+        Poll::Ready(Ok((output, protocol)))
+    }
+}
+
 /// An inbound or outbound upgrade.
 type EitherUpgrade<C, U> = future::Either<InboundUpgradeApply<C, U>, OutboundUpgradeApply<C, U>>;
 
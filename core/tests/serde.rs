@@ -2,7 +2,7 @@
 
 use std::str::FromStr;
 
-use libp2p_core::PeerId;
+use libp2p_core::{multibase::Base, PeerId};
 
 extern crate _serde as serde;
 
@@ -50,3 +50,13 @@ pub fn deserialize_peer_id_msgpack() {
 
     assert_eq!(peer_id, rmp_serde::from_read(&mut &buf[..]).unwrap());
 }
+
+#[test]
+pub fn deserialize_peer_id_cid_json() {
+    let peer_id = PeerId::from_str("12D3KooWRNw2pJC9748Fmq4WNV27HoSTcX3r37132FLkQMrbKAiC").unwrap();
+
+    for base in [Base::Base32Lower, Base::Base36Lower] {
+        let json = format!(r#""{}""#, peer_id.to_cid_string(base));
+        assert_eq!(peer_id, serde_json::from_str(&json).unwrap())
+    }
+}
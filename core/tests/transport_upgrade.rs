@@ -133,8 +133,11 @@ fn upgrade_pipeline() {
     };
 
     let client = async move {
-        let (peer, _mplex) = dialer_transport.dial(listen_addr2).unwrap().await.unwrap();
+        let (peer, mplex) = dialer_transport.dial(listen_addr2).unwrap().await.unwrap();
         assert_eq!(peer, listener_id);
+        let protocols = mplex.negotiated_protocols();
+        assert_eq!(protocols.security.as_deref(), Some("/noise"));
+        assert_eq!(protocols.muxer.as_deref(), Some("/mplex/6.7.0"));
     };
 
     async_std::task::spawn(server);
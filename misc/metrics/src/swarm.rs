@@ -29,6 +29,8 @@ pub struct Metrics {
 
     connections_established: Family<ConnectionEstablishedLabels, Counter>,
     connections_closed: Family<ConnectionClosedLabels, Counter>,
+    connections_trimmed: Counter,
+    inbound_stream_limit_exceeded: Counter,
 
     new_listen_addr: Counter,
     expired_listen_addr: Counter,
@@ -39,6 +41,8 @@ pub struct Metrics {
     dial_attempt: Counter,
     outgoing_connection_error: Family<OutgoingConnectionErrorLabels, Counter>,
     connected_to_banned_peer: Counter,
+    connected_from_banned_ip: Counter,
+    ban_expired: Counter,
 }
 
 impl Metrics {
@@ -108,6 +112,20 @@ impl Metrics {
             Box::new(connected_to_banned_peer.clone()),
         );
 
+        let connected_from_banned_ip = Counter::default();
+        sub_registry.register(
+            "connected_from_banned_ip",
+            "Number of incoming connections rejected due to a banned IP address",
+            Box::new(connected_from_banned_ip.clone()),
+        );
+
+        let ban_expired = Counter::default();
+        sub_registry.register(
+            "ban_expired",
+            "Number of expired bans of peers and IP networks",
+            Box::new(ban_expired.clone()),
+        );
+
         let connections_established = Family::default();
         sub_registry.register(
             "connections_established",
@@ -122,11 +140,27 @@ impl Metrics {
             Box::new(connections_closed.clone()),
         );
 
+        let connections_trimmed = Counter::default();
+        sub_registry.register(
+            "connections_trimmed",
+            "Number of connections trimmed by the connection manager",
+            Box::new(connections_trimmed.clone()),
+        );
+
+        let inbound_stream_limit_exceeded = Counter::default();
+        sub_registry.register(
+            "inbound_stream_limit_exceeded",
+            "Number of inbound substreams rejected due to an inbound stream limit",
+            Box::new(inbound_stream_limit_exceeded.clone()),
+        );
+
         Self {
             connections_incoming,
             connections_incoming_error,
             connections_established,
             connections_closed,
+            connections_trimmed,
+            inbound_stream_limit_exceeded,
             new_listen_addr,
             expired_listen_addr,
             listener_closed,
@@ -134,6 +168,8 @@ impl Metrics {
             dial_attempt,
             outgoing_connection_error,
             connected_to_banned_peer,
+            connected_from_banned_ip,
+            ban_expired,
         }
     }
 }
@@ -144,11 +180,21 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
     fn record(&self, event: &libp2p_swarm::SwarmEvent<TBvEv, THandleErr>) {
         match event {
             libp2p_swarm::SwarmEvent::Behaviour(_) => {}
-            libp2p_swarm::SwarmEvent::ConnectionEstablished { endpoint, .. } => {
+            libp2p_swarm::SwarmEvent::ConnectionEstablished {
+                endpoint,
+                negotiated_protocols,
+                ..
+            } => {
+                let unknown = || "unknown".to_string();
                 self.swarm
                     .connections_established
                     .get_or_create(&ConnectionEstablishedLabels {
                         role: endpoint.into(),
+                        security: negotiated_protocols
+                            .security
+                            .clone()
+                            .unwrap_or_else(unknown),
+                        muxer: negotiated_protocols.muxer.clone().unwrap_or_else(unknown),
                     })
                     .inc();
             }
@@ -225,11 +271,35 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
                     libp2p_swarm::DialError::ConnectionIo(_) => {
                         record(OutgoingConnectionErrorError::ConnectionIo)
                     }
+                    libp2p_swarm::DialError::DeniedDial => {
+                        record(OutgoingConnectionErrorError::DeniedDial)
+                    }
+                    libp2p_swarm::DialError::Backoff { .. } => {
+                        record(OutgoingConnectionErrorError::Backoff)
+                    }
+                    libp2p_swarm::DialError::DeniedSecured { .. } => {
+                        record(OutgoingConnectionErrorError::DeniedSecured)
+                    }
+                    libp2p_swarm::DialError::DeniedUpgraded { .. } => {
+                        record(OutgoingConnectionErrorError::DeniedUpgraded)
+                    }
                 };
             }
+            libp2p_swarm::SwarmEvent::ConnectionTrimmed { .. } => {
+                self.swarm.connections_trimmed.inc();
+            }
+            libp2p_swarm::SwarmEvent::InboundStreamLimitExceeded { .. } => {
+                self.swarm.inbound_stream_limit_exceeded.inc();
+            }
             libp2p_swarm::SwarmEvent::BannedPeer { .. } => {
                 self.swarm.connected_to_banned_peer.inc();
             }
+            libp2p_swarm::SwarmEvent::BannedIp { .. } => {
+                self.swarm.connected_from_banned_ip.inc();
+            }
+            libp2p_swarm::SwarmEvent::BanExpired { .. } => {
+                self.swarm.ban_expired.inc();
+            }
             libp2p_swarm::SwarmEvent::NewListenAddr { .. } => {
                 self.swarm.new_listen_addr.inc();
             }
@@ -252,6 +322,8 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
 #[derive(Encode, Hash, Clone, Eq, PartialEq)]
 struct ConnectionEstablishedLabels {
     role: Role,
+    security: String,
+    muxer: String,
 }
 
 #[derive(Encode, Hash, Clone, Eq, PartialEq)]
@@ -299,6 +371,10 @@ enum OutgoingConnectionErrorError {
     ConnectionIo,
     TransportMultiaddrNotSupported,
     TransportOther,
+    DeniedDial,
+    Backoff,
+    DeniedSecured,
+    DeniedUpgraded,
 }
 
 #[derive(Encode, Hash, Clone, Eq, PartialEq)]
@@ -314,6 +390,7 @@ enum PendingInboundConnectionError {
     Aborted,
     Io,
     ConnectionLimit,
+    Denied,
 }
 
 impl<TTransErr> From<&libp2p_swarm::PendingInboundConnectionError<TTransErr>>
@@ -337,6 +414,12 @@ impl<TTransErr> From<&libp2p_swarm::PendingInboundConnectionError<TTransErr>>
                 PendingInboundConnectionError::Aborted
             }
             libp2p_swarm::PendingInboundConnectionError::IO(_) => PendingInboundConnectionError::Io,
+            libp2p_swarm::PendingInboundConnectionError::DeniedDial
+            | libp2p_swarm::PendingInboundConnectionError::DeniedAccept
+            | libp2p_swarm::PendingInboundConnectionError::DeniedSecured { .. }
+            | libp2p_swarm::PendingInboundConnectionError::DeniedUpgraded { .. } => {
+                PendingInboundConnectionError::Denied
+            }
         }
     }
 }
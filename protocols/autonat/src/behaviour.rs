@@ -26,11 +26,10 @@ use as_client::AsClient;
 pub use as_client::{OutboundProbeError, OutboundProbeEvent};
 use as_server::AsServer;
 pub use as_server::{InboundProbeError, InboundProbeEvent};
-use futures_timer::Delay;
-use instant::Instant;
+use libp2p_core::time::{Delay, Instant};
 use libp2p_core::{
     connection::{ConnectionId, ListenerId},
-    ConnectedPoint, Endpoint, Multiaddr, PeerId,
+    ConnectedPoint, Endpoint, Multiaddr, NegotiatedProtocols, PeerId,
 };
 use libp2p_request_response::{
     handler::RequestResponseHandlerEvent, ProtocolSupport, RequestId, RequestResponse,
@@ -302,6 +301,7 @@ impl NetworkBehaviour for Behaviour {
         peer: &PeerId,
         conn: &ConnectionId,
         endpoint: &ConnectedPoint,
+        negotiated_protocols: &NegotiatedProtocols,
         failed_addresses: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -309,6 +309,7 @@ impl NetworkBehaviour for Behaviour {
             peer,
             conn,
             endpoint,
+            negotiated_protocols,
             failed_addresses,
             other_established,
         );
@@ -414,6 +415,11 @@ impl NetworkBehaviour for Behaviour {
         self.as_client().on_expired_address(addr);
     }
 
+    fn inject_new_external_addr_candidate(&mut self, addr: &Multiaddr) {
+        self.inner.inject_new_external_addr_candidate(addr);
+        self.as_client().on_new_address();
+    }
+
     fn poll(&mut self, cx: &mut Context<'_>, params: &mut impl PollParameters) -> Poll<Action> {
         loop {
             if let Some(event) = self.pending_out_events.pop_front() {
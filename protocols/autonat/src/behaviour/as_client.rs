@@ -25,13 +25,12 @@ use super::{
     ProbeId,
 };
 use futures::FutureExt;
-use futures_timer::Delay;
-use instant::Instant;
+use libp2p_core::time::{self, Delay, Instant};
 use libp2p_core::{connection::ConnectionId, Multiaddr, PeerId};
 use libp2p_request_response::{
     OutboundFailure, RequestId, RequestResponse, RequestResponseEvent, RequestResponseMessage,
 };
-use libp2p_swarm::{AddressScore, NetworkBehaviourAction, PollParameters};
+use libp2p_swarm::{NetworkBehaviourAction, PollParameters};
 use rand::{seq::SliceRandom, thread_rng};
 use std::{
     collections::{HashMap, VecDeque},
@@ -104,7 +103,7 @@ pub struct AsClient<'a> {
 impl<'a> HandleInnerEvent for AsClient<'a> {
     fn handle_event(
         &mut self,
-        params: &mut impl PollParameters,
+        _params: &mut impl PollParameters,
         event: RequestResponseEvent<DialRequest, DialResponse>,
     ) -> (VecDeque<Event>, Option<Action>) {
         let mut events = VecDeque::new();
@@ -147,17 +146,8 @@ impl<'a> HandleInnerEvent for AsClient<'a> {
                 }
 
                 if let Ok(address) = response.result {
-                    // Update observed address score if it is finite.
-                    let score = params
-                        .external_addresses()
-                        .find_map(|r| (r.addr == address).then(|| r.score))
-                        .unwrap_or(AddressScore::Finite(0));
-                    if let AddressScore::Finite(finite_score) = score {
-                        action = Some(NetworkBehaviourAction::ReportObservedAddr {
-                            address,
-                            score: AddressScore::Finite(finite_score + 1),
-                        });
-                    }
+                    // The remote dialed us back on the address, thus confirming it.
+                    action = Some(NetworkBehaviourAction::ConfirmExternalAddr { address });
                 }
             }
             RequestResponseEvent::OutboundFailure {
@@ -200,6 +190,7 @@ impl<'a> AsClient<'a> {
                 self.schedule_probe.reset(self.config.retry_interval);
 
                 let mut addresses: Vec<_> = params.external_addresses().map(|r| r.addr).collect();
+                addresses.extend(params.external_address_candidates());
                 addresses.extend(params.listened_addresses());
 
                 let probe_id = self.probe_id.next();
@@ -254,9 +245,9 @@ impl<'a> AsClient<'a> {
     // Select a random server for the probe.
     fn random_server(&mut self) -> Option<PeerId> {
         // Update list of throttled servers.
-        let i = self.throttled_servers.partition_point(|(_, time)| {
-            *time + self.config.throttle_server_period < Instant::now()
-        });
+        let i = self
+            .throttled_servers
+            .partition_point(|(_, time)| *time + self.config.throttle_server_period < time::now());
         self.throttled_servers.drain(..i);
 
         let mut servers: Vec<&PeerId> = self.servers.iter().collect();
@@ -278,7 +269,7 @@ impl<'a> AsClient<'a> {
         probe_id: ProbeId,
         addresses: Vec<Multiaddr>,
     ) -> Result<PeerId, OutboundProbeError> {
-        let _ = self.last_probe.insert(Instant::now());
+        let _ = self.last_probe.insert(time::now());
         if addresses.is_empty() {
             log::debug!("Outbound dial-back request aborted: No dial-back addresses.");
             return Err(OutboundProbeError::NoAddresses);
@@ -297,7 +288,7 @@ impl<'a> AsClient<'a> {
                 addresses,
             },
         );
-        self.throttled_servers.push((server, Instant::now()));
+        self.throttled_servers.push((server, time::now()));
         log::debug!("Send dial-back request to peer {}.", server);
         self.ongoing_outbound.insert(request_id, probe_id);
         Ok(server)
@@ -314,7 +305,7 @@ impl<'a> AsClient<'a> {
         };
         let schedule_next = *last_probe_instant + delay;
         self.schedule_probe
-            .reset(schedule_next.saturating_duration_since(Instant::now()));
+            .reset(schedule_next.saturating_duration_since(time::now()));
     }
 
     // Adapt current confidence and NAT status to the status reported by the latest probe.
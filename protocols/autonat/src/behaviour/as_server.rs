@@ -22,7 +22,7 @@ use super::{
     Action, AutoNatCodec, Config, DialRequest, DialResponse, Event, HandleInnerEvent, ProbeId,
     ResponseError,
 };
-use instant::Instant;
+use libp2p_core::time::{self, Instant};
 use libp2p_core::{connection::ConnectionId, multiaddr::Protocol, Multiaddr, PeerId};
 use libp2p_request_response::{
     InboundFailure, RequestId, RequestResponse, RequestResponseEvent, RequestResponseMessage,
@@ -123,7 +123,7 @@ impl<'a> HandleInnerEvent for AsServer<'a> {
 
                         self.ongoing_inbound
                             .insert(peer, (probe_id, request_id, addrs.clone(), channel));
-                        self.throttled_clients.push((peer, Instant::now()));
+                        self.throttled_clients.push((peer, time::now()));
 
                         events.push_back(Event::InboundProbe(InboundProbeEvent::Request {
                             probe_id,
@@ -256,9 +256,9 @@ impl<'a> AsServer<'a> {
         request: DialRequest,
     ) -> Result<Vec<Multiaddr>, (String, ResponseError)> {
         // Update list of throttled clients.
-        let i = self.throttled_clients.partition_point(|(_, time)| {
-            *time + self.config.throttle_clients_period < Instant::now()
-        });
+        let i = self
+            .throttled_clients
+            .partition_point(|(_, time)| *time + self.config.throttle_clients_period < time::now());
         self.throttled_clients.drain(..i);
 
         if request.peer_id != sender {
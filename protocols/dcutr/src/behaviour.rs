@@ -23,7 +23,7 @@
 use crate::handler;
 use crate::protocol;
 use either::Either;
-use libp2p_core::connection::{ConnectedPoint, ConnectionId};
+use libp2p_core::connection::{ConnectedPoint, ConnectionId, NegotiatedProtocols};
 use libp2p_core::multiaddr::Protocol;
 use libp2p_core::{Multiaddr, PeerId};
 use libp2p_swarm::dial_opts::{self, DialOpts};
@@ -99,6 +99,7 @@ impl NetworkBehaviour for Behaviour {
         peer_id: &PeerId,
         connection_id: &ConnectionId,
         connected_point: &ConnectedPoint,
+        _negotiated_protocols: &NegotiatedProtocols,
         _failed_addresses: Option<&Vec<Multiaddr>>,
         _other_established: usize,
     ) {
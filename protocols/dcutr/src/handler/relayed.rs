@@ -23,9 +23,9 @@
 use crate::protocol;
 use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
-use instant::Instant;
 use libp2p_core::either::{EitherError, EitherOutput};
 use libp2p_core::multiaddr::Multiaddr;
+use libp2p_core::time;
 use libp2p_core::upgrade::{self, DeniedUpgrade, NegotiationError, UpgradeError};
 use libp2p_core::ConnectedPoint;
 use libp2p_swarm::handler::{InboundUpgradeSend, OutboundUpgradeSend};
@@ -158,7 +158,7 @@ impl Handler {
             pending_error: Default::default(),
             queued_events: Default::default(),
             inbound_connects: Default::default(),
-            keep_alive: KeepAlive::Until(Instant::now() + Duration::from_secs(30)),
+            keep_alive: KeepAlive::Until(time::now() + Duration::from_secs(30)),
         }
     }
 }
@@ -21,12 +21,11 @@
 use crate::message_proto::{hole_punch, HolePunch};
 use asynchronous_codec::Framed;
 use futures::{future::BoxFuture, prelude::*};
-use futures_timer::Delay;
+use libp2p_core::time::{self, Delay};
 use libp2p_core::{multiaddr::Protocol, upgrade, Multiaddr};
 use libp2p_swarm::NegotiatedSubstream;
 use std::convert::TryFrom;
 use std::iter;
-use std::time::Instant;
 use thiserror::Error;
 
 pub struct Upgrade {
@@ -64,7 +63,7 @@ impl upgrade::OutboundUpgrade<NegotiatedSubstream> for Upgrade {
         async move {
             substream.send(msg).await?;
 
-            let sent_time = Instant::now();
+            let sent_time = time::now();
 
             let HolePunch { r#type, obs_addrs } =
                 substream
@@ -27,7 +27,7 @@ use crate::FloodsubConfig;
 use cuckoofilter::{CuckooError, CuckooFilter};
 use fnv::FnvHashSet;
 use libp2p_core::{connection::ConnectionId, PeerId};
-use libp2p_core::{ConnectedPoint, Multiaddr};
+use libp2p_core::{ConnectedPoint, Multiaddr, NegotiatedProtocols};
 use libp2p_swarm::{
     dial_opts::{self, DialOpts},
     NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, OneShotHandler, PollParameters,
@@ -293,6 +293,7 @@ impl NetworkBehaviour for Floodsub {
         id: &PeerId,
         _: &ConnectionId,
         _: &ConnectedPoint,
+        _: &NegotiatedProtocols,
         _: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -20,13 +20,13 @@
 
 //! Data structure for efficiently storing known back-off's when pruning peers.
 use crate::topic::TopicHash;
-use libp2p_core::PeerId;
-use std::collections::{
-    hash_map::{Entry, HashMap},
-    HashSet,
-};
-use std::time::Duration;
-use wasm_timer::Instant;
+use libp2p_core::time::{self, Instant};
+use libp2p_core::PeerId;
+use std::collections::{
+    hash_map::{Entry, HashMap},
+    HashSet,
+};
+use std::time::Duration;
 
 #[derive(Copy, Clone)]
 struct HeartbeatIndex(usize);
@@ -72,7 +72,7 @@ impl BackoffStorage {
     /// Updates the backoff for a peer (if there is already a more restrictive backoff then this call
     /// doesn't change anything).
     pub fn update_backoff(&mut self, topic: &TopicHash, peer: &PeerId, time: Duration) {
-        let instant = Instant::now() + time;
+        let instant = time::now() + time;
         let insert_into_backoffs_by_heartbeat =
             |heartbeat_index: HeartbeatIndex,
              backoffs_by_heartbeat: &mut Vec<HashSet<_>>,
@@ -154,7 +154,7 @@ impl BackoffStorage {
         if let Some(s) = self.backoffs_by_heartbeat.get_mut(self.heartbeat_index.0) {
             let backoffs = &mut self.backoffs;
             let slack = self.heartbeat_interval * self.backoff_slack;
-            let now = Instant::now();
+            let now = time::now();
             s.retain(|(topic, peer)| {
                 let keep = match Self::get_backoff_time_from_backoffs(backoffs, topic, peer) {
                     Some(backoff_time) => backoff_time + slack > now,
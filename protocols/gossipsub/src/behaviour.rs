@@ -36,15 +36,15 @@ use prometheus_client::registry::Registry;
 use prost::Message;
 use rand::{seq::SliceRandom, thread_rng};
 
+use libp2p_core::time::{self, Instant, Interval};
 use libp2p_core::{
     connection::ConnectionId, identity::Keypair, multiaddr::Protocol::Ip4,
-    multiaddr::Protocol::Ip6, ConnectedPoint, Multiaddr, PeerId,
+    multiaddr::Protocol::Ip6, ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId,
 };
 use libp2p_swarm::{
     dial_opts::{self, DialOpts},
     IntoConnectionHandler, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
 };
-use wasm_timer::Instant;
 
 use crate::backoff::BackoffStorage;
 use crate::config::{GossipsubConfig, ValidationMode};
@@ -66,7 +66,6 @@ use crate::types::{
 use crate::types::{GossipsubRpc, PeerConnections, PeerKind};
 use crate::{rpc_proto, TopicScoreParams};
 use std::{cmp::Ordering::Equal, fmt::Debug};
-use wasm_timer::Interval;
 
 #[cfg(test)]
 mod tests;
@@ -442,7 +441,7 @@ where
             ),
             mcache: MessageCache::new(config.history_gossip(), config.history_length()),
             heartbeat: Interval::new_at(
-                Instant::now() + config.heartbeat_initial_delay(),
+                time::now() + config.heartbeat_initial_delay(),
                 config.heartbeat_interval(),
             ),
             heartbeat_ticks: 0,
@@ -704,8 +703,7 @@ where
                         }
                     }
                     // We are publishing to fanout peers - update the time we published
-                    self.fanout_last_pub
-                        .insert(topic_hash.clone(), Instant::now());
+                    self.fanout_last_pub.insert(topic_hash.clone(), time::now());
                 }
             }
         }
@@ -1277,7 +1275,7 @@ where
                 gossip_promises.add_promise(
                     *peer_id,
                     &iwant_ids_vec,
-                    Instant::now() + self.config.iwant_followup_time(),
+                    time::now() + self.config.iwant_followup_time(),
                 );
             }
             trace!(
@@ -1391,7 +1389,7 @@ where
             do_px = false
         } else {
             let (below_zero, score) = self.score_below_threshold(peer_id, |_| 0.0);
-            let now = Instant::now();
+            let now = time::now();
             for topic_hash in topics {
                 if let Some(peers) = self.mesh.get_mut(&topic_hash) {
                     // if the peer is already in the mesh ignore the graft
@@ -2105,7 +2103,7 @@ where
     /// Heartbeat function which shifts the memcache and updates the mesh.
     fn heartbeat(&mut self) {
         debug!("Starting heartbeat");
-        let start = Instant::now();
+        let start = time::now();
 
         self.heartbeat_ticks += 1;
 
@@ -2391,7 +2389,7 @@ where
             let fanout = &mut self.fanout; // help the borrow checker
             let fanout_ttl = self.config.fanout_ttl();
             self.fanout_last_pub.retain(|topic_hash, last_pub_time| {
-                if *last_pub_time + fanout_ttl < Instant::now() {
+                if *last_pub_time + fanout_ttl < time::now() {
                     debug!(
                         "HEARTBEAT: Fanout topic removed due to timeout. Topic: {:?}",
                         topic_hash
@@ -3064,6 +3062,7 @@ where
         peer_id: &PeerId,
         connection_id: &ConnectionId,
         endpoint: &ConnectedPoint,
+        _: &NegotiatedProtocols,
         _: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -199,6 +199,7 @@ mod tests {
                     send_back_addr: address,
                 }
             },
+            &NegotiatedProtocols::default(),
             None,
             0, // first connection
         );
@@ -565,6 +566,7 @@ mod tests {
                     address: "/ip4/127.0.0.1".parse::<Multiaddr>().unwrap(),
                     role_override: Endpoint::Dialer,
                 },
+                &NegotiatedProtocols::default(),
                 None,
                 0,
             );
@@ -4178,6 +4180,7 @@ mod tests {
                     address: addr.clone(),
                     role_override: Endpoint::Dialer,
                 },
+                &NegotiatedProtocols::default(),
                 None,
                 0,
             );
@@ -4199,6 +4202,7 @@ mod tests {
                     address: addr2.clone(),
                     role_override: Endpoint::Dialer,
                 },
+                &NegotiatedProtocols::default(),
                 None,
                 1,
             );
@@ -4229,6 +4233,7 @@ mod tests {
                 address: addr.clone(),
                 role_override: Endpoint::Dialer,
             },
+            &NegotiatedProtocols::default(),
             None,
             2,
         );
@@ -21,10 +21,10 @@
 use crate::error::ValidationError;
 use crate::peer_score::RejectReason;
 use crate::MessageId;
+use libp2p_core::time::{self, Instant};
 use libp2p_core::PeerId;
 use log::debug;
 use std::collections::HashMap;
-use wasm_timer::Instant;
 
 /// Tracks recently sent `IWANT` messages and checks if peers respond to them.
 #[derive(Default)]
@@ -78,7 +78,7 @@ impl GossipPromises {
     /// This should be called not too often relative to the expire times, since it iterates over
     /// the whole stored data.
     pub fn get_broken_promises(&mut self) -> HashMap<PeerId, usize> {
-        let now = Instant::now();
+        let now = time::now();
         let mut result = HashMap::new();
         self.promises.retain(|msg, peers| {
             peers.retain(|peer_id, expires| {
@@ -25,7 +25,7 @@ use crate::types::{GossipsubRpc, PeerKind, RawGossipsubMessage};
 use asynchronous_codec::Framed;
 use futures::prelude::*;
 use futures::StreamExt;
-use instant::Instant;
+use libp2p_core::time;
 use libp2p_core::upgrade::{InboundUpgrade, NegotiationError, OutboundUpgrade, UpgradeError};
 use libp2p_swarm::handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
@@ -191,7 +191,7 @@ impl GossipsubHandler {
             protocol_unsupported: false,
             idle_timeout,
             upgrade_errors: VecDeque::new(),
-            keep_alive: KeepAlive::Until(Instant::now() + Duration::from_secs(INITIAL_KEEP_ALIVE)),
+            keep_alive: KeepAlive::Until(time::now() + Duration::from_secs(INITIAL_KEEP_ALIVE)),
             in_mesh: false,
         }
     }
@@ -277,7 +277,7 @@ impl ConnectionHandler for GossipsubHandler {
                 // If we have left the mesh, start the idle timer.
                 GossipsubHandlerIn::LeftMesh => {
                     self.in_mesh = false;
-                    self.keep_alive = KeepAlive::Until(Instant::now() + self.idle_timeout);
+                    self.keep_alive = KeepAlive::Until(time::now() + self.idle_timeout);
                 }
             }
         }
@@ -317,6 +317,8 @@ impl ConnectionHandler for GossipsubHandler {
                 ConnectionHandlerUpgrErr::Timeout | ConnectionHandlerUpgrErr::Timer => {
                     Some(GossipsubHandlerError::NegotiationTimeout)
                 }
+                // The substream was reset, the connection remains usable.
+                ConnectionHandlerUpgrErr::LimitExceeded(_) => None,
                 // There was an error post negotiation, close the connection.
                 ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => Some(e),
                 ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(negotiation_error)) => {
@@ -394,8 +396,7 @@ impl ConnectionHandler for GossipsubHandler {
                     match substream.poll_next_unpin(cx) {
                         Poll::Ready(Some(Ok(message))) => {
                             if !self.in_mesh {
-                                self.keep_alive =
-                                    KeepAlive::Until(Instant::now() + self.idle_timeout);
+                                self.keep_alive = KeepAlive::Until(time::now() + self.idle_timeout);
                             }
                             self.inbound_substream =
                                 Some(InboundSubstreamState::WaitingInput(substream));
@@ -518,8 +519,7 @@ impl ConnectionHandler for GossipsubHandler {
                         Poll::Ready(Ok(())) => {
                             if !self.in_mesh {
                                 // if not in the mesh, reset the idle timeout
-                                self.keep_alive =
-                                    KeepAlive::Until(Instant::now() + self.idle_timeout);
+                                self.keep_alive = KeepAlive::Until(time::now() + self.idle_timeout);
                             }
                             self.outbound_substream =
                                 Some(OutboundSubstreamState::WaitingOutput(substream))
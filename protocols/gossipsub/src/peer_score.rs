@@ -24,12 +24,12 @@
 use crate::metrics::{Metrics, Penalty};
 use crate::time_cache::TimeCache;
 use crate::{MessageId, TopicHash};
+use libp2p_core::time::{self, Instant};
 use libp2p_core::PeerId;
 use log::{debug, trace, warn};
 use std::collections::{hash_map, HashMap, HashSet};
 use std::net::IpAddr;
 use std::time::Duration;
-use wasm_timer::Instant;
 
 mod params;
 use crate::error::ValidationError;
@@ -145,7 +145,7 @@ impl MeshStatus {
     /// Initialises a new [`MeshStatus::Active`] mesh status.
     pub fn new_active() -> Self {
         MeshStatus::Active {
-            graft_time: Instant::now(),
+            graft_time: time::now(),
             mesh_time: Duration::from_secs(0),
         }
     }
@@ -187,7 +187,7 @@ impl Default for DeliveryRecord {
     fn default() -> Self {
         DeliveryRecord {
             status: DeliveryStatus::Unknown,
-            first_seen: Instant::now(),
+            first_seen: time::now(),
             peers: HashSet::new(),
         }
     }
@@ -368,7 +368,7 @@ impl PeerScore {
     }
 
     pub fn refresh_scores(&mut self) {
-        let now = Instant::now();
+        let now = time::now();
         let params_ref = &self.params;
         let peer_ips_ref = &mut self.peer_ips;
         self.peer_stats.retain(|peer_id, peer_stats| {
@@ -521,7 +521,7 @@ impl PeerScore {
             }
 
             peer_stats.status = ConnectionStatus::Disconnected {
-                expire: Instant::now() + self.params.retain_score,
+                expire: time::now() + self.params.retain_score,
             };
         }
     }
@@ -597,7 +597,7 @@ impl PeerScore {
         }
 
         // mark the message as valid and reward mesh peers that have already forwarded it to us
-        record.status = DeliveryStatus::Valid(Instant::now());
+        record.status = DeliveryStatus::Valid(time::now());
         for peer in record.peers.iter().cloned().collect::<Vec<_>>() {
             // this check is to make sure a peer can't send us a message twice and get a double
             // count if it is a first delivery
@@ -837,7 +837,7 @@ impl PeerScore {
     ) {
         if let Some(peer_stats) = self.peer_stats.get_mut(peer_id) {
             let now = if validated_time.is_some() {
-                Some(Instant::now())
+                Some(time::now())
             } else {
                 None
             };
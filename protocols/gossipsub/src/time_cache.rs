@@ -21,13 +21,13 @@
 //! This implements a time-based LRU cache for checking gossipsub message duplicates.
 
 use fnv::FnvHashMap;
+use libp2p_core::time::{self, Instant};
 use std::collections::hash_map::{
     self,
     Entry::{Occupied, Vacant},
 };
 use std::collections::VecDeque;
 use std::time::Duration;
-use wasm_timer::Instant;
 
 struct ExpiringElement<Element> {
     /// The element that expires
@@ -150,7 +150,7 @@ where
     }
 
     pub fn entry(&mut self, key: Key) -> Entry<Key, Value> {
-        let now = Instant::now();
+        let now = time::now();
         self.remove_expired_keys(now);
         match self.map.entry(key) {
             Occupied(entry) => Entry::Occupied(OccupiedEntry {
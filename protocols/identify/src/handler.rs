@@ -22,8 +22,8 @@ use crate::protocol::{
     IdentifyInfo, IdentifyProtocol, IdentifyPushProtocol, InboundPush, OutboundPush, ReplySubstream,
 };
 use futures::prelude::*;
-use futures_timer::Delay;
 use libp2p_core::either::{EitherError, EitherOutput};
+use libp2p_core::time::Delay;
 use libp2p_core::upgrade::{
     EitherUpgrade, InboundUpgrade, OutboundUpgrade, SelectUpgrade, UpgradeError,
 };
@@ -114,9 +114,15 @@ impl ConnectionHandler for IdentifyHandler {
             EitherOutput::First(substream) => self.events.push(ConnectionHandlerEvent::Custom(
                 IdentifyHandlerEvent::Identify(substream),
             )),
-            EitherOutput::Second(info) => self.events.push(ConnectionHandlerEvent::Custom(
-                IdentifyHandlerEvent::Identified(info),
-            )),
+            EitherOutput::Second(info) => {
+                self.events
+                    .push(ConnectionHandlerEvent::ReportRemoteProtocols(
+                        info.protocols.clone(),
+                    ));
+                self.events.push(ConnectionHandlerEvent::Custom(
+                    IdentifyHandlerEvent::Identified(info),
+                ));
+            }
         }
     }
 
@@ -127,6 +133,10 @@ impl ConnectionHandler for IdentifyHandler {
     ) {
         match output {
             EitherOutput::First(remote_info) => {
+                self.events
+                    .push(ConnectionHandlerEvent::ReportRemoteProtocols(
+                        remote_info.protocols.clone(),
+                    ));
                 self.events.push(ConnectionHandlerEvent::Custom(
                     IdentifyHandlerEvent::Identified(remote_info),
                 ));
@@ -25,12 +25,14 @@ use libp2p_core::{
     connection::{ConnectionId, ListenerId},
     multiaddr::Protocol,
     upgrade::UpgradeError,
-    ConnectedPoint, Multiaddr, PeerId, PublicKey,
+    ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId, PublicKey,
 };
 use libp2p_swarm::{
     dial_opts::{self, DialOpts},
-    AddressScore, ConnectionHandler, ConnectionHandlerUpgrErr, DialError, IntoConnectionHandler,
+    peer_store::{AddressSource, RECENTLY_CONNECTED_ADDRESS_TTL},
+    ConnectionHandler, ConnectionHandlerUpgrErr, DialError, IntoConnectionHandler,
     NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
+    ProtocolsChange,
 };
 use lru::LruCache;
 use std::{
@@ -47,8 +49,8 @@ use std::{
 /// about them, and answers identify queries from other nodes.
 ///
 /// All external addresses of the local node supposedly observed by remotes
-/// are reported via [`NetworkBehaviourAction::ReportObservedAddr`] with a
-/// [score](AddressScore) of `1`.
+/// are reported as candidates via
+/// [`NetworkBehaviourAction::ReportExternalAddrCandidate`].
 pub struct Identify {
     config: IdentifyConfig,
     /// For each peer we're connected to, the observed address to send back to it.
@@ -62,6 +64,8 @@ pub struct Identify {
     pending_push: HashSet<PeerId>,
     /// The addresses of all peers that we have discovered.
     discovered_peers: LruCache<PeerId, HashSet<Multiaddr>>,
+    /// For each connection, the protocols the local node supports on it.
+    local_protocols: HashMap<ConnectionId, HashSet<String>>,
 }
 
 /// A pending reply to an inbound identification request.
@@ -69,6 +73,7 @@ enum Reply {
     /// The reply is queued for sending.
     Queued {
         peer: PeerId,
+        connection: ConnectionId,
         io: ReplySubstream<NegotiatedSubstream>,
         observed: Multiaddr,
     },
@@ -115,6 +120,13 @@ pub struct IdentifyConfig {
     /// Disabled by default.
     pub push_listen_addr_updates: bool,
 
+    /// Whether changes to the protocols the local node supports on a
+    /// connection should trigger an active push of an identify message
+    /// to the peer of that connection.
+    ///
+    /// Disabled by default.
+    pub push_protocol_updates: bool,
+
     /// How many entries of discovered peers to keep before we discard
     /// the least-recently used one.
     ///
@@ -133,6 +145,7 @@ impl IdentifyConfig {
             initial_delay: Duration::from_millis(500),
             interval: Duration::from_secs(5 * 60),
             push_listen_addr_updates: false,
+            push_protocol_updates: false,
             cache_size: 0,
         }
     }
@@ -165,6 +178,14 @@ impl IdentifyConfig {
         self
     }
 
+    /// Configures whether changes to the protocols the local node supports
+    /// on a connection should trigger an active push of an identify message
+    /// to the peer of that connection.
+    pub fn with_push_protocol_updates(mut self, b: bool) -> Self {
+        self.push_protocol_updates = b;
+        self
+    }
+
     /// Configures the size of the LRU cache, caching addresses of discovered peers.
     ///
     /// The [`Swarm`](libp2p_swarm::Swarm) may extend the set of addresses of an outgoing connection attempt via
@@ -187,6 +208,24 @@ impl Identify {
             events: VecDeque::new(),
             pending_push: HashSet::new(),
             discovered_peers,
+            local_protocols: HashMap::new(),
+        }
+    }
+
+    /// The protocols to advertise to the given peer, preferring those the
+    /// local node reported for one of its connections to the peer.
+    fn local_protocols_of(
+        &self,
+        connection: Option<&ConnectionId>,
+        params: &impl PollParameters,
+    ) -> Vec<String> {
+        match connection.and_then(|c| self.local_protocols.get(c)) {
+            Some(protocols) => {
+                let mut protocols = protocols.iter().cloned().collect::<Vec<_>>();
+                protocols.sort();
+                protocols
+            }
+            None => supported_protocols(params),
         }
     }
 
@@ -224,6 +263,7 @@ impl NetworkBehaviour for Identify {
         peer_id: &PeerId,
         conn: &ConnectionId,
         endpoint: &ConnectedPoint,
+        _negotiated_protocols: &NegotiatedProtocols,
         failed_addresses: Option<&Vec<Multiaddr>>,
         _other_established: usize,
     ) {
@@ -255,6 +295,7 @@ impl NetworkBehaviour for Identify {
         _: <Self::ConnectionHandler as IntoConnectionHandler>::Handler,
         remaining_established: usize,
     ) {
+        self.local_protocols.remove(conn);
         if remaining_established == 0 {
             self.connected.remove(peer_id);
             self.pending_push.remove(peer_id);
@@ -296,6 +337,29 @@ impl NetworkBehaviour for Identify {
         }
     }
 
+    fn inject_local_protocols_change(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        change: &ProtocolsChange,
+    ) {
+        // The first report for a connection is the initial protocol set,
+        // which the remote learns through the regular identification.
+        let initial = !self.local_protocols.contains_key(connection);
+        let protocols = self.local_protocols.entry(*connection).or_default();
+        match change {
+            ProtocolsChange::Added(added) => protocols.extend(added.iter().cloned()),
+            ProtocolsChange::Removed(removed) => {
+                for p in removed {
+                    protocols.remove(p);
+                }
+            }
+        }
+        if !initial && self.config.push_protocol_updates {
+            self.pending_push.insert(*peer_id);
+        }
+    }
+
     fn inject_event(
         &mut self,
         peer_id: PeerId,
@@ -317,9 +381,9 @@ impl NetworkBehaviour for Identify {
                     IdentifyEvent::Received { peer_id, info },
                 ));
                 self.events
-                    .push_back(NetworkBehaviourAction::ReportObservedAddr {
+                    .push_back(NetworkBehaviourAction::ReportExternalAddrCandidate {
                         address: observed,
-                        score: AddressScore::Finite(1),
+                        observer: peer_id,
                     });
             }
             IdentifyHandlerEvent::IdentificationPushed => {
@@ -338,6 +402,7 @@ impl NetworkBehaviour for Identify {
                     );
                 self.pending_replies.push_back(Reply::Queued {
                     peer: peer_id,
+                    connection,
                     io: sender,
                     observed: observed.clone(),
                 });
@@ -356,20 +421,36 @@ impl NetworkBehaviour for Identify {
         params: &mut impl PollParameters,
     ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
         if let Some(event) = self.events.pop_front() {
+            if let NetworkBehaviourAction::GenerateEvent(IdentifyEvent::Received {
+                peer_id,
+                info,
+            }) = &event
+            {
+                let peer_store = params.peer_store_mut();
+                for addr in &info.listen_addrs {
+                    peer_store.add_address(
+                        *peer_id,
+                        addr.clone(),
+                        AddressSource::Remote,
+                        Some(RECENTLY_CONNECTED_ADDRESS_TTL),
+                    );
+                }
+                peer_store.set_protocols(*peer_id, info.protocols.clone());
+                peer_store.set_agent_version(*peer_id, info.agent_version.clone());
+            }
             return Poll::Ready(event);
         }
 
         // Check for a pending active push to perform.
         let peer_push = self.pending_push.iter().find_map(|peer| {
             self.connected.get(peer).map(|conns| {
-                let observed_addr = conns
-                    .values()
+                let (connection, observed_addr) = conns
+                    .iter()
                     .next()
-                    .expect("connected peer has a connection")
-                    .clone();
+                    .expect("connected peer has a connection");
 
                 let listen_addrs = listen_addrs(params);
-                let protocols = supported_protocols(params);
+                let protocols = self.local_protocols_of(Some(connection), params);
 
                 let info = IdentifyInfo {
                     public_key: self.config.local_public_key.clone(),
@@ -377,7 +458,7 @@ impl NetworkBehaviour for Identify {
                     agent_version: self.config.agent_version.clone(),
                     listen_addrs,
                     protocols,
-                    observed_addr,
+                    observed_addr: observed_addr.clone(),
                 };
 
                 (*peer, IdentifyPush(info))
@@ -400,10 +481,15 @@ impl NetworkBehaviour for Identify {
             let mut reply = Some(r);
             loop {
                 match reply {
-                    Some(Reply::Queued { peer, io, observed }) => {
+                    Some(Reply::Queued {
+                        peer,
+                        connection,
+                        io,
+                        observed,
+                    }) => {
                         let info = IdentifyInfo {
                             listen_addrs: listen_addrs(params),
-                            protocols: supported_protocols(params),
+                            protocols: self.local_protocols_of(Some(&connection), params),
                             public_key: self.config.local_public_key.clone(),
                             protocol_version: self.config.protocol_version.clone(),
                             agent_version: self.config.agent_version.clone(),
@@ -38,14 +38,15 @@ use crate::record::{
 };
 use crate::K_VALUE;
 use fnv::{FnvHashMap, FnvHashSet};
-use instant::Instant;
+use libp2p_core::time;
 use libp2p_core::{
     connection::{ConnectionId, ListenerId},
-    ConnectedPoint, Multiaddr, PeerId,
+    ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId,
 };
 use libp2p_swarm::{
     dial_opts::{self, DialOpts},
     DialError, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
+    ProtocolsChange,
 };
 use log::{debug, info, warn};
 use smallvec::SmallVec;
@@ -82,6 +83,12 @@ pub struct Kademlia<TStore> {
     /// This is a superset of the connected peers currently in the routing table.
     connected_peers: FnvHashSet<PeerId>,
 
+    /// Whether connected peers support the configured protocol name, as
+    /// reported via [`NetworkBehaviour::inject_remote_protocols_change`].
+    ///
+    /// Peers without support are not inserted into the routing table.
+    remote_protocol_support: FnvHashMap<PeerId, bool>,
+
     /// Periodic job for re-publication of provider records for keys
     /// provided by the local node.
     add_provider_job: Option<AddProviderJob>,
@@ -437,6 +444,7 @@ where
             queued_events: VecDeque::with_capacity(config.query_config.replication_factor.get()),
             queries: QueryPool::new(config.query_config),
             connected_peers: Default::default(),
+            remote_protocol_support: Default::default(),
             add_provider_job,
             put_record_job,
             record_ttl: config.record_ttl,
@@ -568,6 +576,7 @@ where
                         self.queued_events.push_back(NetworkBehaviourAction::Dial {
                             opts: DialOpts::peer_id(disconnected.into_preimage())
                                 .condition(dial_opts::PeerCondition::Disconnected)
+                                .priority(dial_opts::DialPriority::Low)
                                 .build(),
                             handler,
                         });
@@ -684,7 +693,7 @@ where
         let mut records = Vec::with_capacity(quorum.get());
 
         if let Some(record) = self.store.get(&key) {
-            if record.is_expired(Instant::now()) {
+            if record.is_expired(time::now()) {
                 self.store.remove(&key)
             } else {
                 records.push(PeerRecord {
@@ -741,7 +750,7 @@ where
         self.store.put(record.clone())?;
         record.expires = record
             .expires
-            .or_else(|| self.record_ttl.map(|ttl| Instant::now() + ttl));
+            .or_else(|| self.record_ttl.map(|ttl| time::now() + ttl));
         let quorum = quorum.eval(self.queries.config().replication_factor);
         let target = kbucket::Key::new(record.key.clone());
         let peers = self.kbuckets.closest_keys(&target);
@@ -790,7 +799,7 @@ where
         };
         record.expires = record
             .expires
-            .or_else(|| self.record_ttl.map(|ttl| Instant::now() + ttl));
+            .or_else(|| self.record_ttl.map(|ttl| time::now() + ttl));
         let context = PutRecordContext::Custom;
         let info = QueryInfo::PutRecord {
             context,
@@ -925,7 +934,7 @@ where
             .store
             .providers(&key)
             .into_iter()
-            .filter(|p| !p.is_expired(Instant::now()))
+            .filter(|p| !p.is_expired(time::now()))
             .map(|p| p.provider)
             .collect();
         let info = QueryInfo::GetProviders {
@@ -1065,6 +1074,15 @@ where
         address: Option<Multiaddr>,
         new_status: NodeStatus,
     ) {
+        if new_status == NodeStatus::Connected
+            && self.remote_protocol_support.get(&peer) == Some(&false)
+        {
+            debug!(
+                "Protocol not supported. Peer not added to routing table: {}",
+                peer
+            );
+            return;
+        }
         let key = kbucket::Key::from(peer);
         match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, old_status) => {
@@ -1164,6 +1182,7 @@ where
                                     self.queued_events.push_back(NetworkBehaviourAction::Dial {
                                         opts: DialOpts::peer_id(disconnected.into_preimage())
                                             .condition(dial_opts::PeerCondition::Disconnected)
+                                            .priority(dial_opts::DialPriority::Low)
                                             .build(),
                                         handler,
                                     })
@@ -1593,7 +1612,7 @@ where
             return;
         }
 
-        let now = Instant::now();
+        let now = time::now();
 
         // Calculate the expiration exponentially inversely proportional to the
         // number of nodes between the local node and the closest node to the key
@@ -1703,7 +1722,7 @@ where
             let record = ProviderRecord {
                 key,
                 provider: provider.node_id,
-                expires: self.provider_record_ttl.map(|ttl| Instant::now() + ttl),
+                expires: self.provider_record_ttl.map(|ttl| time::now() + ttl),
                 addresses: provider.multiaddrs,
             };
             match self.record_filtering {
@@ -1822,6 +1841,7 @@ where
         peer_id: &PeerId,
         _: &ConnectionId,
         _: &ConnectedPoint,
+        _: &NegotiatedProtocols,
         errors: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -1934,6 +1954,10 @@ where
             | DialError::Aborted
             | DialError::ConnectionIo(_)
             | DialError::Transport(_)
+            | DialError::DeniedDial
+            | DialError::Backoff { .. }
+            | DialError::DeniedSecured { .. }
+            | DialError::DeniedUpgraded { .. }
             | DialError::NoAddresses => {
                 if let DialError::Transport(addresses) = error {
                     for (addr, _) in addresses {
@@ -1971,6 +1995,43 @@ where
             }
             self.connection_updated(*id, None, NodeStatus::Disconnected);
             self.connected_peers.remove(id);
+            self.remote_protocol_support.remove(id);
+        }
+    }
+
+    fn inject_remote_protocols_change(
+        &mut self,
+        peer_id: &PeerId,
+        _: &ConnectionId,
+        change: &ProtocolsChange,
+    ) {
+        // A remote that does not advertise the configured protocol name can
+        // not answer queries, thus it must not be shared with other nodes.
+        let protocol_name = self.protocol_name().to_vec();
+        let supported = match change {
+            ProtocolsChange::Added(protocols)
+                if protocols.iter().any(|p| p.as_bytes() == protocol_name) =>
+            {
+                true
+            }
+            ProtocolsChange::Removed(protocols)
+                if protocols.iter().any(|p| p.as_bytes() == protocol_name) =>
+            {
+                false
+            }
+            // The first report of a peer is its initial set of protocols.
+            ProtocolsChange::Added(_) => *self
+                .remote_protocol_support
+                .entry(*peer_id)
+                .or_insert(false),
+            ProtocolsChange::Removed(_) => return,
+        };
+        self.remote_protocol_support.insert(*peer_id, supported);
+        if !supported && self.remove_peer(peer_id).is_some() {
+            debug!(
+                "Protocol not supported. Peer removed from routing table: {}",
+                peer_id
+            );
         }
     }
 
@@ -2093,7 +2154,7 @@ where
                 // Lookup the record locally.
                 let record = match self.store.get(&key) {
                     Some(record) => {
-                        if record.is_expired(Instant::now()) {
+                        if record.is_expired(time::now()) {
                             self.store.remove(&key);
                             None
                         } else {
@@ -2239,12 +2300,31 @@ where
         }
     }
 
+    fn inject_shutdown(&mut self) {
+        // The Kademlia protocol has no message to revoke a provider record from
+        // remote peers. Stop providing locally, such that the records are no
+        // longer republished and expire at the remote peers.
+        let local_id = *self.kbuckets.local_key().preimage();
+        let keys = self
+            .store
+            .provided()
+            .filter(|record| record.provider == local_id)
+            .map(|record| record.key.clone())
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.stop_providing(&key);
+        }
+
+        self.add_provider_job = None;
+        self.put_record_job = None;
+    }
+
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
         parameters: &mut impl PollParameters,
     ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
-        let now = Instant::now();
+        let now = time::now();
 
         // Calculate the available capacity for queries triggered by background jobs.
         let mut jobs_query_capacity = JOBS_MAX_QUERIES.saturating_sub(self.queries.size());
@@ -2345,6 +2425,7 @@ where
                             self.queued_events.push_back(NetworkBehaviourAction::Dial {
                                 opts: DialOpts::peer_id(peer_id)
                                     .condition(dial_opts::PeerCondition::Disconnected)
+                                    .priority(dial_opts::DialPriority::Low)
                                     .build(),
                                 handler,
                             });
@@ -36,7 +36,7 @@ use libp2p_core::{
     upgrade, Endpoint, PeerId, Transport,
 };
 use libp2p_noise as noise;
-use libp2p_swarm::{Swarm, SwarmEvent};
+use libp2p_swarm::{ProtocolsChange, Swarm, SwarmEvent};
 use libp2p_yamux as yamux;
 use quickcheck::*;
 use rand::{random, rngs::StdRng, thread_rng, Rng, SeedableRng};
@@ -1293,7 +1293,14 @@ fn network_behaviour_inject_address_change() {
     };
 
     // Mimick a connection being established.
-    kademlia.inject_connection_established(&remote_peer_id, &connection_id, &endpoint, None, 0);
+    kademlia.inject_connection_established(
+        &remote_peer_id,
+        &connection_id,
+        &endpoint,
+        &NegotiatedProtocols::default(),
+        None,
+        0,
+    );
 
     // At this point the remote is not yet known to support the
     // configured protocol name, so the peer is not yet in the
@@ -1379,3 +1386,84 @@ fn get_providers() {
     }
     QuickCheck::new().tests(10).quickcheck(prop as fn(_))
 }
+
+#[test]
+fn peers_without_protocol_support_are_not_inserted() {
+    let local_peer_id = PeerId::random();
+    let remote_peer_id = PeerId::random();
+    let connection_id = ConnectionId::new(1);
+    let endpoint = ConnectedPoint::Dialer {
+        address: Protocol::Memory(1).into(),
+        role_override: Endpoint::Dialer,
+    };
+
+    let mut kademlia = Kademlia::new(local_peer_id, MemoryStore::new(local_peer_id));
+    let protocol_name = String::from_utf8(kademlia.protocol_name().to_vec()).unwrap();
+    kademlia.inject_connection_established(
+        &remote_peer_id,
+        &connection_id,
+        &endpoint,
+        &NegotiatedProtocols::default(),
+        None,
+        0,
+    );
+
+    // The remote reports its initial protocols, without Kademlia.
+    kademlia.inject_remote_protocols_change(
+        &remote_peer_id,
+        &connection_id,
+        &ProtocolsChange::Added(vec!["/other/1.0.0".to_string()]),
+    );
+    kademlia.inject_event(
+        remote_peer_id,
+        connection_id,
+        KademliaHandlerEvent::ProtocolConfirmed {
+            endpoint: endpoint.clone(),
+        },
+    );
+    assert!(kademlia.addresses_of_peer(&remote_peer_id).is_empty());
+
+    // Once the remote supports Kademlia it is inserted.
+    kademlia.inject_remote_protocols_change(
+        &remote_peer_id,
+        &connection_id,
+        &ProtocolsChange::Added(vec![protocol_name.clone()]),
+    );
+    kademlia.inject_event(
+        remote_peer_id,
+        connection_id,
+        KademliaHandlerEvent::ProtocolConfirmed { endpoint },
+    );
+    assert_eq!(kademlia.addresses_of_peer(&remote_peer_id).len(), 1);
+
+    // Further protocols do not change the support of Kademlia.
+    kademlia.inject_remote_protocols_change(
+        &remote_peer_id,
+        &connection_id,
+        &ProtocolsChange::Added(vec!["/another/1.0.0".to_string()]),
+    );
+    assert_eq!(kademlia.addresses_of_peer(&remote_peer_id).len(), 1);
+
+    // Once the remote no longer supports Kademlia it is removed.
+    kademlia.inject_remote_protocols_change(
+        &remote_peer_id,
+        &connection_id,
+        &ProtocolsChange::Removed(vec![protocol_name]),
+    );
+    assert!(kademlia.addresses_of_peer(&remote_peer_id).is_empty());
+}
+
+#[test]
+fn shutdown_withdraws_provider_records() {
+    let (_addr, mut swarm) = build_node();
+    let key = record::Key::from(random_multihash());
+    swarm.behaviour_mut().start_providing(key).unwrap();
+    assert_eq!(swarm.behaviour_mut().store.provided().count(), 1);
+
+    swarm.close(Duration::from_secs(1));
+
+    let kad = swarm.behaviour_mut();
+    assert_eq!(kad.store.provided().count(), 0);
+    assert!(kad.add_provider_job.is_none());
+    assert!(kad.put_record_job.is_none());
+}
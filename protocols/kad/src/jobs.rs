@@ -63,8 +63,7 @@
 
 use crate::record::{self, store::RecordStore, ProviderRecord, Record};
 use futures::prelude::*;
-use futures_timer::Delay;
-use instant::Instant;
+use libp2p_core::time::{self, Delay, Instant};
 use libp2p_core::PeerId;
 use std::collections::HashSet;
 use std::pin::Pin;
@@ -100,7 +99,7 @@ impl<T> PeriodicJob<T> {
     /// for the delay to expire.
     fn asap(&mut self) {
         if let PeriodicJobState::Waiting(delay, deadline) = &mut self.state {
-            let new_deadline = Instant::now() - Duration::from_secs(1);
+            let new_deadline = time::now() - Duration::from_secs(1);
             *deadline = new_deadline;
             delay.reset(Duration::from_secs(1));
         }
@@ -147,7 +146,7 @@ impl PutRecordJob {
         publish_interval: Option<Duration>,
         record_ttl: Option<Duration>,
     ) -> Self {
-        let now = Instant::now();
+        let now = time::now();
         let deadline = now + replicate_interval;
         let delay = Delay::new(replicate_interval);
         let next_publish = publish_interval.map(|i| now + i);
@@ -181,7 +180,7 @@ impl PutRecordJob {
     /// The job is guaranteed to run on the next invocation of `poll`.
     pub fn asap(&mut self, publish: bool) {
         if publish {
-            self.next_publish = Some(Instant::now() - Duration::from_secs(1))
+            self.next_publish = Some(time::now() - Duration::from_secs(1))
         }
         self.inner.asap()
     }
@@ -257,7 +256,7 @@ pub struct AddProviderJob {
 impl AddProviderJob {
     /// Creates a new periodic job for provider announcements.
     pub fn new(interval: Duration) -> Self {
-        let now = Instant::now();
+        let now = time::now();
         Self {
             inner: PeriodicJob {
                 interval,
@@ -366,7 +365,7 @@ mod tests {
             }
 
             block_on(poll_fn(|ctx| {
-                let now = Instant::now() + job.inner.interval;
+                let now = time::now() + job.inner.interval;
                 // All (non-expired) records in the store must be yielded by the job.
                 for r in store.records().map(|r| r.into_owned()).collect::<Vec<_>>() {
                     if !r.is_expired(now) {
@@ -396,7 +395,7 @@ mod tests {
             }
 
             block_on(poll_fn(|ctx| {
-                let now = Instant::now() + job.inner.interval;
+                let now = time::now() + job.inner.interval;
                 // All (non-expired) records in the store must be yielded by the job.
                 for r in store.provided().map(|r| r.into_owned()).collect::<Vec<_>>() {
                     if !r.is_expired(now) {
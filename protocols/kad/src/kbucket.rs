@@ -76,8 +76,9 @@ pub use entry::*;
 
 use arrayvec::{self, ArrayVec};
 use bucket::KBucket;
+use libp2p_core::time::{self, Instant};
 use std::collections::VecDeque;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 /// Maximum number of k-buckets.
 const NUM_BUCKETS: usize = 256;
@@ -718,7 +719,7 @@ mod tests {
 
         // Expire the timeout for the pending entry on the full bucket.`
         let full_bucket = &mut table.buckets[full_bucket_index.unwrap().get()];
-        let elapsed = Instant::now() - Duration::from_secs(1);
+        let elapsed = time::now() - Duration::from_secs(1);
         full_bucket.pending_mut().unwrap().set_ready_at(elapsed);
 
         match table.entry(&expected_applied.inserted.key) {
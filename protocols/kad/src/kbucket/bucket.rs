@@ -68,7 +68,7 @@ impl<TKey, TVal> PendingNode<TKey, TVal> {
     }
 
     pub fn is_ready(&self) -> bool {
-        Instant::now() >= self.replace
+        time::now() >= self.replace
     }
 
     pub fn set_ready_at(&mut self, t: Instant) {
@@ -214,7 +214,7 @@ where
     /// bucket remained unchanged.
     pub fn apply_pending(&mut self) -> Option<AppliedPending<TKey, TVal>> {
         if let Some(pending) = self.pending.take() {
-            if pending.replace <= Instant::now() {
+            if pending.replace <= time::now() {
                 if self.nodes.is_full() {
                     if self.status(Position(0)) == NodeStatus::Connected {
                         // The bucket is full with connected nodes. Drop the pending node.
@@ -329,7 +329,7 @@ where
                         self.pending = Some(PendingNode {
                             node,
                             status: NodeStatus::Connected,
-                            replace: Instant::now() + self.pending_timeout,
+                            replace: time::now() + self.pending_timeout,
                         });
                         return InsertResult::Pending {
                             disconnected: self.nodes[0].key.clone(),
@@ -585,7 +585,7 @@ mod tests {
 
             // Apply the pending node.
             let pending = bucket.pending_mut().expect("No pending node.");
-            pending.set_ready_at(Instant::now() - Duration::from_secs(1));
+            pending.set_ready_at(time::now() - Duration::from_secs(1));
             let result = bucket.apply_pending();
             assert_eq!(
                 result,
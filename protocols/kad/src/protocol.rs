@@ -32,7 +32,7 @@ use asynchronous_codec::Framed;
 use bytes::BytesMut;
 use codec::UviBytes;
 use futures::prelude::*;
-use instant::Instant;
+use libp2p_core::time;
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_core::{Multiaddr, PeerId};
 use prost::Message;
@@ -558,7 +558,7 @@ fn record_from_proto(record: proto::Record) -> Result<Record, io::Error> {
     };
 
     let expires = if record.ttl > 0 {
-        Some(Instant::now() + Duration::from_secs(record.ttl as u64))
+        Some(time::now() + Duration::from_secs(record.ttl as u64))
     } else {
         None
     };
@@ -579,7 +579,7 @@ fn record_to_proto(record: Record) -> proto::Record {
         ttl: record
             .expires
             .map(|t| {
-                let now = Instant::now();
+                let now = time::now();
                 if t > now {
                     (t - now).as_secs() as u32
                 } else {
@@ -30,7 +30,7 @@ use crate::kbucket::{Key, KeyBytes};
 use crate::{ALPHA_VALUE, K_VALUE};
 use either::Either;
 use fnv::FnvHashMap;
-use instant::Instant;
+use libp2p_core::time::{self, Instant};
 use libp2p_core::PeerId;
 use std::{num::NonZeroUsize, time::Duration};
 
@@ -487,7 +487,7 @@ impl QueryStats {
             if let Some(e) = self.end {
                 Some(e - s)
             } else {
-                Some(Instant::now() - s)
+                Some(time::now() - s)
             }
         } else {
             None
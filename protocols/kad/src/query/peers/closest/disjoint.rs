@@ -20,7 +20,7 @@
 
 use super::*;
 use crate::kbucket::{Key, KeyBytes};
-use instant::Instant;
+use libp2p_core::time::Instant;
 use libp2p_core::PeerId;
 use std::{
     collections::HashMap,
@@ -23,7 +23,7 @@
 pub mod store;
 
 use bytes::Bytes;
-use instant::Instant;
+use libp2p_core::time::Instant;
 use libp2p_core::{multihash::Multihash, Multiaddr, PeerId};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
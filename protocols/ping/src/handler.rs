@@ -21,7 +21,7 @@
 use crate::protocol;
 use futures::future::BoxFuture;
 use futures::prelude::*;
-use futures_timer::Delay;
+use libp2p_core::time::Delay;
 use libp2p_core::{upgrade::NegotiationError, UpgradeError};
 use libp2p_swarm::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
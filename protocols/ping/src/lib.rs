@@ -117,9 +117,12 @@ impl NetworkBehaviour for Behaviour {
     fn poll(
         &mut self,
         _: &mut Context<'_>,
-        _: &mut impl PollParameters,
+        params: &mut impl PollParameters,
     ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
         if let Some(e) = self.events.pop_back() {
+            if let Ok(Success::Ping { rtt }) = &e.result {
+                params.peer_store_mut().set_latency(e.peer, *rtt);
+            }
             Poll::Ready(NetworkBehaviourAction::GenerateEvent(e))
         } else {
             Poll::Pending
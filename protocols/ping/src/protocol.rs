@@ -19,7 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use futures::prelude::*;
-use instant::Instant;
+use libp2p_core::time;
 use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_swarm::NegotiatedSubstream;
 use rand::{distributions, prelude::*};
@@ -88,7 +88,7 @@ where
     log::debug!("Preparing ping payload {:?}", payload);
     stream.write_all(&payload).await?;
     stream.flush().await?;
-    let started = Instant::now();
+    let started = time::now();
     let mut recv_payload = [0u8; PING_SIZE];
     log::debug!("Awaiting pong for {:?}", payload);
     stream.read_exact(&mut recv_payload).await?;
@@ -26,7 +26,7 @@ use crate::v1::transport::{OutgoingRelayReqError, TransportToBehaviourMsg};
 use crate::v1::{protocol, Connection, RequestId};
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
-use libp2p_core::connection::{ConnectedPoint, ConnectionId, ListenerId};
+use libp2p_core::connection::{ConnectedPoint, ConnectionId, ListenerId, NegotiatedProtocols};
 use libp2p_core::multiaddr::Multiaddr;
 use libp2p_core::PeerId;
 use libp2p_swarm::{
@@ -203,6 +203,7 @@ impl NetworkBehaviour for Relay {
         peer: &PeerId,
         connection_id: &ConnectionId,
         _: &ConnectedPoint,
+        _: &NegotiatedProtocols,
         _: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -24,9 +24,9 @@ use futures::channel::oneshot::{self, Canceled};
 use futures::future::BoxFuture;
 use futures::prelude::*;
 use futures::stream::FuturesUnordered;
-use instant::Instant;
 use libp2p_core::connection::ConnectionId;
 use libp2p_core::either::{EitherError, EitherOutput};
+use libp2p_core::time;
 use libp2p_core::{upgrade, ConnectedPoint, Multiaddr, PeerId};
 use libp2p_swarm::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, IntoConnectionHandler,
@@ -481,7 +481,9 @@ impl ConnectionHandler for RelayHandler {
         error: ConnectionHandlerUpgrErr<protocol::RelayListenError>,
     ) {
         match error {
-            ConnectionHandlerUpgrErr::Timeout | ConnectionHandlerUpgrErr::Timer => {}
+            ConnectionHandlerUpgrErr::Timeout
+            | ConnectionHandlerUpgrErr::Timer
+            | ConnectionHandlerUpgrErr::LimitExceeded(_) => {}
             ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
                 upgrade::NegotiationError::Failed,
             )) => {}
@@ -513,7 +515,9 @@ impl ConnectionHandler for RelayHandler {
                 request_id,
             } => {
                 match error {
-                    ConnectionHandlerUpgrErr::Timeout | ConnectionHandlerUpgrErr::Timer => {}
+                    ConnectionHandlerUpgrErr::Timeout
+                    | ConnectionHandlerUpgrErr::Timer
+                    | ConnectionHandlerUpgrErr::LimitExceeded(_) => {}
                     ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
                         upgrade::NegotiationError::Failed,
                     )) => {}
@@ -593,7 +597,9 @@ impl ConnectionHandler for RelayHandler {
                 ..
             } => {
                 let err_code = match error {
-                    ConnectionHandlerUpgrErr::Timeout | ConnectionHandlerUpgrErr::Timer => {
+                    ConnectionHandlerUpgrErr::Timeout
+                    | ConnectionHandlerUpgrErr::Timer
+                    | ConnectionHandlerUpgrErr::LimitExceeded(_) => {
                         circuit_relay::Status::HopCantOpenDstStream
                     }
                     ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
@@ -815,7 +821,7 @@ impl ConnectionHandler for RelayHandler {
             // Protocol handler is idle.
             if matches!(self.keep_alive, KeepAlive::Yes) {
                 self.keep_alive =
-                    KeepAlive::Until(Instant::now() + self.config.connection_idle_timeout);
+                    KeepAlive::Until(time::now() + self.config.connection_idle_timeout);
             }
         }
 
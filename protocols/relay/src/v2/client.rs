@@ -32,7 +32,7 @@ use futures::future::{BoxFuture, FutureExt};
 use futures::io::{AsyncRead, AsyncWrite};
 use futures::ready;
 use futures::stream::StreamExt;
-use libp2p_core::connection::{ConnectedPoint, ConnectionId};
+use libp2p_core::connection::{ConnectedPoint, ConnectionId, NegotiatedProtocols};
 use libp2p_core::{Multiaddr, PeerId};
 use libp2p_swarm::dial_opts::DialOpts;
 use libp2p_swarm::handler::DummyConnectionHandler;
@@ -128,6 +128,7 @@ impl NetworkBehaviour for Client {
         peer_id: &PeerId,
         connection_id: &ConnectionId,
         endpoint: &ConnectedPoint,
+        _negotiated_protocols: &NegotiatedProtocols,
         _failed_addresses: Option<&Vec<Multiaddr>>,
         _other_established: usize,
     ) {
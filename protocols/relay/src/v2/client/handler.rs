@@ -26,10 +26,9 @@ use futures::channel::{mpsc, oneshot};
 use futures::future::{BoxFuture, FutureExt};
 use futures::sink::SinkExt;
 use futures::stream::{FuturesUnordered, StreamExt};
-use futures_timer::Delay;
-use instant::Instant;
 use libp2p_core::either::EitherError;
 use libp2p_core::multiaddr::Protocol;
+use libp2p_core::time::{self, Delay};
 use libp2p_core::{upgrade, ConnectedPoint, Multiaddr, PeerId};
 use libp2p_swarm::handler::{
     DummyConnectionHandler, InboundUpgradeSend, OutboundUpgradeSend, SendWrapper,
@@ -355,6 +354,9 @@ impl ConnectionHandler for Handler {
         let non_fatal_error = match error {
             ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
             ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                ConnectionHandlerUpgrErr::LimitExceeded(limit)
+            }
             ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
                 upgrade::NegotiationError::Failed,
             )) => ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
@@ -395,6 +397,9 @@ impl ConnectionHandler for Handler {
                 let non_fatal_error = match error {
                     ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
                     ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
+                    ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                        ConnectionHandlerUpgrErr::LimitExceeded(limit)
+                    }
                     ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
                         upgrade::NegotiationError::Failed,
                     )) => ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
@@ -458,6 +463,9 @@ impl ConnectionHandler for Handler {
                 let non_fatal_error = match error {
                     ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
                     ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
+                    ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                        ConnectionHandlerUpgrErr::LimitExceeded(limit)
+                    }
                     ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
                         upgrade::NegotiationError::Failed,
                     )) => ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
@@ -572,7 +580,7 @@ impl ConnectionHandler for Handler {
         {
             match self.keep_alive {
                 KeepAlive::Yes => {
-                    self.keep_alive = KeepAlive::Until(Instant::now() + Duration::from_secs(10));
+                    self.keep_alive = KeepAlive::Until(time::now() + Duration::from_secs(10));
                 }
                 KeepAlive::Until(_) => {}
                 KeepAlive::No => panic!("Handler never sets KeepAlive::No."),
@@ -29,7 +29,7 @@ use futures::future::FutureExt;
 use futures::io::{AsyncBufRead, BufReader};
 use futures::io::{AsyncRead, AsyncWrite};
 use futures::ready;
-use futures_timer::Delay;
+use libp2p_core::time::Delay;
 use std::convert::TryInto;
 use std::io;
 use std::pin::Pin;
@@ -26,9 +26,9 @@ pub mod rate_limiter;
 use crate::v2::message_proto;
 use crate::v2::protocol::inbound_hop;
 use either::Either;
-use instant::Instant;
 use libp2p_core::connection::{ConnectedPoint, ConnectionId};
 use libp2p_core::multiaddr::Protocol;
+use libp2p_core::time;
 use libp2p_core::PeerId;
 use libp2p_swarm::handler::DummyConnectionHandler;
 use libp2p_swarm::{
@@ -279,7 +279,7 @@ impl NetworkBehaviour for Relay {
                 endpoint,
                 renewed,
             } => {
-                let now = Instant::now();
+                let now = time::now();
 
                 assert!(
                     !endpoint.is_relayed(),
@@ -406,7 +406,7 @@ impl NetworkBehaviour for Relay {
                 inbound_circuit_req,
                 endpoint,
             } => {
-                let now = Instant::now();
+                let now = time::now();
 
                 assert!(
                     !endpoint.is_relayed(),
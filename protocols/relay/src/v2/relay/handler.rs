@@ -28,10 +28,9 @@ use futures::channel::oneshot::{self, Canceled};
 use futures::future::{BoxFuture, FutureExt, TryFutureExt};
 use futures::io::AsyncWriteExt;
 use futures::stream::{FuturesUnordered, StreamExt};
-use futures_timer::Delay;
-use instant::Instant;
 use libp2p_core::connection::ConnectionId;
 use libp2p_core::either::EitherError;
+use libp2p_core::time::{self, Delay};
 use libp2p_core::{upgrade, ConnectedPoint, Multiaddr, PeerId};
 use libp2p_swarm::handler::{DummyConnectionHandler, SendWrapper};
 use libp2p_swarm::handler::{InboundUpgradeSend, OutboundUpgradeSend};
@@ -594,6 +593,9 @@ impl ConnectionHandler for Handler {
         let non_fatal_error = match error {
             ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
             ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                ConnectionHandlerUpgrErr::LimitExceeded(limit)
+            }
             ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
                 upgrade::NegotiationError::Failed,
             )) => ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
@@ -636,6 +638,10 @@ impl ConnectionHandler for Handler {
             ConnectionHandlerUpgrErr::Timer => {
                 (ConnectionHandlerUpgrErr::Timer, Status::ConnectionFailed)
             }
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => (
+                ConnectionHandlerUpgrErr::LimitExceeded(limit),
+                Status::ConnectionFailed,
+            ),
             ConnectionHandlerUpgrErr::Upgrade(upgrade::UpgradeError::Select(
                 upgrade::NegotiationError::Failed,
             )) => {
@@ -885,7 +891,7 @@ impl ConnectionHandler for Handler {
         {
             match self.keep_alive {
                 KeepAlive::Yes => {
-                    self.keep_alive = KeepAlive::Until(Instant::now() + Duration::from_secs(10));
+                    self.keep_alive = KeepAlive::Until(time::now() + Duration::from_secs(10));
                 }
                 KeepAlive::Until(_) => {}
                 KeepAlive::No => panic!("Handler never sets KeepAlive::No."),
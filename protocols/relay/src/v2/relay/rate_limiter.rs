@@ -21,8 +21,8 @@
 pub use generic::{
     RateLimiter as GenericRateLimiter, RateLimiterConfig as GenericRateLimiterConfig,
 };
-use instant::Instant;
 use libp2p_core::multiaddr::{Multiaddr, Protocol};
+use libp2p_core::time::Instant;
 use libp2p_core::PeerId;
 use std::net::IpAddr;
 
@@ -35,7 +35,7 @@ use libp2p_core::{Multiaddr, PeerId, PeerRecord};
 use libp2p_swarm::{
     CloseConnection, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
 };
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::iter::FromIterator;
 use std::task::{Context, Poll};
 
@@ -56,6 +56,9 @@ pub struct Behaviour {
 
     /// Tracks the expiry of registrations that we have discovered and stored in `discovered_peers` otherwise we have a memory leak.
     expiring_registrations: FuturesUnordered<BoxFuture<'static, (PeerId, Namespace)>>,
+
+    /// The rendezvous nodes and namespaces we are registered with, unregistered from once the [`libp2p_swarm::Swarm`] shuts down.
+    registrations: HashSet<(PeerId, Namespace)>,
 }
 
 impl Behaviour {
@@ -69,6 +72,7 @@ impl Behaviour {
             expiring_registrations: FuturesUnordered::from_iter(vec![
                 futures::future::pending().boxed()
             ]),
+            registrations: Default::default(),
         }
     }
 
@@ -83,6 +87,8 @@ impl Behaviour {
 
     /// Unregister ourselves from the given namespace with the given rendezvous peer.
     pub fn unregister(&mut self, namespace: Namespace, rendezvous_node: PeerId) {
+        self.registrations
+            .remove(&(rendezvous_node, namespace.clone()));
         self.events
             .push_back(NetworkBehaviourAction::NotifyHandler {
                 peer_id: rendezvous_node,
@@ -196,6 +202,7 @@ impl NetworkBehaviour for Behaviour {
                 peer_id,
                 &mut self.discovered_peers,
                 &mut self.expiring_registrations,
+                &mut self.registrations,
             ),
             handler::OutboundOutEvent::InboundError { error, .. } => void::unreachable(error),
             handler::OutboundOutEvent::OutboundError { error, .. } => {
@@ -211,6 +218,12 @@ impl NetworkBehaviour for Behaviour {
         self.events.extend(new_events);
     }
 
+    fn inject_shutdown(&mut self) {
+        for (rendezvous_node, namespace) in std::mem::take(&mut self.registrations) {
+            self.unregister(namespace, rendezvous_node);
+        }
+    }
+
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
@@ -272,6 +285,7 @@ fn handle_outbound_event(
     peer_id: PeerId,
     discovered_peers: &mut HashMap<(PeerId, Namespace), Vec<Multiaddr>>,
     expiring_registrations: &mut FuturesUnordered<BoxFuture<'static, (PeerId, Namespace)>>,
+    registrations: &mut HashSet<(PeerId, Namespace)>,
 ) -> Vec<
     NetworkBehaviourAction<
         Event,
@@ -280,6 +294,7 @@ fn handle_outbound_event(
 > {
     match event {
         outbound::OutEvent::Registered { namespace, ttl } => {
+            registrations.insert((peer_id, namespace.clone()));
             vec![NetworkBehaviourAction::GenerateEvent(Event::Registered {
                 rendezvous_node: peer_id,
                 ttl,
@@ -310,8 +325,9 @@ fn handle_outbound_event(
             expiring_registrations.extend(registrations.iter().cloned().map(|registration| {
                 async move {
                     // if the timer errors we consider it expired
-                    let _ = futures_timer::Delay::new(Duration::from_secs(registration.ttl as u64))
-                        .await;
+                    let _ =
+                        libp2p_core::time::Delay::new(Duration::from_secs(registration.ttl as u64))
+                            .await;
 
                     (registration.record.peer_id(), registration.namespace)
                 }
@@ -27,7 +27,7 @@
 
 use futures::future::{self, BoxFuture, Fuse, FusedFuture};
 use futures::FutureExt;
-use instant::Instant;
+use libp2p_core::time::{self, Instant};
 use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_swarm::handler::{InboundUpgradeSend, OutboundUpgradeSend};
 use libp2p_swarm::{
@@ -194,7 +194,7 @@ impl<TInboundSubstream, TOutboundSubstream, TOutboundOpenInfo>
             next_inbound_substream_id: InboundSubstreamId(0),
             next_outbound_substream_id: OutboundSubstreamId(0),
             new_substreams: Default::default(),
-            initial_keep_alive_deadline: Instant::now() + initial_keep_alive,
+            initial_keep_alive_deadline: time::now() + initial_keep_alive,
         }
     }
 }
@@ -209,7 +209,7 @@ impl<TOutboundSubstream, TOutboundOpenInfo>
             next_inbound_substream_id: InboundSubstreamId(0),
             next_outbound_substream_id: OutboundSubstreamId(0),
             new_substreams: Default::default(),
-            initial_keep_alive_deadline: Instant::now() + initial_keep_alive,
+            initial_keep_alive_deadline: time::now() + initial_keep_alive,
         }
     }
 }
@@ -224,7 +224,7 @@ impl<TInboundSubstream, TOutboundOpenInfo>
             next_inbound_substream_id: InboundSubstreamId(0),
             next_outbound_substream_id: OutboundSubstreamId(0),
             new_substreams: Default::default(),
-            initial_keep_alive_deadline: Instant::now() + initial_keep_alive,
+            initial_keep_alive_deadline: time::now() + initial_keep_alive,
         }
     }
 }
@@ -429,7 +429,7 @@ where
     fn connection_keep_alive(&self) -> KeepAlive {
         // Rudimentary keep-alive handling, to be extended as needed as this abstraction is used more by other protocols.
 
-        if Instant::now() < self.initial_keep_alive_deadline {
+        if time::now() < self.initial_keep_alive_deadline {
             return KeepAlive::Yes;
         }
 
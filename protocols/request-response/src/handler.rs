@@ -26,8 +26,11 @@ use crate::{RequestId, EMPTY_QUEUE_SHRINK_THRESHOLD};
 pub use protocol::{ProtocolSupport, RequestProtocol, ResponseProtocol};
 
 use futures::{channel::oneshot, future::BoxFuture, prelude::*, stream::FuturesUnordered};
-use instant::Instant;
-use libp2p_core::upgrade::{NegotiationError, UpgradeError};
+use libp2p_core::time;
+use libp2p_core::{
+    upgrade::{NegotiationError, UpgradeError},
+    ProtocolName,
+};
 use libp2p_swarm::{
     handler::{ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive},
     SubstreamProtocol,
@@ -236,6 +239,15 @@ where
         SubstreamProtocol::new(proto, request_id).with_timeout(self.substream_timeout)
     }
 
+    fn listen_protocol_names(&self) -> Vec<String> {
+        // Unlike `listen_protocol`, this must not allocate a request ID
+        // nor register an expected inbound request.
+        self.inbound_protocols
+            .iter()
+            .map(|p| String::from_utf8_lossy(p.protocol_name()).into_owned())
+            .collect()
+    }
+
     fn inject_fully_negotiated_inbound(&mut self, sent: bool, request_id: RequestId) {
         if sent {
             self.pending_events
@@ -381,7 +393,7 @@ where
             // No new inbound or outbound requests. However, we may just have
             // started the latest inbound or outbound upgrade(s), so make sure
             // the keep-alive timeout is preceded by the substream timeout.
-            let until = Instant::now() + self.substream_timeout + self.keep_alive_timeout;
+            let until = time::now() + self.substream_timeout + self.keep_alive_timeout;
             self.keep_alive = KeepAlive::Until(until);
         }
 
@@ -64,7 +64,9 @@ pub use handler::ProtocolSupport;
 
 use futures::channel::oneshot;
 use handler::{RequestProtocol, RequestResponseHandler, RequestResponseHandlerEvent};
-use libp2p_core::{connection::ConnectionId, ConnectedPoint, Multiaddr, PeerId};
+use libp2p_core::{
+    connection::ConnectionId, ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId,
+};
 use libp2p_swarm::{
     dial_opts::{self, DialOpts},
     DialError, IntoConnectionHandler, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler,
@@ -619,6 +621,7 @@ where
         peer: &PeerId,
         conn: &ConnectionId,
         endpoint: &ConnectedPoint,
+        _negotiated_protocols: &NegotiatedProtocols,
         _errors: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -22,7 +22,7 @@
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Ident};
+use syn::{parse_macro_input, Data, DataEnum, DataStruct, DeriveInput, Fields, Ident};
 
 /// Generates a delegating `NetworkBehaviour` implementation for the struct this is used for. See
 /// the trait documentation for better description.
@@ -36,8 +36,13 @@ pub fn hello_macro_derive(input: TokenStream) -> TokenStream {
 fn build(ast: &DeriveInput) -> TokenStream {
     match ast.data {
         Data::Struct(ref s) => build_struct(ast, s),
-        Data::Enum(_) => unimplemented!("Deriving NetworkBehaviour is not implemented for enums"),
-        Data::Union(_) => unimplemented!("Deriving NetworkBehaviour is not implemented for unions"),
+        Data::Enum(ref e) => build_enum(ast, e),
+        Data::Union(ref u) => syn::Error::new_spanned(
+            u.union_token,
+            "Deriving NetworkBehaviour is not implemented for unions",
+        )
+        .to_compile_error()
+        .into(),
     }
 }
 
@@ -57,6 +62,8 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let connection_id = quote! {::libp2p::core::connection::ConnectionId};
     let dial_errors = quote! {Option<&Vec<::libp2p::core::Multiaddr>>};
     let connected_point = quote! {::libp2p::core::ConnectedPoint};
+    let negotiated_protocols = quote! {::libp2p::core::NegotiatedProtocols};
+    let protocols_change = quote! {::libp2p::swarm::ProtocolsChange};
     let listener_id = quote! {::libp2p::core::connection::ListenerId};
     let dial_error = quote! {::libp2p::swarm::DialError};
 
@@ -71,48 +78,57 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     };
 
     // Whether or not we require the `NetworkBehaviourEventProcess` trait to be implemented.
-    let event_process = {
-        let mut event_process = false;
-
-        for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
-            for meta_item in meta_items {
-                match meta_item {
-                    syn::NestedMeta::Meta(syn::Meta::NameValue(ref m))
-                        if m.path.is_ident("event_process") =>
-                    {
-                        if let syn::Lit::Bool(ref b) = m.lit {
-                            event_process = b.value
-                        }
-                    }
-                    _ => (),
-                }
-            }
-        }
-
-        event_process
-    };
+    let event_process = parse_event_process(ast);
 
-    // The final out event.
+    // The out event provided by the user.
     // If we find a `#[behaviour(out_event = "Foo")]` attribute on the struct, we set `Foo` as
-    // the out event. Otherwise we use `()`.
-    let out_event = {
-        let mut out = quote! {()};
-        for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
-            for meta_item in meta_items {
-                match meta_item {
-                    syn::NestedMeta::Meta(syn::Meta::NameValue(ref m))
-                        if m.path.is_ident("out_event") =>
-                    {
-                        if let syn::Lit::Str(ref s) = m.lit {
-                            let ident: syn::Type = syn::parse_str(&s.value()).unwrap();
-                            out = quote! {#ident};
-                        }
+    // the out event.
+    let user_out_event = parse_out_event(ast);
+
+    // The `<Name>Event` enum generated in the absence of both a user provided out event and
+    // `event_process`, with one variant per non-ignored field wrapping the field's out event.
+    // Variants are named after the fields, e.g. `Ping` for `ping`, or after their index for
+    // tuple structs, e.g. `Field0` for `.0`.
+    let generated_out_event = if user_out_event.is_none() && !event_process {
+        let fields = data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| !is_ignored(f))
+            .map(|(index, field)| {
+                let variant = match field.ident {
+                    Some(ref ident) => {
+                        Ident::new(&to_upper_camel_case(&ident.to_string()), ident.span())
                     }
-                    _ => (),
-                }
-            }
-        }
-        out
+                    None => Ident::new(
+                        &format!("Field{}", index),
+                        syn::spanned::Spanned::span(&field.ty),
+                    ),
+                };
+                (variant, &field.ty)
+            })
+            .collect::<Vec<_>>();
+        let (out_event, definition, enum_ident) = build_out_event_enum(ast, &fields);
+
+        Some((
+            out_event,
+            definition,
+            enum_ident,
+            fields
+                .into_iter()
+                .map(|(variant, _)| variant)
+                .collect::<Vec<_>>(),
+        ))
+    } else {
+        None
+    };
+
+    // The final out event. Falls back to `()` when `event_process` is used without a custom
+    // out event.
+    let out_event = match (&user_out_event, &generated_out_event) {
+        (Some(out), _) => out.clone(),
+        (None, Some((out, ..))) => out.clone(),
+        (None, None) => quote! {()},
     };
 
     // Build the `where ...` clause of the trait implementation.
@@ -124,14 +140,17 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             .flat_map(|field| {
                 let ty = &field.ty;
                 vec![
-                    quote! {#ty: #trait_to_impl},
+                    Some(quote! {#ty: #trait_to_impl}),
                     if event_process {
-                        quote! {Self: #net_behv_event_proc<<#ty as #trait_to_impl>::OutEvent>}
+                        Some(quote! {Self: #net_behv_event_proc<<#ty as #trait_to_impl>::OutEvent>})
+                    } else if generated_out_event.is_none() {
+                        Some(quote! {#out_event: From< <#ty as #trait_to_impl>::OutEvent >})
                     } else {
-                        quote! {#out_event: From< <#ty as #trait_to_impl>::OutEvent >}
+                        None
                     },
                 ]
             })
+            .flatten()
             .collect::<Vec<_>>();
 
         if let Some(where_clause) = where_clause {
@@ -155,6 +174,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
 
                 Some(match field.ident {
                     Some(ref i) => quote! { out.extend(self.#i.addresses_of_peer(peer_id)); },
@@ -169,9 +189,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             if is_ignored(field) {
                 return None;
             }
+            let field_n = syn::Index::from(field_n);
             Some(match field.ident {
-                Some(ref i) => quote!{ self.#i.inject_connection_established(peer_id, connection_id, endpoint, errors, other_established); },
-                None => quote!{ self.#field_n.inject_connection_established(peer_id, connection_id, endpoint, errors, other_established); },
+                Some(ref i) => quote!{ self.#i.inject_connection_established(peer_id, connection_id, endpoint, negotiated_protocols, errors, other_established); },
+                None => quote!{ self.#field_n.inject_connection_established(peer_id, connection_id, endpoint, negotiated_protocols, errors, other_established); },
             })
         })
     };
@@ -182,6 +203,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             if is_ignored(field) {
                 return None;
             }
+            let field_n = syn::Index::from(field_n);
             Some(match field.ident {
                 Some(ref i) => quote!{ self.#i.inject_address_change(peer_id, connection_id, old, new); },
                 None => quote!{ self.#field_n.inject_address_change(peer_id, connection_id, old, new); },
@@ -189,6 +211,37 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     };
 
+    // Build the list of statements to put in the body of `inject_local_protocols_change()`.
+    let inject_local_protocols_change_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(field) {
+                return None;
+            }
+            let field_n = syn::Index::from(field_n);
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_local_protocols_change(peer_id, connection_id, change); },
+                None => quote!{ self.#field_n.inject_local_protocols_change(peer_id, connection_id, change); },
+            })
+        })
+    };
+
+    // Build the list of statements to put in the body of `inject_remote_protocols_change()`.
+    let inject_remote_protocols_change_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(field) {
+                return None;
+            }
+            let field_n = syn::Index::from(field_n);
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_remote_protocols_change(peer_id, connection_id, change); },
+                None => quote!{ self.#field_n.inject_remote_protocols_change(peer_id, connection_id, change); },
+            })
+        })
+    };
+
+    // The number of non-ignored fields, i.e. of handlers nested in the combined handler.
+    let fields_count = data_struct.fields.iter().filter(|f| !is_ignored(f)).count();
+
     // Build the list of statements to put in the body of `inject_connection_closed()`.
     let inject_connection_closed_stmts = {
         data_struct
@@ -200,7 +253,8 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             .filter(|f| !is_ignored(f.1))
             .enumerate()
             .map(move |(enum_n, (field_n, field))| {
-                let handler = if field_n == 0 {
+                let field_n = syn::Index::from(field_n);
+                let handler = if enum_n + 1 == fields_count {
                     // Given that the iterator is reversed, this is the innermost handler only.
                     quote! { let handler = handlers }
                 } else {
@@ -210,7 +264,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 };
                 let inject = match field.ident {
                     Some(ref i) => quote!{ self.#i.inject_connection_closed(peer_id, connection_id, endpoint, handler, remaining_established) },
-                    None => quote!{ self.#enum_n.inject_connection_closed(peer_id, connection_id, endpoint, handler, remaining_established) },
+                    None => quote!{ self.#field_n.inject_connection_closed(peer_id, connection_id, endpoint, handler, remaining_established) },
                 };
 
                 quote! {
@@ -231,7 +285,8 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             .filter(|f| !is_ignored(f.1))
             .enumerate()
             .map(move |(enum_n, (field_n, field))| {
-                let handler = if field_n == 0 {
+                let field_n = syn::Index::from(field_n);
+                let handler = if enum_n + 1 == fields_count {
                     // Given that the iterator is reversed, this is the innermost handler only.
                     quote! { let handler = handlers }
                 } else {
@@ -245,7 +300,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                         quote! { self.#i.inject_dial_failure(peer_id, handler, error) }
                     }
                     None => {
-                        quote! { self.#enum_n.inject_dial_failure(peer_id, handler, error) }
+                        quote! { self.#field_n.inject_dial_failure(peer_id, handler, error) }
                     }
                 };
 
@@ -266,7 +321,8 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             .filter(|f| !is_ignored(f.1))
             .enumerate()
             .map(move |(enum_n, (field_n, field))| {
-                let handler = if field_n == 0 {
+                let field_n = syn::Index::from(field_n);
+                let handler = if enum_n + 1 == fields_count {
                     quote! { let handler = handlers }
                 } else {
                     quote! {
@@ -276,7 +332,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
 
                 let inject = match field.ident {
                     Some(ref i) => quote! { self.#i.inject_listen_failure(local_addr, send_back_addr, handler) },
-                    None => quote! { self.#enum_n.inject_listen_failure(local_addr, send_back_addr, handler) },
+                    None => quote! { self.#field_n.inject_listen_failure(local_addr, send_back_addr, handler) },
                 };
 
                 quote! {
@@ -296,6 +352,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
 
                 Some(match field.ident {
                     Some(ref i) => quote! { self.#i.inject_new_listener(id); },
@@ -314,6 +371,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
 
                 Some(match field.ident {
                     Some(ref i) => quote! { self.#i.inject_new_listen_addr(id, addr); },
@@ -332,6 +390,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
 
                 Some(match field.ident {
                     Some(ref i) => quote! { self.#i.inject_expired_listen_addr(id, addr); },
@@ -350,6 +409,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
 
                 Some(match field.ident {
                     Some(ref i) => quote! { self.#i.inject_new_external_addr(addr); },
@@ -368,6 +428,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
 
                 Some(match field.ident {
                     Some(ref i) => quote! { self.#i.inject_expired_external_addr(addr); },
@@ -376,6 +437,63 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             })
     };
 
+    // Build the list of statements to put in the body of `inject_new_external_addr_candidate()`.
+    let inject_new_external_addr_candidate_stmts = {
+        data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(move |(field_n, field)| {
+                if is_ignored(field) {
+                    return None;
+                }
+                let field_n = syn::Index::from(field_n);
+
+                Some(match field.ident {
+                    Some(ref i) => quote! { self.#i.inject_new_external_addr_candidate(addr); },
+                    None => quote! { self.#field_n.inject_new_external_addr_candidate(addr); },
+                })
+            })
+    };
+
+    // Build the list of statements to put in the body of `inject_external_addr_confirmed()`.
+    let inject_external_addr_confirmed_stmts = {
+        data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(move |(field_n, field)| {
+                if is_ignored(field) {
+                    return None;
+                }
+                let field_n = syn::Index::from(field_n);
+
+                Some(match field.ident {
+                    Some(ref i) => quote! { self.#i.inject_external_addr_confirmed(addr); },
+                    None => quote! { self.#field_n.inject_external_addr_confirmed(addr); },
+                })
+            })
+    };
+
+    // Build the list of statements to put in the body of `inject_external_addr_expired()`.
+    let inject_external_addr_expired_stmts = {
+        data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(move |(field_n, field)| {
+                if is_ignored(field) {
+                    return None;
+                }
+                let field_n = syn::Index::from(field_n);
+
+                Some(match field.ident {
+                    Some(ref i) => quote! { self.#i.inject_external_addr_expired(addr); },
+                    None => quote! { self.#field_n.inject_external_addr_expired(addr); },
+                })
+            })
+    };
+
     // Build the list of statements to put in the body of `inject_listener_error()`.
     let inject_listener_error_stmts = {
         data_struct
@@ -386,6 +504,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
                 Some(match field.ident {
                     Some(ref i) => quote!(self.#i.inject_listener_error(id, err);),
                     None => quote!(self.#field_n.inject_listener_error(id, err);),
@@ -403,6 +522,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 if is_ignored(field) {
                     return None;
                 }
+                let field_n = syn::Index::from(field_n);
                 Some(match field.ident {
                     Some(ref i) => quote!(self.#i.inject_listener_closed(id, reason);),
                     None => quote!(self.#field_n.inject_listener_closed(id, reason);),
@@ -410,6 +530,24 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             })
     };
 
+    // Build the list of statements to put in the body of `inject_shutdown()`.
+    let inject_shutdown_stmts = {
+        data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(move |(field_n, field)| {
+                if is_ignored(field) {
+                    return None;
+                }
+                let field_n = syn::Index::from(field_n);
+                Some(match field.ident {
+                    Some(ref i) => quote!(self.#i.inject_shutdown();),
+                    None => quote!(self.#field_n.inject_shutdown();),
+                })
+            })
+    };
+
     // Build the list of variants to put in the body of `inject_event()`.
     //
     // The event type is a construction of nested `#either_ident`s of the events of the children.
@@ -427,7 +565,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
 
         Some(match field.ident {
             Some(ref i) => quote!{ #elem => #trait_to_impl::inject_event(&mut self.#i, peer_id, connection_id, ev) },
-            None => quote!{ #elem => #trait_to_impl::inject_event(&mut self.#field_n, peer_id, connection_id, ev) },
+            None => {
+                let field_n = syn::Index::from(field_n);
+                quote!{ #elem => #trait_to_impl::inject_event(&mut self.#field_n, peer_id, connection_id, ev) }
+            }
         })
     });
 
@@ -461,7 +602,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
 
             let field_name = match field.ident {
                 Some(ref i) => quote! { self.#i },
-                None => quote! { self.#field_n },
+                None => {
+                    let field_n = syn::Index::from(field_n);
+                    quote! { self.#field_n }
+                }
             };
 
             let builder = quote! {
@@ -482,25 +626,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     // The method to use to poll.
     // If we find a `#[behaviour(poll_method = "poll")]` attribute on the struct, we call
     // `self.poll()` at the end of the polling.
-    let poll_method = {
-        let mut poll_method = quote! {std::task::Poll::Pending};
-        for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
-            for meta_item in meta_items {
-                match meta_item {
-                    syn::NestedMeta::Meta(syn::Meta::NameValue(ref m))
-                        if m.path.is_ident("poll_method") =>
-                    {
-                        if let syn::Lit::Str(ref s) = m.lit {
-                            let ident: Ident = syn::parse_str(&s.value()).unwrap();
-                            poll_method = quote! {#name::#ident(self, cx, poll_params)};
-                        }
-                    }
-                    _ => (),
-                }
-            }
-        }
-        poll_method
-    };
+    let poll_method = parse_poll_method(ast);
 
     // List of statements to put in `poll()`.
     //
@@ -508,7 +634,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let poll_stmts = data_struct.fields.iter().enumerate().filter(|f| !is_ignored(f.1)).enumerate().map(|(enum_n, (field_n, field))| {
         let field_name = match field.ident {
             Some(ref i) => quote!{ self.#i },
-            None => quote!{ self.#field_n },
+            None => {
+                let field_n = syn::Index::from(field_n);
+                quote!{ self.#field_n }
+            }
         };
 
         let mut wrapped_event = if enum_n != 0 {
@@ -534,7 +663,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
 
                 let f_name = match f.ident {
                     Some(ref i) => quote! { self.#i },
-                    None => quote! { self.#f_n },
+                    None => {
+                        let f_n = syn::Index::from(f_n);
+                        quote! { self.#f_n }
+                    }
                 };
 
                 let builder = if field_n == f_n {
@@ -562,6 +694,13 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                     #net_behv_event_proc::inject_event(self, event)
                 }
             }
+        } else if let Some((_, _, enum_ident, variants)) = &generated_out_event {
+            let variant = &variants[enum_n];
+            quote! {
+                std::task::Poll::Ready(#network_behaviour_action::GenerateEvent(event)) => {
+                    return std::task::Poll::Ready(#network_behaviour_action::GenerateEvent(#enum_ident::#variant(event)))
+                }
+            }
         } else {
             quote! {
                 std::task::Poll::Ready(#network_behaviour_action::GenerateEvent(event)) => {
@@ -587,6 +726,12 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                     std::task::Poll::Ready(#network_behaviour_action::ReportObservedAddr { address, score }) => {
                         return std::task::Poll::Ready(#network_behaviour_action::ReportObservedAddr { address, score });
                     }
+                    std::task::Poll::Ready(#network_behaviour_action::ReportExternalAddrCandidate { address, observer }) => {
+                        return std::task::Poll::Ready(#network_behaviour_action::ReportExternalAddrCandidate { address, observer });
+                    }
+                    std::task::Poll::Ready(#network_behaviour_action::ConfirmExternalAddr { address }) => {
+                        return std::task::Poll::Ready(#network_behaviour_action::ConfirmExternalAddr { address });
+                    }
                     std::task::Poll::Ready(#network_behaviour_action::CloseConnection { peer_id, connection }) => {
                         return std::task::Poll::Ready(#network_behaviour_action::CloseConnection { peer_id, connection });
                     }
@@ -596,8 +741,14 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     });
 
+    let out_event_definition = generated_out_event
+        .as_ref()
+        .map(|(_, definition, ..)| definition);
+
     // Now the magic happens.
     let final_quote = quote! {
+        #out_event_definition
+
         impl #impl_generics #trait_to_impl for #name #ty_generics
         #where_clause
         {
@@ -615,7 +766,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 out
             }
 
-            fn inject_connection_established(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, endpoint: &#connected_point, errors: #dial_errors, other_established: usize) {
+            fn inject_connection_established(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, endpoint: &#connected_point, negotiated_protocols: &#negotiated_protocols, errors: #dial_errors, other_established: usize) {
                 #(#inject_connection_established_stmts);*
             }
 
@@ -623,6 +774,14 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_address_change_stmts);*
             }
 
+            fn inject_local_protocols_change(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, change: &#protocols_change) {
+                #(#inject_local_protocols_change_stmts);*
+            }
+
+            fn inject_remote_protocols_change(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, change: &#protocols_change) {
+                #(#inject_remote_protocols_change_stmts);*
+            }
+
             fn inject_connection_closed(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, endpoint: &#connected_point, handlers: <Self::ConnectionHandler as #into_protocols_handler>::Handler, remaining_established: usize) {
                 #(#inject_connection_closed_stmts);*
             }
@@ -655,6 +814,18 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_expired_external_addr_stmts);*
             }
 
+            fn inject_new_external_addr_candidate(&mut self, addr: &#multiaddr) {
+                #(#inject_new_external_addr_candidate_stmts);*
+            }
+
+            fn inject_external_addr_confirmed(&mut self, addr: &#multiaddr) {
+                #(#inject_external_addr_confirmed_stmts);*
+            }
+
+            fn inject_external_addr_expired(&mut self, addr: &#multiaddr) {
+                #(#inject_external_addr_expired_stmts);*
+            }
+
             fn inject_listener_error(&mut self, id: #listener_id, err: &(dyn std::error::Error + 'static)) {
                 #(#inject_listener_error_stmts);*
             }
@@ -663,6 +834,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_listener_closed_stmts);*
             }
 
+            fn inject_shutdown(&mut self) {
+                #(#inject_shutdown_stmts);*
+            }
+
             fn inject_event(
                 &mut self,
                 peer_id: #peer_id,
@@ -686,6 +861,584 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     final_quote.into()
 }
 
+/// The version for enums
+///
+/// Exactly one variant, and thus one behaviour, is active at a time. The handler is a nested
+/// [`IntoEitherHandler`] of the handlers of all variants, with the handler of the first variant
+/// being the outermost `Left`.
+fn build_enum(ast: &DeriveInput, data_enum: &DataEnum) -> TokenStream {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let multiaddr = quote! {::libp2p::core::Multiaddr};
+    let trait_to_impl = quote! {::libp2p::swarm::NetworkBehaviour};
+    let network_behaviour_action = quote! {::libp2p::swarm::NetworkBehaviourAction};
+    let into_protocols_handler = quote! {::libp2p::swarm::IntoConnectionHandler};
+    let protocols_handler = quote! {::libp2p::swarm::ConnectionHandler};
+    let into_either_handler = quote! {::libp2p::swarm::handler::either::IntoEitherHandler};
+    let either = quote! {::libp2p::swarm::handler::either::Either};
+    let peer_id = quote! {::libp2p::core::PeerId};
+    let connection_id = quote! {::libp2p::core::connection::ConnectionId};
+    let dial_errors = quote! {Option<&Vec<::libp2p::core::Multiaddr>>};
+    let connected_point = quote! {::libp2p::core::ConnectedPoint};
+    let negotiated_protocols = quote! {::libp2p::core::NegotiatedProtocols};
+    let protocols_change = quote! {::libp2p::swarm::ProtocolsChange};
+    let listener_id = quote! {::libp2p::core::connection::ListenerId};
+    let dial_error = quote! {::libp2p::swarm::DialError};
+    let poll_parameters = quote! {::libp2p::swarm::PollParameters};
+
+    if parse_event_process(ast) {
+        return syn::Error::new_spanned(
+            name,
+            "`event_process` is not implemented when deriving NetworkBehaviour for enums",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut variants = Vec::with_capacity(data_enum.variants.len());
+    for variant in &data_enum.variants {
+        match variant.fields {
+            Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => {
+                variants.push((variant.ident.clone(), &fields.unnamed[0].ty))
+            }
+            _ => {
+                return syn::Error::new_spanned(
+                    variant,
+                    "Deriving NetworkBehaviour for enums requires each variant to wrap exactly \
+                     one behaviour, e.g. `Variant(Behaviour)`",
+                )
+                .to_compile_error()
+                .into()
+            }
+        }
+    }
+    if variants.is_empty() {
+        return syn::Error::new_spanned(
+            name,
+            "Deriving NetworkBehaviour is not implemented for empty enums",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Wraps `inner`, belonging to the variant with index `n`, into the nested `left` and `right`
+    // constructors.
+    // Example output for the second of three variants: `right(left(inner))`.
+    let wrap = |n: usize,
+                inner: proc_macro2::TokenStream,
+                left: &proc_macro2::TokenStream,
+                right: &proc_macro2::TokenStream| {
+        let mut out = if n + 1 == variants.len() {
+            inner
+        } else {
+            quote! { #left(#inner) }
+        };
+        for _ in 0..n {
+            out = quote! { #right(#out) };
+        }
+        out
+    };
+    let into_either_left = quote! { #into_either_handler::Left };
+    let into_either_right = quote! { #into_either_handler::Right };
+    let either_left = quote! { #either::Left };
+    let either_right = quote! { #either::Right };
+
+    let user_out_event = parse_out_event(ast);
+    let generated_out_event = if user_out_event.is_none() {
+        Some(build_out_event_enum(ast, &variants))
+    } else {
+        None
+    };
+    let out_event = match (&user_out_event, &generated_out_event) {
+        (Some(out), _) => out.clone(),
+        (None, Some((out, ..))) => out.clone(),
+        (None, None) => unreachable!("Either the user provided or a generated out event."),
+    };
+
+    // Build the `where ...` clause of the trait implementation.
+    let where_clause = {
+        let additional = variants
+            .iter()
+            .flat_map(|(_, ty)| {
+                vec![
+                    Some(quote! {#ty: #trait_to_impl}),
+                    if user_out_event.is_some() {
+                        Some(quote! {#out_event: From< <#ty as #trait_to_impl>::OutEvent >})
+                    } else {
+                        None
+                    },
+                ]
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if let Some(where_clause) = where_clause {
+            if where_clause.predicates.trailing_punct() {
+                quote! {#where_clause #(#additional),*}
+            } else {
+                quote! {#where_clause, #(#additional),*}
+            }
+        } else {
+            quote! {where #(#additional),*}
+        }
+    };
+
+    // The [`ConnectionHandler`] associated type.
+    // Example output: `IntoEitherHandler<Handler1, IntoEitherHandler<Handler2, Handler3>>`.
+    let protocols_handler_ty = variants
+        .iter()
+        .rev()
+        .map(|(_, ty)| quote! { <#ty as #trait_to_impl>::ConnectionHandler })
+        .reduce(|handlers, handler| quote! { #into_either_handler<#handler, #handlers> })
+        .expect("At least one variant.");
+
+    // Delegates `call` to the behaviour of the active variant.
+    let delegate = |call: proc_macro2::TokenStream| {
+        let arms = variants
+            .iter()
+            .map(|(variant, _)| quote! { #name::#variant(behaviour) => #call });
+        quote! {
+            match self {
+                #(#arms),*
+            }
+        }
+    };
+
+    // Delegates `call` to the behaviour of the active variant, unwrapping `value` with the given
+    // constructors. `value` always belongs to the active variant.
+    let delegate_with = |value: proc_macro2::TokenStream,
+                         left: &proc_macro2::TokenStream,
+                         right: &proc_macro2::TokenStream,
+                         call: proc_macro2::TokenStream| {
+        let arms = variants.iter().enumerate().map(|(n, (variant, _))| {
+            let unwrapped = wrap(n, value.clone(), left, right);
+            quote! { (#name::#variant(behaviour), #unwrapped) => #call }
+        });
+        let unreachable = if variants.len() > 1 {
+            Some(
+                quote! { _ => unreachable!("Handlers and their events belong to the active behaviour.") },
+            )
+        } else {
+            None
+        };
+        quote! {
+            match (self, #value) {
+                #(#arms,)*
+                #unreachable
+            }
+        }
+    };
+
+    let new_handler = {
+        let arms = variants.iter().enumerate().map(|(n, (variant, _))| {
+            let handler = wrap(
+                n,
+                quote! { #trait_to_impl::new_handler(behaviour) },
+                &into_either_left,
+                &into_either_right,
+            );
+            quote! { #name::#variant(behaviour) => #handler }
+        });
+        quote! {
+            match self {
+                #(#arms),*
+            }
+        }
+    };
+    let addresses_of_peer =
+        delegate(quote! { #trait_to_impl::addresses_of_peer(behaviour, peer_id) });
+    let inject_connection_established = delegate(quote! {
+        #trait_to_impl::inject_connection_established(behaviour, peer_id, connection_id, endpoint, negotiated_protocols, errors, other_established)
+    });
+    let inject_address_change = delegate(quote! {
+        #trait_to_impl::inject_address_change(behaviour, peer_id, connection_id, old, new)
+    });
+    let inject_local_protocols_change = delegate(quote! {
+        #trait_to_impl::inject_local_protocols_change(behaviour, peer_id, connection_id, change)
+    });
+    let inject_remote_protocols_change = delegate(quote! {
+        #trait_to_impl::inject_remote_protocols_change(behaviour, peer_id, connection_id, change)
+    });
+    let inject_connection_closed = delegate_with(
+        quote! { handler },
+        &either_left,
+        &either_right,
+        quote! {
+            #trait_to_impl::inject_connection_closed(behaviour, peer_id, connection_id, endpoint, handler, remaining_established)
+        },
+    );
+    let inject_dial_failure = delegate_with(
+        quote! { handler },
+        &into_either_left,
+        &into_either_right,
+        quote! { #trait_to_impl::inject_dial_failure(behaviour, peer_id, handler, error) },
+    );
+    let inject_listen_failure = delegate_with(
+        quote! { handler },
+        &into_either_left,
+        &into_either_right,
+        quote! { #trait_to_impl::inject_listen_failure(behaviour, local_addr, send_back_addr, handler) },
+    );
+    let inject_new_listener =
+        delegate(quote! { #trait_to_impl::inject_new_listener(behaviour, id) });
+    let inject_new_listen_addr =
+        delegate(quote! { #trait_to_impl::inject_new_listen_addr(behaviour, id, addr) });
+    let inject_expired_listen_addr =
+        delegate(quote! { #trait_to_impl::inject_expired_listen_addr(behaviour, id, addr) });
+    let inject_new_external_addr =
+        delegate(quote! { #trait_to_impl::inject_new_external_addr(behaviour, addr) });
+    let inject_expired_external_addr =
+        delegate(quote! { #trait_to_impl::inject_expired_external_addr(behaviour, addr) });
+    let inject_new_external_addr_candidate =
+        delegate(quote! { #trait_to_impl::inject_new_external_addr_candidate(behaviour, addr) });
+    let inject_external_addr_confirmed =
+        delegate(quote! { #trait_to_impl::inject_external_addr_confirmed(behaviour, addr) });
+    let inject_external_addr_expired =
+        delegate(quote! { #trait_to_impl::inject_external_addr_expired(behaviour, addr) });
+    let inject_listener_error =
+        delegate(quote! { #trait_to_impl::inject_listener_error(behaviour, id, err) });
+    let inject_listener_closed =
+        delegate(quote! { #trait_to_impl::inject_listener_closed(behaviour, id, reason) });
+    let inject_shutdown = delegate(quote! { #trait_to_impl::inject_shutdown(behaviour) });
+    let inject_event = delegate_with(
+        quote! { event },
+        &either_left,
+        &either_right,
+        quote! { #trait_to_impl::inject_event(behaviour, peer_id, connection_id, event) },
+    );
+
+    // Polls the behaviour of the active variant, wrapping its handler, the events to its handler
+    // and its out event.
+    let poll_stmts = {
+        let arms = variants.iter().enumerate().map(|(n, (variant, _))| {
+            let out_event = match generated_out_event {
+                Some((_, _, ref enum_ident)) => quote! { #enum_ident::#variant(event) },
+                None => quote! { event.into() },
+            };
+            let handler = wrap(n, quote! { handler }, &into_either_left, &into_either_right);
+            let in_event = wrap(n, quote! { event }, &either_left, &either_right);
+            quote! {
+                #name::#variant(behaviour) => {
+                    if let std::task::Poll::Ready(action) = #trait_to_impl::poll(behaviour, cx, poll_params) {
+                        return std::task::Poll::Ready(
+                            action
+                                .map_out(|event| #out_event)
+                                .map_handler_and_in(|handler| #handler, |event| #in_event)
+                        );
+                    }
+                }
+            }
+        });
+        quote! {
+            match self {
+                #(#arms),*
+            }
+        }
+    };
+    let poll_method = parse_poll_method(ast);
+
+    let out_event_definition = generated_out_event
+        .as_ref()
+        .map(|(_, definition, ..)| definition);
+
+    let final_quote = quote! {
+        #out_event_definition
+
+        impl #impl_generics #trait_to_impl for #name #ty_generics
+        #where_clause
+        {
+            type ConnectionHandler = #protocols_handler_ty;
+            type OutEvent = #out_event;
+
+            fn new_handler(&mut self) -> Self::ConnectionHandler {
+                #new_handler
+            }
+
+            fn addresses_of_peer(&mut self, peer_id: &#peer_id) -> Vec<#multiaddr> {
+                #addresses_of_peer
+            }
+
+            fn inject_connection_established(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, endpoint: &#connected_point, negotiated_protocols: &#negotiated_protocols, errors: #dial_errors, other_established: usize) {
+                #inject_connection_established
+            }
+
+            fn inject_address_change(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, old: &#connected_point, new: &#connected_point) {
+                #inject_address_change
+            }
+
+            fn inject_local_protocols_change(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, change: &#protocols_change) {
+                #inject_local_protocols_change
+            }
+
+            fn inject_remote_protocols_change(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, change: &#protocols_change) {
+                #inject_remote_protocols_change
+            }
+
+            fn inject_connection_closed(&mut self, peer_id: &#peer_id, connection_id: &#connection_id, endpoint: &#connected_point, handler: <Self::ConnectionHandler as #into_protocols_handler>::Handler, remaining_established: usize) {
+                #inject_connection_closed
+            }
+
+            fn inject_dial_failure(&mut self, peer_id: Option<#peer_id>, handler: Self::ConnectionHandler, error: &#dial_error) {
+                #inject_dial_failure
+            }
+
+            fn inject_listen_failure(&mut self, local_addr: &#multiaddr, send_back_addr: &#multiaddr, handler: Self::ConnectionHandler) {
+                #inject_listen_failure
+            }
+
+            fn inject_new_listener(&mut self, id: #listener_id) {
+                #inject_new_listener
+            }
+
+            fn inject_new_listen_addr(&mut self, id: #listener_id, addr: &#multiaddr) {
+                #inject_new_listen_addr
+            }
+
+            fn inject_expired_listen_addr(&mut self, id: #listener_id, addr: &#multiaddr) {
+                #inject_expired_listen_addr
+            }
+
+            fn inject_new_external_addr(&mut self, addr: &#multiaddr) {
+                #inject_new_external_addr
+            }
+
+            fn inject_expired_external_addr(&mut self, addr: &#multiaddr) {
+                #inject_expired_external_addr
+            }
+
+            fn inject_new_external_addr_candidate(&mut self, addr: &#multiaddr) {
+                #inject_new_external_addr_candidate
+            }
+
+            fn inject_external_addr_confirmed(&mut self, addr: &#multiaddr) {
+                #inject_external_addr_confirmed
+            }
+
+            fn inject_external_addr_expired(&mut self, addr: &#multiaddr) {
+                #inject_external_addr_expired
+            }
+
+            fn inject_listener_error(&mut self, id: #listener_id, err: &(dyn std::error::Error + 'static)) {
+                #inject_listener_error
+            }
+
+            fn inject_listener_closed(&mut self, id: #listener_id, reason: std::result::Result<(), &std::io::Error>) {
+                #inject_listener_closed
+            }
+
+            fn inject_shutdown(&mut self) {
+                #inject_shutdown
+            }
+
+            fn inject_event(
+                &mut self,
+                peer_id: #peer_id,
+                connection_id: #connection_id,
+                event: <<Self::ConnectionHandler as #into_protocols_handler>::Handler as #protocols_handler>::OutEvent
+            ) {
+                #inject_event
+            }
+
+            fn poll(&mut self, cx: &mut std::task::Context, poll_params: &mut impl #poll_parameters) -> std::task::Poll<#network_behaviour_action<Self::OutEvent, Self::ConnectionHandler>> {
+                #poll_stmts
+                let f: std::task::Poll<#network_behaviour_action<Self::OutEvent, Self::ConnectionHandler>> = #poll_method;
+                f
+            }
+        }
+    };
+
+    final_quote.into()
+}
+
+/// Returns the value of the `#[behaviour(event_process = ..)]` attribute, defaulting to `false`.
+fn parse_event_process(ast: &DeriveInput) -> bool {
+    let mut event_process = false;
+
+    for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
+        for meta_item in meta_items {
+            match meta_item {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(ref m))
+                    if m.path.is_ident("event_process") =>
+                {
+                    if let syn::Lit::Bool(ref b) = m.lit {
+                        event_process = b.value
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    event_process
+}
+
+/// Returns the type given via `#[behaviour(out_event = "..")]`, if any.
+fn parse_out_event(ast: &DeriveInput) -> Option<proc_macro2::TokenStream> {
+    let mut out = None;
+    for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
+        for meta_item in meta_items {
+            match meta_item {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(ref m))
+                    if m.path.is_ident("out_event") =>
+                {
+                    if let syn::Lit::Str(ref s) = m.lit {
+                        let ident: syn::Type = syn::parse_str(&s.value()).unwrap();
+                        out = Some(quote! {#ident});
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    out
+}
+
+/// Returns a call to the method given via `#[behaviour(poll_method = "..")]`, defaulting to
+/// `Poll::Pending`.
+fn parse_poll_method(ast: &DeriveInput) -> proc_macro2::TokenStream {
+    let name = &ast.ident;
+    let mut poll_method = quote! {std::task::Poll::Pending};
+    for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
+        for meta_item in meta_items {
+            match meta_item {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(ref m))
+                    if m.path.is_ident("poll_method") =>
+                {
+                    if let syn::Lit::Str(ref s) = m.lit {
+                        let ident: Ident = syn::parse_str(&s.value()).unwrap();
+                        poll_method = quote! {#name::#ident(self, cx, poll_params)};
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    poll_method
+}
+
+/// Generates the `<Name>Event` enum with one variant per given behaviour, wrapping the
+/// [`NetworkBehaviour::OutEvent`] of the behaviour.
+///
+/// Returns the type of the enum, its definition and its name.
+fn build_out_event_enum(
+    ast: &DeriveInput,
+    behaviours: &[(Ident, &syn::Type)],
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream, Ident) {
+    let name = &ast.ident;
+    let trait_to_impl = quote! {::libp2p::swarm::NetworkBehaviour};
+    let enum_ident = Ident::new(&format!("{}Event", name), name.span());
+    let visibility = &ast.vis;
+    let doc = format!(
+        " Event emitted by [`{}`], wrapping the events of its behaviours.",
+        name
+    );
+
+    // The generics of the type that are used by the behaviours. Any other generic parameter
+    // would be unused by the enum.
+    let used = {
+        let mut used = Vec::new();
+        for (_, ty) in behaviours {
+            collect_idents(quote! {#ty}, &mut used);
+        }
+        used
+    };
+    let is_used = |param: &syn::GenericParam| match param {
+        syn::GenericParam::Type(t) => used.contains(&t.ident.to_string()),
+        syn::GenericParam::Lifetime(l) => used.contains(&l.lifetime.to_string()),
+        syn::GenericParam::Const(c) => used.contains(&c.ident.to_string()),
+    };
+    let unused = ast
+        .generics
+        .params
+        .iter()
+        .filter(|p| !is_used(p))
+        .map(|p| match p {
+            syn::GenericParam::Type(t) => t.ident.to_string(),
+            syn::GenericParam::Lifetime(l) => l.lifetime.to_string(),
+            syn::GenericParam::Const(c) => c.ident.to_string(),
+        })
+        .collect::<Vec<_>>();
+    // Only bound the behaviours that depend on a type parameter. A bound on a concrete type is a
+    // trivial bound, which is rejected by the compiler if it does not hold.
+    let type_params = ast
+        .generics
+        .type_params()
+        .map(|t| t.ident.to_string())
+        .collect::<Vec<_>>();
+    let is_generic = |ty: &syn::Type| {
+        let mut idents = Vec::new();
+        collect_idents(quote! {#ty}, &mut idents);
+        idents.iter().any(|i| type_params.contains(i))
+    };
+    let enum_generics = {
+        let mut generics = ast.generics.clone();
+        generics.params = generics.params.into_iter().filter(|p| is_used(p)).collect();
+        if let Some(where_clause) = generics.where_clause.as_mut() {
+            where_clause.predicates = where_clause
+                .predicates
+                .clone()
+                .into_iter()
+                .filter(|predicate| {
+                    let mut idents = Vec::new();
+                    collect_idents(quote! {#predicate}, &mut idents);
+                    !idents.iter().any(|i| unused.contains(i))
+                })
+                .collect();
+        }
+        let where_clause = generics.make_where_clause();
+        for (_, ty) in behaviours.iter().filter(|(_, ty)| is_generic(ty)) {
+            where_clause
+                .predicates
+                .push(syn::parse_quote! {#ty: #trait_to_impl});
+        }
+        generics
+    };
+    let (enum_impl_generics, enum_ty_generics, enum_where_clause) = enum_generics.split_for_impl();
+
+    let enum_variants = behaviours
+        .iter()
+        .map(|(variant, ty)| quote! { #variant(<#ty as #trait_to_impl>::OutEvent) });
+    let debug_where_clause = {
+        let mut generics = enum_generics.clone();
+        let where_clause = generics.make_where_clause();
+        for (_, ty) in behaviours {
+            if is_generic(ty) {
+                where_clause
+                    .predicates
+                    .push(syn::parse_quote! {<#ty as #trait_to_impl>::OutEvent: ::std::fmt::Debug});
+            }
+        }
+        generics.where_clause
+    };
+    let debug_arms = behaviours.iter().map(|(variant, _)| {
+        let variant_name = variant.to_string();
+        quote! {
+            #enum_ident::#variant(ref event) => f.debug_tuple(#variant_name).field(event).finish()
+        }
+    });
+
+    let definition = quote! {
+        #[doc = #doc]
+        #visibility enum #enum_ident #enum_impl_generics #enum_where_clause {
+            #(#enum_variants),*
+        }
+
+        impl #enum_impl_generics ::std::fmt::Debug for #enum_ident #enum_ty_generics #debug_where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    #(#debug_arms),*
+                }
+            }
+        }
+    };
+
+    (
+        quote! {#enum_ident #enum_ty_generics},
+        definition,
+        enum_ident,
+    )
+}
+
 fn get_meta_items(attr: &syn::Attribute) -> Option<Vec<syn::NestedMeta>> {
     if attr.path.segments.len() == 1 && attr.path.segments[0].ident == "behaviour" {
         match attr.parse_meta() {
@@ -716,3 +1469,36 @@ fn is_ignored(field: &syn::Field) -> bool {
 
     false
 }
+
+/// Converts a `snake_case` field name into an `UpperCamelCase` variant name.
+fn to_upper_camel_case(name: &str) -> String {
+    name.trim_start_matches("r#")
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Collects all identifiers and lifetimes within the given tokens.
+fn collect_idents(tokens: proc_macro2::TokenStream, out: &mut Vec<String>) {
+    let mut lifetime = false;
+    for token in tokens {
+        match token {
+            proc_macro2::TokenTree::Group(group) => collect_idents(group.stream(), out),
+            proc_macro2::TokenTree::Ident(ident) if lifetime => out.push(format!("'{}", ident)),
+            proc_macro2::TokenTree::Ident(ident) => out.push(ident.to_string()),
+            proc_macro2::TokenTree::Punct(ref p) if p.as_char() == '\'' => {
+                lifetime = true;
+                continue;
+            }
+            _ => {}
+        }
+        lifetime = false;
+    }
+}
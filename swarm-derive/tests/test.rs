@@ -419,3 +419,182 @@ fn no_event_with_either() {
         require_net_behaviour::<Foo>();
     }
 }
+
+#[test]
+fn generated_out_event() {
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    struct Foo {
+        ping: libp2p::ping::Ping,
+        identify: libp2p::identify::Identify,
+    }
+
+    #[allow(dead_code, unreachable_code)]
+    fn bar() {
+        require_net_behaviour::<Foo>();
+
+        let mut _swarm: libp2p::Swarm<Foo> = unimplemented!();
+
+        // check that the event is bubbled up all the way to swarm
+        let _ = async {
+            loop {
+                match _swarm.select_next_some().await {
+                    SwarmEvent::Behaviour(FooEvent::Ping(_)) => break,
+                    SwarmEvent::Behaviour(FooEvent::Identify(event)) => {
+                        let _ = format!("{:?}", event);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        };
+    }
+}
+
+#[test]
+fn generated_out_event_with_generics_and_ignored_fields() {
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    struct Foo<TBehaviour, TData: 'static> {
+        my_ping: libp2p::ping::Ping,
+        inner: TBehaviour,
+        #[behaviour(ignore)]
+        data: TData,
+    }
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<Foo<libp2p::identify::Identify, String>>();
+    }
+
+    let event: FooEvent<libp2p::identify::Identify> = FooEvent::MyPing(libp2p::ping::PingEvent {
+        peer: libp2p::PeerId::random(),
+        result: Ok(libp2p::ping::PingSuccess::Pong),
+    });
+    assert!(format!("{:?}", event).starts_with("MyPing("));
+    assert!(!matches!(event, FooEvent::Inner(_)));
+}
+
+#[test]
+fn generated_out_event_with_public_struct() {
+    mod behaviour {
+        use libp2p::NetworkBehaviour;
+
+        #[allow(dead_code)]
+        #[derive(NetworkBehaviour)]
+        pub struct Foo {
+            ping: libp2p::ping::Ping,
+        }
+    }
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<behaviour::Foo>();
+    }
+
+    let event = behaviour::FooEvent::Ping(libp2p::ping::PingEvent {
+        peer: libp2p::PeerId::random(),
+        result: Ok(libp2p::ping::PingSuccess::Pong),
+    });
+    assert!(format!("{:?}", event).starts_with("Ping("));
+}
+
+#[test]
+fn generated_out_event_for_tuple_struct() {
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    struct Foo(
+        libp2p::ping::Ping,
+        #[behaviour(ignore)] String,
+        libp2p::identify::Identify,
+    );
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<Foo>();
+    }
+
+    let event = FooEvent::Field0(libp2p::ping::PingEvent {
+        peer: libp2p::PeerId::random(),
+        result: Ok(libp2p::ping::PingSuccess::Pong),
+    });
+    assert!(format!("{:?}", event).starts_with("Field0("));
+    assert!(!matches!(event, FooEvent::Field2(_)));
+}
+
+#[test]
+fn enum_generated_out_event() {
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    enum Foo {
+        Ping(libp2p::ping::Ping),
+        Identify(libp2p::identify::Identify),
+        Kad(libp2p::kad::Kademlia<libp2p::kad::record::store::MemoryStore>),
+    }
+
+    #[allow(dead_code, unreachable_code)]
+    fn bar() {
+        require_net_behaviour::<Foo>();
+
+        let mut _swarm: libp2p::Swarm<Foo> = unimplemented!();
+
+        // check that the event is bubbled up all the way to swarm
+        let _ = async {
+            loop {
+                match _swarm.select_next_some().await {
+                    SwarmEvent::Behaviour(FooEvent::Ping(_)) => break,
+                    SwarmEvent::Behaviour(FooEvent::Identify(_)) => break,
+                    SwarmEvent::Behaviour(FooEvent::Kad(_)) => break,
+                    _ => {}
+                }
+            }
+        };
+    }
+}
+
+#[test]
+fn enum_custom_out_event() {
+    enum BehaviourOutEvent {
+        Ping(libp2p::ping::PingEvent),
+        Identify(libp2p::identify::IdentifyEvent),
+    }
+
+    impl From<libp2p::ping::PingEvent> for BehaviourOutEvent {
+        fn from(event: libp2p::ping::PingEvent) -> Self {
+            BehaviourOutEvent::Ping(event)
+        }
+    }
+
+    impl From<libp2p::identify::IdentifyEvent> for BehaviourOutEvent {
+        fn from(event: libp2p::identify::IdentifyEvent) -> Self {
+            BehaviourOutEvent::Identify(event)
+        }
+    }
+
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    #[behaviour(out_event = "BehaviourOutEvent")]
+    enum Foo {
+        Ping(libp2p::ping::Ping),
+        Identify(libp2p::identify::Identify),
+    }
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<Foo>();
+    }
+}
+
+#[test]
+fn enum_single_variant_with_generics() {
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    enum Foo<TBehaviour> {
+        Inner(TBehaviour),
+    }
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<Foo<libp2p::ping::Ping>>();
+    }
+}
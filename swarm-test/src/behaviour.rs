@@ -18,14 +18,14 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::{
-    ConnectionHandler, DialError, IntoConnectionHandler, NetworkBehaviour, NetworkBehaviourAction,
-    PollParameters,
-};
 use libp2p_core::{
     connection::{ConnectionId, ListenerId},
     multiaddr::Multiaddr,
-    ConnectedPoint, PeerId,
+    ConnectedPoint, NegotiatedProtocols, PeerId,
+};
+use libp2p_swarm::{
+    ConnectionHandler, DialError, IntoConnectionHandler, NetworkBehaviour, NetworkBehaviourAction,
+    PollParameters, ProtocolsChange,
 };
 use std::collections::HashMap;
 use std::task::{Context, Poll};
@@ -52,6 +52,7 @@ impl<THandler, TOutEvent> MockBehaviour<THandler, TOutEvent>
 where
     THandler: ConnectionHandler,
 {
+    /// Creates a new [`MockBehaviour`] handing out clones of `handler_proto`.
     pub fn new(handler_proto: THandler) -> Self {
         MockBehaviour {
             handler_proto,
@@ -89,6 +90,9 @@ where
     }
 }
 
+/// Custom event that can be produced by the [`ConnectionHandler`] of the [`NetworkBehaviour`].
+type THandlerOutEvent<TBehaviour> = <<<TBehaviour as NetworkBehaviour>::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent;
+
 /// A `CallTraceBehaviour` is a `NetworkBehaviour` that tracks
 /// invocations of callback methods and their arguments, wrapping
 /// around an inner behaviour. It ensures certain invariants are met.
@@ -99,21 +103,29 @@ where
     inner: TInner,
 
     pub addresses_of_peer: Vec<PeerId>,
-    pub inject_connection_established: Vec<(PeerId, ConnectionId, ConnectedPoint, usize)>,
-    pub inject_connection_closed: Vec<(PeerId, ConnectionId, ConnectedPoint, usize)>,
-    pub inject_event: Vec<(
+    pub inject_connection_established: Vec<(
         PeerId,
         ConnectionId,
-        <<TInner::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent,
+        ConnectedPoint,
+        NegotiatedProtocols,
+        usize,
     )>,
+    pub inject_connection_closed: Vec<(PeerId, ConnectionId, ConnectedPoint, usize)>,
+    pub inject_event: Vec<(PeerId, ConnectionId, THandlerOutEvent<TInner>)>,
     pub inject_dial_failure: Vec<Option<PeerId>>,
     pub inject_new_listener: Vec<ListenerId>,
     pub inject_new_listen_addr: Vec<(ListenerId, Multiaddr)>,
     pub inject_new_external_addr: Vec<Multiaddr>,
     pub inject_expired_listen_addr: Vec<(ListenerId, Multiaddr)>,
     pub inject_expired_external_addr: Vec<Multiaddr>,
+    pub inject_new_external_addr_candidate: Vec<Multiaddr>,
+    pub inject_external_addr_confirmed: Vec<Multiaddr>,
+    pub inject_external_addr_expired: Vec<Multiaddr>,
+    pub inject_local_protocols_change: Vec<(PeerId, ConnectionId, ProtocolsChange)>,
+    pub inject_remote_protocols_change: Vec<(PeerId, ConnectionId, ProtocolsChange)>,
     pub inject_listener_error: Vec<ListenerId>,
     pub inject_listener_closed: Vec<(ListenerId, bool)>,
+    pub inject_shutdown: usize,
     pub poll: usize,
 }
 
@@ -121,6 +133,7 @@ impl<TInner> CallTraceBehaviour<TInner>
 where
     TInner: NetworkBehaviour,
 {
+    /// Creates a new [`CallTraceBehaviour`] wrapping `inner`.
     pub fn new(inner: TInner) -> Self {
         Self {
             inner,
@@ -134,13 +147,19 @@ where
             inject_new_external_addr: Vec::new(),
             inject_expired_listen_addr: Vec::new(),
             inject_expired_external_addr: Vec::new(),
+            inject_new_external_addr_candidate: Vec::new(),
+            inject_external_addr_confirmed: Vec::new(),
+            inject_external_addr_expired: Vec::new(),
+            inject_local_protocols_change: Vec::new(),
+            inject_remote_protocols_change: Vec::new(),
             inject_listener_error: Vec::new(),
             inject_listener_closed: Vec::new(),
+            inject_shutdown: 0,
             poll: 0,
         }
     }
 
-    #[allow(dead_code)]
+    /// Forgets all recorded invocations.
     pub fn reset(&mut self) {
         self.addresses_of_peer = Vec::new();
         self.inject_connection_established = Vec::new();
@@ -150,19 +169,28 @@ where
         self.inject_new_listen_addr = Vec::new();
         self.inject_new_external_addr = Vec::new();
         self.inject_expired_listen_addr = Vec::new();
+        self.inject_new_external_addr_candidate = Vec::new();
+        self.inject_external_addr_confirmed = Vec::new();
+        self.inject_external_addr_expired = Vec::new();
+        self.inject_local_protocols_change = Vec::new();
+        self.inject_remote_protocols_change = Vec::new();
         self.inject_listener_error = Vec::new();
         self.inject_listener_closed = Vec::new();
+        self.inject_shutdown = 0;
         self.poll = 0;
     }
 
+    /// Returns a mutable reference to the wrapped behaviour.
     pub fn inner(&mut self) -> &mut TInner {
         &mut self.inner
     }
 
+    /// Returns the number of connections to `peer` that have been reported
+    /// as established but not yet as closed.
     pub fn num_connections_to_peer(&self, peer: PeerId) -> usize {
         self.inject_connection_established
             .iter()
-            .filter(|(peer_id, _, _, _)| *peer_id == peer)
+            .filter(|(peer_id, ..)| *peer_id == peer)
             .count()
             - self
                 .inject_connection_closed
@@ -234,7 +262,7 @@ where
     }
 
     fn addresses_of_peer(&mut self, p: &PeerId) -> Vec<Multiaddr> {
-        self.addresses_of_peer.push(p.clone());
+        self.addresses_of_peer.push(*p);
         self.inner.addresses_of_peer(p)
     }
 
@@ -243,6 +271,7 @@ where
         p: &PeerId,
         c: &ConnectionId,
         e: &ConnectedPoint,
+        negotiated_protocols: &NegotiatedProtocols,
         errors: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -274,13 +303,20 @@ where
             assert_eq!(other_established, 0)
         }
         self.inject_connection_established.push((
-            p.clone(),
-            c.clone(),
+            *p,
+            *c,
             e.clone(),
+            negotiated_protocols.clone(),
             other_established,
         ));
-        self.inner
-            .inject_connection_established(p, c, e, errors, other_established);
+        self.inner.inject_connection_established(
+            p,
+            c,
+            e,
+            negotiated_protocols,
+            errors,
+            other_established,
+        );
     }
 
     fn inject_connection_closed(
@@ -321,7 +357,7 @@ where
         assert!(
             self.inject_connection_established
                 .iter()
-                .any(|(peer, conn_id, endpoint, _)| (peer, conn_id, endpoint) == (p, c, e)),
+                .any(|(peer, conn_id, endpoint, ..)| (peer, conn_id, endpoint) == (p, c, e)),
             "`inject_connection_closed` is called only for connections for \
             which `inject_connection_established` was called first."
         );
@@ -351,7 +387,7 @@ where
             "`inject_event` is never called for closed connections."
         );
 
-        self.inject_event.push((p.clone(), c.clone(), e.clone()));
+        self.inject_event.push((p, c, e.clone()));
         self.inner.inject_event(p, c, e);
     }
 
@@ -390,8 +426,39 @@ where
         self.inner.inject_expired_external_addr(a);
     }
 
+    fn inject_new_external_addr_candidate(&mut self, a: &Multiaddr) {
+        self.inject_new_external_addr_candidate.push(a.clone());
+        self.inner.inject_new_external_addr_candidate(a);
+    }
+
+    fn inject_external_addr_confirmed(&mut self, a: &Multiaddr) {
+        self.inject_external_addr_confirmed.push(a.clone());
+        self.inner.inject_external_addr_confirmed(a);
+    }
+
+    fn inject_external_addr_expired(&mut self, a: &Multiaddr) {
+        self.inject_external_addr_expired.push(a.clone());
+        self.inner.inject_external_addr_expired(a);
+    }
+
+    fn inject_local_protocols_change(&mut self, p: &PeerId, c: &ConnectionId, e: &ProtocolsChange) {
+        self.inject_local_protocols_change.push((*p, *c, e.clone()));
+        self.inner.inject_local_protocols_change(p, c, e);
+    }
+
+    fn inject_remote_protocols_change(
+        &mut self,
+        p: &PeerId,
+        c: &ConnectionId,
+        e: &ProtocolsChange,
+    ) {
+        self.inject_remote_protocols_change
+            .push((*p, *c, e.clone()));
+        self.inner.inject_remote_protocols_change(p, c, e);
+    }
+
     fn inject_listener_error(&mut self, l: ListenerId, e: &(dyn std::error::Error + 'static)) {
-        self.inject_listener_error.push(l.clone());
+        self.inject_listener_error.push(l);
         self.inner.inject_listener_error(l, e);
     }
 
@@ -400,6 +467,11 @@ where
         self.inner.inject_listener_closed(l, r);
     }
 
+    fn inject_shutdown(&mut self) {
+        self.inject_shutdown += 1;
+        self.inner.inject_shutdown();
+    }
+
     fn poll(
         &mut self,
         cx: &mut Context,
@@ -0,0 +1,369 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Helpers for testing [`NetworkBehaviour`] implementations against real
+//! [`Swarm`]s.
+//!
+//! The [`SwarmExt`] trait allows to spin up [`Swarm`]s with an ephemeral
+//! identity on top of an in-memory transport, to connect them with each other
+//! and to wait for specific [`SwarmEvent`]s. All waiting functions are bound
+//! by a timeout, [`DEFAULT_TIMEOUT`] unless given explicitly, and panic with a
+//! description of the events that were emitted in the meantime, so that a
+//! hanging test points to its cause.
+//!
+//! The [`behaviour`] module provides [`NetworkBehaviour`]s that are useful as
+//! building blocks in tests.
+
+pub mod behaviour;
+
+use async_trait::async_trait;
+use futures::future::{self, Either};
+use futures::{Future, StreamExt};
+use futures_timer::Delay;
+use libp2p_core::{
+    identity::Keypair, multiaddr::Protocol, transport::MemoryTransport, upgrade::Version,
+    Multiaddr, PeerId, Transport,
+};
+use libp2p_plaintext::PlainText2Config;
+use libp2p_swarm::{
+    dial_opts::{DialOpts, PeerCondition},
+    AddressScore, ConnectionHandler, IntoConnectionHandler, NetworkBehaviour, Swarm, SwarmBuilder,
+    SwarmEvent,
+};
+use libp2p_yamux::YamuxConfig;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// The time the helpers of [`SwarmExt`] wait for an expected event before
+/// panicking, unless given explicitly, e.g. via [`SwarmExt::wait_with_timeout`].
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Custom error that can be produced by the [`ConnectionHandler`] of the [`NetworkBehaviour`].
+pub type THandlerErr<TBehaviour> = <<<TBehaviour as NetworkBehaviour>::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::Error;
+
+/// An extension trait for [`Swarm`] that makes it easier to set up a network
+/// of [`Swarm`]s for tests.
+#[async_trait]
+pub trait SwarmExt {
+    type NB: NetworkBehaviour;
+
+    /// Creates a new [`Swarm`] with a random identity.
+    ///
+    /// The [`Swarm`] uses a [`MemoryTransport`] together with plaintext
+    /// authentication and yamux multiplexing. Connections are thus cheap to
+    /// establish but only reach other [`Swarm`]s of the same process.
+    fn new_ephemeral(behaviour_fn: impl FnOnce(Keypair) -> Self::NB) -> Self
+    where
+        Self: Sized;
+
+    /// Listens on a random memory address, polling the [`Swarm`] until the
+    /// transport is ready to accept connections.
+    ///
+    /// The address is also added as external address, so that other
+    /// [`Swarm`]s can [`connect`](SwarmExt::connect) to it.
+    async fn listen_on_random_memory_address(&mut self) -> Multiaddr;
+
+    /// Establishes a connection to the given [`Swarm`], polling both of them
+    /// until the connection is established on both ends.
+    ///
+    /// The other [`Swarm`] needs to listen on at least one address, e.g. via
+    /// [`SwarmExt::listen_on_random_memory_address`].
+    ///
+    /// Panics if the connection is not established within [`DEFAULT_TIMEOUT`].
+    async fn connect<T>(&mut self, other: &mut Swarm<T>)
+    where
+        T: NetworkBehaviour + Send,
+        T::OutEvent: Debug + Send,
+        THandlerErr<T>: Debug + Send,
+    {
+        self.connect_with_timeout(other, DEFAULT_TIMEOUT).await
+    }
+
+    /// Like [`SwarmExt::connect`], panicking if the connection is not
+    /// established within the given timeout.
+    async fn connect_with_timeout<T>(&mut self, other: &mut Swarm<T>, timeout: Duration)
+    where
+        T: NetworkBehaviour + Send,
+        T::OutEvent: Debug + Send,
+        THandlerErr<T>: Debug + Send;
+
+    /// Polls the [`Swarm`] until `predicate` returns [`Some`] for one of its
+    /// events, discarding all other events.
+    ///
+    /// Panics with a list of the discarded events if no event matched within
+    /// [`DEFAULT_TIMEOUT`].
+    async fn wait<E, P>(&mut self, predicate: P) -> E
+    where
+        P: FnMut(
+                SwarmEvent<<Self::NB as NetworkBehaviour>::OutEvent, THandlerErr<Self::NB>>,
+            ) -> Option<E>
+            + Send,
+        E: Send,
+    {
+        self.wait_with_timeout(DEFAULT_TIMEOUT, predicate).await
+    }
+
+    /// Like [`SwarmExt::wait`], panicking if no event matched within the
+    /// given timeout.
+    async fn wait_with_timeout<E, P>(&mut self, timeout: Duration, predicate: P) -> E
+    where
+        P: FnMut(
+                SwarmEvent<<Self::NB as NetworkBehaviour>::OutEvent, THandlerErr<Self::NB>>,
+            ) -> Option<E>
+            + Send,
+        E: Send;
+
+    /// Returns the next [`SwarmEvent`], panicking if there is none within
+    /// [`DEFAULT_TIMEOUT`].
+    async fn next_swarm_event(
+        &mut self,
+    ) -> SwarmEvent<<Self::NB as NetworkBehaviour>::OutEvent, THandlerErr<Self::NB>>;
+
+    /// Returns the next event of the [`NetworkBehaviour`], discarding all
+    /// other [`SwarmEvent`]s.
+    ///
+    /// Panics if there is none within [`DEFAULT_TIMEOUT`].
+    async fn next_behaviour_event(&mut self) -> <Self::NB as NetworkBehaviour>::OutEvent;
+
+    /// Polls the [`Swarm`] forever, logging its events.
+    ///
+    /// Useful to drive a [`Swarm`] in the background, e.g. by spawning the
+    /// returned future onto an executor.
+    async fn loop_on_next(self);
+}
+
+#[async_trait]
+impl<B> SwarmExt for Swarm<B>
+where
+    B: NetworkBehaviour + Send,
+    B::OutEvent: Debug + Send,
+    THandlerErr<B>: Debug + Send,
+{
+    type NB = B;
+
+    fn new_ephemeral(behaviour_fn: impl FnOnce(Keypair) -> Self::NB) -> Self {
+        let identity = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(identity.public());
+
+        let transport = MemoryTransport
+            .upgrade(Version::V1)
+            .authenticate(PlainText2Config {
+                local_public_key: identity.public(),
+            })
+            .multiplex(YamuxConfig::default())
+            .timeout(Duration::from_secs(20))
+            .boxed();
+
+        SwarmBuilder::new(transport, behaviour_fn(identity), peer_id).build()
+    }
+
+    async fn listen_on_random_memory_address(&mut self) -> Multiaddr {
+        let requested = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+        let listener_id = self
+            .listen_on(requested.clone())
+            .unwrap_or_else(|e| panic!("Failed to listen on {}: {}", requested, e));
+
+        let address = self
+            .wait(|event| match event {
+                SwarmEvent::NewListenAddr {
+                    listener_id: id,
+                    address,
+                } if id == listener_id => Some(address),
+                _ => None,
+            })
+            .await;
+
+        self.add_external_address(address.clone(), AddressScore::Infinite);
+
+        address
+    }
+
+    async fn connect_with_timeout<T>(&mut self, other: &mut Swarm<T>, timeout: Duration)
+    where
+        T: NetworkBehaviour + Send,
+        T::OutEvent: Debug + Send,
+        THandlerErr<T>: Debug + Send,
+    {
+        let local_peer_id = *self.local_peer_id();
+        let other_peer_id = *other.local_peer_id();
+
+        let mut addresses = other
+            .external_addresses()
+            .map(|record| record.addr.clone())
+            .collect::<Vec<_>>();
+        if addresses.is_empty() {
+            addresses = other.listeners().cloned().collect();
+        }
+        assert!(
+            !addresses.is_empty(),
+            "Swarm {} cannot connect to swarm {} as the latter has no address to dial.",
+            local_peer_id,
+            other_peer_id
+        );
+
+        let opts = DialOpts::peer_id(other_peer_id)
+            .addresses(addresses)
+            .condition(PeerCondition::Always)
+            .bypass_backoff()
+            .build();
+        self.dial(opts).unwrap_or_else(|e| {
+            panic!(
+                "Swarm {} failed to dial swarm {}: {}",
+                local_peer_id, other_peer_id, e
+            )
+        });
+
+        let mut dialer_events = Vec::new();
+        let mut listener_events = Vec::new();
+        let mut dialer_done = false;
+        let mut listener_done = false;
+
+        let connected = with_timeout(timeout, async {
+            while !(dialer_done && listener_done) {
+                match future::select(self.select_next_some(), other.select_next_some()).await {
+                    Either::Left((event, _)) => match event {
+                        SwarmEvent::ConnectionEstablished { peer_id, .. }
+                            if peer_id == other_peer_id =>
+                        {
+                            dialer_done = true;
+                        }
+                        SwarmEvent::OutgoingConnectionError { error, .. } => {
+                            panic!(
+                                "Swarm {} failed to connect to swarm {}: {}",
+                                local_peer_id, other_peer_id, error
+                            );
+                        }
+                        event => {
+                            log::debug!("Swarm {} emitted {:?}", local_peer_id, event);
+                            dialer_events.push(format!("{:?}", event));
+                        }
+                    },
+                    Either::Right((event, _)) => match event {
+                        SwarmEvent::ConnectionEstablished { peer_id, .. }
+                            if peer_id == local_peer_id =>
+                        {
+                            listener_done = true;
+                        }
+                        SwarmEvent::IncomingConnectionError { error, .. } => {
+                            panic!(
+                                "Swarm {} failed to accept connection from swarm {}: {}",
+                                other_peer_id, local_peer_id, error
+                            );
+                        }
+                        event => {
+                            log::debug!("Swarm {} emitted {:?}", other_peer_id, event);
+                            listener_events.push(format!("{:?}", event));
+                        }
+                    },
+                }
+            }
+        })
+        .await;
+
+        if connected.is_none() {
+            panic!(
+                "Swarm {} did not connect to swarm {} within {:?} \
+                 (established on dialer: {}, on listener: {}).\n\
+                 Events of {}: {:#?}\nEvents of {}: {:#?}",
+                local_peer_id,
+                other_peer_id,
+                timeout,
+                dialer_done,
+                listener_done,
+                local_peer_id,
+                dialer_events,
+                other_peer_id,
+                listener_events
+            );
+        }
+    }
+
+    async fn wait_with_timeout<E, P>(&mut self, timeout: Duration, mut predicate: P) -> E
+    where
+        P: FnMut(SwarmEvent<B::OutEvent, THandlerErr<B>>) -> Option<E> + Send,
+        E: Send,
+    {
+        let local_peer_id = *self.local_peer_id();
+        let mut discarded = Vec::new();
+        let discarded_ref = &mut discarded;
+
+        let matched = with_timeout(timeout, async move {
+            loop {
+                let event = self.select_next_some().await;
+                let description = format!("{:?}", event);
+                log::debug!("Swarm {} emitted {}", local_peer_id, description);
+
+                if let Some(e) = predicate(event) {
+                    break e;
+                }
+                discarded_ref.push(description);
+            }
+        })
+        .await;
+
+        matched.unwrap_or_else(|| {
+            panic!(
+                "Swarm {} did not emit the expected event within {:?}.\n\
+                 Discarded events: {:#?}",
+                local_peer_id, timeout, discarded
+            )
+        })
+    }
+
+    async fn next_swarm_event(&mut self) -> SwarmEvent<B::OutEvent, THandlerErr<B>> {
+        let local_peer_id = *self.local_peer_id();
+
+        with_timeout(DEFAULT_TIMEOUT, self.select_next_some())
+            .await
+            .unwrap_or_else(|| {
+                panic!(
+                    "Swarm {} did not emit an event within {:?}.",
+                    local_peer_id, DEFAULT_TIMEOUT
+                )
+            })
+    }
+
+    async fn next_behaviour_event(&mut self) -> B::OutEvent {
+        self.wait(|event| match event {
+            SwarmEvent::Behaviour(event) => Some(event),
+            _ => None,
+        })
+        .await
+    }
+
+    async fn loop_on_next(mut self) {
+        let local_peer_id = *self.local_peer_id();
+
+        while let Some(event) = self.next().await {
+            log::debug!("Swarm {} emitted {:?}", local_peer_id, event);
+        }
+    }
+}
+
+/// Resolves to the output of `future`, or to [`None`] if `future` did not
+/// complete within `timeout`.
+async fn with_timeout<F: Future>(timeout: Duration, future: F) -> Option<F::Output> {
+    futures::pin_mut!(future);
+
+    match future::select(future, Delay::new(timeout)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(((), _)) => None,
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_swarm::{DummyBehaviour, KeepAlive, Swarm, SwarmEvent};
+use libp2p_swarm_test::behaviour::CallTraceBehaviour;
+use libp2p_swarm_test::SwarmExt;
+use std::time::Duration;
+
+fn new_swarm() -> Swarm<CallTraceBehaviour<DummyBehaviour>> {
+    Swarm::new_ephemeral(|_| {
+        CallTraceBehaviour::new(DummyBehaviour::with_keep_alive(KeepAlive::Yes))
+    })
+}
+
+#[async_std::test]
+async fn connect_establishes_connection_on_both_ends() {
+    let mut swarm1 = new_swarm();
+    let mut swarm2 = new_swarm();
+
+    swarm2.listen_on_random_memory_address().await;
+    swarm1.connect(&mut swarm2).await;
+
+    assert!(swarm1.is_connected(swarm2.local_peer_id()));
+    assert!(swarm2.is_connected(swarm1.local_peer_id()));
+    assert_eq!(
+        swarm1
+            .behaviour()
+            .num_connections_to_peer(*swarm2.local_peer_id()),
+        1
+    );
+    assert_eq!(
+        swarm2
+            .behaviour()
+            .num_connections_to_peer(*swarm1.local_peer_id()),
+        1
+    );
+}
+
+#[async_std::test]
+async fn wait_returns_matching_event() {
+    let mut swarm1 = new_swarm();
+    let mut swarm2 = new_swarm();
+
+    swarm2.listen_on_random_memory_address().await;
+    swarm1.connect(&mut swarm2).await;
+
+    let peer2 = *swarm2.local_peer_id();
+    assert!(swarm1.disconnect_peer_id(peer2).is_ok());
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    let closed = swarm1
+        .wait(|event| match event {
+            SwarmEvent::ConnectionClosed { peer_id, .. } => Some(peer_id),
+            _ => None,
+        })
+        .await;
+    assert_eq!(closed, peer2);
+}
+
+#[async_std::test]
+#[should_panic(expected = "did not emit the expected event")]
+async fn wait_panics_on_timeout() {
+    let mut swarm = new_swarm();
+
+    swarm
+        .wait_with_timeout(Duration::from_millis(100), |_| Some(()))
+        .await;
+}
@@ -0,0 +1,192 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use fnv::FnvHashMap;
+use libp2p_core::time::{self, Instant};
+use libp2p_core::{multiaddr::Protocol, Multiaddr, PeerId};
+use rand::Rng;
+use std::time::Duration;
+
+/// The configuration of the exponential backoff applied to addresses that
+/// failed to be dialed.
+///
+/// After the `n`-th consecutive failure to dial an address of a peer, the
+/// address is skipped by further dials of that peer for `base * 2^(n - 1)`,
+/// capped at `max`, plus a random fraction of up to `jitter` of that delay.
+/// A successful connection to the address resets its backoff.
+///
+/// See [`SwarmBuilder::dial_backoff`](crate::SwarmBuilder::dial_backoff).
+#[derive(Debug, Clone)]
+pub struct DialBackoffConfig {
+    base: Duration,
+    max: Duration,
+    jitter: f64,
+}
+
+impl DialBackoffConfig {
+    /// Sets the backoff after the first failure. Defaults to 1 second.
+    pub fn with_base(mut self, base: Duration) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Sets the maximum backoff. Defaults to 5 minutes.
+    pub fn with_max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets the maximum fraction of the backoff added at random, clamped to
+    /// `[0, 1]`. Defaults to `0.1`.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl Default for DialBackoffConfig {
+    fn default() -> Self {
+        DialBackoffConfig {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(5 * 60),
+            jitter: 0.1,
+        }
+    }
+}
+
+/// Tracks the dial backoff per peer and address.
+#[derive(Debug)]
+pub(crate) struct DialBackoff {
+    config: Option<DialBackoffConfig>,
+    entries: FnvHashMap<(Option<PeerId>, Multiaddr), Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    failures: u32,
+    until: Instant,
+}
+
+impl DialBackoff {
+    pub(crate) fn new(config: Option<DialBackoffConfig>) -> Self {
+        DialBackoff {
+            config,
+            entries: Default::default(),
+        }
+    }
+
+    /// Returns the instant until which dialing the address of the peer is
+    /// backed off, if it is.
+    pub(crate) fn backed_off_until(
+        &self,
+        peer: Option<PeerId>,
+        addr: &Multiaddr,
+    ) -> Option<Instant> {
+        self.entries
+            .get(&key(peer, addr))
+            .map(|e| e.until)
+            .filter(|until| *until > time::now())
+    }
+
+    /// Records a failure to dial the address of the peer.
+    pub(crate) fn record_failure(&mut self, peer: Option<PeerId>, addr: &Multiaddr) {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return,
+        };
+
+        let now = time::now();
+        // Forget about addresses that have not failed for a long time.
+        let max = config.max;
+        self.entries.retain(|_, e| e.until + max > now);
+
+        let entry = self.entries.entry(key(peer, addr)).or_insert(Entry {
+            failures: 0,
+            until: now,
+        });
+        entry.failures = entry.failures.saturating_add(1);
+
+        let backoff = config
+            .base
+            .checked_mul(2u32.saturating_pow(entry.failures - 1))
+            .map_or(config.max, |b| b.min(config.max));
+        let jitter = backoff.mul_f64(rand::thread_rng().gen_range(0.0, 1.0) * config.jitter);
+        entry.until = now + backoff + jitter;
+    }
+
+    /// Records a successful connection to the address of the peer.
+    pub(crate) fn record_success(&mut self, peer: Option<PeerId>, addr: &Multiaddr) {
+        self.entries.remove(&key(peer, addr));
+    }
+}
+
+/// The backoff is tracked without a trailing `/p2p` component of the dialed
+/// peer, which is added when dialing.
+fn key(peer: Option<PeerId>, addr: &Multiaddr) -> (Option<PeerId>, Multiaddr) {
+    let mut addr = addr.clone();
+    if let (Some(peer), Some(Protocol::P2p(hash))) = (peer, addr.iter().last()) {
+        if hash == *peer.as_ref() {
+            addr.pop();
+        }
+    }
+    (peer, addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_max() {
+        let config = DialBackoffConfig::default()
+            .with_base(Duration::from_secs(10))
+            .with_max(Duration::from_secs(25))
+            .with_jitter(0.0);
+        let mut backoff = DialBackoff::new(Some(config));
+        let peer_id = PeerId::random();
+        let peer = Some(peer_id);
+        let addr: Multiaddr = "/memory/1".parse().unwrap();
+
+        assert!(backoff.backed_off_until(peer, &addr).is_none());
+
+        for expected in [10, 20, 25, 25] {
+            let now = time::now();
+            backoff.record_failure(peer, &addr);
+            let until = backoff.backed_off_until(peer, &addr).unwrap();
+            let delay = until - now;
+            assert!(delay >= Duration::from_secs(expected));
+            assert!(delay < Duration::from_secs(expected + 1));
+        }
+
+        let p2p_addr = addr.clone().with(Protocol::P2p(peer_id.into()));
+        assert!(backoff.backed_off_until(peer, &p2p_addr).is_some());
+
+        backoff.record_success(peer, &p2p_addr);
+        assert!(backoff.backed_off_until(peer, &addr).is_none());
+    }
+
+    #[test]
+    fn disabled_backoff_records_nothing() {
+        let mut backoff = DialBackoff::new(None);
+        let addr: Multiaddr = "/memory/1".parse().unwrap();
+        backoff.record_failure(None, &addr);
+        assert!(backoff.backed_off_until(None, &addr).is_none());
+    }
+}
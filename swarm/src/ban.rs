@@ -0,0 +1,279 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use fnv::FnvHashMap;
+use libp2p_core::time::Instant;
+use libp2p_core::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::{
+    error, fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// A range of IP addresses, given by an address and the length of its
+/// network prefix in bits, e.g. `10.0.0.0/8` or `2001:db8::/32`.
+///
+/// Used to ban IP addresses via [`Swarm::ban_ip`](crate::Swarm::ban_ip).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    /// Creates the network of the given address with the given prefix
+    /// length. The host bits of the address are cleared.
+    ///
+    /// Returns an error if the prefix length exceeds the length of the
+    /// address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, InvalidIpNet> {
+        let addr = match addr {
+            IpAddr::V4(a) if prefix_len <= 32 => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(prefix_len))
+                    .unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(a) & mask))
+            }
+            IpAddr::V6(a) if prefix_len <= 128 => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(prefix_len))
+                    .unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(a) & mask))
+            }
+            _ => return Err(InvalidIpNet(format!("{}/{}", addr, prefix_len))),
+        };
+        Ok(IpNet { addr, prefix_len })
+    }
+
+    /// The network address, i.e. the first address of the range.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The length of the network prefix in bits.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Whether the given IP address is within this network.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        IpNet::new(*ip, self.prefix_len).map_or(false, |net| net.addr == self.addr)
+    }
+
+    /// Whether the IP address the given [`Multiaddr`] starts with is within
+    /// this network.
+    ///
+    /// Addresses that do not start with an IP address, e.g. `/dns/...`, are
+    /// never contained.
+    pub fn contains_addr(&self, addr: &Multiaddr) -> bool {
+        ip_of(addr).map_or(false, |ip| self.contains(&ip))
+    }
+}
+
+impl From<IpAddr> for IpNet {
+    /// The network containing only the given address.
+    fn from(addr: IpAddr) -> Self {
+        let prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        IpNet { addr, prefix_len }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = InvalidIpNet;
+
+    /// Parses either a network in CIDR notation or a single IP address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidIpNet(s.to_string());
+        match s.split_once('/') {
+            Some((addr, prefix_len)) => IpNet::new(
+                addr.parse().map_err(|_| invalid())?,
+                prefix_len.parse().map_err(|_| invalid())?,
+            ),
+            None => s.parse::<IpAddr>().map(IpNet::from).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Error of [`IpNet::new`] and of parsing an [`IpNet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidIpNet(String);
+
+impl fmt::Display for InvalidIpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid IP network: {}", self.0)
+    }
+}
+
+impl error::Error for InvalidIpNet {}
+
+/// What a ban applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    /// All connections of a peer.
+    Peer(PeerId),
+    /// All connections from and to the IP addresses of a network.
+    Ip(IpNet),
+}
+
+/// The IP address a [`Multiaddr`] starts with, if any.
+fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    match addr.iter().next()? {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    }
+}
+
+/// The banned peers and IP networks, each banned until an optional instant.
+#[derive(Debug, Default)]
+pub(crate) struct Bans {
+    peers: FnvHashMap<PeerId, Option<Instant>>,
+    ips: FnvHashMap<IpNet, Option<Instant>>,
+}
+
+impl Bans {
+    /// Bans the target until the given instant, or until it is unbanned if
+    /// `None`. A ban of a target that is already banned is replaced.
+    ///
+    /// Returns `true` if the target was not banned before.
+    pub(crate) fn ban(&mut self, target: BanTarget, until: Option<Instant>) -> bool {
+        match target {
+            BanTarget::Peer(peer) => self.peers.insert(peer, until).is_none(),
+            BanTarget::Ip(net) => self.ips.insert(net, until).is_none(),
+        }
+    }
+
+    /// Lifts the ban of the target.
+    ///
+    /// Returns `true` if the target was banned.
+    pub(crate) fn unban(&mut self, target: &BanTarget) -> bool {
+        match target {
+            BanTarget::Peer(peer) => self.peers.remove(peer).is_some(),
+            BanTarget::Ip(net) => self.ips.remove(net).is_some(),
+        }
+    }
+
+    pub(crate) fn is_peer_banned(&self, peer: &PeerId) -> bool {
+        self.peers.contains_key(peer)
+    }
+
+    /// Returns a banned network containing the IP address the given
+    /// [`Multiaddr`] starts with, if any.
+    pub(crate) fn banned_net_of(&self, addr: &Multiaddr) -> Option<IpNet> {
+        if self.ips.is_empty() {
+            return None;
+        }
+        let ip = ip_of(addr)?;
+        self.ips.keys().find(|net| net.contains(&ip)).copied()
+    }
+
+    pub(crate) fn iter_peers(&self) -> impl Iterator<Item = (&PeerId, Option<Instant>)> {
+        self.peers.iter().map(|(peer, until)| (peer, *until))
+    }
+
+    pub(crate) fn iter_ips(&self) -> impl Iterator<Item = (&IpNet, Option<Instant>)> {
+        self.ips.iter().map(|(net, until)| (net, *until))
+    }
+
+    /// Removes and returns a ban that expired at `now`, if any.
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<BanTarget> {
+        let expired = |until: &Option<Instant>| until.map_or(false, |until| until <= now);
+        if let Some(peer) = self.peers.iter().find(|(_, u)| expired(u)).map(|(p, _)| *p) {
+            self.peers.remove(&peer);
+            return Some(BanTarget::Peer(peer));
+        }
+        if let Some(net) = self.ips.iter().find(|(_, u)| expired(u)).map(|(n, _)| *n) {
+            self.ips.remove(&net);
+            return Some(BanTarget::Ip(net));
+        }
+        None
+    }
+
+    /// The instant the next ban expires at, if any.
+    pub(crate) fn next_expiry(&self) -> Option<Instant> {
+        self.peers
+            .values()
+            .chain(self.ips.values())
+            .filter_map(|until| *until)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn ip_net_contains_addresses_of_prefix() {
+        let net: IpNet = "10.1.2.3/16".parse().unwrap();
+        assert_eq!(net.to_string(), "10.1.0.0/16");
+        assert!(net.contains(&"10.1.255.1".parse().unwrap()));
+        assert!(!net.contains(&"10.2.0.1".parse().unwrap()));
+        assert!(!net.contains(&"::1".parse().unwrap()));
+        assert!(net.contains_addr(&"/ip4/10.1.0.7/tcp/4001".parse().unwrap()));
+        assert!(!net.contains_addr(&"/dns4/example.com/tcp/4001".parse().unwrap()));
+
+        let host: IpNet = "2001:db8::1".parse().unwrap();
+        assert_eq!(host.prefix_len(), 128);
+        assert!(host.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(!host.contains(&"2001:db8::2".parse().unwrap()));
+
+        let all: IpNet = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains(&"192.0.2.1".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("10.0.0/8".parse::<IpNet>().is_err());
+    }
+
+    #[test]
+    fn bans_expire() {
+        let mut bans = Bans::default();
+        let now = Instant::now();
+        let peer = PeerId::random();
+        let net: IpNet = "192.0.2.0/24".parse().unwrap();
+        let addr: Multiaddr = "/ip4/192.0.2.9/udp/1".parse().unwrap();
+
+        assert!(bans.ban(BanTarget::Peer(peer), None));
+        assert!(bans.ban(BanTarget::Ip(net), Some(now + Duration::from_secs(60))));
+        assert!(!bans.ban(BanTarget::Ip(net), Some(now + Duration::from_secs(10))));
+        assert_eq!(bans.banned_net_of(&addr), Some(net));
+        assert_eq!(bans.next_expiry(), Some(now + Duration::from_secs(10)));
+
+        assert_eq!(bans.pop_expired(now), None);
+        assert_eq!(
+            bans.pop_expired(now + Duration::from_secs(10)),
+            Some(BanTarget::Ip(net))
+        );
+        assert_eq!(bans.pop_expired(now + Duration::from_secs(10)), None);
+        assert_eq!(bans.banned_net_of(&addr), None);
+        assert!(bans.is_peer_banned(&peer));
+        assert_eq!(bans.next_expiry(), None);
+    }
+}
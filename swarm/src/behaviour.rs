@@ -22,11 +22,12 @@ pub mod either;
 pub mod toggle;
 
 use crate::dial_opts::DialOpts;
-use crate::handler::{ConnectionHandler, IntoConnectionHandler};
-use crate::{AddressRecord, AddressScore, DialError};
+use crate::handler::{ConnectionHandler, IntoConnectionHandler, ProtocolsChange};
+use crate::peer_store::PeerStore;
+use crate::{AddressRecord, AddressScore, ConnectionManager, DialError};
 use libp2p_core::{
     connection::{ConnectionId, ListenerId},
-    ConnectedPoint, Multiaddr, PeerId,
+    ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId,
 };
 use std::{task::Context, task::Poll};
 
@@ -80,11 +81,36 @@ pub(crate) type THandlerOutEvent<THandler> =
 /// it will delegate to each `struct` member and return a concatenated array of all addresses
 /// returned by the struct members.
 ///
-/// By default the derive sets the [`NetworkBehaviour::OutEvent`] as `()` but this can be overridden
-/// with `#[behaviour(out_event = "AnotherType")]`.
+/// By default the derive generates an enum named after the `struct` with an `Event` suffix, and
+/// sets it as the [`NetworkBehaviour::OutEvent`]. The enum has one variant per `struct` member,
+/// named after the member in `UpperCamelCase` and wrapping the [`NetworkBehaviour::OutEvent`] of
+/// the member. The enum implements [`Debug`](std::fmt::Debug), which requires the events of
+/// non-generic members to implement [`Debug`](std::fmt::Debug). The events of members whose type
+/// depends on a generic parameter of the `struct` are bounded instead. The enum has the same
+/// visibility as the `struct`, thus the behaviour types of the members have to be at least as
+/// visible as the `struct` itself. Otherwise set a custom `out_event` as described below.
 ///
-/// When setting a custom `out_event` users have to implement [`From`] converting from each of the
-/// event types generated by the struct members to the custom `out_event`.
+/// ``` rust
+/// # use libp2p::identify::Identify;
+/// # use libp2p::ping::Ping;
+/// # use libp2p::NetworkBehaviour;
+/// #[derive(NetworkBehaviour)]
+/// struct MyBehaviour {
+///   identify: Identify,
+///   ping: Ping,
+/// }
+///
+/// fn handle(event: MyBehaviourEvent) {
+///   match event {
+///     MyBehaviourEvent::Identify(_) => {}
+///     MyBehaviourEvent::Ping(_) => {}
+///   }
+/// }
+/// ```
+///
+/// The out event can be overridden with `#[behaviour(out_event = "AnotherType")]`. When setting a
+/// custom `out_event` users have to implement [`From`] converting from each of the event types
+/// generated by the struct members to the custom `out_event`.
 ///
 /// ``` rust
 /// # use libp2p::identify::{Identify, IdentifyEvent};
@@ -150,6 +176,29 @@ pub(crate) type THandlerOutEvent<THandler> =
 /// # }
 /// ```
 ///
+/// The macro can also be derived on an `enum` whose variants each wrap a single
+/// [`NetworkBehaviour`], to select one of several mutually exclusive behaviours at runtime. All
+/// calls are delegated to the behaviour of the active variant. The generated `Event` enum has one
+/// variant per `enum` variant, named alike.
+///
+/// ``` rust
+/// # use libp2p::identify::Identify;
+/// # use libp2p::ping::Ping;
+/// # use libp2p::NetworkBehaviour;
+/// #[derive(NetworkBehaviour)]
+/// enum MyBehaviour {
+///   Identify(Identify),
+///   Ping(Ping),
+/// }
+///
+/// fn handle(event: MyBehaviourEvent) {
+///   match event {
+///     MyBehaviourEvent::Identify(_) => {}
+///     MyBehaviourEvent::Ping(_) => {}
+///   }
+/// }
+/// ```
+///
 /// For users that need access to the root [`NetworkBehaviour`] implementation while processing
 /// emitted events, one can specify `#[behaviour(event_process = true)]`. Events generated by the
 /// struct members are delegated to [`NetworkBehaviourEventProcess`] implementations. Those must be
@@ -206,6 +255,7 @@ pub trait NetworkBehaviour: 'static {
         _peer_id: &PeerId,
         _connection_id: &ConnectionId,
         _endpoint: &ConnectedPoint,
+        _negotiated_protocols: &NegotiatedProtocols,
         _failed_addresses: Option<&Vec<Multiaddr>>,
         _other_established: usize,
     ) {
@@ -236,6 +286,32 @@ pub trait NetworkBehaviour: 'static {
     ) {
     }
 
+    /// Informs the behaviour that the protocols the local node supports on a
+    /// connection changed, e.g. because a [`Toggle`](crate::behaviour::toggle::Toggle)
+    /// has been enabled.
+    ///
+    /// The protocols are those of [`ConnectionHandler::listen_protocol_names`]. The
+    /// initial protocols are reported as [`ProtocolsChange::Added`] once the
+    /// connection is established.
+    fn inject_local_protocols_change(
+        &mut self,
+        _peer_id: &PeerId,
+        _connection: &ConnectionId,
+        _change: &ProtocolsChange,
+    ) {
+    }
+
+    /// Informs the behaviour that the protocols the remote supports on a
+    /// connection have been learned or changed, as reported by a handler via
+    /// [`ConnectionHandlerEvent::ReportRemoteProtocols`](crate::ConnectionHandlerEvent::ReportRemoteProtocols).
+    fn inject_remote_protocols_change(
+        &mut self,
+        _peer_id: &PeerId,
+        _connection: &ConnectionId,
+        _change: &ProtocolsChange,
+    ) {
+    }
+
     /// Informs the behaviour about an event generated by the handler dedicated to the peer identified by `peer_id`.
     /// for the behaviour.
     ///
@@ -293,6 +369,27 @@ pub trait NetworkBehaviour: 'static {
     /// Indicates to the behaviour that an external address was removed.
     fn inject_expired_external_addr(&mut self, _addr: &Multiaddr) {}
 
+    /// Indicates to the behaviour that a new candidate for an external address
+    /// was observed, see
+    /// [`NetworkBehaviourAction::ReportExternalAddrCandidate`].
+    fn inject_new_external_addr_candidate(&mut self, _addr: &Multiaddr) {}
+
+    /// Indicates to the behaviour that a candidate for an external address was
+    /// confirmed, see [`ExternalAddrConfig`](crate::ExternalAddrConfig).
+    fn inject_external_addr_confirmed(&mut self, _addr: &Multiaddr) {}
+
+    /// Indicates to the behaviour that a confirmed external address expired, as
+    /// it was no longer observed.
+    fn inject_external_addr_expired(&mut self, _addr: &Multiaddr) {}
+
+    /// Indicates to the behaviour that the [`Swarm`](crate::Swarm) is shutting down, see
+    /// [`Swarm::close`](crate::Swarm::close).
+    ///
+    /// Established connections are kept open until [`NetworkBehaviour::poll`] returned
+    /// [`Poll::Pending`], thus the behaviour can still notify connection handlers, e.g. to say
+    /// goodbye to remote peers. New dials are rejected.
+    fn inject_shutdown(&mut self) {}
+
     /// Polls for things that swarm should do.
     ///
     /// This API mimics the API of the `Stream` trait. The method may register the current task in
@@ -327,8 +424,30 @@ pub trait PollParameters {
     /// Returns the list of the addresses nodes can use to reach us.
     fn external_addresses(&self) -> Self::ExternalAddressesIter;
 
+    /// Returns the candidates for external addresses that are not yet
+    /// confirmed, e.g. to be probed.
+    ///
+    /// Returns no candidates by default.
+    fn external_address_candidates(&self) -> std::vec::IntoIter<Multiaddr> {
+        Vec::new().into_iter()
+    }
+
     /// Returns the peer id of the local node.
     fn local_peer_id(&self) -> &PeerId;
+
+    /// Returns the [`PeerStore`] of the [`Swarm`](crate::Swarm).
+    fn peer_store(&self) -> &PeerStore;
+
+    /// Returns the [`PeerStore`] of the [`Swarm`](crate::Swarm), to record
+    /// information learned by the behaviour.
+    fn peer_store_mut(&mut self) -> &mut PeerStore;
+
+    /// Returns the [`ConnectionManager`] of the [`Swarm`](crate::Swarm).
+    fn connection_manager(&self) -> &ConnectionManager;
+
+    /// Returns the [`ConnectionManager`] of the [`Swarm`](crate::Swarm), to
+    /// tag or protect peers.
+    fn connection_manager_mut(&mut self) -> &mut ConnectionManager;
 }
 
 /// When deriving [`NetworkBehaviour`] this trait must by default be implemented for all the
@@ -586,6 +705,31 @@ pub enum NetworkBehaviourAction<
         score: AddressScore,
     },
 
+    /// Informs the `Swarm` about an address of the local node observed by
+    /// the given remote peer.
+    ///
+    /// Unlike [`NetworkBehaviourAction::ReportObservedAddr`], the address is
+    /// only added to the external addresses of the local node once confirmed,
+    /// see [`ExternalAddrConfig`](crate::ExternalAddrConfig). Behaviours are
+    /// informed via [`NetworkBehaviour::inject_new_external_addr_candidate`].
+    ReportExternalAddrCandidate {
+        /// The observed address of the local node.
+        address: Multiaddr,
+        /// The peer that observed the address.
+        observer: PeerId,
+    },
+
+    /// Confirms that the local node is reachable at the given address, e.g.
+    /// after a successful dial-back by a remote peer.
+    ///
+    /// The address is added to the external addresses of the local node and
+    /// expires unless observed or confirmed again, see
+    /// [`ExternalAddrConfig`](crate::ExternalAddrConfig).
+    ConfirmExternalAddr {
+        /// The confirmed address of the local node.
+        address: Multiaddr,
+    },
+
     /// Instructs the `Swarm` to initiate a graceful close of one or all connections
     /// with the given peer.
     ///
@@ -629,6 +773,12 @@ impl<TOutEvent, THandler: IntoConnectionHandler, TInEventOld>
             NetworkBehaviourAction::ReportObservedAddr { address, score } => {
                 NetworkBehaviourAction::ReportObservedAddr { address, score }
             }
+            NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer } => {
+                NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer }
+            }
+            NetworkBehaviourAction::ConfirmExternalAddr { address } => {
+                NetworkBehaviourAction::ConfirmExternalAddr { address }
+            }
             NetworkBehaviourAction::CloseConnection {
                 peer_id,
                 connection,
@@ -660,6 +810,12 @@ impl<TOutEvent, THandler: IntoConnectionHandler> NetworkBehaviourAction<TOutEven
             NetworkBehaviourAction::ReportObservedAddr { address, score } => {
                 NetworkBehaviourAction::ReportObservedAddr { address, score }
             }
+            NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer } => {
+                NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer }
+            }
+            NetworkBehaviourAction::ConfirmExternalAddr { address } => {
+                NetworkBehaviourAction::ConfirmExternalAddr { address }
+            }
             NetworkBehaviourAction::CloseConnection {
                 peer_id,
                 connection,
@@ -703,6 +859,12 @@ where
             NetworkBehaviourAction::ReportObservedAddr { address, score } => {
                 NetworkBehaviourAction::ReportObservedAddr { address, score }
             }
+            NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer } => {
+                NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer }
+            }
+            NetworkBehaviourAction::ConfirmExternalAddr { address } => {
+                NetworkBehaviourAction::ConfirmExternalAddr { address }
+            }
             NetworkBehaviourAction::CloseConnection {
                 peer_id,
                 connection,
@@ -747,6 +909,12 @@ where
             NetworkBehaviourAction::ReportObservedAddr { address, score } => {
                 NetworkBehaviourAction::ReportObservedAddr { address, score }
             }
+            NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer } => {
+                NetworkBehaviourAction::ReportExternalAddrCandidate { address, observer }
+            }
+            NetworkBehaviourAction::ConfirmExternalAddr { address } => {
+                NetworkBehaviourAction::ConfirmExternalAddr { address }
+            }
             NetworkBehaviourAction::CloseConnection {
                 peer_id,
                 connection,
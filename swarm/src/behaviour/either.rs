@@ -18,7 +18,9 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::handler::{either::IntoEitherHandler, ConnectionHandler, IntoConnectionHandler};
+use crate::handler::{
+    either::IntoEitherHandler, ConnectionHandler, IntoConnectionHandler, ProtocolsChange,
+};
 use crate::{
     DialError, NetworkBehaviour, NetworkBehaviourAction, NetworkBehaviourEventProcess,
     PollParameters,
@@ -26,7 +28,7 @@ use crate::{
 use either::Either;
 use libp2p_core::{
     connection::{ConnectionId, ListenerId},
-    ConnectedPoint, Multiaddr, PeerId,
+    ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId,
 };
 use std::{task::Context, task::Poll};
 
@@ -58,6 +60,7 @@ where
         peer_id: &PeerId,
         connection: &ConnectionId,
         endpoint: &ConnectedPoint,
+        negotiated_protocols: &NegotiatedProtocols,
         errors: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
@@ -66,6 +69,7 @@ where
                 peer_id,
                 connection,
                 endpoint,
+                negotiated_protocols,
                 errors,
                 other_established,
             ),
@@ -73,6 +77,7 @@ where
                 peer_id,
                 connection,
                 endpoint,
+                negotiated_protocols,
                 errors,
                 other_established,
             ),
@@ -120,6 +125,30 @@ where
         }
     }
 
+    fn inject_local_protocols_change(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        change: &ProtocolsChange,
+    ) {
+        match self {
+            Either::Left(a) => a.inject_local_protocols_change(peer_id, connection, change),
+            Either::Right(b) => b.inject_local_protocols_change(peer_id, connection, change),
+        }
+    }
+
+    fn inject_remote_protocols_change(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        change: &ProtocolsChange,
+    ) {
+        match self {
+            Either::Left(a) => a.inject_remote_protocols_change(peer_id, connection, change),
+            Either::Right(b) => b.inject_remote_protocols_change(peer_id, connection, change),
+        }
+    }
+
     fn inject_event(
         &mut self,
         peer_id: PeerId,
@@ -206,6 +235,27 @@ where
         }
     }
 
+    fn inject_new_external_addr_candidate(&mut self, addr: &Multiaddr) {
+        match self {
+            Either::Left(a) => a.inject_new_external_addr_candidate(addr),
+            Either::Right(b) => b.inject_new_external_addr_candidate(addr),
+        }
+    }
+
+    fn inject_external_addr_confirmed(&mut self, addr: &Multiaddr) {
+        match self {
+            Either::Left(a) => a.inject_external_addr_confirmed(addr),
+            Either::Right(b) => b.inject_external_addr_confirmed(addr),
+        }
+    }
+
+    fn inject_external_addr_expired(&mut self, addr: &Multiaddr) {
+        match self {
+            Either::Left(a) => a.inject_external_addr_expired(addr),
+            Either::Right(b) => b.inject_external_addr_expired(addr),
+        }
+    }
+
     fn inject_listener_error(&mut self, id: ListenerId, err: &(dyn std::error::Error + 'static)) {
         match self {
             Either::Left(a) => a.inject_listener_error(id, err),
@@ -220,6 +270,13 @@ where
         }
     }
 
+    fn inject_shutdown(&mut self) {
+        match self {
+            Either::Left(a) => a.inject_shutdown(),
+            Either::Right(b) => b.inject_shutdown(),
+        }
+    }
+
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
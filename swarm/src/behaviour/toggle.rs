@@ -20,41 +20,73 @@
 
 use crate::handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, IntoConnectionHandler,
-    KeepAlive, SubstreamProtocol,
+    KeepAlive, ProtocolsChange, SubstreamProtocol,
 };
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend, SendWrapper};
 use crate::{
     DialError, NetworkBehaviour, NetworkBehaviourAction, NetworkBehaviourEventProcess,
-    PollParameters,
+    NotifyHandler, PollParameters,
 };
 use either::Either;
 use libp2p_core::{
     connection::{ConnectionId, ListenerId},
     either::{EitherError, EitherOutput},
     upgrade::{DeniedUpgrade, EitherUpgrade},
-    ConnectedPoint, Multiaddr, PeerId,
+    ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId,
+};
+use std::{
+    collections::{HashSet, VecDeque},
+    task::Context,
+    task::Poll,
 };
-use std::{task::Context, task::Poll};
 
 /// Implementation of `NetworkBehaviour` that can be either in the disabled or enabled state.
 ///
-/// The state can only be chosen at initialization.
+/// A `Toggle` created from `None` is disabled for good. A `Toggle` created from
+/// `Some` behaviour is enabled initially and can be switched via [`Toggle::set_enabled`].
+///
+/// While disabled, the inner behaviour is not polled and the handlers of its connections
+/// neither accept inbound substreams nor are polled. It is still informed about
+/// connections and addresses, so that it is up to date when enabled again.
 pub struct Toggle<TBehaviour> {
     inner: Option<TBehaviour>,
+    /// Whether the inner behaviour, if any, is enabled.
+    enabled: bool,
+    /// The established connections, to inform their handlers about state changes.
+    connections: HashSet<(PeerId, ConnectionId)>,
+    /// State changes not yet sent to the handlers of the given connections.
+    pending_notifications: VecDeque<(PeerId, ConnectionId, bool)>,
 }
 
 impl<TBehaviour> Toggle<TBehaviour> {
     /// Returns `true` if `Toggle` is enabled and `false` if it's disabled.
     pub fn is_enabled(&self) -> bool {
-        self.inner.is_some()
+        self.inner.is_some() && self.enabled
+    }
+
+    /// Enables or disables the inner `NetworkBehaviour`, including the handlers of its
+    /// established connections, which report the change of the local protocols via
+    /// [`ConnectionHandlerEvent::ReportLocalProtocolsChange`].
+    ///
+    /// Has no effect if the `Toggle` has no inner `NetworkBehaviour`.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.inner.is_none() || self.enabled == enabled {
+            return;
+        }
+        self.enabled = enabled;
+        self.pending_notifications.extend(
+            self.connections
+                .iter()
+                .map(|(peer_id, connection)| (*peer_id, *connection, enabled)),
+        );
     }
 
-    /// Returns a reference to the inner `NetworkBehaviour`.
+    /// Returns a reference to the inner `NetworkBehaviour`, whether enabled or not.
     pub fn as_ref(&self) -> Option<&TBehaviour> {
         self.inner.as_ref()
     }
 
-    /// Returns a mutable reference to the inner `NetworkBehaviour`.
+    /// Returns a mutable reference to the inner `NetworkBehaviour`, whether enabled or not.
     pub fn as_mut(&mut self) -> Option<&mut TBehaviour> {
         self.inner.as_mut()
     }
@@ -62,7 +94,12 @@ impl<TBehaviour> Toggle<TBehaviour> {
 
 impl<TBehaviour> From<Option<TBehaviour>> for Toggle<TBehaviour> {
     fn from(inner: Option<TBehaviour>) -> Self {
-        Toggle { inner }
+        Toggle {
+            enabled: inner.is_some(),
+            inner,
+            connections: HashSet::new(),
+            pending_notifications: VecDeque::new(),
+        }
     }
 }
 
@@ -76,14 +113,15 @@ where
     fn new_handler(&mut self) -> Self::ConnectionHandler {
         ToggleIntoProtoHandler {
             inner: self.inner.as_mut().map(|i| i.new_handler()),
+            enabled: self.enabled,
         }
     }
 
     fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
-        self.inner
-            .as_mut()
-            .map(|b| b.addresses_of_peer(peer_id))
-            .unwrap_or_else(Vec::new)
+        match self.inner.as_mut() {
+            Some(inner) if self.enabled => inner.addresses_of_peer(peer_id),
+            _ => Vec::new(),
+        }
     }
 
     fn inject_connection_established(
@@ -91,14 +129,20 @@ where
         peer_id: &PeerId,
         connection: &ConnectionId,
         endpoint: &ConnectedPoint,
+        negotiated_protocols: &NegotiatedProtocols,
         errors: Option<&Vec<Multiaddr>>,
         other_established: usize,
     ) {
         if let Some(inner) = self.inner.as_mut() {
+            self.connections.insert((*peer_id, *connection));
+            // The handler may have been created before the state last changed.
+            self.pending_notifications
+                .push_back((*peer_id, *connection, self.enabled));
             inner.inject_connection_established(
                 peer_id,
                 connection,
                 endpoint,
+                negotiated_protocols,
                 errors,
                 other_established,
             )
@@ -114,6 +158,7 @@ where
         remaining_established: usize,
     ) {
         if let Some(inner) = self.inner.as_mut() {
+            self.connections.remove(&(*peer_id, *connection));
             if let Some(handler) = handler.inner {
                 inner.inject_connection_closed(
                     peer_id,
@@ -138,6 +183,28 @@ where
         }
     }
 
+    fn inject_local_protocols_change(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        change: &ProtocolsChange,
+    ) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_local_protocols_change(peer_id, connection, change)
+        }
+    }
+
+    fn inject_remote_protocols_change(
+        &mut self,
+        peer_id: &PeerId,
+        connection: &ConnectionId,
+        change: &ProtocolsChange,
+    ) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_remote_protocols_change(peer_id, connection, change)
+        }
+    }
+
     fn inject_event(
         &mut self,
         peer_id: PeerId,
@@ -205,6 +272,24 @@ where
         }
     }
 
+    fn inject_new_external_addr_candidate(&mut self, addr: &Multiaddr) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_new_external_addr_candidate(addr)
+        }
+    }
+
+    fn inject_external_addr_confirmed(&mut self, addr: &Multiaddr) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_external_addr_confirmed(addr)
+        }
+    }
+
+    fn inject_external_addr_expired(&mut self, addr: &Multiaddr) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_external_addr_expired(addr)
+        }
+    }
+
     fn inject_listener_error(&mut self, id: ListenerId, err: &(dyn std::error::Error + 'static)) {
         if let Some(inner) = self.inner.as_mut() {
             inner.inject_listener_error(id, err)
@@ -217,17 +302,39 @@ where
         }
     }
 
+    fn inject_shutdown(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_shutdown()
+        }
+    }
+
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
         params: &mut impl PollParameters,
     ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
-        if let Some(inner) = self.inner.as_mut() {
-            inner
-                .poll(cx, params)
-                .map(|action| action.map_handler(|h| ToggleIntoProtoHandler { inner: Some(h) }))
-        } else {
-            Poll::Pending
+        while let Some((peer_id, connection, enabled)) = self.pending_notifications.pop_front() {
+            // Connections may have been closed since the state changed.
+            if self.connections.contains(&(peer_id, connection)) {
+                return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
+                    peer_id,
+                    handler: NotifyHandler::One(connection),
+                    event: ToggleInEvent::SetEnabled(enabled),
+                });
+            }
+        }
+
+        match self.inner.as_mut() {
+            Some(inner) if self.enabled => inner.poll(cx, params).map(|action| {
+                action.map_handler_and_in(
+                    |h| ToggleIntoProtoHandler {
+                        inner: Some(h),
+                        enabled: true,
+                    },
+                    ToggleInEvent::Inner,
+                )
+            }),
+            _ => Poll::Pending,
         }
     }
 }
@@ -246,6 +353,7 @@ where
 /// Implementation of `IntoConnectionHandler` that can be in the disabled state.
 pub struct ToggleIntoProtoHandler<TInner> {
     inner: Option<TInner>,
+    enabled: bool,
 }
 
 impl<TInner> IntoConnectionHandler for ToggleIntoProtoHandler<TInner>
@@ -263,28 +371,48 @@ where
             inner: self
                 .inner
                 .map(|h| h.into_handler(remote_peer_id, connected_point)),
+            enabled: self.enabled,
+            report_local_protocols_change: false,
         }
     }
 
     fn inbound_protocol(&self) -> <Self::Handler as ConnectionHandler>::InboundProtocol {
-        if let Some(inner) = self.inner.as_ref() {
-            EitherUpgrade::A(SendWrapper(inner.inbound_protocol()))
-        } else {
-            EitherUpgrade::B(SendWrapper(DeniedUpgrade))
+        match self.inner.as_ref() {
+            Some(inner) if self.enabled => EitherUpgrade::A(SendWrapper(inner.inbound_protocol())),
+            _ => EitherUpgrade::B(SendWrapper(DeniedUpgrade)),
         }
     }
 }
 
+/// Event received by a [`ToggleProtoHandler`].
+#[derive(Debug)]
+pub enum ToggleInEvent<TInEvent> {
+    /// An event for the inner handler.
+    Inner(TInEvent),
+    /// The [`Toggle`] has been enabled or disabled.
+    SetEnabled(bool),
+}
+
 /// Implementation of [`ConnectionHandler`] that can be in the disabled state.
 pub struct ToggleProtoHandler<TInner> {
     inner: Option<TInner>,
+    /// Whether the inner handler, if any, is enabled.
+    enabled: bool,
+    /// Whether the state changed since the last call to `poll`.
+    report_local_protocols_change: bool,
+}
+
+impl<TInner> ToggleProtoHandler<TInner> {
+    fn enabled_inner(&self) -> Option<&TInner> {
+        self.inner.as_ref().filter(|_| self.enabled)
+    }
 }
 
 impl<TInner> ConnectionHandler for ToggleProtoHandler<TInner>
 where
     TInner: ConnectionHandler,
 {
-    type InEvent = TInner::InEvent;
+    type InEvent = ToggleInEvent<TInner::InEvent>;
     type OutEvent = TInner::OutEvent;
     type Error = TInner::Error;
     type InboundProtocol =
@@ -294,7 +422,7 @@ where
     type InboundOpenInfo = Either<TInner::InboundOpenInfo, ()>;
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
-        if let Some(inner) = self.inner.as_ref() {
+        if let Some(inner) = self.enabled_inner() {
             inner
                 .listen_protocol()
                 .map_upgrade(|u| EitherUpgrade::A(SendWrapper(u)))
@@ -307,6 +435,12 @@ where
         }
     }
 
+    fn listen_protocol_names(&self) -> Vec<String> {
+        self.enabled_inner()
+            .map(|inner| inner.listen_protocol_names())
+            .unwrap_or_default()
+    }
+
     fn inject_fully_negotiated_inbound(
         &mut self,
         out: <Self::InboundProtocol as InboundUpgradeSend>::Output,
@@ -339,10 +473,19 @@ where
     }
 
     fn inject_event(&mut self, event: Self::InEvent) {
-        self.inner
-            .as_mut()
-            .expect("Can't receive events if disabled; QED")
-            .inject_event(event)
+        match event {
+            ToggleInEvent::Inner(event) => self
+                .inner
+                .as_mut()
+                .expect("Can't receive events if disabled; QED")
+                .inject_event(event),
+            ToggleInEvent::SetEnabled(enabled) => {
+                if self.inner.is_some() && self.enabled != enabled {
+                    self.enabled = enabled;
+                    self.report_local_protocols_change = true;
+                }
+            }
+        }
     }
 
     fn inject_address_change(&mut self, addr: &Multiaddr) {
@@ -351,6 +494,18 @@ where
         }
     }
 
+    fn inject_local_protocols_change(&mut self, change: &ProtocolsChange) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_local_protocols_change(change)
+        }
+    }
+
+    fn inject_remote_protocols_change(&mut self, change: &ProtocolsChange) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_remote_protocols_change(change)
+        }
+    }
+
     fn inject_dial_upgrade_error(
         &mut self,
         info: Self::OutboundOpenInfo,
@@ -369,12 +524,8 @@ where
     ) {
         let (inner, info) = match (self.inner.as_mut(), info) {
             (Some(inner), Either::Left(info)) => (inner, info),
-            // Ignore listen upgrade errors in disabled state.
-            (None, Either::Right(())) => return,
-            (Some(_), Either::Right(())) => panic!(
-                "Unexpected `Either::Right` inbound info through \
-                 `inject_listen_upgrade_error` in enabled state.",
-            ),
+            // Ignore listen upgrade errors of substreams accepted in disabled state.
+            (_, Either::Right(())) => return,
             (None, Either::Left(_)) => panic!(
                 "Unexpected `Either::Left` inbound info through \
                  `inject_listen_upgrade_error` in disabled state.",
@@ -390,14 +541,16 @@ where
                     EitherError::B(v) => void::unreachable(v),
                 }))
             }
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                ConnectionHandlerUpgrErr::LimitExceeded(limit)
+            }
         };
 
         inner.inject_listen_upgrade_error(info, err)
     }
 
     fn connection_keep_alive(&self) -> KeepAlive {
-        self.inner
-            .as_ref()
+        self.enabled_inner()
             .map(|h| h.connection_keep_alive())
             .unwrap_or(KeepAlive::No)
     }
@@ -413,10 +566,14 @@ where
             Self::Error,
         >,
     > {
-        if let Some(inner) = self.inner.as_mut() {
-            inner.poll(cx)
-        } else {
-            Poll::Pending
+        if self.report_local_protocols_change {
+            self.report_local_protocols_change = false;
+            return Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange);
+        }
+
+        match self.inner.as_mut() {
+            Some(inner) if self.enabled => inner.poll(cx),
+            _ => Poll::Pending,
         }
     }
 }
@@ -442,8 +599,34 @@ mod tests {
     /// [`ToggleProtoHandler`] should ignore the error in both of these cases.
     #[test]
     fn ignore_listen_upgrade_error_when_disabled() {
-        let mut handler = ToggleProtoHandler::<DummyConnectionHandler> { inner: None };
+        let mut handler = ToggleProtoHandler::<DummyConnectionHandler> {
+            inner: None,
+            enabled: false,
+            report_local_protocols_change: false,
+        };
 
         handler.inject_listen_upgrade_error(Either::Right(()), ConnectionHandlerUpgrErr::Timeout);
     }
+
+    #[test]
+    fn report_local_protocols_change_when_enabled() {
+        let mut handler = ToggleProtoHandler {
+            inner: Some(DummyConnectionHandler {
+                keep_alive: KeepAlive::Yes,
+            }),
+            enabled: false,
+            report_local_protocols_change: false,
+        };
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        assert_eq!(handler.connection_keep_alive(), KeepAlive::No);
+        assert!(handler.poll(&mut cx).is_pending());
+
+        handler.inject_event(ToggleInEvent::SetEnabled(true));
+        assert_eq!(handler.connection_keep_alive(), KeepAlive::Yes);
+        assert!(matches!(
+            handler.poll(&mut cx),
+            Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange)
+        ));
+        assert!(handler.poll(&mut cx).is_pending());
+    }
 }
@@ -18,31 +18,49 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+mod duplicate;
 mod error;
+mod gater;
 mod handler_wrapper;
 mod listeners;
+mod manager;
+mod ranking;
+mod stream_limits;
 mod substream;
+mod tracker;
 
 pub(crate) mod pool;
 
+pub use duplicate::DuplicateConnectionConfig;
 pub use error::{
     ConnectionError, PendingConnectionError, PendingInboundConnectionError,
     PendingOutboundConnectionError,
 };
+pub(crate) use gater::denied_secured;
+pub use gater::{ConnectionGater, InterceptError, SharedConnectionGater};
 pub use listeners::{ListenersEvent, ListenersStream};
+pub use manager::{ConnectionManager, ConnectionManagerConfig, TrimReason};
 pub use pool::{ConnectionCounters, ConnectionLimits};
 pub use pool::{EstablishedConnection, PendingConnection};
+pub(crate) use ranking::RankingOverride;
+pub use ranking::{AddressRanking, DefaultAddressRanking};
+pub use stream_limits::{InboundStreamLimit, InboundStreamLimits};
 pub use substream::{Close, Substream, SubstreamEndpoint};
+pub use tracker::ConnectionInfo;
 
-use crate::handler::ConnectionHandler;
+use crate::handler::{ConnectionHandler, ProtocolsChange};
+use futures::FutureExt;
 use handler_wrapper::HandlerWrapper;
 use libp2p_core::connection::ConnectedPoint;
 use libp2p_core::multiaddr::Multiaddr;
 use libp2p_core::muxing::StreamMuxerBox;
+use libp2p_core::time::Delay;
 use libp2p_core::upgrade;
 use libp2p_core::PeerId;
-use std::{error::Error, fmt, pin::Pin, task::Context, task::Poll};
+use std::{error::Error, fmt, pin::Pin, sync::Arc, task::Context, task::Poll, time::Duration};
+use stream_limits::InboundStreams;
 use substream::{Muxing, SubstreamEvent};
+use tracker::ConnectionTracker;
 
 /// Information about a successfully established connection.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -60,6 +78,19 @@ pub enum Event<T> {
     Handler(T),
     /// Address of the remote has changed.
     AddressChange(Multiaddr),
+    /// The connection has been drained, see [`Connection::start_drain`].
+    Drained,
+    /// An inbound substream was reset for exceeding one of the
+    /// [`InboundStreamLimits`].
+    InboundStreamLimitExceeded {
+        /// The protocol of the substream, if selected.
+        protocol: Option<String>,
+        limit: InboundStreamLimit,
+    },
+    /// The protocols supported by the local node on the connection changed.
+    LocalProtocolsChange(ProtocolsChange),
+    /// The protocols supported by the remote changed.
+    RemoteProtocolsChange(ProtocolsChange),
 }
 
 /// A multiplexed connection to a peer with an associated [`ConnectionHandler`].
@@ -71,6 +102,8 @@ where
     muxing: substream::Muxing<StreamMuxerBox, handler_wrapper::OutboundOpenInfo<THandler>>,
     /// Handler that processes substreams.
     handler: HandlerWrapper<THandler>,
+    /// Deadline of the drain started via [`Connection::start_drain`], if any.
+    drain: Option<Delay>,
 }
 
 impl<THandler> fmt::Debug for Connection<THandler>
@@ -81,6 +114,7 @@ where
         f.debug_struct("Connection")
             .field("muxing", &self.muxing)
             .field("handler", &self.handler)
+            .field("drain", &self.drain)
             .finish()
     }
 }
@@ -97,11 +131,21 @@ where
         muxer: StreamMuxerBox,
         handler: THandler,
         substream_upgrade_protocol_override: Option<upgrade::Version>,
+        inbound_stream_limits: Arc<InboundStreamLimits>,
+        tracker: ConnectionTracker,
     ) -> Self {
-        let wrapped_handler = HandlerWrapper::new(handler, substream_upgrade_protocol_override);
+        let muxer = tracker.wrap_muxer(muxer);
+        let inbound_streams = InboundStreams::new(inbound_stream_limits, tracker.clone());
+        let wrapped_handler = HandlerWrapper::new(
+            handler,
+            substream_upgrade_protocol_override,
+            inbound_streams,
+            tracker,
+        );
         Connection {
             muxing: Muxing::new(muxer),
             handler: wrapped_handler,
+            drain: None,
         }
     }
 
@@ -110,6 +154,18 @@ where
         self.handler.inject_event(event);
     }
 
+    /// Stops accepting inbound substreams and lets the handler finish its
+    /// in-flight work.
+    ///
+    /// [`Connection::poll`] reports [`Event::Drained`] once the handler is
+    /// idle or the given timeout elapsed, after which the connection is to be
+    /// closed via [`Connection::close`].
+    pub fn start_drain(&mut self, timeout: Duration) {
+        if self.drain.is_none() {
+            self.drain = Some(Delay::new(timeout));
+        }
+    }
+
     /// Begins an orderly shutdown of the connection, returning the connection
     /// handler and a `Future` that resolves when connection shutdown is complete.
     pub fn close(self) -> (THandler, Close<StreamMuxerBox>) {
@@ -129,9 +185,15 @@ where
             // of new substreams.
             match self.muxing.poll(cx) {
                 Poll::Pending => io_pending = true,
-                Poll::Ready(Ok(SubstreamEvent::InboundSubstream { substream })) => self
-                    .handler
-                    .inject_substream(substream, SubstreamEndpoint::Listener),
+                Poll::Ready(Ok(SubstreamEvent::InboundSubstream { substream })) => {
+                    if self.drain.is_some() {
+                        log::debug!("Dropping inbound substream of draining connection.");
+                        drop(substream);
+                    } else {
+                        self.handler
+                            .inject_substream(substream, SubstreamEndpoint::Listener)
+                    }
+                }
                 Poll::Ready(Ok(SubstreamEvent::OutboundSubstream {
                     user_data,
                     substream,
@@ -150,6 +212,16 @@ where
             match self.handler.poll(cx) {
                 Poll::Pending => {
                     if io_pending {
+                        if let Connection {
+                            handler,
+                            drain: Some(drain),
+                            ..
+                        } = &mut *self
+                        {
+                            if handler.is_idle() || drain.poll_unpin(cx).is_ready() {
+                                return Poll::Ready(Ok(Event::Drained));
+                            }
+                        }
                         return Poll::Pending; // Nothing to do
                     }
                 }
@@ -159,6 +231,18 @@ where
                 Poll::Ready(Ok(handler_wrapper::Event::Custom(event))) => {
                     return Poll::Ready(Ok(Event::Handler(event)));
                 }
+                Poll::Ready(Ok(handler_wrapper::Event::InboundSubstreamLimitExceeded {
+                    protocol,
+                    limit,
+                })) => {
+                    return Poll::Ready(Ok(Event::InboundStreamLimitExceeded { protocol, limit }));
+                }
+                Poll::Ready(Ok(handler_wrapper::Event::LocalProtocolsChange(change))) => {
+                    return Poll::Ready(Ok(Event::LocalProtocolsChange(change)));
+                }
+                Poll::Ready(Ok(handler_wrapper::Event::RemoteProtocolsChange(change))) => {
+                    return Poll::Ready(Ok(Event::RemoteProtocolsChange(change)));
+                }
                 Poll::Ready(Err(err)) => return Poll::Ready(Err(err.into())),
             }
         }
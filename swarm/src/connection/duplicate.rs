@@ -0,0 +1,228 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::connection::ConnectionId;
+use libp2p_core::{ConnectedPoint, PeerId};
+use std::time::Duration;
+
+/// The configuration of resolving duplicate connections, i.e. multiple
+/// established connections to the same peer, as they result e.g. from two
+/// peers dialing each other simultaneously.
+///
+/// Once a connection to a peer is established while another one is, a single
+/// connection is kept and the others are drained: they no longer accept
+/// inbound substreams and are closed once their handlers are idle, or after
+/// the configured timeout. Such connections are reported as closed with
+/// [`ConnectionError::Duplicate`](crate::ConnectionError::Duplicate).
+///
+/// The connection to keep is picked by preferring, in this order,
+///
+///   1. direct connections over relayed connections, if enabled via
+///      [`DuplicateConnectionConfig::with_prefer_direct`],
+///   2. the connection dialed by the peer with the smaller [`PeerId`],
+///   3. the connection established first.
+///
+/// Both peers agree on the first two criteria. The last one depends on the
+/// local view of a peer, e.g. when one peer dials the other twice. Thus
+/// only the peer with the smaller [`PeerId`] closes connections tied on the
+/// first two criteria. The other peer keeps them until they are closed by
+/// the remote, in which case they are not reported as duplicates.
+///
+/// See [`SwarmBuilder::duplicate_connections`](crate::SwarmBuilder::duplicate_connections).
+/// Use it instead of
+/// [`ConnectionLimits::with_max_established_per_peer`](crate::ConnectionLimits::with_max_established_per_peer),
+/// which denies the connection established last.
+#[derive(Debug, Clone)]
+pub struct DuplicateConnectionConfig {
+    prefer_direct: bool,
+    drain_timeout: Duration,
+}
+
+impl DuplicateConnectionConfig {
+    /// Sets whether direct connections are kept over relayed connections,
+    /// regardless of the peer IDs. Defaults to `true`.
+    pub fn with_prefer_direct(mut self, prefer_direct: bool) -> Self {
+        self.prefer_direct = prefer_direct;
+        self
+    }
+
+    /// Sets the maximum time given to the handlers of a duplicate connection
+    /// to become idle before it is closed. Defaults to 10 seconds.
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn drain_timeout(&self) -> Duration {
+        self.drain_timeout
+    }
+
+    /// Returns the connections to close out of the given connections to
+    /// `remote`.
+    pub(crate) fn duplicates<'a>(
+        &self,
+        local: &PeerId,
+        remote: &PeerId,
+        connections: impl Iterator<Item = (ConnectionId, &'a ConnectedPoint)>,
+    ) -> Vec<ConnectionId> {
+        let smaller_is_local = local < remote;
+        let mut connections = connections
+            .map(|(id, endpoint)| {
+                let relayed = self.prefer_direct && endpoint.is_relayed();
+                let dialed_by_larger = endpoint.is_dialer() != smaller_is_local;
+                ((relayed, dialed_by_larger), id)
+            })
+            .collect::<Vec<_>>();
+        connections.sort();
+        let best = match connections.first() {
+            Some((best, _)) => *best,
+            None => return Vec::new(),
+        };
+        connections
+            .into_iter()
+            .skip(1)
+            .filter(|(key, _)| smaller_is_local || *key != best)
+            .map(|(_, id)| id)
+            .collect()
+    }
+}
+
+impl Default for DuplicateConnectionConfig {
+    fn default() -> Self {
+        DuplicateConnectionConfig {
+            prefer_direct: true,
+            drain_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::{connection::Endpoint, Multiaddr};
+
+    fn dialer(addr: &str) -> ConnectedPoint {
+        ConnectedPoint::Dialer {
+            address: addr.parse::<Multiaddr>().unwrap(),
+            role_override: Endpoint::Dialer,
+        }
+    }
+
+    fn listener(addr: &str) -> ConnectedPoint {
+        ConnectedPoint::Listener {
+            local_addr: "/memory/1".parse().unwrap(),
+            send_back_addr: addr.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn both_peers_select_the_same_connection() {
+        let config = DuplicateConnectionConfig::default();
+        let a = PeerId::random();
+        let b = PeerId::random();
+        let (smaller, larger) = if a < b { (a, b) } else { (b, a) };
+
+        // Both peers dialed each other. Each side sees its own dial as the
+        // dialer and the other one as the listener, with independent IDs.
+        let duplicates = config.duplicates(
+            &smaller,
+            &larger,
+            vec![
+                (ConnectionId::new(1), &listener("/memory/2")),
+                (ConnectionId::new(2), &dialer("/memory/2")),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(duplicates, vec![ConnectionId::new(1)]);
+
+        let duplicates = config.duplicates(
+            &larger,
+            &smaller,
+            vec![
+                (ConnectionId::new(1), &dialer("/memory/1")),
+                (ConnectionId::new(2), &listener("/memory/1")),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(duplicates, vec![ConnectionId::new(1)]);
+    }
+
+    #[test]
+    fn only_smaller_peer_closes_connections_dialed_by_the_same_peer() {
+        let config = DuplicateConnectionConfig::default();
+        let a = PeerId::random();
+        let b = PeerId::random();
+        let (smaller, larger) = if a < b { (a, b) } else { (b, a) };
+
+        for (smaller_endpoint, larger_endpoint) in [
+            (dialer("/memory/2"), listener("/memory/1")),
+            (listener("/memory/2"), dialer("/memory/1")),
+        ] {
+            // One peer dialed the other twice. The IDs of the connections
+            // are ordered differently on both sides.
+            let duplicates = config.duplicates(
+                &smaller,
+                &larger,
+                vec![
+                    (ConnectionId::new(1), &smaller_endpoint),
+                    (ConnectionId::new(2), &smaller_endpoint),
+                ]
+                .into_iter(),
+            );
+            assert_eq!(duplicates, vec![ConnectionId::new(2)]);
+
+            let duplicates = config.duplicates(
+                &larger,
+                &smaller,
+                vec![
+                    (ConnectionId::new(2), &larger_endpoint),
+                    (ConnectionId::new(1), &larger_endpoint),
+                ]
+                .into_iter(),
+            );
+            assert!(duplicates.is_empty());
+        }
+    }
+
+    #[test]
+    fn direct_connections_are_preferred() {
+        let a = PeerId::random();
+        let b = PeerId::random();
+        let relayed = "/memory/2/p2p-circuit";
+        let connections = [
+            (ConnectionId::new(1), dialer(relayed)),
+            (ConnectionId::new(2), listener("/memory/2")),
+        ];
+        for (local, remote) in [(a, b), (b, a)] {
+            let duplicates = DuplicateConnectionConfig::default().duplicates(
+                &local,
+                &remote,
+                connections.iter().map(|(id, e)| (*id, e)),
+            );
+            assert_eq!(duplicates, vec![ConnectionId::new(1)]);
+        }
+
+        let duplicates = DuplicateConnectionConfig::default()
+            .with_prefer_direct(false)
+            .duplicates(&a, &b, connections.iter().map(|(id, e)| (*id, e)));
+        let expected = if a < b { 2 } else { 1 };
+        assert_eq!(duplicates, vec![ConnectionId::new(expected)]);
+    }
+}
@@ -36,6 +36,10 @@ pub enum ConnectionError<THandlerErr> {
 
     /// The connection handler produced an error.
     Handler(THandlerErr),
+
+    /// The connection has been closed in favor of another connection to the
+    /// same peer, see [`DuplicateConnectionConfig`](crate::DuplicateConnectionConfig).
+    Duplicate,
 }
 
 impl<THandlerErr> fmt::Display for ConnectionError<THandlerErr>
@@ -49,6 +53,9 @@ where
                 write!(f, "Connection closed due to expired keep-alive timeout.")
             }
             ConnectionError::Handler(err) => write!(f, "Connection error: Handler error: {}", err),
+            ConnectionError::Duplicate => {
+                write!(f, "Connection closed as duplicate of another connection.")
+            }
         }
     }
 }
@@ -62,6 +69,7 @@ where
             ConnectionError::IO(err) => Some(err),
             ConnectionError::KeepAliveTimeout => None,
             ConnectionError::Handler(err) => Some(err),
+            ConnectionError::Duplicate => None,
         }
     }
 }
@@ -110,6 +118,28 @@ pub enum PendingConnectionError<TTransErr> {
     /// An I/O error occurred on the connection.
     // TODO: Eventually this should also be a custom error?
     IO(io::Error),
+
+    /// The [`ConnectionGater`](crate::ConnectionGater) denied dialing every
+    /// address of an outgoing connection attempt.
+    DeniedDial,
+
+    /// The [`ConnectionGater`](crate::ConnectionGater) denied an incoming
+    /// connection as soon as it was accepted, i.e. before any upgrade.
+    DeniedAccept,
+
+    /// The [`ConnectionGater`](crate::ConnectionGater) denied the connection
+    /// once the security upgrade revealed the identity of the remote.
+    DeniedSecured {
+        peer_id: PeerId,
+        endpoint: ConnectedPoint,
+    },
+
+    /// The [`ConnectionGater`](crate::ConnectionGater) denied the connection
+    /// after the stream multiplexer was negotiated.
+    DeniedUpgraded {
+        peer_id: PeerId,
+        endpoint: ConnectedPoint,
+    },
 }
 
 impl<T> PendingConnectionError<T> {
@@ -124,6 +154,14 @@ impl<T> PendingConnectionError<T> {
                 PendingConnectionError::WrongPeerId { obtained, endpoint }
             }
             PendingConnectionError::IO(e) => PendingConnectionError::IO(e),
+            PendingConnectionError::DeniedDial => PendingConnectionError::DeniedDial,
+            PendingConnectionError::DeniedAccept => PendingConnectionError::DeniedAccept,
+            PendingConnectionError::DeniedSecured { peer_id, endpoint } => {
+                PendingConnectionError::DeniedSecured { peer_id, endpoint }
+            }
+            PendingConnectionError::DeniedUpgraded { peer_id, endpoint } => {
+                PendingConnectionError::DeniedUpgraded { peer_id, endpoint }
+            }
         }
     }
 }
@@ -153,6 +191,26 @@ where
                     obtained, endpoint
                 )
             }
+            PendingConnectionError::DeniedDial => {
+                write!(f, "Pending connection: All addresses denied by gater.")
+            }
+            PendingConnectionError::DeniedAccept => {
+                write!(f, "Pending connection: Denied by gater on accept.")
+            }
+            PendingConnectionError::DeniedSecured { peer_id, endpoint } => {
+                write!(
+                    f,
+                    "Pending connection: Peer {} at {:?} denied by gater after security upgrade.",
+                    peer_id, endpoint
+                )
+            }
+            PendingConnectionError::DeniedUpgraded { peer_id, endpoint } => {
+                write!(
+                    f,
+                    "Pending connection: Peer {} at {:?} denied by gater after muxer upgrade.",
+                    peer_id, endpoint
+                )
+            }
         }
     }
 }
@@ -168,6 +226,10 @@ where
             PendingConnectionError::WrongPeerId { .. } => None,
             PendingConnectionError::Aborted => None,
             PendingConnectionError::ConnectionLimit(..) => None,
+            PendingConnectionError::DeniedDial => None,
+            PendingConnectionError::DeniedAccept => None,
+            PendingConnectionError::DeniedSecured { .. } => None,
+            PendingConnectionError::DeniedUpgraded { .. } => None,
         }
     }
 }
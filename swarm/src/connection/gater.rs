@@ -0,0 +1,233 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use std::{
+    any::Any,
+    error, fmt, io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// A [`ConnectionGater`] decides whether a connection may proceed at each
+/// stage of its establishment.
+///
+/// The gater is consulted by the connection pool of the
+/// [`Swarm`](crate::Swarm):
+///
+///   1. before dialing an address, via [`ConnectionGater::intercept_addr_dial`],
+///   2. when a listener accepts a new socket, via [`ConnectionGater::intercept_accept`],
+///   3. once the security upgrade revealed the [`PeerId`] of the remote and
+///      before the stream multiplexer is negotiated, via
+///      [`ConnectionGater::intercept_secured`],
+///   4. once the stream multiplexer has been negotiated, via
+///      [`ConnectionGater::intercept_upgraded`].
+///
+/// Each method returns `true` to allow the connection to proceed and `false`
+/// to deny it. All methods allow by default. A denied pending connection is
+/// reported through [`SwarmEvent::IncomingConnectionError`](crate::SwarmEvent::IncomingConnectionError)
+/// or [`SwarmEvent::OutgoingConnectionError`](crate::SwarmEvent::OutgoingConnectionError)
+/// with the [`PendingConnectionError`](crate::PendingConnectionError) variant
+/// of the stage that denied it.
+///
+/// > **Note**: The [`Transport`](libp2p_core::Transport) given to the
+/// > [`Swarm`](crate::Swarm) yields connections that are both authenticated
+/// > and multiplexed. For [`ConnectionGater::intercept_secured`] to be
+/// > consulted before the stream multiplexer is negotiated, the gater has to
+/// > be wrapped in a [`SharedConnectionGater`] and installed via
+/// > [`Authenticated::intercept`](libp2p_core::transport::upgrade::Authenticated::intercept)
+/// > between the security and the multiplexer upgrade, see
+/// > [`SharedConnectionGater::intercept_secured`]. Otherwise the pool consults
+/// > [`ConnectionGater::intercept_secured`] itself once the connection is
+/// > upgraded, right before [`ConnectionGater::intercept_upgraded`].
+pub trait ConnectionGater: Send + 'static {
+    /// Whether the given address may be dialed, optionally for the given peer.
+    ///
+    /// Denied addresses are skipped. If all addresses of a dialing attempt are
+    /// denied, the dial fails with
+    /// [`DialError::DeniedDial`](crate::DialError::DeniedDial).
+    fn intercept_addr_dial(&mut self, _peer: Option<&PeerId>, _addr: &Multiaddr) -> bool {
+        true
+    }
+
+    /// Whether a connection accepted by a listener may be upgraded.
+    fn intercept_accept(&mut self, _local_addr: &Multiaddr, _send_back_addr: &Multiaddr) -> bool {
+        true
+    }
+
+    /// Whether a connection to the given, now authenticated, peer may proceed
+    /// to negotiate a stream multiplexer.
+    ///
+    /// Consulted by the transport if it is upgraded with
+    /// [`SharedConnectionGater::intercept_secured`], by the pool once the
+    /// stream multiplexer has been negotiated otherwise.
+    fn intercept_secured(&mut self, _peer: &PeerId, _endpoint: &ConnectedPoint) -> bool {
+        true
+    }
+
+    /// Whether a fully upgraded connection to the given peer may be
+    /// established.
+    fn intercept_upgraded(&mut self, _peer: &PeerId, _endpoint: &ConnectedPoint) -> bool {
+        true
+    }
+}
+
+/// A [`ConnectionGater`] shared between a [`Swarm`](crate::Swarm) and the
+/// upgrade of its transport.
+///
+/// ```
+/// # use libp2p_core::{identity, transport::MemoryTransport, upgrade, Transport};
+/// # use libp2p::plaintext::PlainText2Config;
+/// # use libp2p::yamux::YamuxConfig;
+/// # use libp2p_swarm::{ConnectionGater, SharedConnectionGater};
+/// struct AllowAll;
+///
+/// impl ConnectionGater for AllowAll {}
+///
+/// let local_public_key = identity::Keypair::generate_ed25519().public();
+/// let gater = SharedConnectionGater::new(AllowAll);
+/// let transport = MemoryTransport::default()
+///     .upgrade(upgrade::Version::V1)
+///     .authenticate(PlainText2Config { local_public_key })
+///     .intercept(gater.intercept_secured())
+///     .multiplex(YamuxConfig::default())
+///     .boxed();
+/// // Pass `gater` to `SwarmBuilder::connection_gater`.
+/// ```
+#[derive(Clone)]
+pub struct SharedConnectionGater {
+    gater: Arc<Mutex<Box<dyn ConnectionGater>>>,
+    /// Whether [`SharedConnectionGater::intercept_secured`] was installed on
+    /// a transport.
+    intercepts_secured: Arc<AtomicBool>,
+}
+
+impl SharedConnectionGater {
+    /// Wraps the given gater for it to be shared.
+    ///
+    /// A [`SharedConnectionGater`] is shared as is rather than wrapped again.
+    pub fn new(gater: impl ConnectionGater) -> Self {
+        if let Some(shared) = (&gater as &dyn Any).downcast_ref::<SharedConnectionGater>() {
+            return shared.clone();
+        }
+        SharedConnectionGater {
+            gater: Arc::new(Mutex::new(Box::new(gater))),
+            intercepts_secured: Default::default(),
+        }
+    }
+
+    /// Returns a function consulting [`ConnectionGater::intercept_secured`],
+    /// to be given to
+    /// [`Authenticated::intercept`](libp2p_core::transport::upgrade::Authenticated::intercept).
+    ///
+    /// Connections denied by the gater are reported via
+    /// [`PendingConnectionError::DeniedSecured`](crate::PendingConnectionError::DeniedSecured)
+    /// or [`DialError::DeniedSecured`](crate::DialError::DeniedSecured).
+    pub fn intercept_secured(
+        &self,
+    ) -> impl FnOnce(&PeerId, &ConnectedPoint) -> Result<(), InterceptError> + Clone + Send + Sync
+    {
+        self.intercepts_secured.store(true, Ordering::Relaxed);
+        let gater = self.clone();
+        move |peer, endpoint| {
+            if gater.lock().intercept_secured(peer, endpoint) {
+                Ok(())
+            } else {
+                Err(InterceptError(DeniedSecured(*peer)))
+            }
+        }
+    }
+
+    /// Whether [`SharedConnectionGater::intercept_secured`] was installed on
+    /// a transport, thus whether the transport consults
+    /// [`ConnectionGater::intercept_secured`].
+    pub(crate) fn intercepts_secured(&self) -> bool {
+        self.intercepts_secured.load(Ordering::Relaxed)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Box<dyn ConnectionGater>> {
+        self.gater.lock().expect("Gater not to panic while locked.")
+    }
+}
+
+impl ConnectionGater for SharedConnectionGater {
+    fn intercept_addr_dial(&mut self, peer: Option<&PeerId>, addr: &Multiaddr) -> bool {
+        self.lock().intercept_addr_dial(peer, addr)
+    }
+
+    fn intercept_accept(&mut self, local_addr: &Multiaddr, send_back_addr: &Multiaddr) -> bool {
+        self.lock().intercept_accept(local_addr, send_back_addr)
+    }
+
+    fn intercept_secured(&mut self, peer: &PeerId, endpoint: &ConnectedPoint) -> bool {
+        self.lock().intercept_secured(peer, endpoint)
+    }
+
+    fn intercept_upgraded(&mut self, peer: &PeerId, endpoint: &ConnectedPoint) -> bool {
+        self.lock().intercept_upgraded(peer, endpoint)
+    }
+}
+
+/// The transport error of a connection denied by
+/// [`ConnectionGater::intercept_secured`].
+///
+/// See [`SharedConnectionGater::intercept_secured`].
+#[derive(Debug)]
+pub struct InterceptError(DeniedSecured);
+
+impl fmt::Display for InterceptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Connection denied by gater: {}", self.0)
+    }
+}
+
+impl error::Error for InterceptError {
+    // Returned as source, as the transport's `EitherError`s forward
+    // `source` rather than returning themselves.
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+#[derive(Debug)]
+struct DeniedSecured(PeerId);
+
+impl fmt::Display for DeniedSecured {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Peer {} denied after security upgrade.", self.0)
+    }
+}
+
+impl error::Error for DeniedSecured {}
+
+/// Returns the peer if the given transport error stems from
+/// [`SharedConnectionGater::intercept_secured`] denying the connection.
+pub(crate) fn denied_secured(error: &io::Error) -> Option<PeerId> {
+    let mut source = error.get_ref().map(|e| e as &(dyn error::Error + 'static));
+    while let Some(e) = source {
+        if let Some(DeniedSecured(peer)) = e.downcast_ref() {
+            return Some(*peer);
+        }
+        source = e.source();
+    }
+    None
+}
@@ -18,28 +18,43 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::connection::stream_limits::{
+    InboundStreamLimit, InboundStreams, LimitedUpgrade, LimitedUpgradeError,
+};
+use crate::connection::tracker::{ConnectionTracker, TrackedUpgrade};
 use crate::connection::{Substream, SubstreamEndpoint};
 use crate::handler::{
-    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
+    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive, ProtocolsChange,
 };
 use crate::upgrade::SendWrapper;
 
 use futures::prelude::*;
 use futures::stream::FuturesUnordered;
-use futures_timer::Delay;
-use instant::Instant;
+use libp2p_core::time::{self, Delay, Instant};
 use libp2p_core::{
+    connection::Endpoint,
     muxing::StreamMuxerBox,
     upgrade::{self, InboundUpgradeApply, OutboundUpgradeApply, UpgradeError},
     Multiaddr,
 };
-use std::{error, fmt, pin::Pin, task::Context, task::Poll, time::Duration};
+use std::{
+    collections::{HashSet, VecDeque},
+    error, fmt,
+    pin::Pin,
+    task::Context,
+    task::Poll,
+    time::Duration,
+};
 
 /// A wrapper for an underlying [`ConnectionHandler`].
 ///
 /// It extends [`ConnectionHandler`] with:
 /// - Enforced substream upgrade timeouts
 /// - Driving substream upgrades
+/// - Enforcing the [`InboundStreamLimits`](crate::InboundStreamLimits)
+/// - Recording substream protocols, keep-alive and activity for
+///   [`Swarm::connections`](crate::Swarm::connections)
+/// - Tracking the protocols supported by the local node and the remote
 /// - Handling connection timeout
 // TODO: add a caching system for protocols that are supported or not
 pub struct HandlerWrapper<TProtoHandler>
@@ -54,7 +69,7 @@ where
             TProtoHandler::InboundOpenInfo,
             InboundUpgradeApply<
                 Substream<StreamMuxerBox>,
-                SendWrapper<TProtoHandler::InboundProtocol>,
+                LimitedUpgrade<TProtoHandler::InboundProtocol>,
             >,
         >,
     >,
@@ -64,7 +79,7 @@ where
             TProtoHandler::OutboundOpenInfo,
             OutboundUpgradeApply<
                 Substream<StreamMuxerBox>,
-                SendWrapper<TProtoHandler::OutboundProtocol>,
+                TrackedUpgrade<TProtoHandler::OutboundProtocol>,
             >,
         >,
     >,
@@ -77,6 +92,22 @@ where
     shutdown: Shutdown,
     /// The substream upgrade protocol override, if any.
     substream_upgrade_protocol_override: Option<upgrade::Version>,
+    /// Enforces the limits on inbound substreams.
+    inbound_streams: InboundStreams,
+    /// Inbound substreams that were reset for exceeding a limit, not yet
+    /// reported, with their protocol, if selected.
+    exceeded_inbound_limits: VecDeque<(Option<String>, InboundStreamLimit)>,
+    /// Records the substreams and activity of the connection.
+    tracker: ConnectionTracker,
+    /// The protocols of the latest [`ConnectionHandler::listen_protocol_names`],
+    /// recomputed on [`ConnectionHandlerEvent::ReportLocalProtocolsChange`].
+    local_protocols: HashSet<String>,
+    /// Whether [`HandlerWrapper::local_protocols`] are yet to be computed initially.
+    local_protocols_outdated: bool,
+    /// The protocols the remote supports, as last reported by the handler.
+    remote_protocols: HashSet<String>,
+    /// Changes of the local or remote protocols not yet reported.
+    protocols_changes: VecDeque<Event<OutboundOpenInfo<TProtoHandler>, TProtoHandler::OutEvent>>,
 }
 
 impl<TProtoHandler: ConnectionHandler> std::fmt::Debug for HandlerWrapper<TProtoHandler> {
@@ -98,6 +129,8 @@ impl<TProtoHandler: ConnectionHandler> HandlerWrapper<TProtoHandler> {
     pub(crate) fn new(
         handler: TProtoHandler,
         substream_upgrade_protocol_override: Option<upgrade::Version>,
+        inbound_streams: InboundStreams,
+        tracker: ConnectionTracker,
     ) -> Self {
         Self {
             handler,
@@ -107,6 +140,13 @@ impl<TProtoHandler: ConnectionHandler> HandlerWrapper<TProtoHandler> {
             unique_dial_upgrade_id: 0,
             shutdown: Shutdown::None,
             substream_upgrade_protocol_override,
+            inbound_streams,
+            exceeded_inbound_limits: VecDeque::new(),
+            tracker,
+            local_protocols: HashSet::new(),
+            local_protocols_outdated: true,
+            remote_protocols: HashSet::new(),
+            protocols_changes: VecDeque::new(),
         }
     }
 
@@ -243,10 +283,24 @@ where
     ) {
         match endpoint {
             SubstreamEndpoint::Listener => {
+                let stream = match self.inbound_streams.on_inbound(self.negotiating_in.len()) {
+                    Ok(stream) => stream,
+                    Err(limit) => {
+                        log::debug!("Resetting inbound substream exceeding {}.", limit);
+                        // Dropping the substream resets it.
+                        drop(substream);
+                        self.exceeded_inbound_limits.push_back((None, limit));
+                        return;
+                    }
+                };
                 let protocol = self.handler.listen_protocol();
                 let timeout = *protocol.timeout();
                 let (upgrade, user_data) = protocol.into_upgrade();
-                let upgrade = upgrade::apply_inbound(substream, SendWrapper(upgrade));
+                let upgrade = LimitedUpgrade {
+                    upgrade: SendWrapper(upgrade),
+                    stream,
+                };
+                let upgrade = upgrade::apply_inbound(substream, upgrade);
                 let timeout = Delay::new(timeout);
                 self.negotiating_in.push(SubstreamUpgrade {
                     user_data: Some(user_data),
@@ -279,6 +333,11 @@ where
                         version = v;
                     }
                 }
+                let upgrade = TrackedUpgrade {
+                    upgrade,
+                    id: self.tracker.take_latest(Endpoint::Dialer),
+                    tracker: self.tracker.clone(),
+                };
                 let upgrade = upgrade::apply_outbound(substream, upgrade, version);
                 let timeout = Delay::new(timeout);
                 self.negotiating_out.push(SubstreamUpgrade {
@@ -291,6 +350,7 @@ where
     }
 
     pub fn inject_event(&mut self, event: TProtoHandler::InEvent) {
+        self.tracker.record_activity();
         self.handler.inject_event(event);
     }
 
@@ -298,6 +358,45 @@ where
         self.handler.inject_address_change(new_address);
     }
 
+    /// Informs the handler about changes of the protocols it listens on.
+    fn update_local_protocols(&mut self) {
+        let protocols = self
+            .handler
+            .listen_protocol_names()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        if protocols == self.local_protocols {
+            return;
+        }
+        for change in ProtocolsChange::diff(&self.local_protocols, &protocols) {
+            self.handler.inject_local_protocols_change(&change);
+            self.protocols_changes
+                .push_back(Event::LocalProtocolsChange(change));
+        }
+        self.local_protocols = protocols;
+    }
+
+    /// Informs the handler about changes of the protocols the remote supports.
+    fn update_remote_protocols(&mut self, protocols: Vec<String>) {
+        let protocols = protocols.into_iter().collect::<HashSet<_>>();
+        for change in ProtocolsChange::diff(&self.remote_protocols, &protocols) {
+            self.handler.inject_remote_protocols_change(&change);
+            self.protocols_changes
+                .push_back(Event::RemoteProtocolsChange(change));
+        }
+        self.remote_protocols = protocols;
+    }
+
+    /// Whether the handler has no in-flight work, i.e. no substream is being
+    /// negotiated and the handler does not keep the connection alive via
+    /// [`KeepAlive::Yes`].
+    pub fn is_idle(&self) -> bool {
+        self.negotiating_in.is_empty()
+            && self.negotiating_out.is_empty()
+            && self.queued_dial_upgrades.is_empty()
+            && !matches!(self.handler.connection_keep_alive(), KeepAlive::Yes)
+    }
+
     pub fn poll(
         &mut self,
         cx: &mut Context<'_>,
@@ -307,15 +406,54 @@ where
             Error<TProtoHandler::Error>,
         >,
     > {
+        if self.local_protocols_outdated {
+            self.local_protocols_outdated = false;
+            self.update_local_protocols();
+        }
+        if let Some(event) = self.protocols_changes.pop_front() {
+            return Poll::Ready(Ok(event));
+        }
+
         while let Poll::Ready(Some((user_data, res))) = self.negotiating_in.poll_next_unpin(cx) {
             match res {
                 Ok(upgrade) => self
                     .handler
                     .inject_fully_negotiated_inbound(upgrade, user_data),
-                Err(err) => self.handler.inject_listen_upgrade_error(user_data, err),
+                Err(err) => {
+                    let err = match err {
+                        ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
+                        ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
+                        ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(e)) => {
+                            ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(e))
+                        }
+                        ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(
+                            LimitedUpgradeError::Upgrade(e),
+                        )) => ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)),
+                        ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(
+                            LimitedUpgradeError::Limit { protocol, limit },
+                        )) => {
+                            log::debug!(
+                                "Reset inbound substream for {} exceeding {}.",
+                                protocol,
+                                limit
+                            );
+                            self.exceeded_inbound_limits
+                                .push_back((Some(protocol), limit));
+                            ConnectionHandlerUpgrErr::LimitExceeded(limit)
+                        }
+                        ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                            ConnectionHandlerUpgrErr::LimitExceeded(limit)
+                        }
+                    };
+                    self.handler.inject_listen_upgrade_error(user_data, err)
+                }
             }
         }
 
+        if let Some((protocol, limit)) = self.exceeded_inbound_limits.pop_front() {
+            return Poll::Ready(Ok(Event::InboundSubstreamLimitExceeded { protocol, limit }));
+        }
+
         while let Poll::Ready(Some((user_data, res))) = self.negotiating_out.poll_next_unpin(cx) {
             match res {
                 Ok(upgrade) => self
@@ -331,17 +469,19 @@ where
 
         // Ask the handler whether it wants the connection (and the handler itself)
         // to be kept alive, which determines the planned shutdown, if any.
-        match (&mut self.shutdown, self.handler.connection_keep_alive()) {
+        let keep_alive = self.handler.connection_keep_alive();
+        self.tracker.set_keep_alive(keep_alive);
+        match (&mut self.shutdown, keep_alive) {
             (Shutdown::Later(timer, deadline), KeepAlive::Until(t)) => {
                 if *deadline != t {
                     *deadline = t;
-                    if let Some(dur) = deadline.checked_duration_since(Instant::now()) {
+                    if let Some(dur) = deadline.checked_duration_since(time::now()) {
                         timer.reset(dur)
                     }
                 }
             }
             (_, KeepAlive::Until(t)) => {
-                if let Some(dur) = t.checked_duration_since(Instant::now()) {
+                if let Some(dur) = t.checked_duration_since(time::now()) {
                     self.shutdown = Shutdown::Later(Delay::new(dur), t)
                 }
             }
@@ -351,6 +491,7 @@ where
 
         match poll_result {
             Poll::Ready(ConnectionHandlerEvent::Custom(event)) => {
+                self.tracker.record_activity();
                 return Poll::Ready(Ok(Event::Custom(event)));
             }
             Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest { protocol }) => {
@@ -362,6 +503,24 @@ where
                 return Poll::Ready(Ok(Event::OutboundSubstreamRequest((id, info, timeout))));
             }
             Poll::Ready(ConnectionHandlerEvent::Close(err)) => return Poll::Ready(Err(err.into())),
+            Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(protocols)) => {
+                self.update_remote_protocols(protocols);
+                match self.protocols_changes.pop_front() {
+                    Some(event) => return Poll::Ready(Ok(event)),
+                    // The handler may have further events.
+                    None => cx.waker().wake_by_ref(),
+                }
+            }
+            Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange) => {
+                // Report the changes right away, as the handler may no longer keep the
+                // connection alive, e.g. a disabled `Toggle`.
+                self.update_local_protocols();
+                match self.protocols_changes.pop_front() {
+                    Some(event) => return Poll::Ready(Ok(event)),
+                    // The handler may have further events.
+                    None => cx.waker().wake_by_ref(),
+                }
+            }
             Poll::Pending => (),
         };
 
@@ -383,11 +542,24 @@ where
 }
 
 /// Event produced by a [`HandlerWrapper`].
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Event<TOutboundOpenInfo, TCustom> {
     /// Require a new outbound substream to be opened with the remote.
     OutboundSubstreamRequest(TOutboundOpenInfo),
 
+    /// An inbound substream was reset for exceeding a limit.
+    InboundSubstreamLimitExceeded {
+        /// The protocol of the substream, if selected.
+        protocol: Option<String>,
+        limit: InboundStreamLimit,
+    },
+
+    /// The protocols supported by the local node on the connection changed.
+    LocalProtocolsChange(ProtocolsChange),
+
+    /// The protocols supported by the remote changed.
+    RemoteProtocolsChange(ProtocolsChange),
+
     /// Other event.
     Custom(TCustom),
 }
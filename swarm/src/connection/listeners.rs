@@ -21,11 +21,14 @@
 //! Manage listening on multiple multiaddresses at once.
 
 use crate::{
+    listen_opts::{ListenOpts, ListenerRecoveryConfig},
     transport::{ListenerEvent, TransportError},
     Multiaddr, Transport,
 };
+use fnv::FnvHashMap;
 use futures::{prelude::*, task::Context, task::Poll};
 use libp2p_core::connection::ListenerId;
+use libp2p_core::time::Delay;
 use log::debug;
 use smallvec::SmallVec;
 use std::{collections::VecDeque, fmt, mem, pin::Pin};
@@ -40,7 +43,8 @@ use std::{collections::VecDeque, fmt, mem, pin::Pin};
 ///
 /// The [`ListenersStream`] never ends and never produces errors. If a listener errors or closes, an
 /// event is generated on the stream and the listener is then dropped, but the [`ListenersStream`]
-/// itself continues.
+/// itself continues. Listeners started with [`WithAddress::recover`](crate::listen_opts::WithAddress::recover)
+/// are instead started again after a backoff.
 pub struct ListenersStream<TTrans>
 where
     TTrans: Transport,
@@ -55,6 +59,25 @@ where
     next_id: ListenerId,
     /// Pending listeners events to return from [`ListenersStream::poll`].
     pending_events: VecDeque<ListenersEvent<TTrans>>,
+    /// The listeners that are started again when they fail.
+    supervisors: FnvHashMap<ListenerId, Supervisor<TTrans>>,
+}
+
+/// Recovery state of a listener started with
+/// [`WithAddress::recover`](crate::listen_opts::WithAddress::recover).
+struct Supervisor<TTrans>
+where
+    TTrans: Transport,
+{
+    opts: ListenOpts,
+    /// Starts the listener again. Captured where `TTrans: Clone` is known
+    /// to hold, so that polling does not require it.
+    start: StartListener<TTrans>,
+    recovery: ListenerRecoveryConfig,
+    /// Number of consecutive failures of the listener.
+    failures: u32,
+    /// Set while the listener is down and waiting to be started again.
+    restart: Option<Delay>,
 }
 
 /// A single active listener.
@@ -110,8 +133,8 @@ where
         /// The addresses that the listener was listening on.
         addresses: Vec<Multiaddr>,
         /// Reason for the closure. Contains `Ok(())` if the stream produced `None`, or `Err`
-        /// if the stream produced an error.
-        reason: Result<(), TTrans::Error>,
+        /// if the stream produced an error or the listener could not be started again.
+        reason: Result<(), TransportError<TTrans::Error>>,
     },
     /// A listener errored.
     ///
@@ -136,6 +159,7 @@ where
             listeners: VecDeque::new(),
             next_id: ListenerId::new(1),
             pending_events: VecDeque::new(),
+            supervisors: Default::default(),
         }
     }
 
@@ -149,14 +173,40 @@ where
     where
         TTrans: Clone,
     {
-        let listener = self.transport.clone().listen_on(addr)?;
+        self.listen_with_opts(addr.into())
+    }
+
+    /// Start listening with the given [`ListenOpts`].
+    ///
+    /// Returns an error if the transport doesn't support the given multiaddress
+    /// or, with a port range, if no port of the range can be listened on.
+    pub fn listen_with_opts(
+        &mut self,
+        opts: ListenOpts,
+    ) -> Result<ListenerId, TransportError<TTrans::Error>>
+    where
+        TTrans: Clone,
+    {
+        let listener = start_listener(&self.transport, &opts)?;
+        let id = self.next_id;
+        self.next_id = self.next_id + 1;
         self.listeners.push_back(Box::pin(Listener {
-            id: self.next_id,
+            id,
             listener,
             addresses: SmallVec::new(),
         }));
-        let id = self.next_id;
-        self.next_id = self.next_id + 1;
+        if let Some(recovery) = opts.recovery.clone() {
+            self.supervisors.insert(
+                id,
+                Supervisor {
+                    opts,
+                    start: start_listener::<TTrans>,
+                    recovery,
+                    failures: 0,
+                    restart: None,
+                },
+            );
+        }
         Ok(id)
     }
 
@@ -165,6 +215,7 @@ where
     /// Returns `true` if there was a listener with this ID, `false`
     /// otherwise.
     pub fn remove_listener(&mut self, id: ListenerId) -> bool {
+        let supervisor = self.supervisors.remove(&id);
         if let Some(i) = self.listeners.iter().position(|l| l.id == id) {
             let mut listener = self
                 .listeners
@@ -178,11 +229,30 @@ where
                 reason: Ok(()),
             });
             true
+        } else if supervisor.is_some() {
+            // The listener is waiting to be started again.
+            self.pending_events.push_back(ListenersEvent::Closed {
+                listener_id: id,
+                addresses: Vec::new(),
+                reason: Ok(()),
+            });
+            true
         } else {
             false
         }
     }
 
+    /// Returns an iterator over the IDs of all active listeners, including
+    /// those waiting to be started again.
+    pub fn listener_ids(&self) -> impl Iterator<Item = ListenerId> + '_ {
+        self.listeners.iter().map(|l| l.id).chain(
+            self.supervisors
+                .iter()
+                .filter(|(_, s)| s.restart.is_some())
+                .map(|(id, _)| *id),
+        )
+    }
+
     /// Returns the transport passed when building this object.
     pub fn transport(&self) -> &TTrans {
         &self.transport
@@ -199,6 +269,63 @@ where
         if let Some(event) = self.pending_events.pop_front() {
             return Poll::Ready(event);
         }
+
+        // Start again the failed listeners whose backoff elapsed.
+        let this = &mut *self;
+        let mut given_up = Vec::new();
+        for (id, supervisor) in this.supervisors.iter_mut() {
+            match supervisor.restart.as_mut().map(|d| d.poll_unpin(cx)) {
+                Some(Poll::Ready(())) => {}
+                Some(Poll::Pending) | None => continue,
+            }
+            supervisor.restart = None;
+            let error = match (supervisor.start)(&this.transport, &supervisor.opts) {
+                Ok(listener) => {
+                    debug!("Listener {:?} started again.", id);
+                    this.listeners.push_back(Box::pin(Listener {
+                        id: *id,
+                        listener,
+                        addresses: SmallVec::new(),
+                    }));
+                    continue;
+                }
+                Err(error) => error,
+            };
+            supervisor.failures += 1;
+            // An address that is no longer supported will not become supported by retrying.
+            let backoff = match error {
+                TransportError::Other(_) => supervisor.recovery.backoff(supervisor.failures),
+                TransportError::MultiaddrNotSupported(_) => None,
+            };
+            match (error, backoff) {
+                (TransportError::Other(error), Some(backoff)) => {
+                    debug!("Listener {:?} failed to start again: {}", id, error);
+                    let mut delay = Delay::new(backoff);
+                    let _ = delay.poll_unpin(cx);
+                    supervisor.restart = Some(delay);
+                    this.pending_events.push_back(ListenersEvent::Error {
+                        listener_id: *id,
+                        error,
+                    });
+                }
+                (error, _) => {
+                    debug!("Listener {:?}; Giving up recovery: {:?}", id, error);
+                    given_up.push(*id);
+                    this.pending_events.push_back(ListenersEvent::Closed {
+                        listener_id: *id,
+                        addresses: Vec::new(),
+                        reason: Err(error),
+                    });
+                }
+            }
+        }
+        for id in given_up {
+            this.supervisors.remove(&id);
+        }
+        if let Some(event) = self.pending_events.pop_front() {
+            return Poll::Ready(event);
+        }
+
         // We remove each element from `listeners` one by one and add them back.
         let mut remaining = self.listeners.len();
         while let Some(mut listener) = self.listeners.pop_back() {
@@ -232,6 +359,9 @@ where
                         listener_project.addresses.push(a.clone());
                     }
                     let id = *listener_project.id;
+                    if let Some(supervisor) = self.supervisors.get_mut(&id) {
+                        supervisor.failures = 0;
+                    }
                     self.listeners.push_front(listener);
                     return Poll::Ready(ListenersEvent::NewAddress {
                         listener_id: id,
@@ -256,20 +386,14 @@ where
                     });
                 }
                 Poll::Ready(None) => {
+                    let id = *listener_project.id;
                     let addresses = mem::take(listener_project.addresses).into_vec();
-                    return Poll::Ready(ListenersEvent::Closed {
-                        listener_id: *listener_project.id,
-                        addresses,
-                        reason: Ok(()),
-                    });
+                    return self.on_listener_closed(id, addresses, Ok(()), cx);
                 }
                 Poll::Ready(Some(Err(err))) => {
+                    let id = *listener_project.id;
                     let addresses = mem::take(listener_project.addresses).into_vec();
-                    return Poll::Ready(ListenersEvent::Closed {
-                        listener_id: *listener_project.id,
-                        addresses,
-                        reason: Err(err),
-                    });
+                    return self.on_listener_closed(id, addresses, Err(err), cx);
                 }
             }
         }
@@ -277,6 +401,86 @@ where
         // We register the current task to be woken up if a new listener is added.
         Poll::Pending
     }
+
+    /// Reports a closed listener, or schedules starting it again if it is
+    /// supervised.
+    fn on_listener_closed(
+        mut self: Pin<&mut Self>,
+        id: ListenerId,
+        addresses: Vec<Multiaddr>,
+        reason: Result<(), TTrans::Error>,
+        cx: &mut Context<'_>,
+    ) -> Poll<ListenersEvent<TTrans>> {
+        let this = &mut *self;
+        let backoff = this.supervisors.get_mut(&id).and_then(|supervisor| {
+            supervisor.failures += 1;
+            let backoff = supervisor.recovery.backoff(supervisor.failures)?;
+            supervisor.restart = Some(Delay::new(backoff));
+            Some(backoff)
+        });
+        let backoff = match backoff {
+            Some(backoff) => backoff,
+            None => {
+                this.supervisors.remove(&id);
+                return Poll::Ready(ListenersEvent::Closed {
+                    listener_id: id,
+                    addresses,
+                    reason: reason.map_err(TransportError::Other),
+                });
+            }
+        };
+
+        debug!("Listener {:?} closed; Starting again in {:?}.", id, backoff);
+        for listen_addr in addresses {
+            this.pending_events
+                .push_back(ListenersEvent::AddressExpired {
+                    listener_id: id,
+                    listen_addr,
+                });
+        }
+        if let Err(error) = reason {
+            this.pending_events.push_back(ListenersEvent::Error {
+                listener_id: id,
+                error,
+            });
+        }
+        // Poll again to register interest in the backoff.
+        self.poll(cx)
+    }
+}
+
+/// Starts a listener on the transport with the given [`ListenOpts`].
+type StartListener<TTrans> =
+    fn(
+        &TTrans,
+        &ListenOpts,
+    )
+        -> Result<<TTrans as Transport>::Listener, TransportError<<TTrans as Transport>::Error>>;
+
+/// Starts a listener on the address of the given [`ListenOpts`], trying each
+/// port of its port range in order.
+fn start_listener<TTrans>(
+    transport: &TTrans,
+    opts: &ListenOpts,
+) -> Result<TTrans::Listener, TransportError<TTrans::Error>>
+where
+    TTrans: Transport + Clone,
+{
+    let candidates = opts
+        .candidates()
+        .ok_or_else(|| TransportError::MultiaddrNotSupported(opts.address.clone()))?;
+    let mut last_error = None;
+    for addr in candidates {
+        match transport.clone().listen_on(addr.clone()) {
+            Ok(listener) => return Ok(listener),
+            Err(TransportError::Other(error)) => {
+                debug!("Failed to listen on {}: {}", addr, error);
+                last_error = Some(TransportError::Other(error));
+            }
+            Err(error @ TransportError::MultiaddrNotSupported(_)) => return Err(error),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| TransportError::MultiaddrNotSupported(opts.address.clone())))
 }
 
 impl<TTrans> Stream for ListenersStream<TTrans>
@@ -358,6 +562,10 @@ where
 #[cfg(test)]
 mod tests {
     use futures::{future::BoxFuture, stream::BoxStream};
+    use libp2p_core::multiaddr::Protocol;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
 
     use super::*;
     use crate::transport;
@@ -549,4 +757,243 @@ mod tests {
             }
         });
     }
+
+    type TestListener = BoxStream<
+        'static,
+        Result<
+            ListenerEvent<BoxFuture<'static, Result<(), std::io::Error>>, std::io::Error>,
+            std::io::Error,
+        >,
+    >;
+
+    /// Transport that creates listeners through the given function, which is
+    /// passed the address and the number of previous calls.
+    #[derive(Clone)]
+    struct FnTrans(
+        Arc<
+            dyn Fn(Multiaddr, usize) -> Result<TestListener, TransportError<std::io::Error>>
+                + Send
+                + Sync,
+        >,
+        Arc<AtomicUsize>,
+    );
+
+    impl FnTrans {
+        fn new(
+            f: impl Fn(Multiaddr, usize) -> Result<TestListener, TransportError<std::io::Error>>
+                + Send
+                + Sync
+                + 'static,
+        ) -> Self {
+            FnTrans(Arc::new(f), Default::default())
+        }
+    }
+
+    impl transport::Transport for FnTrans {
+        type Output = ();
+        type Error = std::io::Error;
+        type Listener = TestListener;
+        type ListenerUpgrade = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+        type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+        fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+            (self.0)(addr, self.1.fetch_add(1, Ordering::SeqCst))
+        }
+
+        fn dial(self, _: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+            panic!()
+        }
+
+        fn dial_as_listener(self, _: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+            panic!()
+        }
+
+        fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> {
+            None
+        }
+    }
+
+    fn recovery() -> ListenerRecoveryConfig {
+        ListenerRecoveryConfig::default()
+            .with_base(Duration::from_millis(10))
+            .with_max(Duration::from_millis(10))
+    }
+
+    #[test]
+    fn supervised_listener_is_started_again() {
+        let transport = FnTrans::new(|addr, calls| {
+            let events = stream::iter(vec![Ok(ListenerEvent::NewAddress(addr))]);
+            if calls == 0 {
+                let error = std::io::Error::from(std::io::ErrorKind::Other);
+                Ok(events.chain(stream::iter(vec![Err(error)])).boxed())
+            } else {
+                Ok(events.chain(stream::pending()).boxed())
+            }
+        });
+
+        async_std::task::block_on(async move {
+            let addr: Multiaddr = "/memory/1".parse().unwrap();
+            let mut listeners = ListenersStream::new(transport);
+            let id = listeners
+                .listen_with_opts(
+                    ListenOpts::address(addr.clone())
+                        .recover(recovery())
+                        .build(),
+                )
+                .unwrap();
+
+            match listeners.next().await.unwrap() {
+                ListenersEvent::NewAddress {
+                    listener_id,
+                    listen_addr,
+                } => {
+                    assert_eq!(listener_id, id);
+                    assert_eq!(listen_addr, addr);
+                }
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+            match listeners.next().await.unwrap() {
+                ListenersEvent::AddressExpired {
+                    listener_id,
+                    listen_addr,
+                } => {
+                    assert_eq!(listener_id, id);
+                    assert_eq!(listen_addr, addr);
+                }
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+            match listeners.next().await.unwrap() {
+                ListenersEvent::Error { listener_id, .. } => assert_eq!(listener_id, id),
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+            assert_eq!(listeners.listener_ids().collect::<Vec<_>>(), vec![id]);
+            match listeners.next().await.unwrap() {
+                ListenersEvent::NewAddress {
+                    listener_id,
+                    listen_addr,
+                } => {
+                    assert_eq!(listener_id, id);
+                    assert_eq!(listen_addr, addr);
+                }
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+            assert_eq!(listeners.transport().1.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn supervised_listener_gives_up_after_max_attempts() {
+        let transport = FnTrans::new(|_, calls| {
+            let error = std::io::Error::from(std::io::ErrorKind::Other);
+            if calls == 0 {
+                Ok(stream::iter(vec![Err(error)]).boxed())
+            } else {
+                Err(TransportError::Other(error))
+            }
+        });
+
+        async_std::task::block_on(async move {
+            let mut listeners = ListenersStream::new(transport);
+            let opts = ListenOpts::address("/memory/1".parse().unwrap())
+                .recover(recovery().with_max_attempts(2))
+                .build();
+            let id = listeners.listen_with_opts(opts).unwrap();
+
+            for _ in 0..2 {
+                match listeners.next().await.unwrap() {
+                    ListenersEvent::Error { listener_id, .. } => assert_eq!(listener_id, id),
+                    other => panic!("Unexpected listeners event: {:?}", other),
+                }
+            }
+            match listeners.next().await.unwrap() {
+                ListenersEvent::Closed {
+                    listener_id,
+                    reason: Err(_),
+                    ..
+                } => assert_eq!(listener_id, id),
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+            assert_eq!(listeners.listener_ids().count(), 0);
+            assert_eq!(listeners.transport().1.load(Ordering::SeqCst), 3);
+        });
+    }
+
+    #[test]
+    fn supervised_listener_reports_unsupported_address() {
+        let transport = FnTrans::new(|addr, calls| {
+            if calls == 0 {
+                let error = std::io::Error::from(std::io::ErrorKind::Other);
+                Ok(stream::iter(vec![Err(error)]).boxed())
+            } else {
+                Err(TransportError::MultiaddrNotSupported(addr))
+            }
+        });
+
+        async_std::task::block_on(async move {
+            let mut listeners = ListenersStream::new(transport);
+            let opts = ListenOpts::address("/memory/1".parse().unwrap())
+                .recover(recovery())
+                .build();
+            let id = listeners.listen_with_opts(opts).unwrap();
+
+            match listeners.next().await.unwrap() {
+                ListenersEvent::Error { listener_id, .. } => assert_eq!(listener_id, id),
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+            match listeners.next().await.unwrap() {
+                ListenersEvent::Closed {
+                    listener_id,
+                    reason: Err(TransportError::MultiaddrNotSupported(_)),
+                    ..
+                } => assert_eq!(listener_id, id),
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+            assert_eq!(listeners.listener_ids().count(), 0);
+        });
+    }
+
+    #[test]
+    fn port_range_listens_on_first_free_port() {
+        let transport = FnTrans::new(|addr, _| match addr.iter().last() {
+            Some(Protocol::Tcp(port)) if port >= 4003 => {
+                Ok(stream::iter(vec![Ok(ListenerEvent::NewAddress(addr))])
+                    .chain(stream::pending())
+                    .boxed())
+            }
+            Some(Protocol::Tcp(_)) => Err(TransportError::Other(std::io::Error::from(
+                std::io::ErrorKind::AddrInUse,
+            ))),
+            _ => Err(TransportError::MultiaddrNotSupported(addr)),
+        });
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/0".parse().unwrap();
+
+        async_std::task::block_on(async move {
+            let mut listeners = ListenersStream::new(transport);
+
+            let opts = ListenOpts::address(addr.clone())
+                .port_range(4000..=4010)
+                .build();
+            listeners.listen_with_opts(opts).unwrap();
+            match listeners.next().await.unwrap() {
+                ListenersEvent::NewAddress { listen_addr, .. } => {
+                    assert_eq!(listen_addr, "/ip4/127.0.0.1/tcp/4003".parse().unwrap())
+                }
+                other => panic!("Unexpected listeners event: {:?}", other),
+            }
+
+            let opts = ListenOpts::address(addr).port_range(4000..=4002).build();
+            assert!(matches!(
+                listeners.listen_with_opts(opts),
+                Err(TransportError::Other(_))
+            ));
+
+            let opts = ListenOpts::address("/memory/0".parse().unwrap())
+                .port_range(4000..=4010)
+                .build();
+            assert!(matches!(
+                listeners.listen_with_opts(opts),
+                Err(TransportError::MultiaddrNotSupported(_))
+            ));
+        });
+    }
 }
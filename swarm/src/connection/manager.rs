@@ -0,0 +1,394 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use fnv::{FnvHashMap, FnvHashSet};
+use futures::prelude::*;
+use libp2p_core::time::{self, Delay, Instant};
+use libp2p_core::{connection::ConnectionId, PeerId};
+use std::{
+    cmp::Reverse,
+    collections::VecDeque,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// The configuration of a [`ConnectionManager`].
+///
+/// Once the number of established connections exceeds the high watermark,
+/// the [`ConnectionManager`] closes established connections until their
+/// number is back at the low watermark.
+#[derive(Debug, Clone)]
+pub struct ConnectionManagerConfig {
+    low_watermark: usize,
+    high_watermark: usize,
+    grace_period: Duration,
+}
+
+impl ConnectionManagerConfig {
+    /// Creates a new configuration with the given watermarks and a grace
+    /// period of 20 seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low_watermark` is greater than `high_watermark`.
+    pub fn new(low_watermark: usize, high_watermark: usize) -> Self {
+        assert!(
+            low_watermark <= high_watermark,
+            "low watermark must not exceed high watermark"
+        );
+        ConnectionManagerConfig {
+            low_watermark,
+            high_watermark,
+            grace_period: Duration::from_secs(20),
+        }
+    }
+
+    /// Sets the grace period during which a newly established connection is
+    /// never trimmed.
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+}
+
+/// The reason a connection has been trimmed by the [`ConnectionManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimReason {
+    /// The number of established connections exceeded the high watermark.
+    HighWatermark {
+        /// The number of established connections at the time of trimming.
+        established: usize,
+        /// The configured high watermark.
+        high_watermark: usize,
+    },
+}
+
+/// Keeps the number of established connections of the [`Swarm`](crate::Swarm)
+/// between a low and a high watermark.
+///
+/// Peers can be tagged with weights, e.g. by a behaviour for its mesh peers,
+/// via [`ConnectionManager::tag_peer`]. When trimming, connections to peers
+/// with the lowest total weight are closed first and, among those, the most
+/// recently established ones. Connections to peers protected via
+/// [`ConnectionManager::protect_peer`] and connections still within their
+/// grace period are never trimmed. The tags of a peer are removed once its
+/// last connection closes, whereas protections are kept until lifted.
+///
+/// Trimming only happens if the [`Swarm`](crate::Swarm) has been configured
+/// with a [`ConnectionManagerConfig`] via
+/// [`SwarmBuilder::connection_manager`](crate::SwarmBuilder::connection_manager).
+/// Every trimmed connection is reported via
+/// [`SwarmEvent::ConnectionTrimmed`](crate::SwarmEvent::ConnectionTrimmed).
+#[derive(Debug, Default)]
+pub struct ConnectionManager {
+    config: Option<ConnectionManagerConfig>,
+    peers: FnvHashMap<PeerId, PeerTags>,
+    connections: FnvHashMap<ConnectionId, Established>,
+    /// Connections that have been selected for trimming but are not yet closed.
+    trimming: FnvHashSet<ConnectionId>,
+    /// Trimming decisions not yet reported to the `Swarm`.
+    pending: VecDeque<(PeerId, ConnectionId, TrimReason)>,
+    /// Whether the connections need to be checked against the high watermark.
+    check: bool,
+    /// Fires once the grace period of a connection that could not yet be
+    /// trimmed has elapsed.
+    next_check: Option<Delay>,
+}
+
+#[derive(Debug, Default)]
+struct PeerTags {
+    tags: FnvHashMap<String, i32>,
+    protections: FnvHashSet<String>,
+}
+
+impl PeerTags {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.protections.is_empty()
+    }
+}
+
+#[derive(Debug)]
+struct Established {
+    peer: PeerId,
+    since: Instant,
+}
+
+impl ConnectionManager {
+    pub(crate) fn new(config: Option<ConnectionManagerConfig>) -> Self {
+        ConnectionManager {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Tags the given peer with a weight, replacing a previous weight of the
+    /// same tag.
+    pub fn tag_peer(&mut self, peer: PeerId, tag: impl Into<String>, weight: i32) {
+        self.peers
+            .entry(peer)
+            .or_default()
+            .tags
+            .insert(tag.into(), weight);
+    }
+
+    /// Removes a tag from the given peer.
+    pub fn untag_peer(&mut self, peer: &PeerId, tag: &str) {
+        if let Some(tags) = self.peers.get_mut(peer) {
+            tags.tags.remove(tag);
+            if tags.is_empty() {
+                self.peers.remove(peer);
+            }
+            self.check = true;
+        }
+    }
+
+    /// Returns the total weight of all tags of the given peer.
+    pub fn peer_weight(&self, peer: &PeerId) -> i32 {
+        self.peers
+            .get(peer)
+            .map_or(0, |tags| tags.tags.values().sum())
+    }
+
+    /// Protects the connections to the given peer from being trimmed.
+    ///
+    /// The protection is held under the given tag, such that independent
+    /// protections of the same peer can be lifted separately.
+    pub fn protect_peer(&mut self, peer: PeerId, tag: impl Into<String>) {
+        self.peers
+            .entry(peer)
+            .or_default()
+            .protections
+            .insert(tag.into());
+    }
+
+    /// Lifts the protection of the given peer held under the given tag.
+    ///
+    /// Returns whether the peer is still protected under another tag.
+    pub fn unprotect_peer(&mut self, peer: &PeerId, tag: &str) -> bool {
+        match self.peers.get_mut(peer) {
+            Some(tags) => {
+                tags.protections.remove(tag);
+                let protected = !tags.protections.is_empty();
+                if tags.is_empty() {
+                    self.peers.remove(peer);
+                }
+                self.check = true;
+                protected
+            }
+            None => false,
+        }
+    }
+
+    /// Returns whether the given peer is protected from trimming.
+    pub fn is_protected(&self, peer: &PeerId) -> bool {
+        self.peers
+            .get(peer)
+            .map_or(false, |tags| !tags.protections.is_empty())
+    }
+
+    pub(crate) fn on_connection_established(&mut self, id: ConnectionId, peer: PeerId) {
+        self.connections.insert(
+            id,
+            Established {
+                peer,
+                since: time::now(),
+            },
+        );
+        self.check = true;
+    }
+
+    /// Forgets the given connection and, if it was the last one to its peer,
+    /// the tags of the peer. Protections are kept.
+    pub(crate) fn on_connection_closed(&mut self, id: &ConnectionId) {
+        self.trimming.remove(id);
+        let peer = match self.connections.remove(id) {
+            Some(c) => c.peer,
+            None => return,
+        };
+        if self.connections.values().any(|c| c.peer == peer) {
+            return;
+        }
+        if let Some(tags) = self.peers.get_mut(&peer) {
+            tags.tags.clear();
+            if tags.is_empty() {
+                self.peers.remove(&peer);
+            }
+        }
+    }
+
+    /// Polls for the next connection to close.
+    pub(crate) fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<(PeerId, ConnectionId, TrimReason)> {
+        loop {
+            if let Some(decision) = self.pending.pop_front() {
+                return Poll::Ready(decision);
+            }
+
+            if let Some(delay) = self.next_check.as_mut() {
+                if delay.poll_unpin(cx).is_ready() {
+                    self.next_check = None;
+                    self.check = true;
+                }
+            }
+
+            if !std::mem::take(&mut self.check) {
+                return Poll::Pending;
+            }
+
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return,
+        };
+
+        let established = self.connections.len() - self.trimming.len();
+        if established <= config.high_watermark {
+            return;
+        }
+
+        let now = time::now();
+        let mut grace_ends = None;
+        let mut candidates = self
+            .connections
+            .iter()
+            .filter(|(id, c)| !self.trimming.contains(id) && !self.is_protected(&c.peer))
+            .filter(|(_, c)| {
+                let end = c.since + config.grace_period;
+                if end > now {
+                    grace_ends = Some(grace_ends.map_or(end, |e: Instant| e.min(end)));
+                    false
+                } else {
+                    true
+                }
+            })
+            .map(|(id, c)| (*id, c.peer, self.peer_weight(&c.peer), c.since))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(_, _, weight, since)| (*weight, Reverse(*since)));
+
+        let excess = established - config.low_watermark;
+        let reason = TrimReason::HighWatermark {
+            established,
+            high_watermark: config.high_watermark,
+        };
+        let num_trimmed = candidates.len().min(excess);
+        for (id, peer, _, _) in candidates.into_iter().take(excess) {
+            self.trimming.insert(id);
+            self.pending.push_back((peer, id, reason));
+        }
+
+        if num_trimmed < excess {
+            if let Some(end) = grace_ends {
+                self.next_check = Some(Delay::new(end - now));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    fn next_trimmed(manager: &mut ConnectionManager) -> Option<ConnectionId> {
+        block_on(future::poll_fn(|cx| match manager.poll(cx) {
+            Poll::Ready((_, id, _)) => Poll::Ready(Some(id)),
+            Poll::Pending => Poll::Ready(None),
+        }))
+    }
+
+    #[test]
+    fn trims_lowest_weight_down_to_low_watermark() {
+        let config = ConnectionManagerConfig::new(1, 2).with_grace_period(Duration::ZERO);
+        let mut manager = ConnectionManager::new(Some(config));
+        let peers = (0..3).map(|_| PeerId::random()).collect::<Vec<_>>();
+        manager.tag_peer(peers[0], "mesh", 10);
+        manager.tag_peer(peers[2], "mesh", 5);
+
+        for (i, peer) in peers.iter().enumerate() {
+            manager.on_connection_established(ConnectionId::new(i), *peer);
+        }
+
+        assert_eq!(next_trimmed(&mut manager), Some(ConnectionId::new(1)));
+        assert_eq!(next_trimmed(&mut manager), Some(ConnectionId::new(2)));
+        assert_eq!(next_trimmed(&mut manager), None);
+    }
+
+    #[test]
+    fn protected_peers_are_not_trimmed() {
+        let config = ConnectionManagerConfig::new(0, 1).with_grace_period(Duration::ZERO);
+        let mut manager = ConnectionManager::new(Some(config));
+        let protected = PeerId::random();
+        manager.protect_peer(protected, "relay");
+        manager.protect_peer(protected, "explicit");
+        assert!(manager.unprotect_peer(&protected, "relay"));
+
+        manager.on_connection_established(ConnectionId::new(0), protected);
+        manager.on_connection_established(ConnectionId::new(1), PeerId::random());
+
+        assert_eq!(next_trimmed(&mut manager), Some(ConnectionId::new(1)));
+        assert_eq!(next_trimmed(&mut manager), None);
+    }
+
+    #[test]
+    fn unprotecting_triggers_check() {
+        let config = ConnectionManagerConfig::new(0, 0).with_grace_period(Duration::ZERO);
+        let mut manager = ConnectionManager::new(Some(config));
+        let peer = PeerId::random();
+        manager.protect_peer(peer, "relay");
+        manager.on_connection_established(ConnectionId::new(0), peer);
+        assert_eq!(next_trimmed(&mut manager), None);
+
+        assert!(!manager.unprotect_peer(&peer, "relay"));
+        assert_eq!(next_trimmed(&mut manager), Some(ConnectionId::new(0)));
+    }
+
+    #[test]
+    fn tags_are_cleared_once_disconnected() {
+        let mut manager = ConnectionManager::new(None);
+        let peer = PeerId::random();
+        manager.tag_peer(peer, "mesh", 10);
+        manager.protect_peer(peer, "relay");
+        manager.on_connection_established(ConnectionId::new(0), peer);
+        manager.on_connection_established(ConnectionId::new(1), peer);
+
+        manager.on_connection_closed(&ConnectionId::new(0));
+        assert_eq!(manager.peer_weight(&peer), 10);
+
+        manager.on_connection_closed(&ConnectionId::new(1));
+        assert_eq!(manager.peer_weight(&peer), 0);
+        assert!(manager.is_protected(&peer));
+    }
+
+    #[test]
+    fn connections_within_grace_period_are_not_trimmed() {
+        let config = ConnectionManagerConfig::new(0, 0).with_grace_period(Duration::from_secs(60));
+        let mut manager = ConnectionManager::new(Some(config));
+        manager.on_connection_established(ConnectionId::new(0), PeerId::random());
+
+        assert_eq!(next_trimmed(&mut manager), None);
+        assert!(manager.next_check.is_some());
+    }
+}
@@ -22,11 +22,14 @@
 use crate::{
     behaviour::{THandlerInEvent, THandlerOutEvent},
     connection::{
-        Connected, ConnectionError, ConnectionLimit, IncomingInfo, PendingConnectionError,
-        PendingInboundConnectionError, PendingOutboundConnectionError,
+        tracker::ConnectionTracker, Connected, ConnectionError, ConnectionGater, ConnectionInfo,
+        ConnectionLimit, DuplicateConnectionConfig, InboundStreamLimit, InboundStreamLimits,
+        IncomingInfo, PendingConnectionError, PendingInboundConnectionError,
+        PendingOutboundConnectionError, SharedConnectionGater,
     },
     transport::{Transport, TransportError},
     ConnectedPoint, ConnectionHandler, Executor, IntoConnectionHandler, Multiaddr, PeerId,
+    ProtocolsChange,
 };
 use concurrent_dial::ConcurrentDial;
 use fnv::FnvHashMap;
@@ -37,16 +40,19 @@ use futures::{
     ready,
     stream::FuturesUnordered,
 };
-use libp2p_core::connection::{ConnectionId, Endpoint, PendingPoint};
+use libp2p_core::connection::{ConnectionId, Endpoint, NegotiatedProtocols, PendingPoint};
 use libp2p_core::muxing::{StreamMuxer, StreamMuxerBox};
+use libp2p_core::time::{self, Instant};
 use std::{
     collections::{hash_map, HashMap},
     convert::TryFrom as _,
     fmt,
     num::{NonZeroU8, NonZeroUsize},
     pin::Pin,
+    sync::Arc,
     task::Context,
     task::Poll,
+    time::Duration,
 };
 use void::Void;
 
@@ -87,6 +93,15 @@ where
     /// The configured override for substream protocol upgrades, if any.
     substream_upgrade_protocol_override: Option<libp2p_core::upgrade::Version>,
 
+    /// The limits on inbound substreams of each connection.
+    inbound_stream_limits: Arc<InboundStreamLimits>,
+
+    /// The [`ConnectionGater`] consulted while connections are established, if any.
+    gater: Option<SharedConnectionGater>,
+
+    /// How to resolve duplicate connections to the same peer, if at all.
+    duplicate_connections: Option<DuplicateConnectionConfig>,
+
     /// The executor to use for running the background tasks. If `None`,
     /// the tasks are kept in `local_spawns` instead and polled on the
     /// current thread when the [`Pool`] is polled for new events.
@@ -118,8 +133,17 @@ struct EstablishedConnectionInfo<TInEvent> {
     /// [`PeerId`] of the remote peer.
     peer_id: PeerId,
     endpoint: ConnectedPoint,
+    /// The protocols negotiated while upgrading the connection.
+    negotiated_protocols: NegotiatedProtocols,
+    /// When the connection was established.
+    established: Instant,
+    /// Records the substreams and activity of the connection.
+    tracker: ConnectionTracker,
     /// Channel endpoint to send commands to the task.
     sender: mpsc::Sender<task::Command<TInEvent>>,
+    /// Whether the connection is being drained in favor of another
+    /// connection to the same peer.
+    duplicate: bool,
 }
 
 impl<TInEvent> EstablishedConnectionInfo<TInEvent> {
@@ -134,6 +158,19 @@ impl<TInEvent> EstablishedConnectionInfo<TInEvent> {
             Err(e) => assert!(e.is_disconnected(), "No capacity for close command."),
         };
     }
+
+    /// Initiates a drain of the connection, closing it gracefully once the
+    /// handler is idle or the given timeout elapsed.
+    ///
+    /// Has no effect if the connection is already closing.
+    pub fn start_drain(&mut self, timeout: Duration) {
+        // Clone the sender so that we are guaranteed to have
+        // capacity for the drain command (every sender gets a slot).
+        match self.sender.clone().try_send(task::Command::Drain(timeout)) {
+            Ok(()) => {}
+            Err(e) => assert!(e.is_disconnected(), "No capacity for drain command."),
+        };
+    }
 }
 
 struct PendingConnectionInfo<THandler> {
@@ -231,6 +268,28 @@ where
         event: THandlerOutEvent<THandler>,
     },
 
+    /// An inbound substream of a connection was reset for exceeding one of
+    /// the [`InboundStreamLimits`].
+    InboundStreamLimitExceeded {
+        /// The connection of the substream.
+        connection: EstablishedConnection<'a, THandlerInEvent<THandler>>,
+        /// The protocol of the substream, if selected.
+        protocol: Option<String>,
+        /// The exceeded limit.
+        limit: InboundStreamLimit,
+    },
+
+    /// The protocols supported by the local node or the remote on a
+    /// connection changed.
+    ProtocolsChange {
+        /// The connection of the protocols.
+        connection: EstablishedConnection<'a, THandlerInEvent<THandler>>,
+        /// Whether the protocols of the local node changed.
+        local: bool,
+        /// The change.
+        change: ProtocolsChange,
+    },
+
     /// The connection to a node has changed its address.
     AddressChange {
         /// The connection that has changed address.
@@ -295,6 +354,26 @@ where
                 .field("peer", &connection.peer_id())
                 .field("event", event)
                 .finish(),
+            PoolEvent::InboundStreamLimitExceeded {
+                connection,
+                protocol,
+                limit,
+            } => f
+                .debug_struct("PoolEvent::InboundStreamLimitExceeded")
+                .field("peer", &connection.peer_id())
+                .field("protocol", protocol)
+                .field("limit", limit)
+                .finish(),
+            PoolEvent::ProtocolsChange {
+                connection,
+                local,
+                change,
+            } => f
+                .debug_struct("PoolEvent::ProtocolsChange")
+                .field("peer", &connection.peer_id())
+                .field("local", local)
+                .field("change", change)
+                .finish(),
             PoolEvent::AddressChange {
                 connection,
                 new_endpoint,
@@ -329,6 +408,9 @@ where
             task_command_buffer_size: config.task_command_buffer_size,
             dial_concurrency_factor: config.dial_concurrency_factor,
             substream_upgrade_protocol_override: config.substream_upgrade_protocol_override,
+            inbound_stream_limits: Arc::new(config.inbound_stream_limits),
+            gater: config.connection_gater,
+            duplicate_connections: config.duplicate_connections,
             executor: config.executor,
             local_spawns: FuturesUnordered::new(),
             pending_connection_events_tx,
@@ -415,17 +497,80 @@ where
         }
     }
 
+    /// Drains all established connections, see
+    /// [`EstablishedConnection::start_drain`], and aborts all pending
+    /// connections.
+    pub fn start_drain(&mut self, timeout: Duration) {
+        for conns in self.established.values_mut() {
+            for conn in conns.values_mut() {
+                conn.start_drain(timeout);
+            }
+        }
+
+        for info in self.pending.values_mut() {
+            drop(info.abort_notifier.take());
+        }
+    }
+
+    /// Drains the duplicate established connections to `peer`, if
+    /// configured to resolve duplicate connections.
+    fn resolve_duplicates(&mut self, peer: PeerId) {
+        let config = match &self.duplicate_connections {
+            Some(config) => config,
+            None => return,
+        };
+        let conns = match self.established.get_mut(&peer) {
+            Some(conns) if conns.len() > 1 => conns,
+            _ => return,
+        };
+        let duplicates = config.duplicates(
+            &self.local_id,
+            &peer,
+            conns
+                .iter()
+                .filter(|(_, conn)| !conn.duplicate)
+                .map(|(id, conn)| (*id, &conn.endpoint)),
+        );
+        for id in duplicates {
+            if let Some(conn) = conns.get_mut(&id) {
+                log::debug!("Closing connection {:?} to {} as duplicate.", id, peer);
+                conn.duplicate = true;
+                conn.start_drain(config.drain_timeout());
+            }
+        }
+    }
+
     /// Returns an iterator over all established connections of `peer`.
+    ///
+    /// Connections being closed as duplicates come last.
     pub fn iter_established_connections_of_peer(
         &mut self,
         peer: &PeerId,
     ) -> impl Iterator<Item = ConnectionId> + '_ {
         match self.established.get(peer) {
-            Some(conns) => either::Either::Left(conns.iter().map(|(id, _)| *id)),
+            Some(conns) => either::Either::Left(
+                conns
+                    .iter()
+                    .filter(|(_, conn)| !conn.duplicate)
+                    .chain(conns.iter().filter(|(_, conn)| conn.duplicate))
+                    .map(|(id, _)| *id),
+            ),
             None => either::Either::Right(std::iter::empty()),
         }
     }
 
+    /// Returns an iterator over the endpoints of all established connections
+    /// of `peer`.
+    pub fn iter_established_endpoints_of_peer(
+        &self,
+        peer: &PeerId,
+    ) -> impl Iterator<Item = &ConnectedPoint> + '_ {
+        self.established
+            .get(peer)
+            .into_iter()
+            .flat_map(|conns| conns.values().map(|conn| &conn.endpoint))
+    }
+
     /// Returns an iterator over all pending connection IDs together
     /// with associated endpoints and expected peer IDs in the pool.
     pub fn iter_pending_info(
@@ -441,6 +586,21 @@ where
         )
     }
 
+    /// Returns information about all established connections in the pool.
+    pub fn iter_established_info(&self) -> impl Iterator<Item = ConnectionInfo> + '_ {
+        self.established.values().flat_map(|conns| {
+            conns.iter().map(|(id, conn)| {
+                conn.tracker.info(
+                    *id,
+                    conn.peer_id,
+                    conn.endpoint.clone(),
+                    conn.negotiated_protocols.clone(),
+                    conn.established,
+                )
+            })
+        })
+    }
+
     /// Returns an iterator over all connected peers, i.e. those that have
     /// at least one established connection in the pool.
     pub fn iter_connected(&self) -> impl Iterator<Item = &PeerId> {
@@ -473,23 +633,45 @@ where
     /// Adds a pending outgoing connection to the pool in the form of a `Future`
     /// that establishes and negotiates the connection.
     ///
+    /// The addresses are dialed in the given order, each with the given delay
+    /// as ranked by an [`AddressRanking`](super::AddressRanking).
+    ///
     /// Returns an error if the limit of pending outgoing connections
-    /// has been reached.
+    /// has been reached or if the [`ConnectionGater`] denied all addresses.
     pub fn add_outgoing(
         &mut self,
         transport: TTrans,
-        addresses: impl Iterator<Item = Multiaddr> + Send + 'static,
+        addresses: impl Iterator<Item = (Multiaddr, Duration)>,
         peer: Option<PeerId>,
         handler: THandler,
         role_override: Endpoint,
         dial_concurrency_factor_override: Option<NonZeroU8>,
-    ) -> Result<ConnectionId, (ConnectionLimit, THandler)>
+    ) -> Result<ConnectionId, (PendingOutboundConnectionError<TTrans::Error>, THandler)>
     where
         TTrans: Clone + Send,
         TTrans::Dial: Send + 'static,
     {
         if let Err(limit) = self.counters.check_max_pending_outgoing() {
-            return Err((limit, handler));
+            return Err((PendingConnectionError::ConnectionLimit(limit), handler));
+        };
+
+        let addresses = match &mut self.gater {
+            Some(gater) => {
+                let addresses = addresses
+                    .filter(|(addr, _)| {
+                        let allowed = gater.intercept_addr_dial(peer.as_ref(), addr);
+                        if !allowed {
+                            log::debug!("Dialing {} denied by connection gater.", addr);
+                        }
+                        allowed
+                    })
+                    .collect::<Vec<_>>();
+                if addresses.is_empty() {
+                    return Err((PendingConnectionError::DeniedDial, handler));
+                }
+                either::Either::Left(addresses.into_iter())
+            }
+            None => either::Either::Right(addresses),
         };
 
         let dial = ConcurrentDial::new(
@@ -533,20 +715,26 @@ where
     /// `Future` that establishes and negotiates the connection.
     ///
     /// Returns an error if the limit of pending incoming connections
-    /// has been reached.
+    /// has been reached or if the [`ConnectionGater`] denied the connection.
     pub fn add_incoming<TFut>(
         &mut self,
         future: TFut,
         handler: THandler,
         info: IncomingInfo<'_>,
-    ) -> Result<ConnectionId, (ConnectionLimit, THandler)>
+    ) -> Result<ConnectionId, (PendingInboundConnectionError<TTrans::Error>, THandler)>
     where
         TFut: Future<Output = Result<TTrans::Output, TTrans::Error>> + Send + 'static,
     {
         let endpoint = info.to_connected_point();
 
         if let Err(limit) = self.counters.check_max_pending_incoming() {
-            return Err((limit, handler));
+            return Err((PendingConnectionError::ConnectionLimit(limit), handler));
+        }
+
+        if let Some(gater) = &mut self.gater {
+            if !gater.intercept_accept(info.local_addr, info.send_back_addr) {
+                return Err((PendingConnectionError::DeniedAccept, handler));
+            }
         }
 
         let connection_id = self.next_connection_id();
@@ -606,6 +794,44 @@ where
                     event,
                 });
             }
+            Poll::Ready(Some(task::EstablishedConnectionEvent::InboundStreamLimitExceeded {
+                id,
+                peer_id,
+                protocol,
+                limit,
+            })) => {
+                let entry = self
+                    .established
+                    .get_mut(&peer_id)
+                    .expect("Receive `InboundStreamLimitExceeded` event for established peer.")
+                    .entry(id)
+                    .expect_occupied(
+                        "Receive `InboundStreamLimitExceeded` event from established connection",
+                    );
+                return Poll::Ready(PoolEvent::InboundStreamLimitExceeded {
+                    connection: EstablishedConnection { entry },
+                    protocol,
+                    limit,
+                });
+            }
+            Poll::Ready(Some(task::EstablishedConnectionEvent::ProtocolsChange {
+                id,
+                peer_id,
+                local,
+                change,
+            })) => {
+                let entry = self
+                    .established
+                    .get_mut(&peer_id)
+                    .expect("Receive `ProtocolsChange` event for established peer.")
+                    .entry(id)
+                    .expect_occupied("Receive `ProtocolsChange` event from established connection");
+                return Poll::Ready(PoolEvent::ProtocolsChange {
+                    connection: EstablishedConnection { entry },
+                    local,
+                    change,
+                });
+            }
             Poll::Ready(Some(task::EstablishedConnectionEvent::AddressChange {
                 id,
                 peer_id,
@@ -643,8 +869,18 @@ where
                     .established
                     .get_mut(&peer_id)
                     .expect("`Closed` event for established connection");
-                let EstablishedConnectionInfo { endpoint, .. } =
-                    connections.remove(&id).expect("Connection to be present");
+                let EstablishedConnectionInfo {
+                    endpoint,
+                    duplicate,
+                    ..
+                } = connections.remove(&id).expect("Connection to be present");
+                // The remote may close the duplicate connection first, which is then not
+                // reported as an I/O error.
+                let error = match error {
+                    Some(ConnectionError::Handler(e)) => Some(ConnectionError::Handler(e)),
+                    _ if duplicate => Some(ConnectionError::Duplicate),
+                    error => error,
+                };
                 self.counters.dec_established(&endpoint);
                 let remaining_established_connection_ids: Vec<ConnectionId> =
                     connections.keys().cloned().collect();
@@ -756,6 +992,31 @@ where
                             } else {
                                 Ok(())
                             }
+                        })
+                        // Check the gater allows the upgraded connection. Unless the
+                        // transport consults `intercept_secured`, see
+                        // `SharedConnectionGater::intercept_secured`, it is consulted
+                        // here first.
+                        .and_then(|()| {
+                            let gater = match &mut self.gater {
+                                Some(gater) => gater,
+                                None => return Ok(()),
+                            };
+                            if !gater.intercepts_secured()
+                                && !gater.intercept_secured(&obtained_peer_id, &endpoint)
+                            {
+                                Err(PendingConnectionError::DeniedSecured {
+                                    peer_id: obtained_peer_id,
+                                    endpoint: endpoint.clone(),
+                                })
+                            } else if !gater.intercept_upgraded(&obtained_peer_id, &endpoint) {
+                                Err(PendingConnectionError::DeniedUpgraded {
+                                    peer_id: obtained_peer_id,
+                                    endpoint: endpoint.clone(),
+                                })
+                            } else {
+                                Ok(())
+                            }
                         });
 
                     if let Err(error) = error {
@@ -806,12 +1067,17 @@ where
 
                     let (command_sender, command_receiver) =
                         mpsc::channel(self.task_command_buffer_size);
+                    let tracker = ConnectionTracker::new();
                     conns.insert(
                         id,
                         EstablishedConnectionInfo {
                             peer_id: obtained_peer_id,
                             endpoint: endpoint.clone(),
+                            negotiated_protocols: muxer.negotiated_protocols().clone(),
+                            established: time::now(),
+                            tracker: tracker.clone(),
                             sender: command_sender,
+                            duplicate: false,
                         },
                     );
 
@@ -819,6 +1085,8 @@ where
                         muxer,
                         handler.into_handler(&obtained_peer_id, &endpoint),
                         self.substream_upgrade_protocol_override,
+                        self.inbound_stream_limits.clone(),
+                        tracker,
                     );
                     self.spawn(
                         task::new_for_established_connection(
@@ -830,6 +1098,7 @@ where
                         )
                         .boxed(),
                     );
+                    self.resolve_duplicates(obtained_peer_id);
 
                     match self.get(id) {
                         Some(PoolConnection::Established(connection)) => {
@@ -942,6 +1211,11 @@ impl<TInEvent> EstablishedConnection<'_, TInEvent> {
         self.entry.get().peer_id
     }
 
+    /// Returns the protocols negotiated while upgrading the connection.
+    pub fn negotiated_protocols(&self) -> &NegotiatedProtocols {
+        &self.entry.get().negotiated_protocols
+    }
+
     /// Returns the local connection ID.
     pub fn id(&self) -> ConnectionId {
         *self.entry.key()
@@ -985,6 +1259,15 @@ impl<TInEvent> EstablishedConnection<'_, TInEvent> {
     pub fn start_close(mut self) {
         self.entry.get_mut().start_close()
     }
+
+    /// Initiates a drain of the connection. Inbound substreams are no longer
+    /// accepted and the connection is closed gracefully once its handler is
+    /// idle or the given timeout elapsed.
+    ///
+    /// Has no effect if the connection is already closing.
+    pub fn start_drain(mut self, timeout: Duration) {
+        self.entry.get_mut().start_drain(timeout)
+    }
 }
 
 /// Network connection information.
@@ -1226,6 +1509,15 @@ pub struct PoolConfig {
 
     /// The configured override for substream protocol upgrades, if any.
     substream_upgrade_protocol_override: Option<libp2p_core::upgrade::Version>,
+
+    /// The limits on inbound substreams of each connection.
+    inbound_stream_limits: InboundStreamLimits,
+
+    /// The [`ConnectionGater`] to consult while connections are established, if any.
+    connection_gater: Option<SharedConnectionGater>,
+
+    /// How to resolve duplicate connections to the same peer, if at all.
+    duplicate_connections: Option<DuplicateConnectionConfig>,
 }
 
 impl Default for PoolConfig {
@@ -1237,6 +1529,9 @@ impl Default for PoolConfig {
             // By default, addresses of a single connection attempt are dialed in sequence.
             dial_concurrency_factor: NonZeroU8::new(1).expect("1 > 0"),
             substream_upgrade_protocol_override: None,
+            inbound_stream_limits: Default::default(),
+            connection_gater: None,
+            duplicate_connections: None,
         }
     }
 }
@@ -1295,6 +1590,24 @@ impl PoolConfig {
         self.substream_upgrade_protocol_override = Some(v);
         self
     }
+
+    /// Configures the limits on inbound substreams of each connection.
+    pub fn with_inbound_stream_limits(mut self, limits: InboundStreamLimits) -> Self {
+        self.inbound_stream_limits = limits;
+        self
+    }
+
+    /// Configures the [`ConnectionGater`] consulted while connections are established.
+    pub fn with_connection_gater(mut self, gater: SharedConnectionGater) -> Self {
+        self.connection_gater = Some(gater);
+        self
+    }
+
+    /// Configures resolving duplicate connections to the same peer.
+    pub fn with_duplicate_connections(mut self, config: DuplicateConnectionConfig) -> Self {
+        self.duplicate_connections = Some(config);
+        self
+    }
 }
 
 trait EntryExt<'a, K, V> {
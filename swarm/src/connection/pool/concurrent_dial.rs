@@ -24,15 +24,17 @@ use crate::{
 };
 use futures::{
     future::{BoxFuture, Future, FutureExt},
-    ready,
     stream::{FuturesUnordered, StreamExt},
 };
 use libp2p_core::connection::Endpoint;
 use libp2p_core::multiaddr::Protocol;
+use libp2p_core::time::{self, Delay, Instant};
 use std::{
+    collections::VecDeque,
     num::NonZeroU8,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 type Dial<TTrans> = BoxFuture<
@@ -45,7 +47,14 @@ type Dial<TTrans> = BoxFuture<
 
 pub struct ConcurrentDial<TTrans: Transport> {
     dials: FuturesUnordered<Dial<TTrans>>,
-    pending_dials: Box<dyn Iterator<Item = Dial<TTrans>> + Send>,
+    /// The addresses not yet dialed, each with the delay after `start` at
+    /// which it is dialed while other dials are still in progress.
+    pending_dials: VecDeque<(Multiaddr, Duration)>,
+    dial: Box<dyn FnMut(Multiaddr) -> Dial<TTrans> + Send>,
+    concurrency_factor: usize,
+    start: Instant,
+    /// Fires once the delay of the next pending address has elapsed.
+    next_dial: Option<Delay>,
     errors: Vec<(Multiaddr, TransportError<TTrans::Error>)>,
 }
 
@@ -61,11 +70,11 @@ where
     pub(crate) fn new(
         transport: TTrans,
         peer: Option<PeerId>,
-        addresses: impl Iterator<Item = Multiaddr> + Send + 'static,
+        addresses: impl Iterator<Item = (Multiaddr, Duration)>,
         concurrency_factor: NonZeroU8,
         role_override: Endpoint,
     ) -> Self {
-        let mut pending_dials = addresses.map(move |address| match p2p_addr(peer, address) {
+        let dial = move |address| match p2p_addr(peer, address) {
             Ok(address) => {
                 let dial = match role_override {
                     Endpoint::Dialer => transport.clone().dial(address.clone()),
@@ -83,20 +92,47 @@ where
                 Err(TransportError::MultiaddrNotSupported(address)),
             ))
             .boxed(),
-        });
+        };
 
-        let dials = FuturesUnordered::new();
-        while let Some(dial) = pending_dials.next() {
-            dials.push(dial);
-            if dials.len() == concurrency_factor.get() as usize {
-                break;
+        let mut concurrent_dial = Self {
+            dials: FuturesUnordered::new(),
+            pending_dials: addresses.collect(),
+            dial: Box::new(dial),
+            concurrency_factor: concurrency_factor.get() as usize,
+            start: time::now(),
+            next_dial: None,
+            errors: Default::default(),
+        };
+        concurrent_dial.start_dials();
+        concurrent_dial
+    }
+}
+
+impl<TTrans: Transport> ConcurrentDial<TTrans> {
+    /// Dials the pending addresses whose delay has elapsed, as permitted by
+    /// the concurrency factor. If no dial is in progress, the next address
+    /// is dialed regardless of its delay.
+    fn start_dials(&mut self) {
+        while self.dials.len() < self.concurrency_factor {
+            let delay = match self.pending_dials.front() {
+                Some((_, delay)) => *delay,
+                None => return,
+            };
+
+            if !self.dials.is_empty() {
+                if let Some(remaining) = (self.start + delay).checked_duration_since(time::now()) {
+                    if !remaining.is_zero() {
+                        if self.next_dial.is_none() {
+                            self.next_dial = Some(Delay::new(remaining));
+                        }
+                        return;
+                    }
+                }
             }
-        }
 
-        Self {
-            dials,
-            errors: Default::default(),
-            pending_dials: Box::new(pending_dials),
+            let (address, _) = self.pending_dials.pop_front().expect("Checked above.");
+            self.next_dial = None;
+            self.dials.push((self.dial)(address));
         }
     }
 }
@@ -119,20 +155,28 @@ where
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         loop {
-            match ready!(self.dials.poll_next_unpin(cx)) {
-                Some((addr, Ok(output))) => {
+            self.start_dials();
+
+            if let Some(next_dial) = self.next_dial.as_mut() {
+                if next_dial.poll_unpin(cx).is_ready() {
+                    self.next_dial = None;
+                    continue;
+                }
+            }
+
+            match self.dials.poll_next_unpin(cx) {
+                Poll::Ready(Some((addr, Ok(output)))) => {
                     let errors = std::mem::replace(&mut self.errors, vec![]);
                     return Poll::Ready(Ok((addr, output, errors)));
                 }
-                Some((addr, Err(e))) => {
+                Poll::Ready(Some((addr, Err(e)))) => {
                     self.errors.push((addr, e));
-                    if let Some(dial) = self.pending_dials.next() {
-                        self.dials.push(dial)
-                    }
                 }
-                None => {
+                Poll::Ready(None) if self.pending_dials.is_empty() => {
                     return Poll::Ready(Err(std::mem::replace(&mut self.errors, vec![])));
                 }
+                Poll::Ready(None) => {}
+                Poll::Pending => return Poll::Pending,
             }
         }
     }
@@ -163,3 +207,93 @@ fn p2p_addr(peer: Option<PeerId>, addr: Multiaddr) -> Result<Multiaddr, Multiadd
         Ok(addr.with(Protocol::P2p(peer.into())))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future, stream};
+    use libp2p_core::{multiaddr::multiaddr, transport::ListenerEvent};
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    /// A transport whose dials never complete, recording when each address
+    /// is dialed.
+    #[derive(Clone, Default)]
+    struct PendingTransport {
+        dials: Arc<Mutex<Vec<(Multiaddr, Instant)>>>,
+    }
+
+    impl Transport for PendingTransport {
+        type Output = ();
+        type Error = io::Error;
+        type Listener =
+            stream::Pending<Result<ListenerEvent<Self::ListenerUpgrade, io::Error>, io::Error>>;
+        type ListenerUpgrade = future::Pending<Result<(), io::Error>>;
+        type Dial = future::Pending<Result<(), io::Error>>;
+
+        fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<io::Error>> {
+            Err(TransportError::MultiaddrNotSupported(addr))
+        }
+
+        fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<io::Error>> {
+            self.dials.lock().unwrap().push((addr, time::now()));
+            Ok(future::pending())
+        }
+
+        fn dial_as_listener(
+            self,
+            addr: Multiaddr,
+        ) -> Result<Self::Dial, TransportError<io::Error>> {
+            self.dial(addr)
+        }
+
+        fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> {
+            None
+        }
+    }
+
+    #[test]
+    fn dials_are_staggered_by_their_delays() {
+        let transport = PendingTransport::default();
+        let addresses = (0..3u64)
+            .map(|i| {
+                (
+                    multiaddr![Memory(i + 1)],
+                    Duration::from_millis(100).saturating_mul(i as u32),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let start = time::now();
+        let mut dial = ConcurrentDial::new(
+            transport.clone(),
+            None,
+            addresses.clone().into_iter(),
+            NonZeroU8::new(8).unwrap(),
+            Endpoint::Dialer,
+        );
+        block_on(future::poll_fn(|cx| {
+            assert!(dial.poll_unpin(cx).is_pending());
+            if transport.dials.lock().unwrap().len() == addresses.len() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+
+        let dials = transport.dials.lock().unwrap().clone();
+        for ((dialed, at), (addr, delay)) in dials.into_iter().zip(addresses) {
+            assert_eq!(dialed, addr);
+            let elapsed = at - start;
+            assert!(elapsed >= delay, "{} dialed after {:?}", addr, elapsed);
+            assert!(
+                elapsed < delay + Duration::from_millis(90),
+                "{} dialed after {:?}",
+                addr,
+                elapsed
+            );
+        }
+    }
+}
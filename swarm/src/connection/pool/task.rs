@@ -24,10 +24,11 @@
 use super::concurrent_dial::ConcurrentDial;
 use crate::{
     connection::{
-        self, ConnectionError, PendingInboundConnectionError, PendingOutboundConnectionError,
+        self, ConnectionError, InboundStreamLimit, PendingInboundConnectionError,
+        PendingOutboundConnectionError,
     },
     transport::{Transport, TransportError},
-    ConnectionHandler, Multiaddr, PeerId,
+    ConnectionHandler, Multiaddr, PeerId, ProtocolsChange,
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -35,7 +36,7 @@ use futures::{
     SinkExt, StreamExt,
 };
 use libp2p_core::connection::ConnectionId;
-use std::pin::Pin;
+use std::{pin::Pin, time::Duration};
 use void::Void;
 
 /// Commands that can be sent to a task driving an established connection.
@@ -46,6 +47,9 @@ pub enum Command<T> {
     /// Gracefully close the connection (active close) before
     /// terminating the task.
     Close,
+    /// Stop accepting inbound substreams and gracefully close the connection
+    /// once the handler is idle or the given timeout elapsed.
+    Drain(Duration),
 }
 
 #[derive(Debug)]
@@ -79,6 +83,21 @@ pub enum EstablishedConnectionEvent<THandler: ConnectionHandler> {
         peer_id: PeerId,
         new_address: Multiaddr,
     },
+    /// An inbound substream was reset for exceeding a limit.
+    InboundStreamLimitExceeded {
+        id: ConnectionId,
+        peer_id: PeerId,
+        protocol: Option<String>,
+        limit: InboundStreamLimit,
+    },
+    /// The protocols supported by the local node or the remote changed.
+    ProtocolsChange {
+        id: ConnectionId,
+        peer_id: PeerId,
+        /// Whether the protocols of the local node changed.
+        local: bool,
+        change: ProtocolsChange,
+    },
     /// Notify the manager of an event from the connection.
     Notify {
         id: ConnectionId,
@@ -194,21 +213,8 @@ pub async fn new_for_established_connection<THandler>(
         {
             Either::Left((Some(command), _)) => match command {
                 Command::NotifyHandler(event) => connection.inject_event(event),
-                Command::Close => {
-                    command_receiver.close();
-                    let (handler, closing_muxer) = connection.close();
-
-                    let error = closing_muxer.await.err().map(ConnectionError::IO);
-                    let _ = events
-                        .send(EstablishedConnectionEvent::Closed {
-                            id: connection_id,
-                            peer_id,
-                            error,
-                            handler,
-                        })
-                        .await;
-                    return;
-                }
+                Command::Drain(timeout) => connection.start_drain(timeout),
+                Command::Close => break,
             },
 
             // The manager has disappeared; abort.
@@ -234,6 +240,37 @@ pub async fn new_for_established_connection<THandler>(
                             })
                             .await;
                     }
+                    Ok(connection::Event::InboundStreamLimitExceeded { protocol, limit }) => {
+                        let _ = events
+                            .send(EstablishedConnectionEvent::InboundStreamLimitExceeded {
+                                id: connection_id,
+                                peer_id,
+                                protocol,
+                                limit,
+                            })
+                            .await;
+                    }
+                    Ok(connection::Event::LocalProtocolsChange(change)) => {
+                        let _ = events
+                            .send(EstablishedConnectionEvent::ProtocolsChange {
+                                id: connection_id,
+                                peer_id,
+                                local: true,
+                                change,
+                            })
+                            .await;
+                    }
+                    Ok(connection::Event::RemoteProtocolsChange(change)) => {
+                        let _ = events
+                            .send(EstablishedConnectionEvent::ProtocolsChange {
+                                id: connection_id,
+                                peer_id,
+                                local: false,
+                                change,
+                            })
+                            .await;
+                    }
+                    Ok(connection::Event::Drained) => break,
                     Err(error) => {
                         command_receiver.close();
                         let (handler, _closing_muxer) = connection.close();
@@ -252,4 +289,17 @@ pub async fn new_for_established_connection<THandler>(
             }
         }
     }
+
+    command_receiver.close();
+    let (handler, closing_muxer) = connection.close();
+
+    let error = closing_muxer.await.err().map(ConnectionError::IO);
+    let _ = events
+        .send(EstablishedConnectionEvent::Closed {
+            id: connection_id,
+            peer_id,
+            error,
+            handler,
+        })
+        .await;
 }
@@ -0,0 +1,274 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::{multiaddr::Protocol, Multiaddr};
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+/// An [`AddressRanking`] decides in which order and at which pace the
+/// addresses of an outbound connection attempt are dialed.
+///
+/// The addresses returned by [`AddressRanking::rank`] are dialed in the
+/// returned order, each no earlier than its delay after the start of the
+/// connection attempt, and never more than the dial concurrency factor at
+/// once. Once no dial is in progress, the next address is dialed right away,
+/// regardless of its delay.
+///
+/// The strategy used by default is [`DefaultAddressRanking`]. It can be
+/// configured via [`SwarmBuilder::address_ranking`](crate::SwarmBuilder::address_ranking)
+/// and overridden per dial via
+/// [`WithPeerId::override_address_ranking`](crate::dial_opts::WithPeerId::override_address_ranking).
+pub trait AddressRanking: Send + Sync + 'static {
+    /// Orders the given addresses of a peer and assigns each a delay,
+    /// relative to the start of the connection attempt.
+    ///
+    /// `local_addrs` are the addresses the local node is listening on.
+    fn rank(
+        &self,
+        addresses: Vec<Multiaddr>,
+        local_addrs: &[Multiaddr],
+    ) -> Vec<(Multiaddr, Duration)>;
+}
+
+/// The [`AddressRanking`] used by default.
+///
+/// Addresses are ranked, from first to last, as follows:
+///
+///   1. loopback addresses,
+///   2. private and link-local addresses in the same subnet as one of the
+///      local addresses, i.e. sharing the first 24 bits for IPv4 and the
+///      first 64 bits for IPv6,
+///   3. all other direct addresses,
+///   4. relayed, i.e. `/p2p-circuit`, addresses.
+///
+/// Within each rank, IPv6 and IPv4 addresses alternate, starting with IPv6,
+/// as recommended by [RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305).
+/// Consecutive addresses are staggered by the attempt delay. Relayed
+/// addresses are further delayed by the relay delay.
+#[derive(Debug, Clone)]
+pub struct DefaultAddressRanking {
+    attempt_delay: Duration,
+    relay_delay: Duration,
+}
+
+impl DefaultAddressRanking {
+    /// Sets the delay between consecutive dials. Defaults to 250 milliseconds.
+    pub fn with_attempt_delay(mut self, delay: Duration) -> Self {
+        self.attempt_delay = delay;
+        self
+    }
+
+    /// Sets the additional delay of relayed addresses. Defaults to 500
+    /// milliseconds.
+    pub fn with_relay_delay(mut self, delay: Duration) -> Self {
+        self.relay_delay = delay;
+        self
+    }
+}
+
+impl Default for DefaultAddressRanking {
+    fn default() -> Self {
+        DefaultAddressRanking {
+            attempt_delay: Duration::from_millis(250),
+            relay_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl AddressRanking for DefaultAddressRanking {
+    fn rank(
+        &self,
+        addresses: Vec<Multiaddr>,
+        local_addrs: &[Multiaddr],
+    ) -> Vec<(Multiaddr, Duration)> {
+        let mut ranks: [Vec<Multiaddr>; 4] = Default::default();
+        for addr in addresses {
+            let rank = match (is_relayed(&addr), ip(&addr)) {
+                (true, _) => Rank::Relayed,
+                (false, Some(remote)) if remote.is_loopback() => Rank::Loopback,
+                (false, Some(remote))
+                    if remote.is_private()
+                        && local_addrs
+                            .iter()
+                            .any(|l| ip(l).map_or(false, |l| remote.same_subnet(&l))) =>
+                {
+                    Rank::LocalSubnet
+                }
+                _ => Rank::Direct,
+            };
+            ranks[rank as usize].push(addr);
+        }
+
+        let mut ranked = Vec::new();
+        for (rank, addrs) in ranks.iter_mut().enumerate() {
+            let delay = if rank == Rank::Relayed as usize {
+                self.relay_delay
+            } else {
+                Duration::ZERO
+            };
+            for addr in interleave_families(std::mem::take(addrs)) {
+                let n = u32::try_from(ranked.len()).unwrap_or(u32::MAX);
+                ranked.push((addr, self.attempt_delay.saturating_mul(n) + delay));
+            }
+        }
+        ranked
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Rank {
+    Loopback = 0,
+    LocalSubnet = 1,
+    Direct = 2,
+    Relayed = 3,
+}
+
+enum Ip {
+    V4(Ipv4Addr),
+    V6(Ipv6Addr),
+}
+
+impl Ip {
+    fn is_loopback(&self) -> bool {
+        match self {
+            Ip::V4(ip) => ip.is_loopback(),
+            Ip::V6(ip) => ip.is_loopback(),
+        }
+    }
+
+    fn is_private(&self) -> bool {
+        match self {
+            Ip::V4(ip) => ip.is_private() || ip.is_link_local(),
+            // Unique local (fc00::/7) and link-local (fe80::/10) addresses.
+            Ip::V6(ip) => {
+                (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80
+            }
+        }
+    }
+
+    fn same_subnet(&self, other: &Ip) -> bool {
+        match (self, other) {
+            (Ip::V4(a), Ip::V4(b)) => a.octets()[..3] == b.octets()[..3],
+            (Ip::V6(a), Ip::V6(b)) => a.segments()[..4] == b.segments()[..4],
+            _ => false,
+        }
+    }
+}
+
+fn ip(addr: &Multiaddr) -> Option<Ip> {
+    match addr.iter().next()? {
+        Protocol::Ip4(ip) => Some(Ip::V4(ip)),
+        Protocol::Ip6(ip) => Some(Ip::V6(ip)),
+        _ => None,
+    }
+}
+
+fn is_relayed(addr: &Multiaddr) -> bool {
+    addr.iter().any(|p| p == Protocol::P2pCircuit)
+}
+
+/// Alternates between IPv6 and IPv4 addresses, starting with IPv6, followed
+/// by all other addresses.
+fn interleave_families(addrs: Vec<Multiaddr>) -> Vec<Multiaddr> {
+    let mut v6 = Vec::new();
+    let mut v4 = Vec::new();
+    let mut other = Vec::new();
+    for addr in addrs {
+        match ip(&addr) {
+            Some(Ip::V6(_)) => v6.push(addr),
+            Some(Ip::V4(_)) => v4.push(addr),
+            None => other.push(addr),
+        }
+    }
+
+    let mut v6 = v6.into_iter();
+    let mut v4 = v4.into_iter();
+    let mut interleaved = Vec::new();
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => break,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+    interleaved.extend(other);
+    interleaved
+}
+
+/// An [`AddressRanking`] overriding the configured one for a single dial.
+#[derive(Clone)]
+pub(crate) struct RankingOverride(pub(crate) Arc<dyn AddressRanking>);
+
+impl fmt::Debug for RankingOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RankingOverride").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_and_staggers_addresses() {
+        let ranking = DefaultAddressRanking::default()
+            .with_attempt_delay(Duration::from_millis(10))
+            .with_relay_delay(Duration::from_millis(100));
+        let local_addrs = ["/ip4/192.168.1.2/tcp/4001".parse().unwrap()];
+        let addresses = [
+            "/ip4/1.2.3.4/tcp/4001/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC/p2p-circuit",
+            "/ip4/1.2.3.4/tcp/4001",
+            "/ip4/192.168.2.3/tcp/4001",
+            "/ip6/2001:db8::1/tcp/4001",
+            "/dns4/example.com/tcp/4001",
+            "/ip4/192.168.1.3/tcp/4001",
+            "/ip6/::1/tcp/4001",
+        ]
+        .iter()
+        .map(|a| a.parse().unwrap())
+        .collect();
+
+        let ranked = ranking
+            .rank(addresses, &local_addrs)
+            .into_iter()
+            .map(|(a, d)| (a.to_string(), d.as_millis()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ranked,
+            vec![
+                ("/ip6/::1/tcp/4001".into(), 0),
+                ("/ip4/192.168.1.3/tcp/4001".into(), 10),
+                ("/ip6/2001:db8::1/tcp/4001".into(), 20),
+                ("/ip4/1.2.3.4/tcp/4001".into(), 30),
+                ("/ip4/192.168.2.3/tcp/4001".into(), 40),
+                ("/dns4/example.com/tcp/4001".into(), 50),
+                (
+                    "/ip4/1.2.3.4/tcp/4001/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC/p2p-circuit"
+                        .into(),
+                    160
+                ),
+            ]
+        );
+    }
+}
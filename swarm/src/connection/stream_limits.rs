@@ -0,0 +1,410 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::connection::tracker::ConnectionTracker;
+use crate::upgrade::{InboundUpgradeSend, SendWrapper, UpgradeInfoSend};
+use crate::NegotiatedSubstream;
+use futures::{future::BoxFuture, prelude::*};
+use libp2p_core::connection::Endpoint;
+use libp2p_core::upgrade::{self, ProtocolName};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// Limits on the inbound substreams of a single connection.
+///
+/// A substream is _negotiating_ from the moment the remote opens it until the
+/// inbound upgrade of the [`ConnectionHandler`](crate::ConnectionHandler)
+/// completed. A substream is _open_ from the moment the remote opens it until
+/// it is dropped. Limits per protocol apply once the remote selected the
+/// protocol of the substream.
+///
+/// Substreams exceeding a limit are reset and reported via
+/// [`SwarmEvent::InboundStreamLimitExceeded`](crate::SwarmEvent::InboundStreamLimitExceeded).
+/// Substreams exceeding a limit of their protocol are in addition reported to
+/// the [`ConnectionHandler`](crate::ConnectionHandler) as
+/// [`ConnectionHandlerUpgrErr::LimitExceeded`](crate::ConnectionHandlerUpgrErr::LimitExceeded).
+///
+/// By default, all limits are disabled.
+#[derive(Debug, Clone, Default)]
+pub struct InboundStreamLimits {
+    max_negotiating: Option<usize>,
+    max_negotiating_per_protocol: Option<usize>,
+    max_open: Option<usize>,
+    max_open_per_protocol: Option<usize>,
+    protocols: HashMap<String, ProtocolLimits>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ProtocolLimits {
+    max_negotiating: Option<usize>,
+    max_open: Option<usize>,
+}
+
+impl InboundStreamLimits {
+    /// Configures the maximum number of concurrently negotiating inbound
+    /// substreams.
+    pub fn with_max_negotiating(mut self, limit: Option<usize>) -> Self {
+        self.max_negotiating = limit;
+        self
+    }
+
+    /// Configures the maximum number of concurrently negotiating inbound
+    /// substreams of each protocol.
+    pub fn with_max_negotiating_per_protocol(mut self, limit: Option<usize>) -> Self {
+        self.max_negotiating_per_protocol = limit;
+        self
+    }
+
+    /// Configures the maximum number of concurrently open inbound substreams.
+    pub fn with_max_open(mut self, limit: Option<usize>) -> Self {
+        self.max_open = limit;
+        self
+    }
+
+    /// Configures the maximum number of concurrently open inbound substreams
+    /// of each protocol.
+    pub fn with_max_open_per_protocol(mut self, limit: Option<usize>) -> Self {
+        self.max_open_per_protocol = limit;
+        self
+    }
+
+    /// Configures the maximum number of concurrently negotiating inbound
+    /// substreams of the given protocol, overriding
+    /// [`InboundStreamLimits::with_max_negotiating_per_protocol`].
+    pub fn with_protocol_max_negotiating(
+        mut self,
+        protocol: impl Into<String>,
+        limit: usize,
+    ) -> Self {
+        self.protocols
+            .entry(protocol.into())
+            .or_default()
+            .max_negotiating = Some(limit);
+        self
+    }
+
+    /// Configures the maximum number of concurrently open inbound substreams
+    /// of the given protocol, overriding
+    /// [`InboundStreamLimits::with_max_open_per_protocol`].
+    pub fn with_protocol_max_open(mut self, protocol: impl Into<String>, limit: usize) -> Self {
+        self.protocols.entry(protocol.into()).or_default().max_open = Some(limit);
+        self
+    }
+
+    fn max_negotiating_of(&self, protocol: &str) -> Option<usize> {
+        self.protocols
+            .get(protocol)
+            .and_then(|p| p.max_negotiating)
+            .or(self.max_negotiating_per_protocol)
+    }
+
+    fn max_open_of(&self, protocol: &str) -> Option<usize> {
+        self.protocols
+            .get(protocol)
+            .and_then(|p| p.max_open)
+            .or(self.max_open_per_protocol)
+    }
+}
+
+/// A limit of [`InboundStreamLimits`] that an inbound substream exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundStreamLimit {
+    /// The maximum number of concurrently negotiating inbound substreams.
+    Negotiating(usize),
+    /// The maximum number of concurrently negotiating inbound substreams of
+    /// the protocol.
+    NegotiatingPerProtocol(usize),
+    /// The maximum number of concurrently open inbound substreams.
+    Open(usize),
+    /// The maximum number of concurrently open inbound substreams of the
+    /// protocol.
+    OpenPerProtocol(usize),
+}
+
+impl fmt::Display for InboundStreamLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InboundStreamLimit::Negotiating(limit) => {
+                write!(f, "{} negotiating inbound substreams", limit)
+            }
+            InboundStreamLimit::NegotiatingPerProtocol(limit) => {
+                write!(f, "{} negotiating inbound substreams per protocol", limit)
+            }
+            InboundStreamLimit::Open(limit) => write!(f, "{} open inbound substreams", limit),
+            InboundStreamLimit::OpenPerProtocol(limit) => {
+                write!(f, "{} open inbound substreams per protocol", limit)
+            }
+        }
+    }
+}
+
+/// Enforces the [`InboundStreamLimits`] of a single connection.
+#[derive(Debug)]
+pub(crate) struct InboundStreams {
+    limits: Arc<InboundStreamLimits>,
+    tracker: ConnectionTracker,
+    /// The number of substreams negotiating each protocol.
+    negotiating: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl InboundStreams {
+    pub(crate) fn new(limits: Arc<InboundStreamLimits>, tracker: ConnectionTracker) -> Self {
+        InboundStreams {
+            limits,
+            tracker,
+            negotiating: Default::default(),
+        }
+    }
+
+    /// Checks the limits across all protocols for a new inbound substream,
+    /// given the number of inbound substreams currently negotiating.
+    pub(crate) fn on_inbound(
+        &self,
+        negotiating: usize,
+    ) -> Result<InboundStream, InboundStreamLimit> {
+        let id = self.tracker.take_latest(Endpoint::Listener);
+
+        if let Some(max) = self.limits.max_negotiating {
+            if negotiating >= max {
+                return Err(InboundStreamLimit::Negotiating(max));
+            }
+        }
+        if let Some(max) = self.limits.max_open {
+            // The new substream is part of the open substreams.
+            if self.tracker.num_open(Endpoint::Listener, None) > max {
+                return Err(InboundStreamLimit::Open(max));
+            }
+        }
+
+        Ok(InboundStream {
+            id,
+            limits: self.limits.clone(),
+            tracker: self.tracker.clone(),
+            negotiating: self.negotiating.clone(),
+        })
+    }
+}
+
+/// A new inbound substream, subject to the limits per protocol.
+pub(crate) struct InboundStream {
+    /// The muxer substream ID.
+    id: Option<usize>,
+    limits: Arc<InboundStreamLimits>,
+    tracker: ConnectionTracker,
+    negotiating: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl InboundStream {
+    /// Checks the limits of the selected protocol, counting the substream as
+    /// negotiating the protocol for as long as the returned guard is alive.
+    fn on_protocol(&self, protocol: &str) -> Result<NegotiatingGuard, InboundStreamLimit> {
+        let mut negotiating = self.negotiating.lock().expect("Mutex not to be poisoned.");
+
+        if let Some(max) = self.limits.max_negotiating_of(protocol) {
+            if negotiating.get(protocol).copied().unwrap_or_default() >= max {
+                return Err(InboundStreamLimit::NegotiatingPerProtocol(max));
+            }
+        }
+        if let Some(max) = self.limits.max_open_of(protocol) {
+            if self.tracker.num_open(Endpoint::Listener, Some(protocol)) >= max {
+                return Err(InboundStreamLimit::OpenPerProtocol(max));
+            }
+        }
+        if let Some(id) = self.id {
+            self.tracker.set_protocol(id, protocol);
+        }
+
+        *negotiating.entry(protocol.to_owned()).or_default() += 1;
+        Ok(NegotiatingGuard {
+            protocol: protocol.to_owned(),
+            negotiating: self.negotiating.clone(),
+        })
+    }
+}
+
+/// Counts a substream as negotiating its protocol until dropped.
+struct NegotiatingGuard {
+    protocol: String,
+    negotiating: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Drop for NegotiatingGuard {
+    fn drop(&mut self) {
+        let mut negotiating = self.negotiating.lock().expect("Mutex not to be poisoned.");
+        if let Some(n) = negotiating.get_mut(&self.protocol) {
+            *n -= 1;
+            if *n == 0 {
+                negotiating.remove(&self.protocol);
+            }
+        }
+    }
+}
+
+/// An inbound upgrade enforcing the limits per protocol before applying the
+/// upgrade of the [`ConnectionHandler`](crate::ConnectionHandler).
+pub(crate) struct LimitedUpgrade<TUpgrade> {
+    pub(crate) upgrade: SendWrapper<TUpgrade>,
+    pub(crate) stream: InboundStream,
+}
+
+/// Error of a [`LimitedUpgrade`].
+#[derive(Debug)]
+pub(crate) enum LimitedUpgradeError<TErr> {
+    /// The selected protocol exceeded one of its limits.
+    Limit {
+        protocol: String,
+        limit: InboundStreamLimit,
+    },
+    /// The upgrade of the handler failed.
+    Upgrade(TErr),
+}
+
+impl<TUpgrade: UpgradeInfoSend> upgrade::UpgradeInfo for LimitedUpgrade<TUpgrade> {
+    type Info = TUpgrade::Info;
+    type InfoIter = TUpgrade::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        upgrade::UpgradeInfo::protocol_info(&self.upgrade)
+    }
+}
+
+impl<TUpgrade: InboundUpgradeSend> upgrade::InboundUpgrade<NegotiatedSubstream>
+    for LimitedUpgrade<TUpgrade>
+{
+    type Output = TUpgrade::Output;
+    type Error = LimitedUpgradeError<TUpgrade::Error>;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: NegotiatedSubstream, info: Self::Info) -> Self::Future {
+        let protocol = String::from_utf8_lossy(info.protocol_name()).into_owned();
+        match self.stream.on_protocol(&protocol) {
+            // Dropping the substream resets it.
+            Err(limit) => {
+                future::ready(Err(LimitedUpgradeError::Limit { protocol, limit })).boxed()
+            }
+            Ok(guard) => {
+                let upgrade = upgrade::InboundUpgrade::upgrade_inbound(self.upgrade, socket, info);
+                async move {
+                    let result = upgrade.await.map_err(LimitedUpgradeError::Upgrade);
+                    drop(guard);
+                    result
+                }
+                .boxed()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn streams(limits: InboundStreamLimits) -> InboundStreams {
+        InboundStreams::new(Arc::new(limits), ConnectionTracker::new())
+    }
+
+    /// Simulates the muxer reporting a new inbound substream.
+    fn open(streams: &InboundStreams, id: usize) {
+        streams.tracker.on_open(id, Endpoint::Listener);
+    }
+
+    #[test]
+    fn no_limits_by_default() {
+        let streams = streams(InboundStreamLimits::default());
+
+        let mut negotiating = Vec::new();
+        for id in 0..1000 {
+            open(&streams, id);
+            let stream = streams.on_inbound(id).unwrap();
+            negotiating.push(stream.on_protocol("/a").unwrap());
+        }
+    }
+
+    #[test]
+    fn limits_negotiating() {
+        let streams = streams(InboundStreamLimits::default().with_max_negotiating(Some(2)));
+
+        assert!(streams.on_inbound(1).is_ok());
+        assert_eq!(
+            streams.on_inbound(2).err(),
+            Some(InboundStreamLimit::Negotiating(2))
+        );
+    }
+
+    #[test]
+    fn limits_negotiating_per_protocol() {
+        let streams = streams(
+            InboundStreamLimits::default()
+                .with_max_negotiating_per_protocol(Some(1))
+                .with_protocol_max_negotiating("/b", 2),
+        );
+
+        let a = streams.on_inbound(0).unwrap().on_protocol("/a").unwrap();
+        assert_eq!(
+            streams.on_inbound(1).unwrap().on_protocol("/a").err(),
+            Some(InboundStreamLimit::NegotiatingPerProtocol(1))
+        );
+
+        let _b1 = streams.on_inbound(1).unwrap().on_protocol("/b").unwrap();
+        let _b2 = streams.on_inbound(2).unwrap().on_protocol("/b").unwrap();
+        assert_eq!(
+            streams.on_inbound(3).unwrap().on_protocol("/b").err(),
+            Some(InboundStreamLimit::NegotiatingPerProtocol(2))
+        );
+
+        // Once negotiated, the substream no longer counts towards the limit.
+        drop(a);
+        assert!(streams.on_inbound(3).unwrap().on_protocol("/a").is_ok());
+    }
+
+    #[test]
+    fn limits_open() {
+        let streams = streams(
+            InboundStreamLimits::default()
+                .with_max_open(Some(2))
+                .with_protocol_max_open("/a", 1),
+        );
+
+        open(&streams, 0);
+        let stream = streams.on_inbound(0).unwrap();
+        drop(stream.on_protocol("/a").unwrap());
+
+        // The first substream is still open after its negotiation completed.
+        open(&streams, 1);
+        assert_eq!(
+            streams.on_inbound(0).unwrap().on_protocol("/a").err(),
+            Some(InboundStreamLimit::OpenPerProtocol(1))
+        );
+        // The rejected substream is reset.
+        streams.tracker.on_close(1);
+
+        open(&streams, 2);
+        assert!(streams.on_inbound(0).unwrap().on_protocol("/b").is_ok());
+
+        open(&streams, 3);
+        assert_eq!(
+            streams.on_inbound(0).err(),
+            Some(InboundStreamLimit::Open(2))
+        );
+    }
+}
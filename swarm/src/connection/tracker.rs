@@ -0,0 +1,431 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::handler::KeepAlive;
+use crate::upgrade::{OutboundUpgradeSend, SendWrapper, UpgradeInfoSend};
+use crate::NegotiatedSubstream;
+use futures::ready;
+use libp2p_core::connection::{ConnectedPoint, ConnectionId, Endpoint, NegotiatedProtocols};
+use libp2p_core::muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent};
+use libp2p_core::time::{self, Instant};
+use libp2p_core::upgrade::{self, ProtocolName};
+use libp2p_core::PeerId;
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Information about an established connection, as returned by
+/// [`Swarm::connections`](crate::Swarm::connections).
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    id: ConnectionId,
+    peer_id: PeerId,
+    endpoint: ConnectedPoint,
+    negotiated_protocols: NegotiatedProtocols,
+    established: Instant,
+    keep_alive: KeepAlive,
+    last_activity: Instant,
+    inbound_substreams: HashMap<String, usize>,
+    outbound_substreams: HashMap<String, usize>,
+}
+
+impl ConnectionInfo {
+    /// The ID of the connection.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// The remote peer of the connection.
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    /// The endpoint of the connection.
+    pub fn endpoint(&self) -> &ConnectedPoint {
+        &self.endpoint
+    }
+
+    /// The security and stream multiplexer protocols negotiated while
+    /// upgrading the connection.
+    pub fn negotiated_protocols(&self) -> &NegotiatedProtocols {
+        &self.negotiated_protocols
+    }
+
+    /// When the connection was established.
+    pub fn established(&self) -> Instant {
+        self.established
+    }
+
+    /// The [`KeepAlive`] last returned by the
+    /// [`ConnectionHandler`](crate::ConnectionHandler) of the connection.
+    pub fn keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    /// When a substream of the connection was last opened, closed, read
+    /// from or written to, or the handler last received or emitted an event.
+    pub fn last_activity(&self) -> Instant {
+        self.last_activity
+    }
+
+    /// The number of open inbound substreams, by negotiated protocol.
+    ///
+    /// Substreams still negotiating their protocol are not included.
+    pub fn inbound_substreams(&self) -> &HashMap<String, usize> {
+        &self.inbound_substreams
+    }
+
+    /// The number of open outbound substreams, by negotiated protocol.
+    ///
+    /// Substreams still negotiating their protocol are not included.
+    pub fn outbound_substreams(&self) -> &HashMap<String, usize> {
+        &self.outbound_substreams
+    }
+}
+
+/// Tracks the substreams and the activity of a single connection.
+///
+/// Shared between the task of the connection, which records, and the
+/// [`Pool`](super::pool::Pool), which reports.
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectionTracker {
+    state: Arc<Mutex<State>>,
+    activity: Arc<Activity>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// The substreams alive in the muxer, by muxer substream ID.
+    substreams: HashMap<usize, SubstreamState>,
+    /// The muxer substream ID of the latest inbound substream.
+    latest_inbound: Option<usize>,
+    /// The muxer substream ID of the latest outbound substream.
+    latest_outbound: Option<usize>,
+    keep_alive: KeepAlive,
+}
+
+/// The last activity of a connection.
+///
+/// Recorded on every read from and write to a substream, thus updated without
+/// locking.
+#[derive(Debug)]
+struct Activity {
+    /// The reference point of `last`.
+    start: Instant,
+    /// The last activity, in microseconds since `start`.
+    last: AtomicU64,
+}
+
+impl Activity {
+    fn record(&self) {
+        let elapsed = self.start.elapsed().as_micros() as u64;
+        self.last.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.start + Duration::from_micros(self.last.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug)]
+struct SubstreamState {
+    endpoint: Endpoint,
+    /// The negotiated protocol, once selected.
+    protocol: Option<String>,
+}
+
+impl ConnectionTracker {
+    pub(crate) fn new() -> Self {
+        ConnectionTracker {
+            state: Arc::new(Mutex::new(State {
+                substreams: HashMap::new(),
+                latest_inbound: None,
+                latest_outbound: None,
+                keep_alive: KeepAlive::Yes,
+            })),
+            activity: Arc::new(Activity {
+                start: time::now(),
+                last: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("Mutex not to be poisoned.")
+    }
+
+    /// Wraps the given muxer to track its substreams.
+    pub(crate) fn wrap_muxer(&self, muxer: StreamMuxerBox) -> StreamMuxerBox {
+        StreamMuxerBox::new(TrackingMuxer {
+            inner: muxer,
+            tracker: self.clone(),
+        })
+    }
+
+    /// Takes the muxer substream ID of the substream of the given endpoint
+    /// most recently opened.
+    pub(crate) fn take_latest(&self, endpoint: Endpoint) -> Option<usize> {
+        let mut state = self.lock();
+        match endpoint {
+            Endpoint::Listener => state.latest_inbound.take(),
+            Endpoint::Dialer => state.latest_outbound.take(),
+        }
+    }
+
+    /// Records the negotiated protocol of the given substream.
+    pub(crate) fn set_protocol(&self, id: usize, protocol: &str) {
+        if let Some(substream) = self.lock().substreams.get_mut(&id) {
+            substream.protocol = Some(protocol.to_owned());
+        }
+    }
+
+    /// The number of open substreams of the given endpoint, optionally only
+    /// those of the given protocol.
+    pub(crate) fn num_open(&self, endpoint: Endpoint, protocol: Option<&str>) -> usize {
+        self.lock()
+            .substreams
+            .values()
+            .filter(|s| s.endpoint == endpoint)
+            .filter(|s| protocol.is_none() || s.protocol.as_deref() == protocol)
+            .count()
+    }
+
+    pub(crate) fn set_keep_alive(&self, keep_alive: KeepAlive) {
+        self.lock().keep_alive = keep_alive;
+    }
+
+    pub(crate) fn record_activity(&self) {
+        self.activity.record();
+    }
+
+    pub(crate) fn info(
+        &self,
+        id: ConnectionId,
+        peer_id: PeerId,
+        endpoint: ConnectedPoint,
+        negotiated_protocols: NegotiatedProtocols,
+        established: Instant,
+    ) -> ConnectionInfo {
+        let state = self.lock();
+        let mut inbound_substreams = HashMap::new();
+        let mut outbound_substreams = HashMap::new();
+        for substream in state.substreams.values() {
+            if let Some(protocol) = &substream.protocol {
+                let substreams = match substream.endpoint {
+                    Endpoint::Listener => &mut inbound_substreams,
+                    Endpoint::Dialer => &mut outbound_substreams,
+                };
+                *substreams.entry(protocol.clone()).or_default() += 1;
+            }
+        }
+        ConnectionInfo {
+            id,
+            peer_id,
+            endpoint,
+            negotiated_protocols,
+            established,
+            keep_alive: state.keep_alive,
+            last_activity: self.activity.last(),
+            inbound_substreams,
+            outbound_substreams,
+        }
+    }
+
+    /// Records a new substream opened by the muxer.
+    pub(crate) fn on_open(&self, id: usize, endpoint: Endpoint) {
+        let mut state = self.lock();
+        state.substreams.insert(
+            id,
+            SubstreamState {
+                endpoint,
+                protocol: None,
+            },
+        );
+        match endpoint {
+            Endpoint::Listener => state.latest_inbound = Some(id),
+            Endpoint::Dialer => state.latest_outbound = Some(id),
+        }
+        drop(state);
+        self.record_activity();
+    }
+
+    /// Records a substream destroyed by the muxer.
+    pub(crate) fn on_close(&self, id: usize) {
+        self.lock().substreams.remove(&id);
+        self.record_activity();
+    }
+}
+
+/// An outbound upgrade recording the negotiated protocol of its substream
+/// before applying the upgrade of the [`ConnectionHandler`](crate::ConnectionHandler).
+pub(crate) struct TrackedUpgrade<TUpgrade> {
+    pub(crate) upgrade: SendWrapper<TUpgrade>,
+    /// The muxer substream ID of the substream.
+    pub(crate) id: Option<usize>,
+    pub(crate) tracker: ConnectionTracker,
+}
+
+impl<TUpgrade: UpgradeInfoSend> upgrade::UpgradeInfo for TrackedUpgrade<TUpgrade> {
+    type Info = TUpgrade::Info;
+    type InfoIter = TUpgrade::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        upgrade::UpgradeInfo::protocol_info(&self.upgrade)
+    }
+}
+
+impl<TUpgrade: OutboundUpgradeSend> upgrade::OutboundUpgrade<NegotiatedSubstream>
+    for TrackedUpgrade<TUpgrade>
+{
+    type Output = TUpgrade::Output;
+    type Error = TUpgrade::Error;
+    type Future = TUpgrade::Future;
+
+    fn upgrade_outbound(self, socket: NegotiatedSubstream, info: Self::Info) -> Self::Future {
+        if let Some(id) = self.id {
+            self.tracker
+                .set_protocol(id, &String::from_utf8_lossy(info.protocol_name()));
+        }
+        upgrade::OutboundUpgrade::upgrade_outbound(self.upgrade, socket, info)
+    }
+}
+
+/// A [`StreamMuxer`] recording the substreams of the wrapped muxer with a
+/// [`ConnectionTracker`].
+struct TrackingMuxer {
+    inner: StreamMuxerBox,
+    tracker: ConnectionTracker,
+}
+
+impl StreamMuxer for TrackingMuxer {
+    type Substream = <StreamMuxerBox as StreamMuxer>::Substream;
+    type OutboundSubstream = <StreamMuxerBox as StreamMuxer>::OutboundSubstream;
+    type Error = io::Error;
+
+    fn poll_event(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent<Self::Substream>, Self::Error>> {
+        let event = ready!(self.inner.poll_event(cx))?;
+        if let StreamMuxerEvent::InboundSubstream(id) = &event {
+            self.tracker.on_open(*id, Endpoint::Listener);
+        }
+        Poll::Ready(Ok(event))
+    }
+
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.inner.open_outbound()
+    }
+
+    fn poll_outbound(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::OutboundSubstream,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let id = ready!(self.inner.poll_outbound(cx, s))?;
+        self.tracker.on_open(id, Endpoint::Dialer);
+        Poll::Ready(Ok(id))
+    }
+
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.inner.destroy_outbound(s)
+    }
+
+    fn read_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        let n = ready!(self.inner.read_substream(cx, s, buf))?;
+        if n > 0 {
+            self.tracker.record_activity();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn write_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        let n = ready!(self.inner.write_substream(cx, s, buf))?;
+        if n > 0 {
+            self.tracker.record_activity();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn flush_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.flush_substream(cx, s)
+    }
+
+    fn shutdown_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.shutdown_substream(cx, s)
+    }
+
+    fn destroy_substream(&self, s: Self::Substream) {
+        self.tracker.on_close(s);
+        self.inner.destroy_substream(s)
+    }
+
+    fn close(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.close(cx)
+    }
+
+    fn flush_all(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.flush_all(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_activity() {
+        let tracker = ConnectionTracker::new();
+        let start = tracker.activity.last();
+
+        std::thread::sleep(Duration::from_millis(10));
+        tracker.record_activity();
+        let last = tracker.activity.last();
+        assert!(last >= start + Duration::from_millis(10));
+
+        tracker.on_open(0, Endpoint::Listener);
+        assert!(tracker.activity.last() >= last);
+    }
+}
@@ -19,9 +19,12 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::connection::RankingOverride;
+use crate::AddressRanking;
 use libp2p_core::connection::Endpoint;
 use libp2p_core::{Multiaddr, PeerId};
 use std::num::NonZeroU8;
+use std::sync::Arc;
 
 /// Options to configure a dial to a known or unknown peer.
 ///
@@ -54,6 +57,9 @@ impl DialOpts {
             condition: Default::default(),
             role_override: Endpoint::Dialer,
             dial_concurrency_factor_override: Default::default(),
+            address_ranking_override: None,
+            bypass_backoff: false,
+            priority: Default::default(),
         }
     }
 
@@ -112,6 +118,9 @@ pub struct WithPeerId {
     pub(crate) condition: PeerCondition,
     pub(crate) role_override: Endpoint,
     pub(crate) dial_concurrency_factor_override: Option<NonZeroU8>,
+    pub(crate) address_ranking_override: Option<RankingOverride>,
+    pub(crate) bypass_backoff: bool,
+    pub(crate) priority: DialPriority,
 }
 
 impl WithPeerId {
@@ -128,6 +137,13 @@ impl WithPeerId {
         self
     }
 
+    /// Override the [`AddressRanking`] configured via
+    /// [`SwarmBuilder::address_ranking`](crate::SwarmBuilder::address_ranking).
+    pub fn override_address_ranking(mut self, ranking: impl AddressRanking) -> Self {
+        self.address_ranking_override = Some(RankingOverride(Arc::new(ranking)));
+        self
+    }
+
     /// Specify a set of addresses to be used to dial the known peer.
     pub fn addresses(self, addresses: Vec<Multiaddr>) -> WithPeerIdWithAddresses {
         WithPeerIdWithAddresses {
@@ -137,6 +153,9 @@ impl WithPeerId {
             extend_addresses_through_behaviour: false,
             role_override: self.role_override,
             dial_concurrency_factor_override: self.dial_concurrency_factor_override,
+            address_ranking_override: self.address_ranking_override,
+            bypass_backoff: self.bypass_backoff,
+            priority: self.priority,
         }
     }
 
@@ -151,6 +170,22 @@ impl WithPeerId {
         self
     }
 
+    /// Dial all addresses, including those currently backed off after
+    /// failed dials.
+    ///
+    /// See [`DialBackoffConfig`](crate::DialBackoffConfig).
+    pub fn bypass_backoff(mut self) -> Self {
+        self.bypass_backoff = true;
+        self
+    }
+
+    /// Set the [`DialPriority`] of the dial, deciding its position in the
+    /// queue of dials waiting for a free slot.
+    pub fn priority(mut self, priority: DialPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     /// Build the final [`DialOpts`].
     ///
     /// Addresses to dial the peer are retrieved via
@@ -168,6 +203,9 @@ pub struct WithPeerIdWithAddresses {
     pub(crate) extend_addresses_through_behaviour: bool,
     pub(crate) role_override: Endpoint,
     pub(crate) dial_concurrency_factor_override: Option<NonZeroU8>,
+    pub(crate) address_ranking_override: Option<RankingOverride>,
+    pub(crate) bypass_backoff: bool,
+    pub(crate) priority: DialPriority,
 }
 
 impl WithPeerIdWithAddresses {
@@ -202,6 +240,29 @@ impl WithPeerIdWithAddresses {
         self
     }
 
+    /// Override the [`AddressRanking`] configured via
+    /// [`SwarmBuilder::address_ranking`](crate::SwarmBuilder::address_ranking).
+    pub fn override_address_ranking(mut self, ranking: impl AddressRanking) -> Self {
+        self.address_ranking_override = Some(RankingOverride(Arc::new(ranking)));
+        self
+    }
+
+    /// Dial all addresses, including those currently backed off after
+    /// failed dials.
+    ///
+    /// See [`DialBackoffConfig`](crate::DialBackoffConfig).
+    pub fn bypass_backoff(mut self) -> Self {
+        self.bypass_backoff = true;
+        self
+    }
+
+    /// Set the [`DialPriority`] of the dial, deciding its position in the
+    /// queue of dials waiting for a free slot.
+    pub fn priority(mut self, priority: DialPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     /// Build the final [`DialOpts`].
     pub fn build(self) -> DialOpts {
         DialOpts(Opts::WithPeerIdWithAddresses(self))
@@ -217,6 +278,8 @@ impl WithoutPeerId {
         WithoutPeerIdWithAddress {
             address,
             role_override: Endpoint::Dialer,
+            bypass_backoff: false,
+            priority: Default::default(),
         }
     }
 }
@@ -225,6 +288,8 @@ impl WithoutPeerId {
 pub struct WithoutPeerIdWithAddress {
     pub(crate) address: Multiaddr,
     pub(crate) role_override: Endpoint,
+    pub(crate) bypass_backoff: bool,
+    pub(crate) priority: DialPriority,
 }
 
 impl WithoutPeerIdWithAddress {
@@ -238,12 +303,60 @@ impl WithoutPeerIdWithAddress {
         self.role_override = Endpoint::Listener;
         self
     }
+
+    /// Dial all addresses, including those currently backed off after
+    /// failed dials.
+    ///
+    /// See [`DialBackoffConfig`](crate::DialBackoffConfig).
+    pub fn bypass_backoff(mut self) -> Self {
+        self.bypass_backoff = true;
+        self
+    }
+
+    /// Set the [`DialPriority`] of the dial, deciding its position in the
+    /// queue of dials waiting for a free slot.
+    pub fn priority(mut self, priority: DialPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     /// Build the final [`DialOpts`].
     pub fn build(self) -> DialOpts {
         DialOpts(Opts::WithoutPeerIdWithAddress(self))
     }
 }
 
+/// The priority of a dial waiting for a free slot, when the number of
+/// concurrent dials is limited via
+/// [`SwarmBuilder::max_concurrent_dials`](crate::SwarmBuilder::max_concurrent_dials).
+///
+/// Queued dials are started by descending priority and, among dials of the
+/// same priority, in the order they were issued.
+///
+/// ```
+/// # use libp2p_swarm::dial_opts::{DialOpts, DialPriority};
+/// # use libp2p_core::PeerId;
+/// #
+/// DialOpts::peer_id(PeerId::random())
+///    .priority(DialPriority::High)
+///    .build();
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DialPriority {
+    /// For background dials, e.g. routing table maintenance.
+    Low,
+    /// The default priority.
+    Normal,
+    /// For dials that should preempt all others.
+    High,
+}
+
+impl Default for DialPriority {
+    fn default() -> Self {
+        DialPriority::Normal
+    }
+}
+
 /// The available conditions under which a new dialing attempt to
 /// a known peer is initiated.
 ///
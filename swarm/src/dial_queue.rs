@@ -0,0 +1,154 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::dial_opts::DialPriority;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::num::NonZeroUsize;
+
+/// Queues the outbound dials exceeding the configured number of concurrent
+/// dials.
+///
+/// Queued dials are started by descending [`DialPriority`] and, among dials
+/// of the same priority, in the order they were queued.
+///
+/// See [`SwarmBuilder::max_concurrent_dials`](crate::SwarmBuilder::max_concurrent_dials).
+#[derive(Debug)]
+pub(crate) struct DialQueue<T> {
+    max_concurrent_dials: Option<NonZeroUsize>,
+    queue: BinaryHeap<Entry<T>>,
+    next_seq: u64,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    priority: DialPriority,
+    seq: u64,
+    dial: T,
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher priority first, then lower sequence number first.
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl<T> DialQueue<T> {
+    pub(crate) fn new(max_concurrent_dials: Option<NonZeroUsize>) -> Self {
+        DialQueue {
+            max_concurrent_dials,
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Whether another dial may be started given the number of ongoing dials.
+    pub(crate) fn has_capacity(&self, ongoing: u32) -> bool {
+        self.max_concurrent_dials
+            .map_or(true, |max| (ongoing as usize) < max.get())
+    }
+
+    /// Whether a new dial may be started right away, i.e. without overtaking
+    /// queued dials.
+    pub(crate) fn may_start(&self, ongoing: u32) -> bool {
+        self.queue.is_empty() && self.has_capacity(ongoing)
+    }
+
+    pub(crate) fn push(&mut self, priority: DialPriority, dial: T) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.queue.push(Entry {
+            priority,
+            seq,
+            dial,
+        });
+    }
+
+    /// Removes the next dial to start, if there is capacity for it.
+    pub(crate) fn pop(&mut self, ongoing: u32) -> Option<T> {
+        if !self.has_capacity(ongoing) {
+            return None;
+        }
+        self.queue.pop().map(|e| e.dial)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.queue.iter().map(|e| &e.dial)
+    }
+
+    /// Removes all queued dials.
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.queue.drain().map(|e| e.dial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dials_are_ordered_by_priority_then_fifo() {
+        let mut queue = DialQueue::new(NonZeroUsize::new(1));
+        assert!(queue.may_start(0));
+        assert!(!queue.may_start(1));
+
+        queue.push(DialPriority::Low, "low-1");
+        queue.push(DialPriority::Normal, "normal-1");
+        queue.push(DialPriority::Low, "low-2");
+        queue.push(DialPriority::High, "high");
+        queue.push(DialPriority::Normal, "normal-2");
+        assert_eq!(queue.len(), 5);
+        assert!(!queue.may_start(0));
+
+        assert_eq!(queue.pop(1), None);
+        let mut order = Vec::new();
+        while let Some(dial) = queue.pop(0) {
+            order.push(dial);
+        }
+        assert_eq!(order, ["high", "normal-1", "normal-2", "low-1", "low-2"]);
+    }
+
+    #[test]
+    fn unlimited_queue_always_has_capacity() {
+        let queue = DialQueue::<()>::new(None);
+        assert!(queue.may_start(u32::MAX));
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use fnv::{FnvHashMap, FnvHashSet};
+use libp2p_core::time::Instant;
+use libp2p_core::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::time::Duration;
+
+/// The configuration of the confirmation of external address candidates.
+///
+/// Addresses of the local node observed by remote peers, reported via
+/// [`NetworkBehaviourAction::ReportExternalAddrCandidate`](crate::NetworkBehaviourAction::ReportExternalAddrCandidate),
+/// are candidates for external addresses. A candidate is confirmed, and
+/// thus added to the external addresses of the local node, once it was
+/// observed by at least `min_peers` distinct peers from at least
+/// `min_subnets` distinct IP subnets, or once it is confirmed directly via
+/// [`NetworkBehaviourAction::ConfirmExternalAddr`](crate::NetworkBehaviourAction::ConfirmExternalAddr),
+/// e.g. by an AutoNAT dial-back.
+///
+/// By default a candidate needs to be observed by 3 distinct peers from 2
+/// distinct subnets, such that a single peer cannot confirm a bogus address.
+/// Addresses reported via
+/// [`NetworkBehaviourAction::ReportObservedAddr`](crate::NetworkBehaviourAction::ReportObservedAddr)
+/// are not candidates and are added to the external addresses right away.
+///
+/// Observations are forgotten after `ttl`. A confirmed address that has
+/// neither been observed nor confirmed again within `ttl` expires.
+///
+/// See [`SwarmBuilder::external_addr_config`](crate::SwarmBuilder::external_addr_config).
+#[derive(Debug, Clone)]
+pub struct ExternalAddrConfig {
+    min_peers: usize,
+    min_subnets: usize,
+    ttl: Duration,
+    max_candidates: usize,
+}
+
+impl ExternalAddrConfig {
+    /// Sets the number of distinct peers that need to observe a candidate
+    /// for it to be confirmed. Defaults to 3.
+    pub fn with_min_peers(mut self, min_peers: usize) -> Self {
+        self.min_peers = min_peers;
+        self
+    }
+
+    /// Sets the number of distinct IP subnets, i.e. `/24` for IPv4 and `/48`
+    /// for IPv6, the observing peers need to be connected from for a candidate
+    /// to be confirmed. Peers connected via other than IP addresses are not
+    /// accounted for. Defaults to 2.
+    pub fn with_min_subnets(mut self, min_subnets: usize) -> Self {
+        self.min_subnets = min_subnets;
+        self
+    }
+
+    /// Sets the duration after which an observation is forgotten. Defaults to
+    /// 30 minutes.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum number of unconfirmed candidates. When exceeded, the
+    /// least recently observed candidate is dropped. Defaults to 32.
+    pub fn with_max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates;
+        self
+    }
+}
+
+impl Default for ExternalAddrConfig {
+    fn default() -> Self {
+        ExternalAddrConfig {
+            min_peers: 3,
+            min_subnets: 2,
+            ttl: Duration::from_secs(30 * 60),
+            max_candidates: 32,
+        }
+    }
+}
+
+/// The IP subnet a peer is connected from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Subnet {
+    V4([u8; 3]),
+    V6([u8; 6]),
+}
+
+impl Subnet {
+    /// Returns the subnet of the first IP address in the given address, if any.
+    pub(crate) fn of(addr: &Multiaddr) -> Option<Subnet> {
+        addr.iter().find_map(|p| match p {
+            Protocol::Ip4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                Some(Subnet::V4([a, b, c]))
+            }
+            Protocol::Ip6(ip) => {
+                let o = ip.octets();
+                Some(Subnet::V6([o[0], o[1], o[2], o[3], o[4], o[5]]))
+            }
+            _ => None,
+        })
+    }
+}
+
+/// A change of the state of an external address candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CandidateEvent {
+    /// A new candidate was observed.
+    New(Multiaddr),
+    /// A candidate was confirmed.
+    Confirmed(Multiaddr),
+    /// A confirmed address expired. `added` tells whether the address was
+    /// added to the external addresses on confirmation, see
+    /// [`ExternalAddrCandidates::set_added`].
+    Expired { address: Multiaddr, added: bool },
+}
+
+#[derive(Debug)]
+struct Candidate {
+    /// The peers that observed the address, with the subnet they are
+    /// connected from and the time of their last observation.
+    observers: FnvHashMap<PeerId, (Option<Subnet>, Instant)>,
+    last_seen: Instant,
+    confirmed: bool,
+    added: bool,
+}
+
+/// Tracks the candidates for external addresses of the local node.
+#[derive(Debug)]
+pub(crate) struct ExternalAddrCandidates {
+    config: ExternalAddrConfig,
+    candidates: FnvHashMap<Multiaddr, Candidate>,
+}
+
+impl ExternalAddrCandidates {
+    pub(crate) fn new(config: ExternalAddrConfig) -> Self {
+        ExternalAddrCandidates {
+            config,
+            candidates: Default::default(),
+        }
+    }
+
+    /// Records that the given peer, connected from the given subnet,
+    /// observed the address.
+    pub(crate) fn report(
+        &mut self,
+        address: Multiaddr,
+        observer: PeerId,
+        subnet: Option<Subnet>,
+        now: Instant,
+    ) -> Vec<CandidateEvent> {
+        let mut events = self.insert(&address, now);
+        let candidate = self.candidates.get_mut(&address).expect("inserted");
+        candidate.observers.insert(observer, (subnet, now));
+        if !candidate.confirmed {
+            let subnets = candidate
+                .observers
+                .values()
+                .filter_map(|(subnet, _)| *subnet)
+                .collect::<FnvHashSet<_>>();
+            if candidate.observers.len() >= self.config.min_peers
+                && subnets.len() >= self.config.min_subnets
+            {
+                candidate.confirmed = true;
+                events.push(CandidateEvent::Confirmed(address));
+            }
+        }
+        events
+    }
+
+    /// Confirms the address directly, e.g. after a successful dial-back.
+    pub(crate) fn confirm(&mut self, address: Multiaddr, now: Instant) -> Vec<CandidateEvent> {
+        let mut events = self.insert(&address, now);
+        let candidate = self.candidates.get_mut(&address).expect("inserted");
+        if !candidate.confirmed {
+            candidate.confirmed = true;
+            events.push(CandidateEvent::Confirmed(address));
+        }
+        events
+    }
+
+    /// Marks a confirmed address as added to the external addresses.
+    pub(crate) fn set_added(&mut self, address: &Multiaddr) {
+        if let Some(candidate) = self.candidates.get_mut(address) {
+            candidate.added = true;
+        }
+    }
+
+    /// Forgets outdated observations and expires the confirmed addresses
+    /// that have not been observed within the configured TTL.
+    pub(crate) fn expire(&mut self, now: Instant) -> Vec<CandidateEvent> {
+        let ttl = self.config.ttl;
+        let mut events = Vec::new();
+        self.candidates.retain(|address, candidate| {
+            candidate.observers.retain(|_, (_, seen)| *seen + ttl > now);
+            if candidate.last_seen + ttl > now {
+                return true;
+            }
+            if candidate.confirmed {
+                events.push(CandidateEvent::Expired {
+                    address: address.clone(),
+                    added: candidate.added,
+                });
+            }
+            false
+        });
+        events
+    }
+
+    /// Returns the instant at which the next observation is forgotten.
+    pub(crate) fn next_expiry(&self) -> Option<Instant> {
+        self.candidates
+            .values()
+            .flat_map(|c| {
+                std::iter::once(c.last_seen).chain(c.observers.values().map(|(_, seen)| *seen))
+            })
+            .min()
+            .map(|seen| seen + self.config.ttl)
+    }
+
+    /// Returns the candidates that are not yet confirmed.
+    pub(crate) fn unconfirmed(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.candidates
+            .iter()
+            .filter(|(_, c)| !c.confirmed)
+            .map(|(address, _)| address)
+    }
+
+    /// Inserts or refreshes the candidate, dropping the least recently
+    /// observed unconfirmed candidate if the limit is exceeded.
+    fn insert(&mut self, address: &Multiaddr, now: Instant) -> Vec<CandidateEvent> {
+        if let Some(candidate) = self.candidates.get_mut(address) {
+            candidate.last_seen = now;
+            return Vec::new();
+        }
+
+        if self.unconfirmed().count() >= self.config.max_candidates {
+            let oldest = self
+                .candidates
+                .iter()
+                .filter(|(_, c)| !c.confirmed)
+                .min_by_key(|(_, c)| c.last_seen)
+                .map(|(address, _)| address.clone());
+            if let Some(oldest) = oldest {
+                self.candidates.remove(&oldest);
+            }
+        }
+
+        self.candidates.insert(
+            address.clone(),
+            Candidate {
+                observers: Default::default(),
+                last_seen: now,
+                confirmed: false,
+                added: false,
+            },
+        );
+        vec![CandidateEvent::New(address.clone())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observer(ip: &str) -> (PeerId, Option<Subnet>) {
+        let addr: Multiaddr = format!("/ip4/{}/tcp/4001", ip).parse().unwrap();
+        (PeerId::random(), Subnet::of(&addr))
+    }
+
+    #[test]
+    fn candidate_is_confirmed_by_distinct_peers_and_subnets() {
+        let mut candidates = ExternalAddrCandidates::new(ExternalAddrConfig::default());
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let now = Instant::now();
+
+        let (peer, subnet) = observer("10.0.0.1");
+        assert_eq!(
+            candidates.report(addr.clone(), peer, subnet, now),
+            vec![CandidateEvent::New(addr.clone())]
+        );
+        // Repeated reports by the same peer do not count.
+        for _ in 0..5 {
+            assert!(candidates
+                .report(addr.clone(), peer, subnet, now)
+                .is_empty());
+        }
+        // Peers from the same subnet do not suffice.
+        let (peer, subnet) = observer("10.0.0.2");
+        assert!(candidates
+            .report(addr.clone(), peer, subnet, now)
+            .is_empty());
+        let (peer, subnet) = observer("10.0.0.3");
+        assert!(candidates
+            .report(addr.clone(), peer, subnet, now)
+            .is_empty());
+        assert_eq!(candidates.unconfirmed().count(), 1);
+
+        let (peer, subnet) = observer("10.0.1.1");
+        assert_eq!(
+            candidates.report(addr.clone(), peer, subnet, now),
+            vec![CandidateEvent::Confirmed(addr)]
+        );
+        assert_eq!(candidates.unconfirmed().count(), 0);
+    }
+
+    #[test]
+    fn confirmed_address_expires_unless_observed() {
+        let ttl = Duration::from_secs(60);
+        let mut candidates =
+            ExternalAddrCandidates::new(ExternalAddrConfig::default().with_ttl(ttl));
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let start = Instant::now();
+
+        assert_eq!(
+            candidates.confirm(addr.clone(), start),
+            vec![
+                CandidateEvent::New(addr.clone()),
+                CandidateEvent::Confirmed(addr.clone())
+            ]
+        );
+        candidates.set_added(&addr);
+        assert_eq!(candidates.next_expiry(), Some(start + ttl));
+
+        let (peer, subnet) = observer("10.0.0.1");
+        let later = start + ttl / 2;
+        assert!(candidates
+            .report(addr.clone(), peer, subnet, later)
+            .is_empty());
+        assert!(candidates.expire(start + ttl).is_empty());
+
+        assert_eq!(
+            candidates.expire(later + ttl),
+            vec![CandidateEvent::Expired {
+                address: addr,
+                added: true
+            }]
+        );
+        assert_eq!(candidates.next_expiry(), None);
+    }
+
+    #[test]
+    fn least_recently_observed_candidate_is_dropped() {
+        let mut candidates =
+            ExternalAddrCandidates::new(ExternalAddrConfig::default().with_max_candidates(2));
+        let (peer, subnet) = observer("10.0.0.1");
+        let now = Instant::now();
+
+        for (i, port) in [1, 2, 3].into_iter().enumerate() {
+            let addr: Multiaddr = format!("/ip4/1.2.3.4/tcp/{}", port).parse().unwrap();
+            candidates.report(addr, peer, subnet, now + Duration::from_secs(i as u64));
+        }
+
+        let mut unconfirmed = candidates.unconfirmed().cloned().collect::<Vec<_>>();
+        unconfirmed.sort();
+        assert_eq!(
+            unconfirmed,
+            vec![
+                "/ip4/1.2.3.4/tcp/2".parse::<Multiaddr>().unwrap(),
+                "/ip4/1.2.3.4/tcp/3".parse().unwrap()
+            ]
+        );
+    }
+}
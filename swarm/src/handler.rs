@@ -48,9 +48,15 @@ mod select;
 
 pub use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend, SendWrapper, UpgradeInfoSend};
 
-use instant::Instant;
-use libp2p_core::{upgrade::UpgradeError, ConnectedPoint, Multiaddr, PeerId};
-use std::{cmp::Ordering, error, fmt, task::Context, task::Poll, time::Duration};
+use crate::connection::InboundStreamLimit;
+use libp2p_core::time::Instant;
+use libp2p_core::{
+    upgrade::{ProtocolName, UpgradeError},
+    ConnectedPoint, Multiaddr, PeerId,
+};
+use std::{
+    cmp::Ordering, collections::HashSet, error, fmt, task::Context, task::Poll, time::Duration,
+};
 
 pub use dummy::DummyConnectionHandler;
 pub use map_in::MapInEvent;
@@ -115,6 +121,22 @@ pub trait ConnectionHandler: Send + 'static {
     /// >           This allows a remote to put the list of supported protocols in a cache.
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo>;
 
+    /// The names of the protocols currently accepted by
+    /// [`ConnectionHandler::listen_protocol`].
+    ///
+    /// Used to determine the protocols the local node supports on the connection, see
+    /// [`ConnectionHandler::inject_local_protocols_change`]. The default implementation
+    /// builds a [`ConnectionHandler::listen_protocol`] and discards it. Handlers for which
+    /// that has side effects, e.g. allocating state for an expected inbound substream,
+    /// should override this method.
+    fn listen_protocol_names(&self) -> Vec<String> {
+        self.listen_protocol()
+            .upgrade()
+            .protocol_info()
+            .map(|p| String::from_utf8_lossy(p.protocol_name()).into_owned())
+            .collect()
+    }
+
     /// Injects the output of a successful upgrade on a new inbound substream.
     fn inject_fully_negotiated_inbound(
         &mut self,
@@ -138,6 +160,21 @@ pub trait ConnectionHandler: Send + 'static {
     /// Notifies the handler of a change in the address of the remote.
     fn inject_address_change(&mut self, _new_address: &Multiaddr) {}
 
+    /// Notifies the handler of a change of the protocols the local node
+    /// supports on the connection, i.e. of the
+    /// [`ConnectionHandler::listen_protocol_names`] of the handler of the connection.
+    ///
+    /// The initial protocols are reported as [`ProtocolsChange::Added`] once
+    /// the connection is established. Later changes are only picked up when a
+    /// handler of the connection emits
+    /// [`ConnectionHandlerEvent::ReportLocalProtocolsChange`].
+    fn inject_local_protocols_change(&mut self, _change: &ProtocolsChange) {}
+
+    /// Notifies the handler of a change of the protocols the remote supports,
+    /// as reported by a handler of the connection via
+    /// [`ConnectionHandlerEvent::ReportRemoteProtocols`].
+    fn inject_remote_protocols_change(&mut self, _change: &ProtocolsChange) {}
+
     /// Indicates to the handler that upgrading an outbound substream to the given protocol has failed.
     fn inject_dial_upgrade_error(
         &mut self,
@@ -297,7 +334,7 @@ impl<TUpgrade, TInfo> SubstreamProtocol<TUpgrade, TInfo> {
 }
 
 /// Event produced by a handler.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConnectionHandlerEvent<TConnectionUpgrade, TOutboundOpenInfo, TCustom, TErr> {
     /// Request a new outbound substream to be opened with the remote.
     OutboundSubstreamRequest {
@@ -315,6 +352,22 @@ pub enum ConnectionHandlerEvent<TConnectionUpgrade, TOutboundOpenInfo, TCustom,
     /// [`ConnectionHandler::connection_keep_alive`].
     Close(TErr),
 
+    /// Report the full set of protocols the remote supports, e.g. as learned
+    /// via the identify protocol.
+    ///
+    /// The changes to the previously reported protocols are passed to
+    /// [`ConnectionHandler::inject_remote_protocols_change`] and to
+    /// [`NetworkBehaviour::inject_remote_protocols_change`](crate::NetworkBehaviour::inject_remote_protocols_change).
+    ReportRemoteProtocols(Vec<String>),
+
+    /// Signal that the protocols of [`ConnectionHandler::listen_protocol`]
+    /// changed.
+    ///
+    /// The protocols are compared to the previous ones and the changes are
+    /// passed to [`ConnectionHandler::inject_local_protocols_change`] and to
+    /// [`NetworkBehaviour::inject_local_protocols_change`](crate::NetworkBehaviour::inject_local_protocols_change).
+    ReportLocalProtocolsChange,
+
     /// Other event.
     Custom(TCustom),
 }
@@ -340,6 +393,12 @@ impl<TConnectionUpgrade, TOutboundOpenInfo, TCustom, TErr>
             }
             ConnectionHandlerEvent::Custom(val) => ConnectionHandlerEvent::Custom(val),
             ConnectionHandlerEvent::Close(val) => ConnectionHandlerEvent::Close(val),
+            ConnectionHandlerEvent::ReportRemoteProtocols(protocols) => {
+                ConnectionHandlerEvent::ReportRemoteProtocols(protocols)
+            }
+            ConnectionHandlerEvent::ReportLocalProtocolsChange => {
+                ConnectionHandlerEvent::ReportLocalProtocolsChange
+            }
         }
     }
 
@@ -360,6 +419,12 @@ impl<TConnectionUpgrade, TOutboundOpenInfo, TCustom, TErr>
             }
             ConnectionHandlerEvent::Custom(val) => ConnectionHandlerEvent::Custom(val),
             ConnectionHandlerEvent::Close(val) => ConnectionHandlerEvent::Close(val),
+            ConnectionHandlerEvent::ReportRemoteProtocols(protocols) => {
+                ConnectionHandlerEvent::ReportRemoteProtocols(protocols)
+            }
+            ConnectionHandlerEvent::ReportLocalProtocolsChange => {
+                ConnectionHandlerEvent::ReportLocalProtocolsChange
+            }
         }
     }
 
@@ -377,6 +442,12 @@ impl<TConnectionUpgrade, TOutboundOpenInfo, TCustom, TErr>
             }
             ConnectionHandlerEvent::Custom(val) => ConnectionHandlerEvent::Custom(map(val)),
             ConnectionHandlerEvent::Close(val) => ConnectionHandlerEvent::Close(val),
+            ConnectionHandlerEvent::ReportRemoteProtocols(protocols) => {
+                ConnectionHandlerEvent::ReportRemoteProtocols(protocols)
+            }
+            ConnectionHandlerEvent::ReportLocalProtocolsChange => {
+                ConnectionHandlerEvent::ReportLocalProtocolsChange
+            }
         }
     }
 
@@ -394,6 +465,46 @@ impl<TConnectionUpgrade, TOutboundOpenInfo, TCustom, TErr>
             }
             ConnectionHandlerEvent::Custom(val) => ConnectionHandlerEvent::Custom(val),
             ConnectionHandlerEvent::Close(val) => ConnectionHandlerEvent::Close(map(val)),
+            ConnectionHandlerEvent::ReportRemoteProtocols(protocols) => {
+                ConnectionHandlerEvent::ReportRemoteProtocols(protocols)
+            }
+            ConnectionHandlerEvent::ReportLocalProtocolsChange => {
+                ConnectionHandlerEvent::ReportLocalProtocolsChange
+            }
+        }
+    }
+}
+
+/// A change of the protocols supported by the local node or the remote on a
+/// connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolsChange {
+    /// The protocols are now supported.
+    Added(Vec<String>),
+    /// The protocols are no longer supported.
+    Removed(Vec<String>),
+}
+
+impl ProtocolsChange {
+    /// Returns the changes turning the protocols `old` into `new`, added
+    /// protocols first. Both changes are ordered by protocol name.
+    pub(crate) fn diff(
+        old: &HashSet<String>,
+        new: &HashSet<String>,
+    ) -> impl Iterator<Item = ProtocolsChange> {
+        let mut added = new.difference(old).cloned().collect::<Vec<_>>();
+        let mut removed = old.difference(new).cloned().collect::<Vec<_>>();
+        added.sort();
+        removed.sort();
+        std::iter::once(ProtocolsChange::Added(added))
+            .chain(std::iter::once(ProtocolsChange::Removed(removed)))
+            .filter(|change| !change.protocols().is_empty())
+    }
+
+    /// The added or removed protocols.
+    pub fn protocols(&self) -> &[String] {
+        match self {
+            ProtocolsChange::Added(protocols) | ProtocolsChange::Removed(protocols) => protocols,
         }
     }
 }
@@ -407,6 +518,9 @@ pub enum ConnectionHandlerUpgrErr<TUpgrErr> {
     Timer,
     /// Error while upgrading the substream to the protocol we want.
     Upgrade(UpgradeError<TUpgrErr>),
+    /// The inbound substream exceeded one of the configured
+    /// [`InboundStreamLimits`](crate::InboundStreamLimits) and was reset.
+    LimitExceeded(InboundStreamLimit),
 }
 
 impl<TUpgrErr> ConnectionHandlerUpgrErr<TUpgrErr> {
@@ -419,6 +533,9 @@ impl<TUpgrErr> ConnectionHandlerUpgrErr<TUpgrErr> {
             ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
             ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
             ConnectionHandlerUpgrErr::Upgrade(e) => ConnectionHandlerUpgrErr::Upgrade(f(e)),
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                ConnectionHandlerUpgrErr::LimitExceeded(limit)
+            }
         }
     }
 }
@@ -436,6 +553,9 @@ where
                 write!(f, "Timer error while opening a substream")
             }
             ConnectionHandlerUpgrErr::Upgrade(err) => write!(f, "{}", err),
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                write!(f, "Inbound substream exceeded {}", limit)
+            }
         }
     }
 }
@@ -449,6 +569,7 @@ where
             ConnectionHandlerUpgrErr::Timeout => None,
             ConnectionHandlerUpgrErr::Timer => None,
             ConnectionHandlerUpgrErr::Upgrade(err) => Some(err),
+            ConnectionHandlerUpgrErr::LimitExceeded(_) => None,
         }
     }
 }
@@ -20,15 +20,16 @@
 
 use crate::handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, IntoConnectionHandler,
-    KeepAlive, SubstreamProtocol,
+    KeepAlive, ProtocolsChange, SubstreamProtocol,
 };
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend, SendWrapper};
-use either::Either;
 use libp2p_core::either::{EitherError, EitherOutput};
 use libp2p_core::upgrade::{EitherUpgrade, UpgradeError};
 use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
 use std::task::{Context, Poll};
 
+pub use either::Either;
+
 pub enum IntoEitherHandler<L, R> {
     Left(L),
     Right(R),
@@ -94,6 +95,13 @@ where
         }
     }
 
+    fn listen_protocol_names(&self) -> Vec<String> {
+        match self {
+            Either::Left(a) => a.listen_protocol_names(),
+            Either::Right(b) => b.listen_protocol_names(),
+        }
+    }
+
     fn inject_fully_negotiated_outbound(
         &mut self,
         output: <Self::OutboundProtocol as OutboundUpgradeSend>::Output,
@@ -141,6 +149,20 @@ where
         }
     }
 
+    fn inject_local_protocols_change(&mut self, change: &ProtocolsChange) {
+        match self {
+            Either::Left(handler) => handler.inject_local_protocols_change(change),
+            Either::Right(handler) => handler.inject_local_protocols_change(change),
+        }
+    }
+
+    fn inject_remote_protocols_change(&mut self, change: &ProtocolsChange) {
+        match self {
+            Either::Left(handler) => handler.inject_remote_protocols_change(change),
+            Either::Right(handler) => handler.inject_remote_protocols_change(change),
+        }
+    }
+
     fn inject_dial_upgrade_error(
         &mut self,
         info: Self::OutboundOpenInfo,
@@ -165,6 +187,21 @@ where
                 }
                 _ => unreachable!(),
             },
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => match (self, info) {
+                (Either::Left(handler), Either::Left(info)) => {
+                    handler.inject_dial_upgrade_error(
+                        info,
+                        ConnectionHandlerUpgrErr::LimitExceeded(limit),
+                    );
+                }
+                (Either::Right(handler), Either::Right(info)) => {
+                    handler.inject_dial_upgrade_error(
+                        info,
+                        ConnectionHandlerUpgrErr::LimitExceeded(limit),
+                    );
+                }
+                _ => unreachable!(),
+            },
             ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(error)) => match (self, info) {
                 (Either::Left(handler), Either::Left(info)) => {
                     handler.inject_dial_upgrade_error(
@@ -229,6 +266,21 @@ where
                 }
                 _ => unreachable!(),
             },
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => match (self, info) {
+                (Either::Left(handler), Either::Left(info)) => {
+                    handler.inject_listen_upgrade_error(
+                        info,
+                        ConnectionHandlerUpgrErr::LimitExceeded(limit),
+                    );
+                }
+                (Either::Right(handler), Either::Right(info)) => {
+                    handler.inject_listen_upgrade_error(
+                        info,
+                        ConnectionHandlerUpgrErr::LimitExceeded(limit),
+                    );
+                }
+                _ => unreachable!(),
+            },
             ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(error)) => match (self, info) {
                 (Either::Left(handler), Either::Left(info)) => {
                     handler.inject_listen_upgrade_error(
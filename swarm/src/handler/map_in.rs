@@ -20,7 +20,7 @@
 
 use crate::handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
-    SubstreamProtocol,
+    ProtocolsChange, SubstreamProtocol,
 };
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend};
 use libp2p_core::Multiaddr;
@@ -63,6 +63,10 @@ where
         self.inner.listen_protocol()
     }
 
+    fn listen_protocol_names(&self) -> Vec<String> {
+        self.inner.listen_protocol_names()
+    }
+
     fn inject_fully_negotiated_inbound(
         &mut self,
         protocol: <Self::InboundProtocol as InboundUpgradeSend>::Output,
@@ -89,6 +93,14 @@ where
         self.inner.inject_address_change(addr)
     }
 
+    fn inject_local_protocols_change(&mut self, change: &ProtocolsChange) {
+        self.inner.inject_local_protocols_change(change)
+    }
+
+    fn inject_remote_protocols_change(&mut self, change: &ProtocolsChange) {
+        self.inner.inject_remote_protocols_change(change)
+    }
+
     fn inject_dial_upgrade_error(
         &mut self,
         info: Self::OutboundOpenInfo,
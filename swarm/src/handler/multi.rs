@@ -23,7 +23,7 @@
 
 use crate::handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, IntoConnectionHandler,
-    KeepAlive, SubstreamProtocol,
+    KeepAlive, ProtocolsChange, SubstreamProtocol,
 };
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend, UpgradeInfoSend};
 use crate::NegotiatedSubstream;
@@ -121,6 +121,13 @@ where
         SubstreamProtocol::new(upgrade, info).with_timeout(timeout)
     }
 
+    fn listen_protocol_names(&self) -> Vec<String> {
+        self.handlers
+            .values()
+            .flat_map(|h| h.listen_protocol_names())
+            .collect()
+    }
+
     fn inject_fully_negotiated_outbound(
         &mut self,
         protocol: <Self::OutboundProtocol as OutboundUpgradeSend>::Output,
@@ -161,6 +168,18 @@ where
         }
     }
 
+    fn inject_local_protocols_change(&mut self, change: &ProtocolsChange) {
+        for h in self.handlers.values_mut() {
+            h.inject_local_protocols_change(change)
+        }
+    }
+
+    fn inject_remote_protocols_change(&mut self, change: &ProtocolsChange) {
+        for h in self.handlers.values_mut() {
+            h.inject_remote_protocols_change(change)
+        }
+    }
+
     fn inject_dial_upgrade_error(
         &mut self,
         (key, arg): Self::OutboundOpenInfo,
@@ -193,6 +212,16 @@ where
                     }
                 }
             }
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                for (k, h) in &mut self.handlers {
+                    if let Some(i) = info.take(k) {
+                        h.inject_listen_upgrade_error(
+                            i,
+                            ConnectionHandlerUpgrErr::LimitExceeded(limit),
+                        )
+                    }
+                }
+            }
             ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(NegotiationError::Failed)) => {
                 for (k, h) in &mut self.handlers {
                     if let Some(i) = info.take(k) {
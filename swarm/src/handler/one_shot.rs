@@ -23,7 +23,7 @@ use crate::handler::{
     SubstreamProtocol,
 };
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend};
-use instant::Instant;
+use libp2p_core::time;
 use smallvec::SmallVec;
 use std::{error, fmt::Debug, task::Context, task::Poll, time::Duration};
 
@@ -139,7 +139,7 @@ where
     ) {
         // If we're shutting down the connection for inactivity, reset the timeout.
         if !self.keep_alive.is_yes() {
-            self.keep_alive = KeepAlive::Until(Instant::now() + self.config.keep_alive_timeout);
+            self.keep_alive = KeepAlive::Until(time::now() + self.config.keep_alive_timeout);
         }
 
         self.events_out.push(out.into());
@@ -206,7 +206,7 @@ where
             self.dial_queue.shrink_to_fit();
 
             if self.dial_negotiated == 0 && self.keep_alive.is_yes() {
-                self.keep_alive = KeepAlive::Until(Instant::now() + self.config.keep_alive_timeout);
+                self.keep_alive = KeepAlive::Until(time::now() + self.config.keep_alive_timeout);
             }
         }
 
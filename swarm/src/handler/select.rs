@@ -20,7 +20,7 @@
 
 use crate::handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, IntoConnectionHandler,
-    KeepAlive, SubstreamProtocol,
+    KeepAlive, ProtocolsChange, SubstreamProtocol,
 };
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend, SendWrapper};
 
@@ -126,6 +126,12 @@ where
         SubstreamProtocol::new(choice, (i1, i2)).with_timeout(timeout)
     }
 
+    fn listen_protocol_names(&self) -> Vec<String> {
+        let mut names = self.proto1.listen_protocol_names();
+        names.extend(self.proto2.listen_protocol_names());
+        names
+    }
+
     fn inject_fully_negotiated_outbound(
         &mut self,
         protocol: <Self::OutboundProtocol as OutboundUpgradeSend>::Output,
@@ -174,6 +180,16 @@ where
         self.proto2.inject_address_change(new_address)
     }
 
+    fn inject_local_protocols_change(&mut self, change: &ProtocolsChange) {
+        self.proto1.inject_local_protocols_change(change);
+        self.proto2.inject_local_protocols_change(change)
+    }
+
+    fn inject_remote_protocols_change(&mut self, change: &ProtocolsChange) {
+        self.proto1.inject_remote_protocols_change(change);
+        self.proto2.inject_remote_protocols_change(change)
+    }
+
     fn inject_dial_upgrade_error(
         &mut self,
         info: Self::OutboundOpenInfo,
@@ -186,6 +202,9 @@ where
             (EitherOutput::First(info), ConnectionHandlerUpgrErr::Timeout) => self
                 .proto1
                 .inject_dial_upgrade_error(info, ConnectionHandlerUpgrErr::Timeout),
+            (EitherOutput::First(info), ConnectionHandlerUpgrErr::LimitExceeded(limit)) => self
+                .proto1
+                .inject_dial_upgrade_error(info, ConnectionHandlerUpgrErr::LimitExceeded(limit)),
             (
                 EitherOutput::First(info),
                 ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(err)),
@@ -209,6 +228,9 @@ where
             (EitherOutput::Second(info), ConnectionHandlerUpgrErr::Timeout) => self
                 .proto2
                 .inject_dial_upgrade_error(info, ConnectionHandlerUpgrErr::Timeout),
+            (EitherOutput::Second(info), ConnectionHandlerUpgrErr::LimitExceeded(limit)) => self
+                .proto2
+                .inject_dial_upgrade_error(info, ConnectionHandlerUpgrErr::LimitExceeded(limit)),
             (EitherOutput::Second(info), ConnectionHandlerUpgrErr::Timer) => self
                 .proto2
                 .inject_dial_upgrade_error(info, ConnectionHandlerUpgrErr::Timer),
@@ -253,6 +275,14 @@ where
                 self.proto2
                     .inject_listen_upgrade_error(i2, ConnectionHandlerUpgrErr::Timeout)
             }
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => {
+                self.proto1.inject_listen_upgrade_error(
+                    i1,
+                    ConnectionHandlerUpgrErr::LimitExceeded(limit),
+                );
+                self.proto2
+                    .inject_listen_upgrade_error(i2, ConnectionHandlerUpgrErr::LimitExceeded(limit))
+            }
             ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(NegotiationError::Failed)) => {
                 self.proto1.inject_listen_upgrade_error(
                     i1,
@@ -340,6 +370,12 @@ where
             Poll::Ready(ConnectionHandlerEvent::Close(event)) => {
                 return Poll::Ready(ConnectionHandlerEvent::Close(EitherError::A(event)));
             }
+            Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(protocols)) => {
+                return Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(protocols));
+            }
+            Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange) => {
+                return Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange);
+            }
             Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest { protocol }) => {
                 return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                     protocol: protocol
@@ -357,6 +393,12 @@ where
             Poll::Ready(ConnectionHandlerEvent::Close(event)) => {
                 return Poll::Ready(ConnectionHandlerEvent::Close(EitherError::B(event)));
             }
+            Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(protocols)) => {
+                return Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(protocols));
+            }
+            Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange) => {
+                return Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange);
+            }
             Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest { protocol }) => {
                 return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                     protocol: protocol
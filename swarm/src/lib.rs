@@ -53,39 +53,60 @@
 //! are supported, when to open a new outbound substream, etc.
 //!
 
+#[cfg(feature = "serde")]
+extern crate _serde as serde;
+
+mod backoff;
+mod ban;
 mod connection;
+mod dial_queue;
+mod external_addr;
 mod registry;
-#[cfg(test)]
-mod test;
 mod upgrade;
 
 pub mod behaviour;
 pub mod dial_opts;
 pub mod handler;
+pub mod listen_opts;
+pub mod peer_store;
+pub mod stream;
 
+pub use backoff::DialBackoffConfig;
+pub use ban::{BanTarget, InvalidIpNet, IpNet};
 pub use behaviour::{
     CloseConnection, NetworkBehaviour, NetworkBehaviourAction, NetworkBehaviourEventProcess,
     NotifyHandler, PollParameters,
 };
 pub use connection::{
-    ConnectionCounters, ConnectionError, ConnectionLimit, ConnectionLimits, PendingConnectionError,
-    PendingInboundConnectionError, PendingOutboundConnectionError,
+    AddressRanking, ConnectionCounters, ConnectionError, ConnectionGater, ConnectionInfo,
+    ConnectionLimit, ConnectionLimits, ConnectionManager, ConnectionManagerConfig,
+    DefaultAddressRanking, DuplicateConnectionConfig, InboundStreamLimit, InboundStreamLimits,
+    InterceptError, PendingConnectionError, PendingInboundConnectionError,
+    PendingOutboundConnectionError, SharedConnectionGater, TrimReason,
 };
+pub use external_addr::ExternalAddrConfig;
 pub use handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerSelect, ConnectionHandlerUpgrErr,
     IntoConnectionHandler, IntoConnectionHandlerSelect, KeepAlive, OneShotHandler,
-    OneShotHandlerConfig, SubstreamProtocol,
+    OneShotHandlerConfig, ProtocolsChange, SubstreamProtocol,
 };
+pub use peer_store::PeerStore;
 pub use registry::{AddAddressResult, AddressRecord, AddressScore};
 
+use backoff::DialBackoff;
+use ban::Bans;
+use connection::denied_secured;
 use connection::pool::{Pool, PoolConfig, PoolEvent};
 use connection::{EstablishedConnection, IncomingInfo, ListenersEvent, ListenersStream, Substream};
 use dial_opts::{DialOpts, PeerCondition};
+use dial_queue::DialQueue;
 use either::Either;
+use external_addr::{CandidateEvent, ExternalAddrCandidates, Subnet};
 use futures::{executor::ThreadPoolBuilder, prelude::*, stream::FusedStream};
-use libp2p_core::connection::{ConnectionId, PendingPoint};
+use libp2p_core::connection::{ConnectionId, Endpoint, PendingPoint};
+use libp2p_core::time::{self, Delay, Instant};
 use libp2p_core::{
-    connection::{ConnectedPoint, ListenerId},
+    connection::{ConnectedPoint, ListenerId, NegotiatedProtocols},
     multiaddr::Protocol,
     multihash::Multihash,
     muxing::StreamMuxerBox,
@@ -93,6 +114,8 @@ use libp2p_core::{
     upgrade::ProtocolName,
     Executor, Multiaddr, Negotiated, PeerId, Transport,
 };
+use listen_opts::ListenOpts;
+use peer_store::AddressSource;
 use registry::{AddressIntoIter, Addresses};
 use smallvec::SmallVec;
 use std::collections::HashSet;
@@ -102,10 +125,18 @@ use std::{
     convert::TryFrom,
     error, fmt, io,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 use upgrade::UpgradeInfoSend as _;
 
+/// The interval at which expired entries are removed from the [`PeerStore`].
+const PEER_STORE_EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The drain timeout of a shutdown started via [`Swarm::poll_close`].
+pub const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Substream for which a protocol has been chosen.
 ///
 /// Implements the [`AsyncRead`](futures::io::AsyncRead) and
@@ -150,6 +181,9 @@ pub enum SwarmEvent<TBehaviourOutEvent, THandlerErr> {
         /// Addresses are dialed concurrently. Contains the addresses and errors
         /// of dial attempts that failed before the one successful dial.
         concurrent_dial_errors: Option<Vec<(Multiaddr, TransportError<io::Error>)>>,
+        /// The security and stream multiplexer protocols negotiated while
+        /// upgrading the connection, as far as recorded by the transport.
+        negotiated_protocols: NegotiatedProtocols,
     },
     /// A connection with the given peer has been closed,
     /// possibly as a result of an error.
@@ -199,13 +233,55 @@ pub enum SwarmEvent<TBehaviourOutEvent, THandlerErr> {
         /// Error that has been encountered.
         error: DialError,
     },
-    /// We connected to a peer, but we immediately closed the connection because that peer is banned.
+    /// The [`ConnectionManager`] started closing a connection to keep the number of
+    /// established connections within the configured watermarks.
+    ///
+    /// A corresponding [`ConnectionClosed`](SwarmEvent::ConnectionClosed) event will
+    /// later be generated for this connection.
+    ConnectionTrimmed {
+        /// Identity of the peer of the trimmed connection.
+        peer_id: PeerId,
+        /// Endpoint of the trimmed connection.
+        endpoint: ConnectedPoint,
+        /// Why the connection has been trimmed.
+        reason: TrimReason,
+    },
+    /// An inbound substream opened by a peer was reset for exceeding one of the
+    /// configured [`InboundStreamLimits`].
+    InboundStreamLimitExceeded {
+        /// Identity of the peer that opened the substream.
+        peer_id: PeerId,
+        /// Endpoint of the connection of the substream.
+        endpoint: ConnectedPoint,
+        /// The protocol of the substream, if the peer already selected one.
+        protocol: Option<String>,
+        /// The exceeded limit.
+        limit: InboundStreamLimit,
+    },
+    /// We connected to a peer, but we immediately closed the connection because that peer or
+    /// its IP address is banned.
     BannedPeer {
         /// Identity of the banned peer.
         peer_id: PeerId,
         /// Endpoint of the connection that has been closed.
         endpoint: ConnectedPoint,
     },
+    /// A listener accepted a connection from a banned IP address, which has been closed
+    /// before upgrading it.
+    BannedIp {
+        /// The banned network containing the IP address of the remote.
+        ip_net: IpNet,
+        /// Local connection address.
+        local_addr: Multiaddr,
+        /// Address used to send back data to the remote.
+        send_back_addr: Multiaddr,
+    },
+    /// A ban given a duration via [`Swarm::ban_peer_id_for`] or [`Swarm::ban_ip_for`]
+    /// has expired.
+    BanExpired {
+        /// The peer or IP network that is no longer banned.
+        target: BanTarget,
+    },
     /// One of our listeners has reported a new local listening address.
     NewListenAddr {
         /// The listener that is listening on the new address.
@@ -280,8 +356,37 @@ where
     /// similar mechanisms.
     external_addrs: Addresses,
 
-    /// List of nodes for which we deny any incoming connection.
-    banned_peers: HashSet<PeerId>,
+    /// Candidates for external addresses, confirmed by distinct observers or
+    /// directly.
+    external_addr_candidates: ExternalAddrCandidates,
+
+    /// Fires when the next observation of an external address candidate is
+    /// to be forgotten.
+    external_addr_expiry: Option<Delay>,
+
+    /// Information about known peers, shared with the behaviour.
+    peer_store: PeerStore,
+
+    /// Fires when expired addresses are to be removed from the `peer_store`.
+    peer_store_expiry: Delay,
+
+    /// Trims established connections once the configured high watermark is exceeded.
+    connection_manager: ConnectionManager,
+
+    /// Addresses skipped when dialing after failed dials.
+    dial_backoff: DialBackoff,
+
+    /// Dials waiting for a free slot.
+    dial_queue: DialQueue<QueuedDial<<TBehaviour as NetworkBehaviour>::ConnectionHandler>>,
+
+    /// Orders and staggers the addresses of outbound connection attempts.
+    address_ranking: Arc<dyn AddressRanking>,
+
+    /// Peers and IP networks for which we deny any connection.
+    bans: Bans,
+
+    /// Fires when the next ban with a duration expires.
+    ban_expiry: Option<Delay>,
 
     /// Connections for which we withhold any reporting. These belong to banned peers.
     ///
@@ -293,6 +398,23 @@ where
     /// (or dropped if the peer disconnected) before the `behaviour`
     /// can be polled again.
     pending_event: Option<(PeerId, PendingNotifyHandler, THandlerInEvent<TBehaviour>)>,
+
+    /// Progress of a graceful shutdown started via [`Swarm::close`].
+    shutdown: Shutdown,
+}
+
+/// Progress of a graceful shutdown of a [`Swarm`], see [`Swarm::close`].
+#[derive(Debug, Clone, Copy)]
+enum Shutdown {
+    /// No shutdown was requested.
+    None,
+    /// A shutdown with the given drain timeout was requested. Connections are
+    /// drained once the behaviour is idle.
+    Requested(Duration),
+    /// Established connections are being drained until the given deadline.
+    Draining(Instant),
+    /// All listeners and connections are closed.
+    Done,
 }
 
 impl<TBehaviour> Unpin for Swarm<TBehaviour> where TBehaviour: NetworkBehaviour {}
@@ -317,6 +439,7 @@ where
         NetworkInfo {
             num_peers,
             connection_counters,
+            num_queued_dials: self.dial_queue.len(),
         }
     }
 
@@ -325,12 +448,29 @@ where
     ///
     /// Listeners report their new listening addresses as [`SwarmEvent::NewListenAddr`].
     /// Depending on the underlying transport, one listener may have multiple listening addresses.
+    ///
+    /// See [`Swarm::listen_with_opts`] to listen on the first free port of a
+    /// range or to start the listener again when it fails.
     pub fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<io::Error>> {
         let id = self.listeners.listen_on(addr)?;
         self.behaviour.inject_new_listener(id);
         Ok(id)
     }
 
+    /// Starts listening with the given [`ListenOpts`].
+    /// Returns an error if the address is not supported or, with a port
+    /// range, if no port of the range can be listened on.
+    ///
+    /// See [`Swarm::listen_on`].
+    pub fn listen_with_opts(
+        &mut self,
+        opts: ListenOpts,
+    ) -> Result<ListenerId, TransportError<io::Error>> {
+        let id = self.listeners.listen_with_opts(opts)?;
+        self.behaviour.inject_new_listener(id);
+        Ok(id)
+    }
+
     /// Remove some listener.
     ///
     /// Returns `true` if there was a listener with this ID, `false`
@@ -372,151 +512,315 @@ where
         swarm_dial_opts: DialOpts,
         handler: <TBehaviour as NetworkBehaviour>::ConnectionHandler,
     ) -> Result<(), DialError> {
-        let (peer_id, addresses, dial_concurrency_factor_override, role_override) =
-            match swarm_dial_opts.0 {
-                // Dial a known peer.
-                dial_opts::Opts::WithPeerId(dial_opts::WithPeerId {
-                    peer_id,
-                    condition,
-                    role_override,
-                    dial_concurrency_factor_override,
-                })
-                | dial_opts::Opts::WithPeerIdWithAddresses(dial_opts::WithPeerIdWithAddresses {
-                    peer_id,
-                    condition,
-                    role_override,
-                    dial_concurrency_factor_override,
-                    ..
-                }) => {
-                    // Check [`PeerCondition`] if provided.
-                    let condition_matched = match condition {
-                        PeerCondition::Disconnected => !self.is_connected(&peer_id),
-                        PeerCondition::NotDialing => {
-                            !self
-                                .pool
-                                .iter_pending_info()
-                                .any(move |(_, endpoint, peer)| {
-                                    matches!(endpoint, PendingPoint::Dialer { .. })
-                                        && peer.as_ref() == Some(&peer_id)
-                                })
+        if !matches!(self.shutdown, Shutdown::None) {
+            let error = DialError::Aborted;
+            self.behaviour
+                .inject_dial_failure(swarm_dial_opts.get_peer_id(), handler, &error);
+            return Err(error);
+        }
+
+        let (
+            peer_id,
+            addresses,
+            dial_concurrency_factor_override,
+            address_ranking_override,
+            role_override,
+            bypass_backoff,
+            priority,
+            condition,
+        ) = match swarm_dial_opts.0 {
+            // Dial a known peer.
+            dial_opts::Opts::WithPeerId(dial_opts::WithPeerId {
+                peer_id,
+                condition,
+                role_override,
+                dial_concurrency_factor_override,
+                ref address_ranking_override,
+                bypass_backoff,
+                priority,
+            })
+            | dial_opts::Opts::WithPeerIdWithAddresses(dial_opts::WithPeerIdWithAddresses {
+                peer_id,
+                condition,
+                role_override,
+                dial_concurrency_factor_override,
+                ref address_ranking_override,
+                bypass_backoff,
+                priority,
+                ..
+            }) => {
+                let address_ranking_override = address_ranking_override.clone();
+
+                // Check [`PeerCondition`] if provided.
+                if !self.dial_condition_matched(peer_id, condition) {
+                    self.behaviour.inject_dial_failure(
+                        Some(peer_id),
+                        handler,
+                        &DialError::DialPeerConditionFalse(condition),
+                    );
+
+                    return Err(DialError::DialPeerConditionFalse(condition));
+                }
+
+                // Check if peer is banned.
+                if self.bans.is_peer_banned(&peer_id) {
+                    let error = DialError::Banned;
+                    self.behaviour
+                        .inject_dial_failure(Some(peer_id), handler, &error);
+                    return Err(error);
+                }
+
+                // Retrieve the addresses to dial.
+                let addresses = {
+                    let mut addresses = match swarm_dial_opts.0 {
+                        dial_opts::Opts::WithPeerId(dial_opts::WithPeerId { .. }) => {
+                            let mut addresses = self.behaviour.addresses_of_peer(&peer_id);
+                            addresses.extend(self.peer_store.addresses_of_peer(&peer_id).cloned());
+                            addresses
+                        }
+                        dial_opts::Opts::WithPeerIdWithAddresses(
+                            dial_opts::WithPeerIdWithAddresses {
+                                peer_id,
+                                mut addresses,
+                                extend_addresses_through_behaviour,
+                                ..
+                            },
+                        ) => {
+                            if extend_addresses_through_behaviour {
+                                addresses.extend(self.behaviour.addresses_of_peer(&peer_id));
+                                addresses
+                                    .extend(self.peer_store.addresses_of_peer(&peer_id).cloned());
+                            }
+                            addresses
+                        }
+                        dial_opts::Opts::WithoutPeerIdWithAddress { .. } => {
+                            unreachable!("Due to outer match.")
                         }
-                        PeerCondition::Always => true,
                     };
-                    if !condition_matched {
-                        self.behaviour.inject_dial_failure(
-                            Some(peer_id),
-                            handler,
-                            &DialError::DialPeerConditionFalse(condition),
-                        );
 
-                        return Err(DialError::DialPeerConditionFalse(condition));
-                    }
+                    let mut unique_addresses = HashSet::new();
+                    addresses.retain(|a| {
+                        !self.listened_addrs.contains(a) && unique_addresses.insert(a.clone())
+                    });
 
-                    // Check if peer is banned.
-                    if self.banned_peers.contains(&peer_id) {
-                        let error = DialError::Banned;
+                    if addresses.is_empty() {
+                        let error = DialError::NoAddresses;
                         self.behaviour
                             .inject_dial_failure(Some(peer_id), handler, &error);
                         return Err(error);
-                    }
+                    };
 
-                    // Retrieve the addresses to dial.
-                    let addresses = {
-                        let mut addresses = match swarm_dial_opts.0 {
-                            dial_opts::Opts::WithPeerId(dial_opts::WithPeerId { .. }) => {
-                                self.behaviour.addresses_of_peer(&peer_id)
-                            }
-                            dial_opts::Opts::WithPeerIdWithAddresses(
-                                dial_opts::WithPeerIdWithAddresses {
-                                    peer_id,
-                                    mut addresses,
-                                    extend_addresses_through_behaviour,
-                                    ..
-                                },
-                            ) => {
-                                if extend_addresses_through_behaviour {
-                                    addresses.extend(self.behaviour.addresses_of_peer(&peer_id))
-                                }
-                                addresses
-                            }
-                            dial_opts::Opts::WithoutPeerIdWithAddress { .. } => {
-                                unreachable!("Due to outer match.")
-                            }
-                        };
+                    addresses
+                };
 
-                        let mut unique_addresses = HashSet::new();
-                        addresses.retain(|a| {
-                            !self.listened_addrs.contains(a) && unique_addresses.insert(a.clone())
-                        });
+                (
+                    Some(peer_id),
+                    Either::Left(addresses.into_iter()),
+                    dial_concurrency_factor_override,
+                    address_ranking_override,
+                    role_override,
+                    bypass_backoff,
+                    priority,
+                    Some(condition),
+                )
+            }
+            // Dial an unknown peer.
+            dial_opts::Opts::WithoutPeerIdWithAddress(dial_opts::WithoutPeerIdWithAddress {
+                address,
+                role_override,
+                bypass_backoff,
+                priority,
+            }) => {
+                // If the address ultimately encapsulates an expected peer ID, dial that peer
+                // such that any mismatch is detected. We do not "pop off" the `P2p` protocol
+                // from the address, because it may be used by the `Transport`, i.e. `P2p`
+                // is a protocol component that can influence any transport, like `libp2p-dns`.
+                let peer_id = match address
+                    .iter()
+                    .last()
+                    .and_then(|p| {
+                        if let Protocol::P2p(ma) = p {
+                            Some(PeerId::try_from(ma))
+                        } else {
+                            None
+                        }
+                    })
+                    .transpose()
+                {
+                    Ok(peer_id) => peer_id,
+                    Err(multihash) => return Err(DialError::InvalidPeerId(multihash)),
+                };
 
-                        if addresses.is_empty() {
-                            let error = DialError::NoAddresses;
-                            self.behaviour
-                                .inject_dial_failure(Some(peer_id), handler, &error);
-                            return Err(error);
-                        };
+                (
+                    peer_id,
+                    Either::Right(iter::once(address)),
+                    None,
+                    None,
+                    role_override,
+                    bypass_backoff,
+                    priority,
+                    None,
+                )
+            }
+        };
 
-                        addresses
-                    };
+        // Skip the addresses of banned IP networks.
+        let mut num_banned = 0;
+        let addresses = addresses.filter(|addr| match self.bans.banned_net_of(addr) {
+            Some(ip_net) => {
+                log::debug!("Skipping dial of {} in banned network {}.", addr, ip_net);
+                num_banned += 1;
+                false
+            }
+            None => true,
+        });
+        let addresses = addresses.collect::<Vec<_>>();
+        if addresses.is_empty() && num_banned > 0 {
+            let error = DialError::Banned;
+            self.behaviour.inject_dial_failure(peer_id, handler, &error);
+            return Err(error);
+        }
+        let addresses = addresses.into_iter();
 
-                    (
-                        Some(peer_id),
-                        Either::Left(addresses.into_iter()),
-                        dial_concurrency_factor_override,
-                        role_override,
-                    )
-                }
-                // Dial an unknown peer.
-                dial_opts::Opts::WithoutPeerIdWithAddress(
-                    dial_opts::WithoutPeerIdWithAddress {
-                        address,
-                        role_override,
+        // Skip the addresses that are backed off after failed dials.
+        let addresses = if bypass_backoff {
+            addresses.collect()
+        } else {
+            let mut backed_off_until: Option<Instant> = None;
+            let addresses = addresses
+                .filter(
+                    |addr| match self.dial_backoff.backed_off_until(peer_id, addr) {
+                        Some(until) => {
+                            log::debug!("Skipping dial of {} backed off until {:?}.", addr, until);
+                            backed_off_until =
+                                Some(backed_off_until.map_or(until, |u| u.min(until)));
+                            false
+                        }
+                        None => true,
                     },
-                ) => {
-                    // If the address ultimately encapsulates an expected peer ID, dial that peer
-                    // such that any mismatch is detected. We do not "pop off" the `P2p` protocol
-                    // from the address, because it may be used by the `Transport`, i.e. `P2p`
-                    // is a protocol component that can influence any transport, like `libp2p-dns`.
-                    let peer_id = match address
-                        .iter()
-                        .last()
-                        .and_then(|p| {
-                            if let Protocol::P2p(ma) = p {
-                                Some(PeerId::try_from(ma))
-                            } else {
-                                None
-                            }
-                        })
-                        .transpose()
-                    {
-                        Ok(peer_id) => peer_id,
-                        Err(multihash) => return Err(DialError::InvalidPeerId(multihash)),
-                    };
+                )
+                .collect::<Vec<_>>();
 
-                    (
-                        peer_id,
-                        Either::Right(iter::once(address)),
-                        None,
-                        role_override,
-                    )
-                }
-            };
+            if let (true, Some(until)) = (addresses.is_empty(), backed_off_until) {
+                let error = DialError::Backoff { until };
+                self.behaviour.inject_dial_failure(peer_id, handler, &error);
+                return Err(error);
+            }
 
-        match self.pool.add_outgoing(
-            self.listeners.transport().clone(),
-            addresses,
+            addresses
+        };
+
+        // Order and stagger the addresses to dial.
+        let ranking = address_ranking_override
+            .map_or_else(|| self.address_ranking.clone(), |ranking| ranking.0);
+        let addresses = ranking.rank(addresses, &self.listened_addrs);
+
+        let dial = QueuedDial {
             peer_id,
+            condition,
+            addresses,
             handler,
             role_override,
             dial_concurrency_factor_override,
+        };
+        if self
+            .dial_queue
+            .may_start(self.pool.counters().num_pending_outgoing())
+        {
+            self.start_dial(dial)
+        } else {
+            log::debug!(
+                "Queueing dial of {:?}; {} dials queued.",
+                peer_id,
+                self.dial_queue.len() + 1
+            );
+            self.dial_queue.push(priority, dial);
+            Ok(())
+        }
+    }
+
+    /// Whether the given [`PeerCondition`] for dialing the peer is met.
+    fn dial_condition_matched(&self, peer_id: PeerId, condition: PeerCondition) -> bool {
+        match condition {
+            PeerCondition::Disconnected => !self.is_connected(&peer_id),
+            PeerCondition::NotDialing => {
+                !self
+                    .pool
+                    .iter_pending_info()
+                    .any(move |(_, endpoint, peer)| {
+                        matches!(endpoint, PendingPoint::Dialer { .. })
+                            && peer.as_ref() == Some(&peer_id)
+                    })
+                    && !self
+                        .dial_queue
+                        .iter()
+                        .any(|dial| dial.peer_id == Some(peer_id))
+            }
+            PeerCondition::Always => true,
+        }
+    }
+
+    fn start_dial(
+        &mut self,
+        dial: QueuedDial<<TBehaviour as NetworkBehaviour>::ConnectionHandler>,
+    ) -> Result<(), DialError> {
+        match self.pool.add_outgoing(
+            self.listeners.transport().clone(),
+            dial.addresses.into_iter(),
+            dial.peer_id,
+            dial.handler,
+            dial.role_override,
+            dial.dial_concurrency_factor_override,
         ) {
             Ok(_connection_id) => Ok(()),
-            Err((connection_limit, handler)) => {
-                let error = DialError::ConnectionLimit(connection_limit);
+            Err((error, handler)) => {
+                let error = DialError::from(error);
                 self.behaviour.inject_dial_failure(None, handler, &error);
-                return Err(error);
+                Err(error)
+            }
+        }
+    }
+
+    /// Starts queued dials as long as the number of concurrent dials permits.
+    ///
+    /// Returns an event for a queued dial that failed to start.
+    fn poll_dial_queue(
+        &mut self,
+    ) -> Option<SwarmEvent<TBehaviour::OutEvent, THandlerErr<TBehaviour>>> {
+        while let Some(mut dial) = self
+            .dial_queue
+            .pop(self.pool.counters().num_pending_outgoing())
+        {
+            let peer_id = dial.peer_id;
+
+            // Conditions may have changed while the dial was queued.
+            let bans = &self.bans;
+            dial.addresses
+                .retain(|(addr, _)| bans.banned_net_of(addr).is_none());
+            let error = match (peer_id, dial.condition) {
+                (Some(peer_id), _) if self.bans.is_peer_banned(&peer_id) => Some(DialError::Banned),
+                _ if dial.addresses.is_empty() => Some(DialError::Banned),
+                (Some(peer_id), Some(condition))
+                    if !self.dial_condition_matched(peer_id, condition) =>
+                {
+                    Some(DialError::DialPeerConditionFalse(condition))
+                }
+                _ => None,
+            };
+            let result = match error {
+                Some(error) => {
+                    self.behaviour
+                        .inject_dial_failure(peer_id, dial.handler, &error);
+                    Err(error)
+                }
+                None => self.start_dial(dial),
+            };
+
+            if let Err(error) = result {
+                return Some(SwarmEvent::OutgoingConnectionError { peer_id, error });
             }
         }
+        None
     }
 
     /// Returns an iterator that produces the list of addresses we're listening on.
@@ -529,6 +833,28 @@ where
         &self.local_peer_id
     }
 
+    /// Returns the [`PeerStore`] holding the information about known peers.
+    pub fn peer_store(&self) -> &PeerStore {
+        &self.peer_store
+    }
+
+    /// Returns the [`PeerStore`] holding the information about known peers,
+    /// e.g. to add addresses learned by the application.
+    pub fn peer_store_mut(&mut self) -> &mut PeerStore {
+        &mut self.peer_store
+    }
+
+    /// Returns the [`ConnectionManager`] deciding which connections to trim.
+    pub fn connection_manager(&self) -> &ConnectionManager {
+        &self.connection_manager
+    }
+
+    /// Returns the [`ConnectionManager`] deciding which connections to trim,
+    /// e.g. to tag or protect peers.
+    pub fn connection_manager_mut(&mut self) -> &mut ConnectionManager {
+        &mut self.connection_manager
+    }
+
     /// Returns an iterator for [`AddressRecord`]s of external addresses
     /// of the local node, in decreasing order of their current
     /// [score](AddressScore).
@@ -565,6 +891,14 @@ where
         result
     }
 
+    /// Returns an iterator over the candidates for external addresses of the
+    /// local node that are not yet confirmed.
+    ///
+    /// See [`ExternalAddrConfig`].
+    pub fn external_address_candidates(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.external_addr_candidates.unconfirmed()
+    }
+
     /// Removes an external address of the local node, regardless of
     /// its current score. See [`Swarm::add_external_address`]
     /// for details.
@@ -580,23 +914,197 @@ where
         }
     }
 
+    /// Maps the given address of the local node, observed by a remote peer,
+    /// onto the locally known listen addresses to yield one or more addresses
+    /// of the local node that may be publicly reachable.
+    fn translate_observed_addr(&self, observed: &Multiaddr) -> Vec<Multiaddr> {
+        let transport = self.listeners.transport();
+        let mut addrs: Vec<_> = self
+            .listeners
+            .listen_addrs()
+            .filter_map(move |server| transport.address_translation(server, observed))
+            .collect();
+
+        // remove duplicates
+        addrs.sort_unstable();
+        addrs.dedup();
+        addrs
+    }
+
+    /// Informs the behaviour about changes of external address candidates
+    /// and adds or removes confirmed addresses.
+    fn handle_candidate_events(&mut self, events: Vec<CandidateEvent>) {
+        for event in events {
+            match event {
+                CandidateEvent::New(address) => {
+                    log::debug!("New external address candidate {}.", address);
+                    self.behaviour.inject_new_external_addr_candidate(&address);
+                }
+                CandidateEvent::Confirmed(address) => {
+                    log::debug!("Confirmed external address {}.", address);
+                    self.behaviour.inject_external_addr_confirmed(&address);
+                    if !self.external_addrs.iter().any(|r| r.addr == address) {
+                        self.external_addr_candidates.set_added(&address);
+                        self.add_external_address(address, AddressScore::Infinite);
+                    }
+                }
+                CandidateEvent::Expired { address, added } => {
+                    log::debug!("External address {} expired.", address);
+                    self.behaviour.inject_external_addr_expired(&address);
+                    if added {
+                        self.remove_external_address(&address);
+                    }
+                }
+            }
+        }
+        if self.external_addr_expiry.is_none() {
+            self.reset_external_addr_expiry();
+        }
+    }
+
+    fn reset_external_addr_expiry(&mut self) {
+        self.external_addr_expiry = self
+            .external_addr_candidates
+            .next_expiry()
+            .map(|at| Delay::new(at.saturating_duration_since(time::now())));
+    }
+
+    /// Expires outdated observations of external address candidates.
+    fn poll_external_addr_expiry(&mut self, cx: &mut Context<'_>) {
+        while let Some(delay) = self.external_addr_expiry.as_mut() {
+            if delay.poll_unpin(cx).is_pending() {
+                return;
+            }
+            let events = self.external_addr_candidates.expire(time::now());
+            self.reset_external_addr_expiry();
+            self.handle_candidate_events(events);
+        }
+    }
+
+    /// Periodically removes expired addresses and peers that are neither
+    /// reachable nor connected from the [`PeerStore`].
+    fn poll_peer_store_expiry(&mut self, cx: &mut Context<'_>) {
+        while self.peer_store_expiry.poll_unpin(cx).is_ready() {
+            self.peer_store.remove_expired();
+            let pool = &self.pool;
+            self.peer_store
+                .remove_unreachable(|peer| pool.is_connected(*peer));
+            self.peer_store_expiry.reset(PEER_STORE_EXPIRY_INTERVAL);
+        }
+    }
+
     /// Bans a peer by its peer ID.
     ///
     /// Any incoming connection and any dialing attempt will immediately be rejected.
-    /// This function has no effect if the peer is already banned.
+    /// This function has no effect if the peer is already banned, except for
+    /// making a ban given via [`Swarm::ban_peer_id_for`] permanent.
     pub fn ban_peer_id(&mut self, peer_id: PeerId) {
-        if self.banned_peers.insert(peer_id) {
-            // Note that established connections to the now banned peer are closed but not
-            // added to [`Swarm::banned_peer_connections`]. They have been previously reported
-            // as open to the behaviour and need be reported as closed once closing the
-            // connection finishes.
-            self.pool.disconnect(peer_id);
-        }
+        self.ban(BanTarget::Peer(peer_id), None)
+    }
+
+    /// Bans a peer by its peer ID for the given duration, see
+    /// [`Swarm::ban_peer_id`].
+    ///
+    /// Once the duration elapsed, the peer is unbanned and
+    /// [`SwarmEvent::BanExpired`] is reported. Banning an already banned
+    /// peer replaces the expiry of its ban.
+    pub fn ban_peer_id_for(&mut self, peer_id: PeerId, duration: Duration) {
+        self.ban(BanTarget::Peer(peer_id), Some(time::now() + duration))
     }
 
     /// Unbans a peer.
     pub fn unban_peer_id(&mut self, peer_id: PeerId) {
-        self.banned_peers.remove(&peer_id);
+        self.bans.unban(&BanTarget::Peer(peer_id));
+    }
+
+    /// Bans all IP addresses of the given network, e.g. parsed from
+    /// `"203.0.113.0/24"` or a single address `"203.0.113.7"`.
+    ///
+    /// Established connections to addresses of the network are closed.
+    /// Connections accepted by a listener from an address of the network are
+    /// closed before upgrading them and reported as [`SwarmEvent::BannedIp`].
+    /// Addresses of the network are skipped when dialing, failing the dial
+    /// with [`DialError::Banned`] if no other address remains.
+    ///
+    /// Only addresses starting with an IP address are matched, i.e. not
+    /// addresses like `/dns4/example.com/tcp/4001`.
+    pub fn ban_ip(&mut self, ip_net: IpNet) {
+        self.ban(BanTarget::Ip(ip_net), None)
+    }
+
+    /// Bans all IP addresses of the given network for the given duration, see
+    /// [`Swarm::ban_ip`].
+    ///
+    /// Once the duration elapsed, the network is unbanned and
+    /// [`SwarmEvent::BanExpired`] is reported. Banning an already banned
+    /// network replaces the expiry of its ban.
+    pub fn ban_ip_for(&mut self, ip_net: IpNet, duration: Duration) {
+        self.ban(BanTarget::Ip(ip_net), Some(time::now() + duration))
+    }
+
+    /// Unbans an IP network banned via [`Swarm::ban_ip`] or
+    /// [`Swarm::ban_ip_for`].
+    ///
+    /// Addresses of the network remain banned if they are part of another
+    /// banned network.
+    pub fn unban_ip(&mut self, ip_net: IpNet) {
+        self.bans.unban(&BanTarget::Ip(ip_net));
+    }
+
+    /// Returns an iterator over the banned peers, each with the instant its
+    /// ban expires at, if any.
+    pub fn banned_peers(&self) -> impl Iterator<Item = (&PeerId, Option<Instant>)> {
+        self.bans.iter_peers()
+    }
+
+    /// Returns an iterator over the banned IP networks, each with the instant
+    /// its ban expires at, if any.
+    pub fn banned_ips(&self) -> impl Iterator<Item = (&IpNet, Option<Instant>)> {
+        self.bans.iter_ips()
+    }
+
+    fn ban(&mut self, target: BanTarget, until: Option<Instant>) {
+        let is_new = self.bans.ban(target, until);
+        self.ban_expiry = self
+            .bans
+            .next_expiry()
+            .map(|at| Delay::new(at.saturating_duration_since(time::now())));
+
+        // Note that established connections to the now banned peer or network are closed but
+        // not added to [`Swarm::banned_peer_connections`]. They have been previously reported
+        // as open to the behaviour and need be reported as closed once closing the
+        // connection finishes.
+        match target {
+            BanTarget::Peer(peer_id) if is_new => self.pool.disconnect(peer_id),
+            BanTarget::Peer(_) => {}
+            BanTarget::Ip(ip_net) => {
+                let banned = self
+                    .pool
+                    .iter_established_info()
+                    .filter(|info| ip_net.contains_addr(info.endpoint().get_remote_address()))
+                    .map(|info| info.id())
+                    .collect::<Vec<_>>();
+                for id in banned {
+                    if let Some(conn) = self.pool.get_established(id) {
+                        conn.start_close();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lifts a ban whose duration elapsed, if any.
+    fn poll_ban_expiry(&mut self, cx: &mut Context<'_>) -> Option<BanTarget> {
+        let delay = self.ban_expiry.as_mut()?;
+        if delay.poll_unpin(cx).is_pending() {
+            return None;
+        }
+        let expired = self.bans.pop_expired(time::now());
+        self.ban_expiry = self
+            .bans
+            .next_expiry()
+            .map(|at| Delay::new(at.saturating_duration_since(time::now())));
+        expired
     }
 
     /// Disconnects a peer by its peer ID, closing all connections to said peer.
@@ -630,6 +1138,84 @@ where
         self.pool.iter_connected()
     }
 
+    /// Returns information about each established connection, such as the
+    /// substreams open per protocol, the [`KeepAlive`] of its handler and its
+    /// last activity.
+    pub fn connections(&self) -> impl Iterator<Item = ConnectionInfo> + '_ {
+        self.pool.iter_established_info()
+    }
+
+    /// Starts a graceful shutdown of the [`Swarm`].
+    ///
+    /// All listeners are removed and the behaviour is informed via
+    /// [`NetworkBehaviour::inject_shutdown`]. Once the behaviour is idle, pending
+    /// connections are aborted and established connections stop accepting
+    /// inbound substreams. Each connection is closed as soon as its handler
+    /// finished its in-flight work, at the latest once `timeout` elapsed.
+    /// Dials are rejected with [`DialError::Aborted`] from now on.
+    ///
+    /// The shutdown makes progress while the [`Swarm`] is polled, reporting
+    /// [`SwarmEvent::ListenerClosed`] and [`SwarmEvent::ConnectionClosed`] for
+    /// every listener and connection. The [`Swarm`] terminates as a [`Stream`]
+    /// once all of them are closed. See also [`Swarm::poll_close`].
+    ///
+    /// Has no effect if a shutdown is already in progress.
+    pub fn close(&mut self, timeout: Duration) {
+        if !matches!(self.shutdown, Shutdown::None) {
+            return;
+        }
+
+        let listener_ids = self.listeners.listener_ids().collect::<Vec<_>>();
+        for id in listener_ids {
+            self.listeners.remove_listener(id);
+        }
+        let queued_dials = self.dial_queue.drain().collect::<Vec<_>>();
+        for dial in queued_dials {
+            self.behaviour
+                .inject_dial_failure(dial.peer_id, dial.handler, &DialError::Aborted);
+        }
+        self.behaviour.inject_shutdown();
+        self.shutdown = Shutdown::Requested(timeout);
+    }
+
+    /// Drives a graceful shutdown of the [`Swarm`] to completion.
+    ///
+    /// Starts the shutdown with a drain timeout of [`DEFAULT_CLOSE_TIMEOUT`]
+    /// unless [`Swarm::close`] has been called before. Returns the events
+    /// emitted while shutting down, e.g. [`SwarmEvent::ConnectionClosed`], and
+    /// `None` once all listeners and connections are closed.
+    pub fn poll_close(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<SwarmEvent<TBehaviourOutEvent<TBehaviour>, THandlerErr<TBehaviour>>>> {
+        if matches!(self.shutdown, Shutdown::None) {
+            self.close(DEFAULT_CLOSE_TIMEOUT);
+        }
+        self.poll_next_unpin(cx)
+    }
+
+    /// Advances a shutdown started via [`Swarm::close`], once the [`Swarm`]
+    /// has no other work to do.
+    ///
+    /// Returns `true` if the shutdown made progress.
+    fn advance_shutdown(&mut self) -> bool {
+        match self.shutdown {
+            Shutdown::Requested(timeout) if self.pending_event.is_none() => {
+                self.pool.start_drain(timeout);
+                self.shutdown = Shutdown::Draining(time::now() + timeout);
+                true
+            }
+            Shutdown::Draining(_)
+                if self.pool.counters().num_connections() == 0
+                    && self.listeners.listener_ids().next().is_none() =>
+            {
+                self.shutdown = Shutdown::Done;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Returns a reference to the provided [`NetworkBehaviour`].
     pub fn behaviour(&self) -> &TBehaviour {
         &self.behaviour
@@ -655,6 +1241,19 @@ where
             let mut listeners_not_ready = false;
             let mut connections_not_ready = false;
 
+            this.poll_external_addr_expiry(cx);
+            this.poll_peer_store_expiry(cx);
+
+            if let Some(target) = this.poll_ban_expiry(cx) {
+                log::debug!("Ban of {:?} expired.", target);
+                return Poll::Ready(SwarmEvent::BanExpired { target });
+            }
+
+            // Start queued dials as other dials complete.
+            if let Some(event) = this.poll_dial_queue() {
+                return Poll::Ready(event);
+            }
+
             // Poll the listener(s) for new connections.
             match ListenersStream::poll(Pin::new(&mut this.listeners), cx) {
                 Poll::Pending => {
@@ -666,6 +1265,18 @@ where
                     local_addr,
                     send_back_addr,
                 }) => {
+                    if let Some(ip_net) = this.bans.banned_net_of(&send_back_addr) {
+                        log::debug!(
+                            "Incoming connection from {} in banned network {} rejected.",
+                            send_back_addr,
+                            ip_net
+                        );
+                        return Poll::Ready(SwarmEvent::BannedIp {
+                            ip_net,
+                            local_addr,
+                            send_back_addr,
+                        });
+                    }
                     let handler = this.behaviour.new_handler();
                     match this.pool.add_incoming(
                         upgrade,
@@ -681,7 +1292,10 @@ where
                                 send_back_addr,
                             });
                         }
-                        Err((connection_limit, handler)) => {
+                        Err((
+                            PendingConnectionError::ConnectionLimit(connection_limit),
+                            handler,
+                        )) => {
                             this.behaviour.inject_listen_failure(
                                 &local_addr,
                                 &send_back_addr,
@@ -689,6 +1303,19 @@ where
                             );
                             log::warn!("Incoming connection rejected: {:?}", connection_limit);
                         }
+                        Err((error, handler)) => {
+                            log::debug!("Incoming connection rejected: {:?}", error);
+                            this.behaviour.inject_listen_failure(
+                                &local_addr,
+                                &send_back_addr,
+                                handler,
+                            );
+                            return Poll::Ready(SwarmEvent::IncomingConnectionError {
+                                local_addr,
+                                send_back_addr,
+                                error,
+                            });
+                        }
                     };
                 }
                 Poll::Ready(ListenersEvent::NewAddress {
@@ -729,6 +1356,12 @@ where
                     reason,
                 }) => {
                     log::debug!("Listener {:?}; Closed by {:?}.", listener_id, reason);
+                    let reason = reason.map_err(|error| match error {
+                        TransportError::Other(error) => error,
+                        error @ TransportError::MultiaddrNotSupported(_) => {
+                            io::Error::new(io::ErrorKind::Other, error.to_string())
+                        }
+                    });
                     for addr in addresses.iter() {
                         this.behaviour.inject_expired_listen_addr(listener_id, addr);
                     }
@@ -763,12 +1396,27 @@ where
                 }) => {
                     let peer_id = connection.peer_id();
                     let endpoint = connection.endpoint().clone();
-                    if this.banned_peers.contains(&peer_id) {
+                    if let ConnectedPoint::Dialer { address, .. } = &endpoint {
+                        this.dial_backoff.record_success(Some(peer_id), address);
+                    }
+                    for (address, _) in concurrent_dial_errors.iter().flatten() {
+                        this.dial_backoff.record_failure(Some(peer_id), address);
+                    }
+                    if this.bans.is_peer_banned(&peer_id) {
                         // Mark the connection for the banned peer as banned, thus withholding any
                         // future events from the connection to the behaviour.
                         this.banned_peer_connections.insert(connection.id());
                         this.pool.disconnect(peer_id);
                         return Poll::Ready(SwarmEvent::BannedPeer { peer_id, endpoint });
+                    } else if this
+                        .bans
+                        .banned_net_of(endpoint.get_remote_address())
+                        .is_some()
+                    {
+                        // The network has been banned while the connection was pending.
+                        this.banned_peer_connections.insert(connection.id());
+                        connection.start_close();
+                        return Poll::Ready(SwarmEvent::BannedPeer { peer_id, endpoint });
                     } else {
                         let num_established = NonZeroU32::new(
                             u32::try_from(other_established_connection_ids.len() + 1).unwrap(),
@@ -787,6 +1435,18 @@ where
                             non_banned_established + 1,
                         );
                         let endpoint = connection.endpoint().clone();
+                        let negotiated_protocols = connection.negotiated_protocols().clone();
+                        this.peer_store.record_seen(peer_id);
+                        if let ConnectedPoint::Dialer { address, .. } = &endpoint {
+                            this.peer_store.add_address(
+                                peer_id,
+                                address.clone(),
+                                AddressSource::Connection,
+                                Some(peer_store::RECENTLY_CONNECTED_ADDRESS_TTL),
+                            );
+                        }
+                        this.connection_manager
+                            .on_connection_established(connection.id(), peer_id);
                         let failed_addresses = concurrent_dial_errors
                             .as_ref()
                             .map(|es| es.iter().map(|(a, _)| a).cloned().collect());
@@ -794,14 +1454,22 @@ where
                             &peer_id,
                             &connection.id(),
                             &endpoint,
+                            &negotiated_protocols,
                             failed_addresses.as_ref(),
                             non_banned_established,
                         );
+                        if let Shutdown::Draining(deadline) = this.shutdown {
+                            let timeout = deadline
+                                .checked_duration_since(time::now())
+                                .unwrap_or_default();
+                            connection.start_drain(timeout);
+                        }
                         return Poll::Ready(SwarmEvent::ConnectionEstablished {
                             peer_id,
                             num_established,
                             endpoint,
                             concurrent_dial_errors,
+                            negotiated_protocols,
                         });
                     }
                 }
@@ -811,6 +1479,28 @@ where
                     handler,
                     peer,
                 }) => {
+                    // A dial fails with `DeniedSecured` if the gater denied
+                    // the connection to every address.
+                    let error = match error {
+                        PendingConnectionError::Transport(errors) => {
+                            match denied_secured_dial(&errors) {
+                                Some((address, peer_id)) => PendingConnectionError::DeniedSecured {
+                                    peer_id,
+                                    endpoint: ConnectedPoint::Dialer {
+                                        address,
+                                        role_override: Endpoint::Dialer,
+                                    },
+                                },
+                                None => PendingConnectionError::Transport(errors),
+                            }
+                        }
+                        error => error,
+                    };
+                    if let PendingConnectionError::Transport(errors) = &error {
+                        for (address, _) in errors {
+                            this.dial_backoff.record_failure(peer, address);
+                        }
+                    }
                     let error = error.into();
 
                     this.behaviour.inject_dial_failure(peer, handler, &error);
@@ -833,6 +1523,20 @@ where
                     error,
                     handler,
                 }) => {
+                    let error = match error {
+                        PendingConnectionError::Transport(TransportError::Other(e))
+                            if denied_secured(&e).is_some() =>
+                        {
+                            PendingConnectionError::DeniedSecured {
+                                peer_id: denied_secured(&e).expect("Checked above."),
+                                endpoint: ConnectedPoint::Listener {
+                                    local_addr: local_addr.clone(),
+                                    send_back_addr: send_back_addr.clone(),
+                                },
+                            }
+                        }
+                        error => error,
+                    };
                     log::debug!("Incoming connection failed: {:?}", error);
                     this.behaviour
                         .inject_listen_failure(&local_addr, &send_back_addr, handler);
@@ -868,8 +1572,18 @@ where
                     let endpoint = connected.endpoint;
                     let num_established =
                         u32::try_from(remaining_established_connection_ids.len()).unwrap();
+                    this.connection_manager.on_connection_closed(&id);
                     let conn_was_reported = !this.banned_peer_connections.remove(&id);
                     if conn_was_reported {
+                        this.peer_store.record_seen(peer_id);
+                        if let ConnectedPoint::Dialer { address, .. } = &endpoint {
+                            this.peer_store.add_address(
+                                peer_id,
+                                address.clone(),
+                                AddressSource::Connection,
+                                Some(peer_store::RECENTLY_CONNECTED_ADDRESS_TTL),
+                            );
+                        }
                         let remaining_non_banned = remaining_established_connection_ids
                             .into_iter()
                             .filter(|conn_id| !this.banned_peer_connections.contains(&conn_id))
@@ -898,6 +1612,42 @@ where
                         this.behaviour.inject_event(peer, conn_id, event);
                     }
                 }
+                Poll::Ready(PoolEvent::InboundStreamLimitExceeded {
+                    connection,
+                    protocol,
+                    limit,
+                }) => {
+                    let peer_id = connection.peer_id();
+                    log::debug!(
+                        "Reset inbound substream of {} for {:?} exceeding {}.",
+                        peer_id,
+                        protocol,
+                        limit
+                    );
+                    return Poll::Ready(SwarmEvent::InboundStreamLimitExceeded {
+                        peer_id,
+                        endpoint: connection.endpoint().clone(),
+                        protocol,
+                        limit,
+                    });
+                }
+                Poll::Ready(PoolEvent::ProtocolsChange {
+                    connection,
+                    local,
+                    change,
+                }) => {
+                    let peer = connection.peer_id();
+                    let conn_id = connection.id();
+                    if !this.banned_peer_connections.contains(&conn_id) {
+                        if local {
+                            this.behaviour
+                                .inject_local_protocols_change(&peer, &conn_id, &change);
+                        } else {
+                            this.behaviour
+                                .inject_remote_protocols_change(&peer, &conn_id, &change);
+                        }
+                    }
+                }
                 Poll::Ready(PoolEvent::AddressChange {
                     connection,
                     new_endpoint,
@@ -916,6 +1666,26 @@ where
                 }
             };
 
+            // Close the connections the connection manager decided to trim.
+            if let Poll::Ready((peer_id, id, reason)) = this.connection_manager.poll(cx) {
+                if let Some(conn) = this.pool.get_established(id) {
+                    let endpoint = conn.endpoint().clone();
+                    conn.start_close();
+                    log::debug!(
+                        "Trimming connection {:?} to {:?}: {:?}",
+                        id,
+                        peer_id,
+                        reason
+                    );
+                    return Poll::Ready(SwarmEvent::ConnectionTrimmed {
+                        peer_id,
+                        endpoint,
+                        reason,
+                    });
+                }
+                continue;
+            }
+
             // After the network had a chance to make progress, try to deliver
             // the pending event emitted by the behaviour in the previous iteration
             // to the connection handler(s). The pending event must be delivered
@@ -959,6 +1729,9 @@ where
                     supported_protocols: &this.supported_protocols,
                     listened_addrs: &this.listened_addrs,
                     external_addrs: &this.external_addrs,
+                    external_addr_candidates: &this.external_addr_candidates,
+                    peer_store: &mut this.peer_store,
+                    connection_manager: &mut this.connection_manager,
                 };
                 this.behaviour.poll(cx, &mut parameters)
             };
@@ -1016,37 +1789,35 @@ where
                     }
                 },
                 Poll::Ready(NetworkBehaviourAction::ReportObservedAddr { address, score }) => {
-                    // Maps the given `observed_addr`, representing an address of the local
-                    // node observed by a remote peer, onto the locally known listen addresses
-                    // to yield one or more addresses of the local node that may be publicly
-                    // reachable.
-                    //
-                    // I.e. this method incorporates the view of other peers into the listen
-                    // addresses seen by the local node to account for possible IP and port
-                    // mappings performed by intermediate network devices in an effort to
-                    // obtain addresses for the local peer that are also reachable for peers
-                    // other than the peer who reported the `observed_addr`.
+                    // Incorporates the view of other peers into the listen addresses seen by
+                    // the local node to account for possible IP and port mappings performed by
+                    // intermediate network devices.
                     //
                     // The translation is transport-specific. See [`Transport::address_translation`].
-                    let translated_addresses = {
-                        let transport = this.listeners.transport();
-                        let mut addrs: Vec<_> = this
-                            .listeners
-                            .listen_addrs()
-                            .filter_map(move |server| {
-                                transport.address_translation(server, &address)
-                            })
-                            .collect();
-
-                        // remove duplicates
-                        addrs.sort_unstable();
-                        addrs.dedup();
-                        addrs
-                    };
-                    for addr in translated_addresses {
+                    for addr in this.translate_observed_addr(&address) {
                         this.add_external_address(addr, score);
                     }
                 }
+                Poll::Ready(NetworkBehaviourAction::ReportExternalAddrCandidate {
+                    address,
+                    observer,
+                }) => {
+                    let subnet = this
+                        .pool
+                        .iter_established_endpoints_of_peer(&observer)
+                        .find_map(|endpoint| Subnet::of(endpoint.get_remote_address()));
+                    let now = time::now();
+                    for addr in this.translate_observed_addr(&address) {
+                        let events = this
+                            .external_addr_candidates
+                            .report(addr, observer, subnet, now);
+                        this.handle_candidate_events(events);
+                    }
+                }
+                Poll::Ready(NetworkBehaviourAction::ConfirmExternalAddr { address }) => {
+                    let events = this.external_addr_candidates.confirm(address, time::now());
+                    this.handle_candidate_events(events);
+                }
                 Poll::Ready(NetworkBehaviourAction::CloseConnection {
                     peer_id,
                     connection,
@@ -1159,8 +1930,10 @@ where
 /// Includes events from the [`NetworkBehaviour`] as well as events about
 /// connection and listener status. See [`SwarmEvent`] for details.
 ///
-/// Note: This stream is infinite and it is guaranteed that
-/// [`Stream::poll_next`] will never return `Poll::Ready(None)`.
+/// Note: This stream only ends after a graceful shutdown has been started
+/// via [`Swarm::close`] or [`Swarm::poll_close`]. [`Stream::poll_next`] then
+/// returns `Poll::Ready(None)` once all listeners and connections are closed,
+/// and never before.
 impl<TBehaviour> Stream for Swarm<TBehaviour>
 where
     TBehaviour: NetworkBehaviour,
@@ -1168,20 +1941,47 @@ where
     type Item = SwarmEvent<TBehaviourOutEvent<TBehaviour>, THandlerErr<TBehaviour>>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.as_mut().poll_next_event(cx).map(Some)
+        loop {
+            if let Shutdown::Done = self.shutdown {
+                return Poll::Ready(None);
+            }
+
+            match self.as_mut().poll_next_event(cx) {
+                Poll::Ready(event) => return Poll::Ready(Some(event)),
+                Poll::Pending => {
+                    if !self.advance_shutdown() {
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
     }
 }
 
-/// The stream of swarm events never terminates, so we can implement fused for it.
+/// The stream of swarm events only terminates once the swarm has been closed
+/// via [`Swarm::close`].
 impl<TBehaviour> FusedStream for Swarm<TBehaviour>
 where
     TBehaviour: NetworkBehaviour,
 {
     fn is_terminated(&self) -> bool {
-        false
+        matches!(self.shutdown, Shutdown::Done)
     }
 }
 
+/// A dial waiting for a free slot, see [`SwarmBuilder::max_concurrent_dials`].
+struct QueuedDial<THandler> {
+    peer_id: Option<PeerId>,
+    /// The condition to check again before starting the dial, if dialing a
+    /// known peer.
+    condition: Option<PeerCondition>,
+    /// The ranked addresses to dial.
+    addresses: Vec<(Multiaddr, Duration)>,
+    handler: THandler,
+    role_override: Endpoint,
+    dial_concurrency_factor_override: Option<NonZeroU8>,
+}
+
 /// Parameters passed to `poll()`, that the `NetworkBehaviour` has access to.
 // TODO: #[derive(Debug)]
 pub struct SwarmPollParameters<'a> {
@@ -1189,6 +1989,9 @@ pub struct SwarmPollParameters<'a> {
     supported_protocols: &'a [Vec<u8>],
     listened_addrs: &'a [Multiaddr],
     external_addrs: &'a Addresses,
+    external_addr_candidates: &'a ExternalAddrCandidates,
+    peer_store: &'a mut PeerStore,
+    connection_manager: &'a mut ConnectionManager,
 }
 
 impl<'a> PollParameters for SwarmPollParameters<'a> {
@@ -1208,9 +2011,33 @@ impl<'a> PollParameters for SwarmPollParameters<'a> {
         self.external_addrs.clone().into_iter()
     }
 
+    fn external_address_candidates(&self) -> std::vec::IntoIter<Multiaddr> {
+        self.external_addr_candidates
+            .unconfirmed()
+            .cloned()
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
     fn local_peer_id(&self) -> &PeerId {
         self.local_peer_id
     }
+
+    fn peer_store(&self) -> &PeerStore {
+        self.peer_store
+    }
+
+    fn peer_store_mut(&mut self) -> &mut PeerStore {
+        self.peer_store
+    }
+
+    fn connection_manager(&self) -> &ConnectionManager {
+        self.connection_manager
+    }
+
+    fn connection_manager_mut(&mut self) -> &mut ConnectionManager {
+        self.connection_manager
+    }
 }
 
 /// A [`SwarmBuilder`] provides an API for configuring and constructing a [`Swarm`].
@@ -1220,6 +2047,12 @@ pub struct SwarmBuilder<TBehaviour> {
     behaviour: TBehaviour,
     pool_config: PoolConfig,
     connection_limits: ConnectionLimits,
+    peer_store: PeerStore,
+    connection_manager: Option<ConnectionManagerConfig>,
+    dial_backoff: Option<DialBackoffConfig>,
+    address_ranking: Arc<dyn AddressRanking>,
+    external_addr_config: ExternalAddrConfig,
+    max_concurrent_dials: Option<NonZeroUsize>,
 }
 
 impl<TBehaviour> SwarmBuilder<TBehaviour>
@@ -1240,6 +2073,12 @@ where
             behaviour,
             pool_config: Default::default(),
             connection_limits: Default::default(),
+            peer_store: Default::default(),
+            connection_manager: None,
+            dial_backoff: None,
+            address_ranking: Arc::new(DefaultAddressRanking::default()),
+            external_addr_config: Default::default(),
+            max_concurrent_dials: None,
         }
     }
 
@@ -1300,12 +2139,105 @@ where
         self
     }
 
+    /// Configures the maximum number of concurrent outbound connection
+    /// attempts across all peers.
+    ///
+    /// Dials exceeding the limit are queued instead of failed and started by
+    /// their [`DialPriority`](dial_opts::DialPriority) once other dials
+    /// complete. The number of queued dials is reported by
+    /// [`NetworkInfo::num_queued_dials`]. Unlike
+    /// [`ConnectionLimits::with_max_pending_outgoing`], which denies dials
+    /// exceeding its limit.
+    ///
+    /// By default, the number of concurrent dials is not limited.
+    pub fn max_concurrent_dials(mut self, limit: NonZeroUsize) -> Self {
+        self.max_concurrent_dials = Some(limit);
+        self
+    }
+
     /// Configures the connection limits.
     pub fn connection_limits(mut self, limits: ConnectionLimits) -> Self {
         self.connection_limits = limits;
         self
     }
 
+    /// Configures the limits on the inbound substreams of each connection.
+    pub fn inbound_stream_limits(mut self, limits: InboundStreamLimits) -> Self {
+        self.pool_config = self.pool_config.with_inbound_stream_limits(limits);
+        self
+    }
+
+    /// Configures the [`ConnectionGater`] consulted at each stage of
+    /// establishing a connection.
+    ///
+    /// Pass the [`SharedConnectionGater`] installed on the transport via
+    /// [`SharedConnectionGater::intercept_secured`], if any. Otherwise
+    /// [`ConnectionGater::intercept_secured`] is consulted once the stream
+    /// multiplexer has been negotiated.
+    pub fn connection_gater(mut self, gater: impl ConnectionGater) -> Self {
+        self.pool_config = self
+            .pool_config
+            .with_connection_gater(SharedConnectionGater::new(gater));
+        self
+    }
+
+    /// Configures resolving multiple connections to the same peer, e.g. after
+    /// both peers dialed each other simultaneously, by keeping a single one.
+    ///
+    /// By default, all connections to a peer are kept.
+    pub fn duplicate_connections(mut self, config: DuplicateConnectionConfig) -> Self {
+        self.pool_config = self.pool_config.with_duplicate_connections(config);
+        self
+    }
+
+    /// Configures the [`ConnectionManager`] to trim established connections
+    /// once their number exceeds the given high watermark.
+    ///
+    /// By default, established connections are never trimmed.
+    pub fn connection_manager(mut self, config: ConnectionManagerConfig) -> Self {
+        self.connection_manager = Some(config);
+        self
+    }
+
+    /// Configures the [`AddressRanking`] deciding the order and pace in which
+    /// the addresses of an outbound connection attempt are dialed.
+    ///
+    /// Defaults to [`DefaultAddressRanking`].
+    pub fn address_ranking(mut self, ranking: impl AddressRanking) -> Self {
+        self.address_ranking = Arc::new(ranking);
+        self
+    }
+
+    /// Enables the backoff of addresses that failed to be dialed.
+    ///
+    /// By default, failed addresses are not backed off. Note that dials for
+    /// which all addresses are backed off fail with [`DialError::Backoff`].
+    pub fn dial_backoff(mut self, config: DialBackoffConfig) -> Self {
+        self.dial_backoff = Some(config);
+        self
+    }
+
+    /// Disables the backoff of addresses that failed to be dialed, the default.
+    pub fn without_dial_backoff(mut self) -> Self {
+        self.dial_backoff = None;
+        self
+    }
+
+    /// Configures the confirmation of external address candidates.
+    ///
+    /// Defaults to [`ExternalAddrConfig::default`].
+    pub fn external_addr_config(mut self, config: ExternalAddrConfig) -> Self {
+        self.external_addr_config = config;
+        self
+    }
+
+    /// Configures the initial [`PeerStore`], e.g. one restored via
+    /// [`PeerStore::from_snapshot`].
+    pub fn peer_store(mut self, peer_store: PeerStore) -> Self {
+        self.peer_store = peer_store;
+        self
+    }
+
     /// Configures an override for the substream upgrade protocol to use.
     ///
     /// The subtream upgrade protocol is the multistream-select protocol
@@ -1355,9 +2287,19 @@ where
             supported_protocols,
             listened_addrs: SmallVec::new(),
             external_addrs: Addresses::default(),
-            banned_peers: HashSet::new(),
+            external_addr_candidates: ExternalAddrCandidates::new(self.external_addr_config),
+            external_addr_expiry: None,
+            peer_store: self.peer_store,
+            peer_store_expiry: Delay::new(PEER_STORE_EXPIRY_INTERVAL),
+            connection_manager: ConnectionManager::new(self.connection_manager),
+            dial_backoff: DialBackoff::new(self.dial_backoff),
+            dial_queue: DialQueue::new(self.max_concurrent_dials),
+            address_ranking: self.address_ranking,
+            bans: Default::default(),
+            ban_expiry: None,
             banned_peer_connections: HashSet::new(),
             pending_event: None,
+            shutdown: Shutdown::None,
         }
     }
 }
@@ -1372,13 +2314,14 @@ pub enum DialError {
     ConnectionLimit(ConnectionLimit),
     /// The peer being dialed is the local peer and thus the dial was aborted.
     LocalPeerId,
-    /// [`NetworkBehaviour::addresses_of_peer`] returned no addresses
-    /// for the peer to dial.
+    /// Neither [`NetworkBehaviour::addresses_of_peer`] nor the [`PeerStore`]
+    /// returned any addresses for the peer to dial.
     NoAddresses,
     /// The provided [`dial_opts::PeerCondition`] evaluated to false and thus
     /// the dial was aborted.
     DialPeerConditionFalse(dial_opts::PeerCondition),
-    /// Pending connection attempt has been aborted.
+    /// Pending connection attempt has been aborted, or the [`Swarm`] is
+    /// shutting down, see [`Swarm::close`].
     Aborted,
     /// The provided peer identity is invalid.
     InvalidPeerId(Multihash),
@@ -1391,6 +2334,40 @@ pub enum DialError {
     ConnectionIo(io::Error),
     /// An error occurred while negotiating the transport protocol(s) on a connection.
     Transport(Vec<(Multiaddr, TransportError<io::Error>)>),
+    /// The [`ConnectionGater`] denied dialing every address of the peer.
+    DeniedDial,
+    /// Every address of the peer is backed off after failed dials, the
+    /// earliest until the given instant.
+    ///
+    /// See [`DialBackoffConfig`] and [`WithPeerId::bypass_backoff`](dial_opts::WithPeerId::bypass_backoff).
+    Backoff { until: Instant },
+    /// The [`ConnectionGater`] denied the connection once the identity of the
+    /// remote was known.
+    DeniedSecured {
+        obtained: PeerId,
+        endpoint: ConnectedPoint,
+    },
+    /// The [`ConnectionGater`] denied the connection after the stream
+    /// multiplexer was negotiated.
+    DeniedUpgraded {
+        obtained: PeerId,
+        endpoint: ConnectedPoint,
+    },
+}
+
+/// Returns the last address and the peer if every dialed address failed due
+/// to [`ConnectionGater::intercept_secured`] denying the connection.
+fn denied_secured_dial(
+    errors: &[(Multiaddr, TransportError<io::Error>)],
+) -> Option<(Multiaddr, PeerId)> {
+    let mut denied = None;
+    for (address, error) in errors {
+        match error {
+            TransportError::Other(e) => denied = Some((address.clone(), denied_secured(e)?)),
+            TransportError::MultiaddrNotSupported(_) => return None,
+        }
+    }
+    denied
 }
 
 impl From<PendingOutboundConnectionError<io::Error>> for DialError {
@@ -1403,6 +2380,22 @@ impl From<PendingOutboundConnectionError<io::Error>> for DialError {
             }
             PendingConnectionError::IO(e) => DialError::ConnectionIo(e),
             PendingConnectionError::Transport(e) => DialError::Transport(e),
+            PendingConnectionError::DeniedDial => DialError::DeniedDial,
+            // Accepting is only gated for inbound connections, thus never
+            // reached. Reported as the closest outbound equivalent regardless.
+            PendingConnectionError::DeniedAccept => DialError::DeniedDial,
+            PendingConnectionError::DeniedSecured { peer_id, endpoint } => {
+                DialError::DeniedSecured {
+                    obtained: peer_id,
+                    endpoint,
+                }
+            }
+            PendingConnectionError::DeniedUpgraded { peer_id, endpoint } => {
+                DialError::DeniedUpgraded {
+                    obtained: peer_id,
+                    endpoint,
+                }
+            }
         }
     }
 }
@@ -1432,6 +2425,14 @@ impl fmt::Display for DialError {
                 "Dial error: An I/O error occurred on the connection: {:?}.", e
             ),
             DialError::Transport(e) => write!(f, "An error occurred while negotiating the transport protocol(s) on a connection: {:?}.", e),
+            DialError::DeniedDial => write!(f, "Dial error: all addresses denied by connection gater."),
+            DialError::Backoff { until } => write!(
+                f,
+                "Dial error: all addresses backed off, earliest until {:?}.",
+                until
+            ),
+            DialError::DeniedSecured { obtained, endpoint } => write!(f, "Dial error: peer {} at {:?} denied by connection gater after security upgrade.", obtained, endpoint),
+            DialError::DeniedUpgraded { obtained, endpoint } => write!(f, "Dial error: peer {} at {:?} denied by connection gater after muxer upgrade.", obtained, endpoint),
         }
     }
 }
@@ -1449,6 +2450,10 @@ impl error::Error for DialError {
             DialError::WrongPeerId { .. } => None,
             DialError::ConnectionIo(_) => None,
             DialError::Transport(_) => None,
+            DialError::DeniedDial => None,
+            DialError::Backoff { .. } => None,
+            DialError::DeniedSecured { .. } => None,
+            DialError::DeniedUpgraded { .. } => None,
         }
     }
 }
@@ -1512,6 +2517,8 @@ pub struct NetworkInfo {
     num_peers: usize,
     /// Counters of ongoing network connections.
     connection_counters: ConnectionCounters,
+    /// The number of dials waiting for a free slot.
+    num_queued_dials: usize,
 }
 
 impl NetworkInfo {
@@ -1525,42 +2532,23 @@ impl NetworkInfo {
     pub fn connection_counters(&self) -> &ConnectionCounters {
         &self.connection_counters
     }
+
+    /// The number of dials waiting for a free slot, see
+    /// [`SwarmBuilder::max_concurrent_dials`].
+    pub fn num_queued_dials(&self) -> usize {
+        self.num_queued_dials
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::handler::DummyConnectionHandler;
-    use crate::test::{CallTraceBehaviour, MockBehaviour};
-    use futures::executor::block_on;
-    use futures::future::poll_fn;
-    use futures::future::Either;
-    use futures::{executor, future, ready};
-    use libp2p::core::{identity, multiaddr, transport, upgrade};
+    use libp2p::core::{identity, transport, upgrade};
     use libp2p::plaintext;
     use libp2p::yamux;
     use libp2p_core::multiaddr::multiaddr;
-    use libp2p_core::transport::ListenerEvent;
-    use libp2p_core::Endpoint;
-    use quickcheck::{quickcheck, Arbitrary, Gen, QuickCheck};
-    use rand::prelude::SliceRandom;
-    use rand::Rng;
-
-    // Test execution state.
-    // Connection => Disconnecting => Connecting.
-    enum State {
-        Connecting,
-        Disconnecting,
-    }
-
-    fn new_test_swarm<T, O>(
-        handler_proto: T,
-    ) -> SwarmBuilder<CallTraceBehaviour<MockBehaviour<T, O>>>
-    where
-        T: ConnectionHandler + Clone,
-        T::OutEvent: Clone,
-        O: Send + 'static,
-    {
+
+    fn new_swarm() -> Swarm<DummyBehaviour> {
         let id_keys = identity::Keypair::generate_ed25519();
         let local_public_key = id_keys.public();
         let transport = transport::MemoryTransport::default()
@@ -1570,871 +2558,44 @@ mod tests {
             })
             .multiplex(yamux::YamuxConfig::default())
             .boxed();
-        let behaviour = CallTraceBehaviour::new(MockBehaviour::new(handler_proto));
-        SwarmBuilder::new(transport, behaviour, local_public_key.into())
-    }
-
-    fn swarms_connected<TBehaviour>(
-        swarm1: &Swarm<CallTraceBehaviour<TBehaviour>>,
-        swarm2: &Swarm<CallTraceBehaviour<TBehaviour>>,
-        num_connections: usize,
-    ) -> bool
-    where
-        TBehaviour: NetworkBehaviour,
-        <<TBehaviour::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent: Clone,
-    {
-        swarm1
-            .behaviour()
-            .num_connections_to_peer(*swarm2.local_peer_id())
-            == num_connections
-            && swarm2
-                .behaviour()
-                .num_connections_to_peer(*swarm1.local_peer_id())
-                == num_connections
-            && swarm1.is_connected(swarm2.local_peer_id())
-            && swarm2.is_connected(swarm1.local_peer_id())
-    }
-
-    fn swarms_disconnected<TBehaviour: NetworkBehaviour>(
-        swarm1: &Swarm<CallTraceBehaviour<TBehaviour>>,
-        swarm2: &Swarm<CallTraceBehaviour<TBehaviour>>,
-    ) -> bool
-    where
-        TBehaviour: NetworkBehaviour,
-        <<TBehaviour::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent: Clone
-    {
-        swarm1
-            .behaviour()
-            .num_connections_to_peer(*swarm2.local_peer_id())
-            == 0
-            && swarm2
-                .behaviour()
-                .num_connections_to_peer(*swarm1.local_peer_id())
-                == 0
-            && !swarm1.is_connected(swarm2.local_peer_id())
-            && !swarm2.is_connected(swarm1.local_peer_id())
-    }
-
-    /// Establishes multiple connections between two peers,
-    /// after which one peer bans the other.
-    ///
-    /// The test expects both behaviours to be notified via pairs of
-    /// inject_connected / inject_disconnected as well as
-    /// inject_connection_established / inject_connection_closed calls
-    /// while unbanned.
-    ///
-    /// While the ban is in effect, further dials occur. For these connections no
-    /// `inject_connected`, `inject_connection_established`, `inject_disconnected`,
-    /// `inject_connection_closed` calls should be registered.
-    #[test]
-    fn test_connect_disconnect_ban() {
-        // Since the test does not try to open any substreams, we can
-        // use the dummy protocols handler.
-        let handler_proto = DummyConnectionHandler {
-            keep_alive: KeepAlive::Yes,
-        };
-
-        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
-        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
-
-        let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-
-        swarm1.listen_on(addr1.clone()).unwrap();
-        swarm2.listen_on(addr2.clone()).unwrap();
-
-        let swarm1_id = *swarm1.local_peer_id();
-
-        enum Stage {
-            /// Waiting for the peers to connect. Banning has not occurred.
-            Connecting,
-            /// Ban occurred.
-            Banned,
-            // Ban is in place and a dial is ongoing.
-            BannedDial,
-            // Mid-ban dial was registered and the peer was unbanned.
-            Unbanned,
-            // There are dial attempts ongoing for the no longer banned peers.
-            Reconnecting,
-        }
-
-        let num_connections = 10;
-
-        for _ in 0..num_connections {
-            swarm1.dial(addr2.clone()).unwrap();
-        }
-
-        let mut s1_expected_conns = num_connections;
-        let mut s2_expected_conns = num_connections;
-
-        let mut stage = Stage::Connecting;
-
-        executor::block_on(future::poll_fn(move |cx| loop {
-            let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
-            let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
-            match stage {
-                Stage::Connecting => {
-                    if swarm1.behaviour.assert_connected(s1_expected_conns, 1)
-                        && swarm2.behaviour.assert_connected(s2_expected_conns, 1)
-                    {
-                        // Setup to test that already established connections are correctly closed
-                        // and reported as such after the peer is banned.
-                        swarm2.ban_peer_id(swarm1_id);
-                        stage = Stage::Banned;
-                    }
-                }
-                Stage::Banned => {
-                    if swarm1.behaviour.assert_disconnected(s1_expected_conns, 1)
-                        && swarm2.behaviour.assert_disconnected(s2_expected_conns, 1)
-                    {
-                        // Setup to test that new connections of banned peers are not reported.
-                        swarm1.dial(addr2.clone()).unwrap();
-                        s1_expected_conns += 1;
-                        stage = Stage::BannedDial;
-                    }
-                }
-                Stage::BannedDial => {
-                    if swarm2.network_info().num_peers() == 1 {
-                        // The banned connection was established. Check that it was not reported to
-                        // the behaviour of the banning swarm.
-                        assert_eq!(
-                            swarm2.behaviour.inject_connection_established.len(), s2_expected_conns,
-                            "No additional closed connections should be reported for the banned peer"
-                        );
-
-                        // Setup to test that the banned connection is not reported upon closing
-                        // even if the peer is unbanned.
-                        swarm2.unban_peer_id(swarm1_id);
-                        stage = Stage::Unbanned;
-                    }
-                }
-                Stage::Unbanned => {
-                    if swarm2.network_info().num_peers() == 0 {
-                        // The banned connection has closed. Check that it was not reported.
-                        assert_eq!(
-                            swarm2.behaviour.inject_connection_closed.len(), s2_expected_conns,
-                            "No additional closed connections should be reported for the banned peer"
-                        );
-                        assert!(swarm2.banned_peer_connections.is_empty());
-
-                        // Setup to test that a ban lifted does not affect future connections.
-                        for _ in 0..num_connections {
-                            swarm1.dial(addr2.clone()).unwrap();
-                        }
-                        s1_expected_conns += num_connections;
-                        s2_expected_conns += num_connections;
-                        stage = Stage::Reconnecting;
-                    }
-                }
-                Stage::Reconnecting => {
-                    if swarm1.behaviour.inject_connection_established.len() == s1_expected_conns
-                        && swarm2.behaviour.assert_connected(s2_expected_conns, 2)
-                    {
-                        return Poll::Ready(());
-                    }
-                }
-            }
-
-            if poll1.is_pending() && poll2.is_pending() {
-                return Poll::Pending;
-            }
-        }))
-    }
-
-    /// Establishes multiple connections between two peers,
-    /// after which one peer disconnects the other using [`Swarm::disconnect_peer_id`].
-    ///
-    /// The test expects both behaviours to be notified via pairs of
-    /// inject_connected / inject_disconnected as well as
-    /// inject_connection_established / inject_connection_closed calls.
-    #[test]
-    fn test_swarm_disconnect() {
-        // Since the test does not try to open any substreams, we can
-        // use the dummy protocols handler.
-        let handler_proto = DummyConnectionHandler {
-            keep_alive: KeepAlive::Yes,
-        };
-
-        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
-        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
-
-        let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-
-        swarm1.listen_on(addr1.clone()).unwrap();
-        swarm2.listen_on(addr2.clone()).unwrap();
-
-        let swarm1_id = *swarm1.local_peer_id();
-
-        let mut reconnected = false;
-        let num_connections = 10;
-
-        for _ in 0..num_connections {
-            swarm1.dial(addr2.clone()).unwrap();
-        }
-        let mut state = State::Connecting;
-
-        executor::block_on(future::poll_fn(move |cx| loop {
-            let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
-            let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
-            match state {
-                State::Connecting => {
-                    if swarms_connected(&swarm1, &swarm2, num_connections) {
-                        if reconnected {
-                            return Poll::Ready(());
-                        }
-                        swarm2
-                            .disconnect_peer_id(swarm1_id)
-                            .expect("Error disconnecting");
-                        state = State::Disconnecting;
-                    }
-                }
-                State::Disconnecting => {
-                    if swarms_disconnected(&swarm1, &swarm2) {
-                        if reconnected {
-                            return Poll::Ready(());
-                        }
-                        reconnected = true;
-                        for _ in 0..num_connections {
-                            swarm2.dial(addr1.clone()).unwrap();
-                        }
-                        state = State::Connecting;
-                    }
-                }
-            }
-
-            if poll1.is_pending() && poll2.is_pending() {
-                return Poll::Pending;
-            }
-        }))
+        let behaviour = DummyBehaviour::with_keep_alive(KeepAlive::Yes);
+        SwarmBuilder::new(transport, behaviour, local_public_key.into()).build()
     }
 
-    /// Establishes multiple connections between two peers,
-    /// after which one peer disconnects the other
-    /// using [`NetworkBehaviourAction::CloseConnection`] returned by a [`NetworkBehaviour`].
-    ///
-    /// The test expects both behaviours to be notified via pairs of
-    /// inject_connected / inject_disconnected as well as
-    /// inject_connection_established / inject_connection_closed calls.
+    /// Connections of banned peers are withheld from the behaviour, thus
+    /// tracked internally until they are closed.
     #[test]
-    fn test_behaviour_disconnect_all() {
-        // Since the test does not try to open any substreams, we can
-        // use the dummy protocols handler.
-        let handler_proto = DummyConnectionHandler {
-            keep_alive: KeepAlive::Yes,
-        };
-
-        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
-        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
-
-        let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-
-        swarm1.listen_on(addr1.clone()).unwrap();
-        swarm2.listen_on(addr2.clone()).unwrap();
-
-        let swarm1_id = *swarm1.local_peer_id();
-
-        let mut reconnected = false;
-        let num_connections = 10;
-
-        for _ in 0..num_connections {
-            swarm1.dial(addr2.clone()).unwrap();
-        }
-        let mut state = State::Connecting;
-
-        executor::block_on(future::poll_fn(move |cx| loop {
-            let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
-            let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
-            match state {
-                State::Connecting => {
-                    if swarms_connected(&swarm1, &swarm2, num_connections) {
-                        if reconnected {
-                            return Poll::Ready(());
-                        }
-                        swarm2.behaviour.inner().next_action.replace(
-                            NetworkBehaviourAction::CloseConnection {
-                                peer_id: swarm1_id,
-                                connection: CloseConnection::All,
-                            },
-                        );
-                        state = State::Disconnecting;
-                        continue;
-                    }
-                }
-                State::Disconnecting => {
-                    if swarms_disconnected(&swarm1, &swarm2) {
-                        reconnected = true;
-                        for _ in 0..num_connections {
-                            swarm2.dial(addr1.clone()).unwrap();
-                        }
-                        state = State::Connecting;
-                        continue;
-                    }
-                }
-            }
+    fn banned_peer_connections_are_forgotten_once_closed() {
+        let mut swarm1 = new_swarm();
+        let mut swarm2 = new_swarm();
 
-            if poll1.is_pending() && poll2.is_pending() {
-                return Poll::Pending;
-            }
-        }))
-    }
-
-    /// Establishes multiple connections between two peers,
-    /// after which one peer closes a single connection
-    /// using [`NetworkBehaviourAction::CloseConnection`] returned by a [`NetworkBehaviour`].
-    ///
-    /// The test expects both behaviours to be notified via pairs of
-    /// inject_connected / inject_disconnected as well as
-    /// inject_connection_established / inject_connection_closed calls.
-    #[test]
-    fn test_behaviour_disconnect_one() {
-        // Since the test does not try to open any substreams, we can
-        // use the dummy protocols handler.
-        let handler_proto = DummyConnectionHandler {
-            keep_alive: KeepAlive::Yes,
+        swarm2.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        let address = match futures::executor::block_on(swarm2.next()).unwrap() {
+            SwarmEvent::NewListenAddr { address, .. } => address,
+            e => panic!("Unexpected network event: {:?}", e),
         };
 
-        let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
-        let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
-
-        let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
-
-        swarm1.listen_on(addr1.clone()).unwrap();
-        swarm2.listen_on(addr2.clone()).unwrap();
-
-        let swarm1_id = *swarm1.local_peer_id();
-
-        let num_connections = 10;
-
-        for _ in 0..num_connections {
-            swarm1.dial(addr2.clone()).unwrap();
-        }
-        let mut state = State::Connecting;
-        let mut disconnected_conn_id = None;
-
-        executor::block_on(future::poll_fn(move |cx| loop {
-            let poll1 = Swarm::poll_next_event(Pin::new(&mut swarm1), cx);
-            let poll2 = Swarm::poll_next_event(Pin::new(&mut swarm2), cx);
-            match state {
-                State::Connecting => {
-                    if swarms_connected(&swarm1, &swarm2, num_connections) {
-                        disconnected_conn_id = {
-                            let conn_id = swarm2.behaviour.inject_connection_established
-                                [num_connections / 2]
-                                .1;
-                            swarm2.behaviour.inner().next_action.replace(
-                                NetworkBehaviourAction::CloseConnection {
-                                    peer_id: swarm1_id,
-                                    connection: CloseConnection::One(conn_id),
-                                },
-                            );
-                            Some(conn_id)
-                        };
-                        state = State::Disconnecting;
-                    }
-                }
-                State::Disconnecting => {
-                    for s in &[&swarm1, &swarm2] {
-                        assert!(s
-                            .behaviour
-                            .inject_connection_closed
-                            .iter()
-                            .all(|(.., remaining_conns)| *remaining_conns > 0));
-                        assert_eq!(
-                            s.behaviour.inject_connection_established.len(),
-                            num_connections
-                        );
-                        s.behaviour.assert_connected(num_connections, 1);
-                    }
-                    if [&swarm1, &swarm2]
-                        .iter()
-                        .all(|s| s.behaviour.inject_connection_closed.len() == 1)
-                    {
-                        let conn_id = swarm2.behaviour.inject_connection_closed[0].1;
-                        assert_eq!(Some(conn_id), disconnected_conn_id);
-                        return Poll::Ready(());
-                    }
-                }
-            }
-
-            if poll1.is_pending() && poll2.is_pending() {
-                return Poll::Pending;
-            }
-        }))
-    }
-
-    #[test]
-    fn concurrent_dialing() {
-        #[derive(Clone, Debug)]
-        struct DialConcurrencyFactor(NonZeroU8);
-
-        impl Arbitrary for DialConcurrencyFactor {
-            fn arbitrary<G: Gen>(g: &mut G) -> Self {
-                Self(NonZeroU8::new(g.gen_range(1, 11)).unwrap())
-            }
-        }
-
-        fn prop(concurrency_factor: DialConcurrencyFactor) {
-            block_on(async {
-                let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler {
-                    keep_alive: KeepAlive::Yes,
-                })
-                .dial_concurrency_factor(concurrency_factor.0)
-                .build();
-
-                // Listen on `concurrency_factor + 1` addresses.
-                //
-                // `+ 2` to ensure a subset of addresses is dialed by network_2.
-                let num_listen_addrs = concurrency_factor.0.get() + 2;
-                let mut listen_addresses = Vec::new();
-                let mut listeners = Vec::new();
-                for _ in 0..num_listen_addrs {
-                    let mut listener = transport::MemoryTransport {}
-                        .listen_on("/memory/0".parse().unwrap())
-                        .unwrap();
-
-                    match listener.next().await.unwrap().unwrap() {
-                        ListenerEvent::NewAddress(address) => {
-                            listen_addresses.push(address);
-                        }
-                        _ => panic!("Expected `NewListenAddr` event."),
-                    }
-
-                    listeners.push(listener);
-                }
+        swarm2.ban_peer_id(*swarm1.local_peer_id());
+        swarm1.dial(address).unwrap();
 
-                // Have swarm dial each listener and wait for each listener to receive the incoming
-                // connections.
-                swarm
-                    .dial(
-                        DialOpts::peer_id(PeerId::random())
-                            .addresses(listen_addresses.into())
-                            .build(),
-                    )
-                    .unwrap();
-                for mut listener in listeners.into_iter() {
-                    loop {
-                        match futures::future::select(listener.next(), swarm.next()).await {
-                            Either::Left((Some(Ok(ListenerEvent::Upgrade { .. })), _)) => {
-                                break;
-                            }
-                            Either::Left(_) => {
-                                panic!("Unexpected listener event.")
-                            }
-                            Either::Right((e, _)) => {
-                                panic!("Expect swarm to not emit any event {:?}", e)
-                            }
-                        }
+        let mut banned = false;
+        futures::executor::block_on(future::poll_fn(|cx| {
+            let _ = swarm1.poll_next_unpin(cx);
+            while let Poll::Ready(Some(event)) = swarm2.poll_next_unpin(cx) {
+                match event {
+                    SwarmEvent::BannedPeer { .. } => banned = true,
+                    SwarmEvent::IncomingConnection { .. } | SwarmEvent::ConnectionClosed { .. } => {
                     }
-                }
-
-                match swarm.next().await.unwrap() {
-                    SwarmEvent::OutgoingConnectionError { .. } => {}
-                    e => panic!("Unexpected swarm event {:?}", e),
-                }
-            })
-        }
-
-        QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _);
-    }
-
-    #[test]
-    fn max_outgoing() {
-        use rand::Rng;
-
-        let outgoing_limit = rand::thread_rng().gen_range(1, 10);
-
-        let limits = ConnectionLimits::default().with_max_pending_outgoing(Some(outgoing_limit));
-        let mut network = new_test_swarm::<_, ()>(DummyConnectionHandler {
-            keep_alive: KeepAlive::Yes,
-        })
-        .connection_limits(limits)
-        .build();
-
-        let addr: Multiaddr = "/memory/1234".parse().unwrap();
-
-        let target = PeerId::random();
-        for _ in 0..outgoing_limit {
-            network
-                .dial(
-                    DialOpts::peer_id(target)
-                        .addresses(vec![addr.clone()])
-                        .build(),
-                )
-                .ok()
-                .expect("Unexpected connection limit.");
-        }
-
-        match network
-            .dial(
-                DialOpts::peer_id(target)
-                    .addresses(vec![addr.clone()])
-                    .build(),
-            )
-            .expect_err("Unexpected dialing success.")
-        {
-            DialError::ConnectionLimit(limit) => {
-                assert_eq!(limit.current, outgoing_limit);
-                assert_eq!(limit.limit, outgoing_limit);
-            }
-            e => panic!("Unexpected error: {:?}", e),
-        }
-
-        let info = network.network_info();
-        assert_eq!(info.num_peers(), 0);
-        assert_eq!(
-            info.connection_counters().num_pending_outgoing(),
-            outgoing_limit
-        );
-    }
-
-    #[test]
-    fn max_established_incoming() {
-        use rand::Rng;
-
-        #[derive(Debug, Clone)]
-        struct Limit(u32);
-
-        impl Arbitrary for Limit {
-            fn arbitrary<G: Gen>(g: &mut G) -> Self {
-                Self(g.gen_range(1, 10))
-            }
-        }
-
-        fn limits(limit: u32) -> ConnectionLimits {
-            ConnectionLimits::default().with_max_established_incoming(Some(limit))
-        }
-
-        fn prop(limit: Limit) {
-            let limit = limit.0;
-
-            let mut network1 = new_test_swarm::<_, ()>(DummyConnectionHandler {
-                keep_alive: KeepAlive::Yes,
-            })
-            .connection_limits(limits(limit))
-            .build();
-            let mut network2 = new_test_swarm::<_, ()>(DummyConnectionHandler {
-                keep_alive: KeepAlive::Yes,
-            })
-            .connection_limits(limits(limit))
-            .build();
-
-            let _ = network1.listen_on(multiaddr![Memory(0u64)]).unwrap();
-            let listen_addr = async_std::task::block_on(poll_fn(|cx| {
-                match ready!(network1.poll_next_unpin(cx)).unwrap() {
-                    SwarmEvent::NewListenAddr { address, .. } => Poll::Ready(address),
                     e => panic!("Unexpected network event: {:?}", e),
                 }
-            }));
-
-            // Spawn and block on the dialer.
-            async_std::task::block_on({
-                let mut n = 0;
-                let _ = network2.dial(listen_addr.clone()).unwrap();
-
-                let mut expected_closed = false;
-                let mut network_1_established = false;
-                let mut network_2_established = false;
-                let mut network_1_limit_reached = false;
-                let mut network_2_limit_reached = false;
-                poll_fn(move |cx| {
-                    loop {
-                        let mut network_1_pending = false;
-                        let mut network_2_pending = false;
-
-                        match network1.poll_next_unpin(cx) {
-                            Poll::Ready(Some(SwarmEvent::IncomingConnection { .. })) => {}
-                            Poll::Ready(Some(SwarmEvent::ConnectionEstablished { .. })) => {
-                                network_1_established = true;
-                            }
-                            Poll::Ready(Some(SwarmEvent::IncomingConnectionError {
-                                error: PendingConnectionError::ConnectionLimit(err),
-                                ..
-                            })) => {
-                                assert_eq!(err.limit, limit);
-                                assert_eq!(err.limit, err.current);
-                                let info = network1.network_info();
-                                let counters = info.connection_counters();
-                                assert_eq!(counters.num_established_incoming(), limit);
-                                assert_eq!(counters.num_established(), limit);
-                                network_1_limit_reached = true;
-                            }
-                            Poll::Pending => {
-                                network_1_pending = true;
-                            }
-                            e => panic!("Unexpected network event: {:?}", e),
-                        }
-
-                        match network2.poll_next_unpin(cx) {
-                            Poll::Ready(Some(SwarmEvent::ConnectionEstablished { .. })) => {
-                                network_2_established = true;
-                            }
-                            Poll::Ready(Some(SwarmEvent::ConnectionClosed { .. })) => {
-                                assert!(expected_closed);
-                                let info = network2.network_info();
-                                let counters = info.connection_counters();
-                                assert_eq!(counters.num_established_outgoing(), limit);
-                                assert_eq!(counters.num_established(), limit);
-                                network_2_limit_reached = true;
-                            }
-                            Poll::Pending => {
-                                network_2_pending = true;
-                            }
-                            e => panic!("Unexpected network event: {:?}", e),
-                        }
-
-                        if network_1_pending && network_2_pending {
-                            return Poll::Pending;
-                        }
-
-                        if network_1_established && network_2_established {
-                            network_1_established = false;
-                            network_2_established = false;
-
-                            if n <= limit {
-                                // Dial again until the limit is exceeded.
-                                n += 1;
-                                network2.dial(listen_addr.clone()).unwrap();
-
-                                if n == limit {
-                                    // The the next dialing attempt exceeds the limit, this
-                                    // is the connection we expected to get closed.
-                                    expected_closed = true;
-                                }
-                            } else {
-                                panic!("Expect networks not to establish connections beyond the limit.")
-                            }
-                        }
-
-                        if network_1_limit_reached && network_2_limit_reached {
-                            return Poll::Ready(());
-                        }
-                    }
-                })
-            });
-        }
-
-        quickcheck(prop as fn(_));
-    }
-
-    #[test]
-    fn invalid_peer_id() {
-        // Checks whether dialing an address containing the wrong peer id raises an error
-        // for the expected peer id instead of the obtained peer id.
-
-        let mut swarm1 = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
-        let mut swarm2 = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
-
-        swarm1.listen_on("/memory/0".parse().unwrap()).unwrap();
-
-        let address =
-            futures::executor::block_on(future::poll_fn(|cx| match swarm1.poll_next_unpin(cx) {
-                Poll::Ready(Some(SwarmEvent::NewListenAddr { address, .. })) => {
-                    Poll::Ready(address)
-                }
-                Poll::Pending => Poll::Pending,
-                _ => panic!("Was expecting the listen address to be reported"),
-            }));
-
-        let other_id = PeerId::random();
-        let other_addr = address.with(Protocol::P2p(other_id.into()));
-
-        swarm2.dial(other_addr.clone()).unwrap();
-
-        let (peer_id, error) = futures::executor::block_on(future::poll_fn(|cx| {
-            if let Poll::Ready(Some(SwarmEvent::IncomingConnection { .. })) =
-                swarm1.poll_next_unpin(cx)
-            {}
-
-            match swarm2.poll_next_unpin(cx) {
-                Poll::Ready(Some(SwarmEvent::OutgoingConnectionError {
-                    peer_id, error, ..
-                })) => Poll::Ready((peer_id, error)),
-                Poll::Ready(x) => panic!("unexpected {:?}", x),
-                Poll::Pending => Poll::Pending,
             }
-        }));
-        assert_eq!(peer_id.unwrap(), other_id);
-        match error {
-            DialError::WrongPeerId { obtained, endpoint } => {
-                assert_eq!(obtained, *swarm1.local_peer_id());
-                assert_eq!(
-                    endpoint,
-                    ConnectedPoint::Dialer {
-                        address: other_addr,
-                        role_override: Endpoint::Dialer,
-                    }
-                );
+            if banned && swarm2.network_info().num_peers() == 0 {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
             }
-            x => panic!("wrong error {:?}", x),
-        }
-    }
-
-    #[test]
-    fn dial_self() {
-        // Check whether dialing ourselves correctly fails.
-        //
-        // Dialing the same address we're listening should result in three events:
-        //
-        // - The incoming connection notification (before we know the incoming peer ID).
-        // - The connection error for the dialing endpoint (once we've determined that it's our own ID).
-        // - The connection error for the listening endpoint (once we've determined that it's our own ID).
-        //
-        // The last two can happen in any order.
-
-        let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
-        swarm.listen_on("/memory/0".parse().unwrap()).unwrap();
-
-        let local_address =
-            futures::executor::block_on(future::poll_fn(|cx| match swarm.poll_next_unpin(cx) {
-                Poll::Ready(Some(SwarmEvent::NewListenAddr { address, .. })) => {
-                    Poll::Ready(address)
-                }
-                Poll::Pending => Poll::Pending,
-                _ => panic!("Was expecting the listen address to be reported"),
-            }));
-
-        swarm.dial(local_address.clone()).unwrap();
-
-        let mut got_dial_err = false;
-        let mut got_inc_err = false;
-        futures::executor::block_on(future::poll_fn(|cx| -> Poll<Result<(), io::Error>> {
-            loop {
-                match swarm.poll_next_unpin(cx) {
-                    Poll::Ready(Some(SwarmEvent::OutgoingConnectionError {
-                        peer_id,
-                        error: DialError::WrongPeerId { .. },
-                        ..
-                    })) => {
-                        assert_eq!(&peer_id.unwrap(), swarm.local_peer_id());
-                        assert!(!got_dial_err);
-                        got_dial_err = true;
-                        if got_inc_err {
-                            return Poll::Ready(Ok(()));
-                        }
-                    }
-                    Poll::Ready(Some(SwarmEvent::IncomingConnectionError {
-                        local_addr, ..
-                    })) => {
-                        assert!(!got_inc_err);
-                        assert_eq!(local_addr, local_address);
-                        got_inc_err = true;
-                        if got_dial_err {
-                            return Poll::Ready(Ok(()));
-                        }
-                    }
-                    Poll::Ready(Some(SwarmEvent::IncomingConnection { local_addr, .. })) => {
-                        assert_eq!(local_addr, local_address);
-                    }
-                    Poll::Ready(ev) => {
-                        panic!("Unexpected event: {:?}", ev)
-                    }
-                    Poll::Pending => break Poll::Pending,
-                }
-            }
-        }))
-        .unwrap();
-    }
-
-    #[test]
-    fn dial_self_by_id() {
-        // Trying to dial self by passing the same `PeerId` shouldn't even be possible in the first
-        // place.
-        let swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
-        let peer_id = *swarm.local_peer_id();
-        assert!(!swarm.is_connected(&peer_id));
-    }
-
-    #[test]
-    fn multiple_addresses_err() {
-        // Tries dialing multiple addresses, and makes sure there's one dialing error per address.
-
-        let target = PeerId::random();
-
-        let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
-
-        let mut addresses = Vec::new();
-        for _ in 0..3 {
-            addresses.push(multiaddr![Ip4([0, 0, 0, 0]), Tcp(rand::random::<u16>())]);
-        }
-        for _ in 0..5 {
-            addresses.push(multiaddr![Udp(rand::random::<u16>())]);
-        }
-        addresses.shuffle(&mut rand::thread_rng());
-
-        swarm
-            .dial(
-                DialOpts::peer_id(target)
-                    .addresses(addresses.clone())
-                    .build(),
-            )
-            .unwrap();
-
-        futures::executor::block_on(future::poll_fn(|cx| -> Poll<Result<(), io::Error>> {
-            loop {
-                match swarm.poll_next_unpin(cx) {
-                    Poll::Ready(Some(SwarmEvent::OutgoingConnectionError {
-                        peer_id,
-                        // multiaddr,
-                        error: DialError::Transport(errors),
-                    })) => {
-                        assert_eq!(peer_id.unwrap(), target);
-
-                        let failed_addresses =
-                            errors.into_iter().map(|(addr, _)| addr).collect::<Vec<_>>();
-                        assert_eq!(
-                            failed_addresses,
-                            addresses
-                                .clone()
-                                .into_iter()
-                                .map(|addr| addr.with(Protocol::P2p(target.into())))
-                                .collect::<Vec<_>>()
-                        );
-
-                        return Poll::Ready(Ok(()));
-                    }
-                    Poll::Ready(_) => unreachable!(),
-                    Poll::Pending => break Poll::Pending,
-                }
-            }
-        }))
-        .unwrap();
-    }
-
-    #[test]
-    fn aborting_pending_connection_surfaces_error() {
-        let _ = env_logger::try_init();
-
-        let mut dialer = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
-        let mut listener = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
-
-        let listener_peer_id = *listener.local_peer_id();
-        listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
-        let listener_address = match block_on(listener.next()).unwrap() {
-            SwarmEvent::NewListenAddr { address, .. } => address,
-            e => panic!("Unexpected network event: {:?}", e),
-        };
+        }));
 
-        dialer
-            .dial(
-                DialOpts::peer_id(listener_peer_id)
-                    .addresses(vec![listener_address])
-                    .build(),
-            )
-            .unwrap();
-
-        dialer
-            .disconnect_peer_id(listener_peer_id)
-            .expect_err("Expect peer to not yet be connected.");
-
-        match block_on(dialer.next()).unwrap() {
-            SwarmEvent::OutgoingConnectionError {
-                error: DialError::Aborted,
-                ..
-            } => {}
-            e => panic!("Unexpected swarm event {:?}.", e),
-        }
+        assert!(swarm2.banned_peer_connections.is_empty());
     }
 }
@@ -0,0 +1,197 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::Multiaddr;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Options to configure a listener.
+///
+/// Used in [`Swarm::listen_with_opts`](crate::Swarm::listen_with_opts). A plain [`Multiaddr`]
+/// converts into [`ListenOpts`] without port range and without recovery.
+///
+///   ```
+///   # use libp2p_swarm::listen_opts::{ListenOpts, ListenerRecoveryConfig};
+///   ListenOpts::address("/ip4/0.0.0.0/tcp/0".parse().unwrap())
+///      .port_range(4000..=4010)
+///      .recover(ListenerRecoveryConfig::default())
+///      .build();
+///   ```
+#[derive(Debug, Clone)]
+pub struct ListenOpts {
+    pub(crate) address: Multiaddr,
+    pub(crate) port_range: Option<RangeInclusive<u16>>,
+    pub(crate) recovery: Option<ListenerRecoveryConfig>,
+}
+
+impl ListenOpts {
+    /// Listen on the given address.
+    pub fn address(address: Multiaddr) -> WithAddress {
+        WithAddress {
+            address,
+            port_range: None,
+            recovery: None,
+        }
+    }
+
+    /// Get the address specified in a [`ListenOpts`].
+    pub fn get_address(&self) -> &Multiaddr {
+        &self.address
+    }
+
+    /// Returns the addresses to try, in order, to start the listener.
+    ///
+    /// Returns `None` if a port range is configured but the address contains
+    /// neither a TCP nor a UDP port.
+    pub(crate) fn candidates(&self) -> Option<Vec<Multiaddr>> {
+        let range = match &self.port_range {
+            Some(range) => range.clone(),
+            None => return Some(vec![self.address.clone()]),
+        };
+
+        let index = self
+            .address
+            .iter()
+            .position(|p| matches!(p, Protocol::Tcp(_) | Protocol::Udp(_)))?;
+        let candidates = range
+            .map(|port| {
+                self.address
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| match p {
+                        Protocol::Tcp(_) if i == index => Protocol::Tcp(port),
+                        Protocol::Udp(_) if i == index => Protocol::Udp(port),
+                        p => p,
+                    })
+                    .collect()
+            })
+            .collect();
+        Some(candidates)
+    }
+}
+
+impl From<Multiaddr> for ListenOpts {
+    fn from(address: Multiaddr) -> Self {
+        ListenOpts::address(address).build()
+    }
+}
+
+#[derive(Debug)]
+pub struct WithAddress {
+    address: Multiaddr,
+    port_range: Option<RangeInclusive<u16>>,
+    recovery: Option<ListenerRecoveryConfig>,
+}
+
+impl WithAddress {
+    /// Listen on the first port of the given range that is free.
+    ///
+    /// Replaces the port of the first TCP or UDP component of the address,
+    /// e.g. `/ip4/0.0.0.0/tcp/0` with the range `4000..=4010` is tried as
+    /// `/ip4/0.0.0.0/tcp/4000`, `/ip4/0.0.0.0/tcp/4001` and so on. Listening
+    /// fails if no port of the range is free or if the address contains
+    /// neither a TCP nor a UDP component.
+    pub fn port_range(mut self, range: RangeInclusive<u16>) -> Self {
+        self.port_range = Some(range);
+        self
+    }
+
+    /// Listen again, after a backoff, whenever the listener closes or fails.
+    ///
+    /// The listener keeps its [`ListenerId`](libp2p_core::connection::ListenerId)
+    /// while it recovers. Its addresses are reported as expired when it fails
+    /// and as new once it is listening again. A
+    /// [`SwarmEvent::ListenerClosed`](crate::SwarmEvent::ListenerClosed) is only
+    /// reported once the listener is removed or recovery is given up.
+    pub fn recover(mut self, config: ListenerRecoveryConfig) -> Self {
+        self.recovery = Some(config);
+        self
+    }
+
+    /// Build the final [`ListenOpts`].
+    pub fn build(self) -> ListenOpts {
+        ListenOpts {
+            address: self.address,
+            port_range: self.port_range,
+            recovery: self.recovery,
+        }
+    }
+}
+
+/// The configuration of the exponential backoff applied before listening
+/// again with a failed listener.
+///
+/// After the `n`-th consecutive failure, the listener is restarted after
+/// `base * 2^(n - 1)`, capped at `max`. The failures are reset once the
+/// restarted listener reports a new address.
+///
+/// See [`WithAddress::recover`].
+#[derive(Debug, Clone)]
+pub struct ListenerRecoveryConfig {
+    base: Duration,
+    max: Duration,
+    max_attempts: Option<u32>,
+}
+
+impl ListenerRecoveryConfig {
+    /// Sets the backoff after the first failure. Defaults to 1 second.
+    pub fn with_base(mut self, base: Duration) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Sets the maximum backoff. Defaults to 1 minute.
+    pub fn with_max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets the maximum number of consecutive attempts to listen again, after
+    /// which recovery is given up and the listener is closed. Unlimited by
+    /// default.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Returns the backoff after the given number of consecutive failures, or
+    /// `None` if recovery is to be given up.
+    pub(crate) fn backoff(&self, failures: u32) -> Option<Duration> {
+        if matches!(self.max_attempts, Some(max) if failures > max) {
+            return None;
+        }
+        let backoff = self
+            .base
+            .checked_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+            .map_or(self.max, |b| b.min(self.max));
+        Some(backoff)
+    }
+}
+
+impl Default for ListenerRecoveryConfig {
+    fn default() -> Self {
+        ListenerRecoveryConfig {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
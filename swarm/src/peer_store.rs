@@ -0,0 +1,509 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! A store of information about known peers, owned by the [`Swarm`](crate::Swarm).
+//!
+//! The [`PeerStore`] collects the addresses of remote peers together with
+//! the source they were learned from and an optional time-to-live, the
+//! protocols and agent version a peer reported about itself, as well as
+//! when the peer was last seen and its most recently measured latency.
+//!
+//! The [`Swarm`](crate::Swarm) records the addresses of the connections it
+//! establishes. Behaviours can read and contribute to the store via
+//! [`PollParameters::peer_store`](crate::PollParameters::peer_store) and
+//! [`PollParameters::peer_store_mut`](crate::PollParameters::peer_store_mut).
+//! Applications access it through [`Swarm::peer_store`](crate::Swarm::peer_store)
+//! and [`Swarm::peer_store_mut`](crate::Swarm::peer_store_mut).
+//!
+//! Each peer holds at most [`PeerStore::max_addresses_per_peer`] addresses.
+//! The [`Swarm`](crate::Swarm) periodically removes expired addresses, as well
+//! as peers it is not connected to and that are left without addresses.
+//! Addresses learned from connections and from remotes are recorded with
+//! [`RECENTLY_CONNECTED_ADDRESS_TTL`].
+//!
+//! With the `serde` feature enabled, a [`PeerStoreSnapshot`] can be
+//! serialized, e.g. to restore the store of a restarted node via
+//! [`PeerStore::from_snapshot`] and [`SwarmBuilder::peer_store`](crate::SwarmBuilder::peer_store).
+
+use fnv::FnvHashMap;
+use libp2p_core::time::{self, Instant};
+use libp2p_core::{multiaddr::Protocol, Multiaddr, PeerId};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The time-to-live of addresses recorded for established connections and of
+/// addresses reported by remote peers about themselves.
+///
+/// Addresses of established connections are refreshed when the connection
+/// closes, thus expire once the peer has been disconnected for this long.
+pub const RECENTLY_CONNECTED_ADDRESS_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// The default of [`PeerStore::max_addresses_per_peer`].
+pub const DEFAULT_MAX_ADDRESSES_PER_PEER: usize = 32;
+
+/// The source an address of a peer was learned from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "_serde"))]
+pub enum AddressSource {
+    /// The address of an established connection to the peer.
+    Connection,
+    /// The peer reported the address about itself, e.g. via identify.
+    Remote,
+    /// The address was discovered, e.g. via Kademlia or mDNS.
+    Discovery,
+    /// The address was added by the application.
+    Manual,
+}
+
+/// An address of a peer held by the [`PeerStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressEntry {
+    addr: Multiaddr,
+    source: AddressSource,
+    expires: Option<Instant>,
+}
+
+impl AddressEntry {
+    /// The address.
+    pub fn addr(&self) -> &Multiaddr {
+        &self.addr
+    }
+
+    /// The source the address was (most recently) learned from.
+    pub fn source(&self) -> AddressSource {
+        self.source
+    }
+
+    /// The instant at which the address expires, if any.
+    pub fn expires(&self) -> Option<Instant> {
+        self.expires
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires.map_or(false, |e| e <= now)
+    }
+}
+
+/// The information held by the [`PeerStore`] about a single peer.
+#[derive(Debug, Clone, Default)]
+pub struct PeerInfo {
+    addresses: Vec<AddressEntry>,
+    protocols: Vec<String>,
+    agent_version: Option<String>,
+    last_seen: Option<Instant>,
+    latency: Option<Duration>,
+}
+
+impl PeerInfo {
+    /// Returns the addresses of the peer that have not yet expired.
+    pub fn addresses(&self) -> impl Iterator<Item = &AddressEntry> {
+        let now = time::now();
+        self.addresses.iter().filter(move |a| !a.is_expired(now))
+    }
+
+    /// The protocols the peer reported to support.
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+
+    /// The agent version the peer reported.
+    pub fn agent_version(&self) -> Option<&str> {
+        self.agent_version.as_deref()
+    }
+
+    /// The instant at which a connection to the peer was last established
+    /// or closed.
+    pub fn last_seen(&self) -> Option<Instant> {
+        self.last_seen
+    }
+
+    /// The most recently measured round-trip time to the peer.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        self.addresses.retain(|a| !a.is_expired(now));
+    }
+}
+
+/// A store of information about known peers.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug, Clone)]
+pub struct PeerStore {
+    peers: FnvHashMap<PeerId, PeerInfo>,
+    max_addresses_per_peer: usize,
+}
+
+impl Default for PeerStore {
+    fn default() -> Self {
+        PeerStore {
+            peers: Default::default(),
+            max_addresses_per_peer: DEFAULT_MAX_ADDRESSES_PER_PEER,
+        }
+    }
+}
+
+impl PeerStore {
+    /// Creates an empty [`PeerStore`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of addresses held per peer.
+    ///
+    /// Once a peer holds that many addresses, adding another one evicts the
+    /// address expiring first, or the oldest one if none expires.
+    pub fn with_max_addresses_per_peer(mut self, max: usize) -> Self {
+        self.max_addresses_per_peer = max;
+        self
+    }
+
+    /// The maximum number of addresses held per peer.
+    pub fn max_addresses_per_peer(&self) -> usize {
+        self.max_addresses_per_peer
+    }
+
+    /// Returns the information about the given peer, if any.
+    pub fn get(&self, peer: &PeerId) -> Option<&PeerInfo> {
+        self.peers.get(peer)
+    }
+
+    /// Returns an iterator over all known peers and their information.
+    pub fn peers(&self) -> impl Iterator<Item = (&PeerId, &PeerInfo)> {
+        self.peers.iter()
+    }
+
+    /// Returns the addresses of the given peer that have not yet expired.
+    pub fn addresses_of_peer(&self, peer: &PeerId) -> impl Iterator<Item = &Multiaddr> {
+        self.peers
+            .get(peer)
+            .into_iter()
+            .flat_map(|info| info.addresses())
+            .map(|a| a.addr())
+    }
+
+    /// Adds an address of a peer, learned from the given source.
+    ///
+    /// The address expires after the given `ttl`, or never if `ttl` is `None`.
+    /// A trailing `/p2p/<peer>` component is removed from the address.
+    ///
+    /// If the address is already known, its source is updated and its expiry
+    /// is extended, but never shortened.
+    pub fn add_address(
+        &mut self,
+        peer: PeerId,
+        mut addr: Multiaddr,
+        source: AddressSource,
+        ttl: Option<Duration>,
+    ) {
+        if let Some(Protocol::P2p(hash)) = addr.iter().last() {
+            if hash == *peer.as_ref() {
+                addr.pop();
+            }
+        }
+
+        let now = time::now();
+        let expires = ttl.map(|ttl| now + ttl);
+        let info = self.peers.entry(peer).or_default();
+        info.remove_expired(now);
+
+        match info.addresses.iter_mut().find(|a| a.addr == addr) {
+            Some(entry) => {
+                entry.source = source;
+                entry.expires = match (entry.expires, expires) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                };
+            }
+            None => {
+                if info.addresses.len() >= self.max_addresses_per_peer {
+                    let evict = info
+                        .addresses
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, a)| (a.expires.is_none(), a.expires))
+                        .map(|(i, _)| i);
+                    match evict {
+                        Some(i) => {
+                            info.addresses.remove(i);
+                        }
+                        // A maximum of zero addresses.
+                        None => return,
+                    }
+                }
+                info.addresses.push(AddressEntry {
+                    addr,
+                    source,
+                    expires,
+                })
+            }
+        }
+    }
+
+    /// Removes an address of a peer.
+    ///
+    /// Returns `true` if the address was known.
+    pub fn remove_address(&mut self, peer: &PeerId, addr: &Multiaddr) -> bool {
+        match self.peers.get_mut(peer) {
+            Some(info) => {
+                let len = info.addresses.len();
+                info.addresses.retain(|a| &a.addr != addr);
+                info.addresses.len() != len
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the protocols the given peer reported to support.
+    pub fn set_protocols(&mut self, peer: PeerId, protocols: Vec<String>) {
+        self.peers.entry(peer).or_default().protocols = protocols;
+    }
+
+    /// Sets the agent version the given peer reported.
+    pub fn set_agent_version(&mut self, peer: PeerId, agent_version: String) {
+        self.peers.entry(peer).or_default().agent_version = Some(agent_version);
+    }
+
+    /// Sets the most recently measured round-trip time to the given peer.
+    pub fn set_latency(&mut self, peer: PeerId, latency: Duration) {
+        self.peers.entry(peer).or_default().latency = Some(latency);
+    }
+
+    /// Records that the given peer has been seen just now.
+    pub fn record_seen(&mut self, peer: PeerId) {
+        self.peers.entry(peer).or_default().last_seen = Some(time::now());
+    }
+
+    /// Removes all information about the given peer.
+    pub fn remove_peer(&mut self, peer: &PeerId) -> Option<PeerInfo> {
+        self.peers.remove(peer)
+    }
+
+    /// Removes all expired addresses.
+    pub fn remove_expired(&mut self) {
+        let now = time::now();
+        for info in self.peers.values_mut() {
+            info.remove_expired(now);
+        }
+    }
+
+    /// Removes the peers without addresses for which `is_connected` returns
+    /// `false`.
+    pub(crate) fn remove_unreachable(&mut self, is_connected: impl Fn(&PeerId) -> bool) {
+        self.peers
+            .retain(|peer, info| !info.addresses.is_empty() || is_connected(peer));
+    }
+
+    /// Creates a snapshot of the addresses, protocols and agent versions of
+    /// all known peers.
+    ///
+    /// Expired addresses are omitted. The time-to-live of the remaining
+    /// addresses is recorded relative to now.
+    pub fn snapshot(&self) -> PeerStoreSnapshot {
+        let now = time::now();
+        let peers = self
+            .peers
+            .iter()
+            .map(|(peer_id, info)| PeerSnapshot {
+                peer_id: *peer_id,
+                addresses: info
+                    .addresses
+                    .iter()
+                    .filter(|a| !a.is_expired(now))
+                    .map(|a| AddressSnapshot {
+                        addr: a.addr.clone(),
+                        source: a.source,
+                        ttl: a.expires.map(|e| e - now),
+                    })
+                    .collect(),
+                protocols: info.protocols.clone(),
+                agent_version: info.agent_version.clone(),
+            })
+            .collect();
+
+        PeerStoreSnapshot { peers }
+    }
+
+    /// Creates a [`PeerStore`] from a snapshot previously obtained via
+    /// [`PeerStore::snapshot`].
+    ///
+    /// The store holds at most [`DEFAULT_MAX_ADDRESSES_PER_PEER`] addresses
+    /// per peer, see [`PeerStore::with_max_addresses_per_peer`].
+    pub fn from_snapshot(snapshot: PeerStoreSnapshot) -> Self {
+        let mut store = PeerStore::new();
+        for peer in snapshot.peers {
+            for a in peer.addresses {
+                store.add_address(peer.peer_id, a.addr, a.source, a.ttl);
+            }
+            let info = store.peers.entry(peer.peer_id).or_default();
+            info.protocols = peer.protocols;
+            info.agent_version = peer.agent_version;
+        }
+        store
+    }
+}
+
+/// A snapshot of a [`PeerStore`], see [`PeerStore::snapshot`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "_serde"))]
+pub struct PeerStoreSnapshot {
+    pub peers: Vec<PeerSnapshot>,
+}
+
+/// A snapshot of the information about a single peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "_serde"))]
+pub struct PeerSnapshot {
+    pub peer_id: PeerId,
+    pub addresses: Vec<AddressSnapshot>,
+    pub protocols: Vec<String>,
+    pub agent_version: Option<String>,
+}
+
+/// A snapshot of an address of a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "_serde"))]
+pub struct AddressSnapshot {
+    pub addr: Multiaddr,
+    pub source: AddressSource,
+    /// The remaining time-to-live of the address, `None` if it never expires.
+    pub ttl: Option<Duration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p2p_suffix_is_stripped_and_duplicates_merged() {
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/memory/1234".parse().unwrap();
+        let mut store = PeerStore::new();
+
+        store.add_address(peer, addr.clone(), AddressSource::Discovery, None);
+        store.add_address(
+            peer,
+            addr.clone().with(Protocol::P2p(peer.into())),
+            AddressSource::Connection,
+            None,
+        );
+
+        let entries = store.get(&peer).unwrap().addresses().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].addr(), &addr);
+        assert_eq!(entries[0].source(), AddressSource::Connection);
+    }
+
+    #[test]
+    fn expired_addresses_are_ignored() {
+        let peer = PeerId::random();
+        let mut store = PeerStore::new();
+
+        store.add_address(
+            peer,
+            "/memory/1".parse().unwrap(),
+            AddressSource::Manual,
+            Some(Duration::from_secs(0)),
+        );
+        store.add_address(
+            peer,
+            "/memory/2".parse().unwrap(),
+            AddressSource::Manual,
+            Some(Duration::from_secs(60)),
+        );
+
+        let addrs = store.addresses_of_peer(&peer).cloned().collect::<Vec<_>>();
+        assert_eq!(addrs, vec!["/memory/2".parse::<Multiaddr>().unwrap()]);
+        assert_eq!(store.snapshot().peers[0].addresses.len(), 1);
+    }
+
+    #[test]
+    fn addresses_are_capped_per_peer() {
+        let peer = PeerId::random();
+        let mut store = PeerStore::new().with_max_addresses_per_peer(2);
+
+        store.add_address(
+            peer,
+            "/memory/1".parse().unwrap(),
+            AddressSource::Manual,
+            None,
+        );
+        store.add_address(
+            peer,
+            "/memory/2".parse().unwrap(),
+            AddressSource::Remote,
+            Some(Duration::from_secs(60)),
+        );
+        store.add_address(
+            peer,
+            "/memory/3".parse().unwrap(),
+            AddressSource::Remote,
+            Some(Duration::from_secs(120)),
+        );
+
+        let addrs = store.addresses_of_peer(&peer).cloned().collect::<Vec<_>>();
+        assert_eq!(
+            addrs,
+            vec![
+                "/memory/1".parse::<Multiaddr>().unwrap(),
+                "/memory/3".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_peers_are_removed() {
+        let connected = PeerId::random();
+        let disconnected = PeerId::random();
+        let mut store = PeerStore::new();
+        store.record_seen(connected);
+        store.record_seen(disconnected);
+
+        store.remove_unreachable(|peer| peer == &connected);
+
+        assert!(store.get(&connected).is_some());
+        assert!(store.get(&disconnected).is_none());
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let peer = PeerId::random();
+        let mut store = PeerStore::new();
+        store.add_address(
+            peer,
+            "/memory/1".parse().unwrap(),
+            AddressSource::Remote,
+            None,
+        );
+        store.set_protocols(peer, vec!["/ipfs/ping/1.0.0".into()]);
+        store.set_agent_version(peer, "test/1.0".into());
+
+        let snapshot = store.snapshot();
+        let restored = PeerStore::from_snapshot(snapshot.clone());
+        assert_eq!(restored.snapshot(), snapshot);
+    }
+}
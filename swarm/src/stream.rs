@@ -0,0 +1,641 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Raw, application-level streams.
+//!
+//! The [`Behaviour`] of this module allows application code to open and
+//! accept negotiated substreams for arbitrary protocols without writing a
+//! dedicated [`ConnectionHandler`](crate::ConnectionHandler) and
+//! [`NetworkBehaviour`].
+//!
+//! Streams are opened and accepted through a [`Control`], obtained via
+//! [`Behaviour::new_control`]. A [`Control`] can be cloned and used from
+//! any task:
+//!
+//!   * [`Control::open_stream`] opens a [`Stream`] to a peer, dialing the
+//!     peer if it is not yet connected.
+//!   * [`Control::accept`] registers a protocol and returns the
+//!     [`IncomingStreams`] opened by remote peers for that protocol.
+//!
+//! A connection is kept alive as long as any of its [`Stream`]s is alive.
+
+mod handler;
+
+use crate::dial_opts::{DialOpts, PeerCondition};
+use crate::{
+    DialError, IntoConnectionHandler, NegotiatedSubstream, NetworkBehaviour,
+    NetworkBehaviourAction, NotifyHandler, PollParameters,
+};
+use futures::channel::{mpsc, oneshot};
+use futures::prelude::*;
+use futures::task::AtomicWaker;
+use handler::{Handler, InboundStream, OpenRequest};
+use libp2p_core::{
+    connection::ConnectionId, ConnectedPoint, Multiaddr, NegotiatedProtocols, PeerId,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    error, fmt, io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+/// The number of inbound streams of a protocol buffered until they are
+/// consumed from the [`IncomingStreams`]. Further inbound streams are dropped,
+/// see [`IncomingStreams::num_dropped`].
+const INCOMING_STREAMS_BUFFER: usize = 16;
+
+/// A [`NetworkBehaviour`] for opening and accepting raw streams via a
+/// [`Control`].
+pub struct Behaviour {
+    shared: Arc<Mutex<Shared>>,
+    command_sender: mpsc::UnboundedSender<OpenStream>,
+    command_receiver: mpsc::UnboundedReceiver<OpenStream>,
+    /// The number of established connections per peer.
+    connected: HashMap<PeerId, usize>,
+    /// Requests for peers that are being dialed.
+    pending_dials: HashMap<PeerId, Vec<OpenRequest>>,
+    actions: VecDeque<NetworkBehaviourAction<void::Void, Handler>>,
+}
+
+/// State shared between the [`Behaviour`], its handlers and all [`Control`]s.
+#[derive(Default)]
+struct Shared {
+    /// The protocols accepted on inbound streams.
+    incoming: HashMap<String, Incoming>,
+}
+
+/// The sending side of an [`IncomingStreams`].
+struct Incoming {
+    sender: mpsc::Sender<(PeerId, Stream)>,
+    /// The number of streams dropped because the buffer was full.
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Shared {
+    fn supported_protocols(&self) -> Vec<String> {
+        self.incoming
+            .iter()
+            .filter(|(_, incoming)| !incoming.sender.is_closed())
+            .map(|(protocol, _)| protocol.clone())
+            .collect()
+    }
+}
+
+/// A request of a [`Control`] to open a stream.
+struct OpenStream {
+    peer: PeerId,
+    request: OpenRequest,
+}
+
+impl Behaviour {
+    /// Creates a new [`Behaviour`].
+    pub fn new() -> Self {
+        let (command_sender, command_receiver) = mpsc::unbounded();
+        Behaviour {
+            shared: Default::default(),
+            command_sender,
+            command_receiver,
+            connected: HashMap::new(),
+            pending_dials: HashMap::new(),
+            actions: VecDeque::new(),
+        }
+    }
+
+    /// Returns a new [`Control`] for opening and accepting streams.
+    pub fn new_control(&self) -> Control {
+        Control {
+            shared: self.shared.clone(),
+            command_sender: self.command_sender.clone(),
+        }
+    }
+
+    fn send_request(&mut self, peer: PeerId, connection: NotifyHandler, request: OpenRequest) {
+        self.actions
+            .push_back(NetworkBehaviourAction::NotifyHandler {
+                peer_id: peer,
+                handler: connection,
+                event: request,
+            });
+    }
+}
+
+impl Default for Behaviour {
+    fn default() -> Self {
+        Behaviour::new()
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = Handler;
+    type OutEvent = void::Void;
+
+    fn new_handler(&mut self) -> Self::ConnectionHandler {
+        Handler::new(self.shared.clone())
+    }
+
+    fn inject_connection_established(
+        &mut self,
+        peer_id: &PeerId,
+        connection_id: &ConnectionId,
+        _: &ConnectedPoint,
+        _: &NegotiatedProtocols,
+        _: Option<&Vec<Multiaddr>>,
+        _: usize,
+    ) {
+        *self.connected.entry(*peer_id).or_default() += 1;
+
+        for request in self.pending_dials.remove(peer_id).into_iter().flatten() {
+            self.send_request(*peer_id, NotifyHandler::One(*connection_id), request);
+        }
+    }
+
+    fn inject_connection_closed(
+        &mut self,
+        peer_id: &PeerId,
+        _: &ConnectionId,
+        _: &ConnectedPoint,
+        _: <Self::ConnectionHandler as IntoConnectionHandler>::Handler,
+        remaining_established: usize,
+    ) {
+        if remaining_established == 0 {
+            self.connected.remove(peer_id);
+        }
+    }
+
+    fn inject_dial_failure(
+        &mut self,
+        peer_id: Option<PeerId>,
+        _: Self::ConnectionHandler,
+        error: &DialError,
+    ) {
+        if let DialError::DialPeerConditionFalse(_) = error {
+            return;
+        }
+
+        if let Some(peer_id) = peer_id {
+            for request in self.pending_dials.remove(&peer_id).into_iter().flatten() {
+                request.respond(Err(OpenStreamError::Dial));
+            }
+        }
+    }
+
+    fn inject_event(&mut self, peer_id: PeerId, _: ConnectionId, event: InboundStream) {
+        let InboundStream { protocol, stream } = event;
+        let mut shared = self.shared.lock().expect("lock is not poisoned");
+
+        if let Some(incoming) = shared.incoming.get_mut(&protocol) {
+            match incoming.sender.try_send((peer_id, stream)) {
+                Ok(()) => {}
+                Err(e) if e.is_full() => {
+                    let dropped = incoming.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                    log::warn!(
+                        "Dropping inbound stream for {} from {}: buffer is full ({} dropped so far).",
+                        protocol,
+                        peer_id,
+                        dropped
+                    );
+                }
+                Err(_) => {
+                    shared.incoming.remove(&protocol);
+                }
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        _: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
+        loop {
+            if let Some(action) = self.actions.pop_front() {
+                return Poll::Ready(action);
+            }
+
+            let OpenStream { peer, request } = match self.command_receiver.poll_next_unpin(cx) {
+                Poll::Ready(Some(command)) => command,
+                Poll::Ready(None) => unreachable!("`Behaviour` holds a sender."),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if self.connected.contains_key(&peer) {
+                self.send_request(peer, NotifyHandler::Any, request);
+                continue;
+            }
+
+            let pending = self.pending_dials.entry(peer).or_default();
+            pending.push(request);
+            if pending.len() == 1 {
+                return Poll::Ready(NetworkBehaviourAction::Dial {
+                    opts: DialOpts::peer_id(peer)
+                        .condition(PeerCondition::Disconnected)
+                        .build(),
+                    handler: self.new_handler(),
+                });
+            }
+        }
+    }
+}
+
+/// A cloneable handle for opening and accepting streams of a [`Behaviour`].
+#[derive(Clone)]
+pub struct Control {
+    shared: Arc<Mutex<Shared>>,
+    command_sender: mpsc::UnboundedSender<OpenStream>,
+}
+
+impl Control {
+    /// Opens a new stream to the given peer, negotiating the given protocol.
+    ///
+    /// The peer is dialed if it is not yet connected.
+    pub async fn open_stream(
+        &mut self,
+        peer: PeerId,
+        protocol: impl Into<String>,
+    ) -> Result<Stream, OpenStreamError> {
+        let (sender, receiver) = oneshot::channel();
+        let request = OpenRequest {
+            protocol: protocol.into(),
+            sender,
+        };
+
+        self.command_sender
+            .unbounded_send(OpenStream { peer, request })
+            .map_err(|_| OpenStreamError::ConnectionClosed)?;
+
+        receiver
+            .await
+            .unwrap_or(Err(OpenStreamError::ConnectionClosed))
+    }
+
+    /// Accepts inbound streams for the given protocol.
+    ///
+    /// The protocol is supported on inbound streams for as long as the
+    /// returned [`IncomingStreams`] is alive. Fails if the protocol is
+    /// already being accepted.
+    pub fn accept(
+        &mut self,
+        protocol: impl Into<String>,
+    ) -> Result<IncomingStreams, AlreadyRegistered> {
+        let protocol = protocol.into();
+        let mut shared = self.shared.lock().expect("lock is not poisoned");
+
+        if let Some(incoming) = shared.incoming.get(&protocol) {
+            if !incoming.sender.is_closed() {
+                return Err(AlreadyRegistered(protocol));
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel(INCOMING_STREAMS_BUFFER);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        shared.incoming.insert(
+            protocol,
+            Incoming {
+                sender,
+                dropped: dropped.clone(),
+            },
+        );
+
+        Ok(IncomingStreams { receiver, dropped })
+    }
+}
+
+/// The inbound [`Stream`]s of a protocol registered via [`Control::accept`],
+/// together with the [`PeerId`] of the remote.
+///
+/// A limited number of streams is buffered until they are consumed. Further inbound
+/// streams are dropped and counted, see [`IncomingStreams::num_dropped`].
+pub struct IncomingStreams {
+    receiver: mpsc::Receiver<(PeerId, Stream)>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl IncomingStreams {
+    /// Returns the number of inbound streams dropped so far because they
+    /// were not consumed quickly enough.
+    pub fn num_dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl futures::Stream for IncomingStreams {
+    type Item = (PeerId, Stream);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+/// The alive [`Stream`]s of a connection, shared with its handler.
+#[derive(Default)]
+struct ActiveStreams {
+    /// The number of alive streams.
+    count: AtomicUsize,
+    /// Wakes the handler of the connection when a stream is dropped.
+    waker: AtomicWaker,
+}
+
+impl ActiveStreams {
+    /// Returns the number of alive streams.
+    ///
+    /// The handler registers its waker before reading the number, such that
+    /// it is woken by any stream dropped after reading it.
+    fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+}
+
+/// A negotiated stream opened via a [`Control`].
+///
+/// The connection of the stream is kept alive as long as the stream is.
+pub struct Stream {
+    inner: NegotiatedSubstream,
+    active: Arc<ActiveStreams>,
+}
+
+impl Stream {
+    fn new(inner: NegotiatedSubstream, active: Arc<ActiveStreams>) -> Self {
+        active.count.fetch_add(1, Ordering::SeqCst);
+        Stream { inner, active }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        // Release the stream before waking the handler, which otherwise may
+        // still count it when polled in between.
+        self.active.count.fetch_sub(1, Ordering::SeqCst);
+        self.active.waker.wake();
+    }
+}
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Stream").finish()
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// Possible errors of [`Control::open_stream`].
+#[derive(Debug)]
+pub enum OpenStreamError {
+    /// The peer could not be dialed.
+    ///
+    /// The cause is reported via
+    /// [`SwarmEvent::OutgoingConnectionError`](crate::SwarmEvent::OutgoingConnectionError).
+    Dial,
+    /// The remote does not support the requested protocol.
+    UnsupportedProtocol(String),
+    /// The connection closed before the stream was negotiated.
+    ConnectionClosed,
+    /// Negotiating the stream timed out.
+    Timeout,
+    /// An I/O error occurred while negotiating the stream.
+    Io(io::Error),
+}
+
+impl fmt::Display for OpenStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenStreamError::Dial => write!(f, "Failed to dial peer."),
+            OpenStreamError::UnsupportedProtocol(p) => {
+                write!(f, "Remote does not support protocol {}.", p)
+            }
+            OpenStreamError::ConnectionClosed => {
+                write!(f, "Connection closed before stream was negotiated.")
+            }
+            OpenStreamError::Timeout => write!(f, "Timeout negotiating stream."),
+            OpenStreamError::Io(e) => write!(f, "I/O error negotiating stream: {}", e),
+        }
+    }
+}
+
+impl error::Error for OpenStreamError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            OpenStreamError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Error of [`Control::accept`] if the protocol is already being accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlreadyRegistered(pub String);
+
+impl fmt::Display for AlreadyRegistered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Protocol {} is already being accepted.", self.0)
+    }
+}
+
+impl error::Error for AlreadyRegistered {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer_store::AddressSource;
+    use crate::{Swarm, SwarmBuilder, SwarmEvent};
+    use libp2p::core::{identity, multiaddr::multiaddr, transport, upgrade, Transport};
+    use libp2p::plaintext;
+    use libp2p::yamux;
+
+    fn new_swarm() -> (Swarm<Behaviour>, Control) {
+        let id_keys = identity::Keypair::generate_ed25519();
+        let local_public_key = id_keys.public();
+        let transport = transport::MemoryTransport
+            .upgrade(upgrade::Version::V1)
+            .authenticate(plaintext::PlainText2Config {
+                local_public_key: local_public_key.clone(),
+            })
+            .multiplex(yamux::YamuxConfig::default())
+            .boxed();
+        let behaviour = Behaviour::new();
+        let control = behaviour.new_control();
+        let swarm = SwarmBuilder::new(transport, behaviour, local_public_key.into()).build();
+        (swarm, control)
+    }
+
+    #[async_std::test]
+    async fn open_and_accept_stream() {
+        let (mut listener, mut listener_control) = new_swarm();
+        let (mut dialer, mut dialer_control) = new_swarm();
+        let listener_id = *listener.local_peer_id();
+        let dialer_id = *dialer.local_peer_id();
+
+        listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        let address = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = listener.select_next_some().await {
+                break address;
+            }
+        };
+        dialer
+            .peer_store_mut()
+            .add_address(listener_id, address, AddressSource::Manual, None);
+
+        let mut incoming = listener_control.accept("/echo/1.0.0").unwrap();
+        assert_eq!(
+            listener_control.accept("/echo/1.0.0").err(),
+            Some(AlreadyRegistered("/echo/1.0.0".into()))
+        );
+
+        async_std::task::spawn(listener.for_each(|_| future::ready(())));
+        async_std::task::spawn(dialer.for_each(|_| future::ready(())));
+
+        let mut outbound = dialer_control
+            .open_stream(listener_id, "/echo/1.0.0")
+            .await
+            .unwrap();
+        outbound.write_all(b"ping").await.unwrap();
+        outbound.flush().await.unwrap();
+
+        let (peer, mut inbound) = incoming.next().await.unwrap();
+        assert_eq!(peer, dialer_id);
+        let mut buf = [0; 4];
+        inbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        match dialer_control
+            .open_stream(listener_id, "/unknown/1.0.0")
+            .await
+        {
+            Err(OpenStreamError::UnsupportedProtocol(p)) => assert_eq!(p, "/unknown/1.0.0"),
+            r => panic!("Unexpected result: {:?}", r),
+        }
+    }
+
+    #[async_std::test]
+    async fn inbound_streams_beyond_buffer_are_counted() {
+        let (mut listener, mut listener_control) = new_swarm();
+        let (mut dialer, mut dialer_control) = new_swarm();
+        let listener_id = *listener.local_peer_id();
+
+        listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        let address = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = listener.select_next_some().await {
+                break address;
+            }
+        };
+        dialer
+            .peer_store_mut()
+            .add_address(listener_id, address, AddressSource::Manual, None);
+
+        let incoming = listener_control.accept("/echo/1.0.0").unwrap();
+
+        async_std::task::spawn(listener.for_each(|_| future::ready(())));
+        async_std::task::spawn(dialer.for_each(|_| future::ready(())));
+
+        // The channel holds one more item than its buffer size per sender.
+        let mut outbound = Vec::new();
+        for _ in 0..INCOMING_STREAMS_BUFFER + 1 {
+            let stream = dialer_control
+                .open_stream(listener_id, "/echo/1.0.0")
+                .await
+                .unwrap();
+            outbound.push(stream);
+        }
+        // The listener drops the next stream right after negotiating it, which
+        // may reset it before the dialer has read the confirmation.
+        let _ = dialer_control.open_stream(listener_id, "/echo/1.0.0").await;
+
+        while incoming.num_dropped() == 0 {
+            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(incoming.num_dropped(), 1);
+    }
+
+    #[async_std::test]
+    async fn connections_report_substreams_by_protocol() {
+        let (mut listener, mut listener_control) = new_swarm();
+        let (mut dialer, mut dialer_control) = new_swarm();
+        let listener_id = *listener.local_peer_id();
+
+        listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        let address = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = listener.select_next_some().await {
+                break address;
+            }
+        };
+        dialer
+            .peer_store_mut()
+            .add_address(listener_id, address, AddressSource::Manual, None);
+
+        let _incoming = listener_control.accept("/echo/1.0.0").unwrap();
+        async_std::task::spawn(listener.for_each(|_| future::ready(())));
+
+        let open = dialer_control.open_stream(listener_id, "/echo/1.0.0");
+        futures::pin_mut!(open);
+        let outbound = loop {
+            match future::select(open, dialer.select_next_some()).await {
+                future::Either::Left((stream, _)) => break stream.unwrap(),
+                future::Either::Right((_, o)) => open = o,
+            }
+        };
+
+        let connections = dialer.connections().collect::<Vec<_>>();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].peer_id(), listener_id);
+        assert!(connections[0].endpoint().is_dialer());
+        let protocols = connections[0].negotiated_protocols();
+        assert_eq!(protocols.security.as_deref(), Some("/plaintext/2.0.0"));
+        assert_eq!(protocols.muxer.as_deref(), Some("/yamux/1.0.0"));
+        assert_eq!(
+            connections[0].outbound_substreams().get("/echo/1.0.0"),
+            Some(&1)
+        );
+        assert!(connections[0].inbound_substreams().is_empty());
+
+        drop(outbound);
+        let connections = dialer.connections().collect::<Vec<_>>();
+        assert!(connections[0].outbound_substreams().is_empty());
+    }
+}
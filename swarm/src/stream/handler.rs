@@ -0,0 +1,231 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use super::{ActiveStreams, OpenStreamError, Shared, Stream};
+use crate::handler::{
+    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
+    SubstreamProtocol,
+};
+use crate::NegotiatedSubstream;
+use futures::channel::oneshot;
+use futures::future;
+use libp2p_core::time;
+use libp2p_core::upgrade::{
+    InboundUpgrade, NegotiationError, OutboundUpgrade, UpgradeError, UpgradeInfo,
+};
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+use void::Void;
+
+/// The duration for which a connection without any streams is kept alive.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A request to open a stream, sent from a [`Control`](super::Control) to
+/// the [`Handler`] of a connection.
+#[derive(Debug)]
+pub struct OpenRequest {
+    pub(super) protocol: String,
+    pub(super) sender: oneshot::Sender<Result<Stream, OpenStreamError>>,
+}
+
+impl OpenRequest {
+    pub(super) fn respond(self, result: Result<Stream, OpenStreamError>) {
+        let _ = self.sender.send(result);
+    }
+}
+
+/// A stream opened by the remote.
+#[derive(Debug)]
+pub struct InboundStream {
+    pub(super) protocol: String,
+    pub(super) stream: Stream,
+}
+
+/// The [`ConnectionHandler`] of the stream [`Behaviour`](super::Behaviour).
+pub struct Handler {
+    shared: Arc<Mutex<Shared>>,
+    /// Shared with all [`Stream`]s of the connection, which wake the handler
+    /// when they are dropped.
+    active: Arc<ActiveStreams>,
+    /// Requests for outbound streams not yet requested from the connection.
+    pending: VecDeque<OpenRequest>,
+    /// The number of outbound streams being negotiated.
+    negotiating: usize,
+    /// Inbound streams to report to the behaviour.
+    events: VecDeque<InboundStream>,
+    keep_alive: KeepAlive,
+}
+
+impl Handler {
+    pub(super) fn new(shared: Arc<Mutex<Shared>>) -> Self {
+        Handler {
+            shared,
+            active: Arc::new(ActiveStreams::default()),
+            pending: VecDeque::new(),
+            negotiating: 0,
+            events: VecDeque::new(),
+            keep_alive: KeepAlive::Yes,
+        }
+    }
+}
+
+impl ConnectionHandler for Handler {
+    type InEvent = OpenRequest;
+    type OutEvent = InboundStream;
+    type Error = Void;
+    type InboundProtocol = Upgrade;
+    type OutboundProtocol = Upgrade;
+    type InboundOpenInfo = ();
+    type OutboundOpenInfo = OpenRequest;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        let protocols = self
+            .shared
+            .lock()
+            .expect("lock is not poisoned")
+            .supported_protocols();
+        SubstreamProtocol::new(Upgrade { protocols }, ())
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        (stream, protocol): (NegotiatedSubstream, String),
+        (): Self::InboundOpenInfo,
+    ) {
+        let stream = Stream::new(stream, self.active.clone());
+        self.events.push_back(InboundStream { protocol, stream });
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        (stream, _): (NegotiatedSubstream, String),
+        request: Self::OutboundOpenInfo,
+    ) {
+        self.negotiating -= 1;
+        request.respond(Ok(Stream::new(stream, self.active.clone())));
+    }
+
+    fn inject_event(&mut self, request: Self::InEvent) {
+        self.pending.push_back(request);
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        request: Self::OutboundOpenInfo,
+        error: ConnectionHandlerUpgrErr<Void>,
+    ) {
+        self.negotiating -= 1;
+        let error = match error {
+            ConnectionHandlerUpgrErr::Timeout | ConnectionHandlerUpgrErr::Timer => {
+                OpenStreamError::Timeout
+            }
+            ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(NegotiationError::Failed)) => {
+                OpenStreamError::UnsupportedProtocol(request.protocol.clone())
+            }
+            ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(
+                NegotiationError::ProtocolError(e),
+            )) => OpenStreamError::Io(e.into()),
+            ConnectionHandlerUpgrErr::LimitExceeded(limit) => OpenStreamError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Stream exceeded {}", limit),
+            )),
+            ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(v)) => void::unreachable(v),
+        };
+        request.respond(Err(error));
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        ConnectionHandlerEvent<
+            Self::OutboundProtocol,
+            Self::OutboundOpenInfo,
+            Self::OutEvent,
+            Self::Error,
+        >,
+    > {
+        self.active.waker.register(cx.waker());
+
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(ConnectionHandlerEvent::Custom(event));
+        }
+
+        if let Some(request) = self.pending.pop_front() {
+            self.negotiating += 1;
+            let protocols = vec![request.protocol.clone()];
+            return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(Upgrade { protocols }, request),
+            });
+        }
+
+        if self.negotiating > 0 || self.active.count() > 0 {
+            self.keep_alive = KeepAlive::Yes;
+        } else if self.keep_alive.is_yes() {
+            self.keep_alive = KeepAlive::Until(time::now() + IDLE_TIMEOUT);
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Negotiates one of the given protocols, yielding the plain substream.
+#[derive(Debug, Clone)]
+pub struct Upgrade {
+    protocols: Vec<String>,
+}
+
+impl UpgradeInfo for Upgrade {
+    type Info = String;
+    type InfoIter = std::vec::IntoIter<String>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.protocols.clone().into_iter()
+    }
+}
+
+impl InboundUpgrade<NegotiatedSubstream> for Upgrade {
+    type Output = (NegotiatedSubstream, String);
+    type Error = Void;
+    type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: NegotiatedSubstream, protocol: String) -> Self::Future {
+        future::ready(Ok((socket, protocol)))
+    }
+}
+
+impl OutboundUpgrade<NegotiatedSubstream> for Upgrade {
+    type Output = (NegotiatedSubstream, String);
+    type Error = Void;
+    type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+    fn upgrade_outbound(self, socket: NegotiatedSubstream, protocol: String) -> Self::Future {
+        future::ready(Ok((socket, protocol)))
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Runs multiple [`Swarm`]s on top of a [`SimNetwork`], polling all of them
+//! from a single thread and only advancing the virtual clock once none of
+//! them can make progress. The virtual clock also drives the timers of the
+//! [`Swarm`]s and their connections, and the identities of the nodes are
+//! derived from the seed of the simulation.
+
+use futures::stream::FuturesUnordered;
+use futures::task::{waker, ArcWake};
+use futures::{Future, StreamExt};
+use libp2p::core::time::{self, ClockGuard};
+use libp2p::core::transport::simulation::{LinkConfig, NodeId, SimNetwork};
+use libp2p::core::{identity, upgrade, Multiaddr, PeerId, Transport};
+use libp2p::plaintext;
+use libp2p::yamux;
+use libp2p_swarm::{DummyBehaviour, KeepAlive, Swarm, SwarmBuilder, SwarmEvent};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use void::Void;
+
+type Tasks = Arc<Mutex<FuturesUnordered<Pin<Box<dyn Future<Output = ()> + Send>>>>>;
+
+/// A set of [`Swarm`]s on a [`SimNetwork`], whose connection tasks are run on
+/// the polling thread to keep the simulation deterministic.
+struct Simulation {
+    network: SimNetwork,
+    nodes: Vec<NodeId>,
+    swarms: Vec<Swarm<DummyBehaviour>>,
+    tasks: Tasks,
+    _clock: ClockGuard,
+}
+
+impl Simulation {
+    fn new(seed: u64, num_nodes: usize) -> Self {
+        Self::with_keep_alive(seed, num_nodes, || KeepAlive::Yes)
+    }
+
+    /// Creates a [`Simulation`] whose connections are kept alive according
+    /// to `keep_alive`, called once the virtual clock is in effect.
+    fn with_keep_alive(seed: u64, num_nodes: usize, keep_alive: impl Fn() -> KeepAlive) -> Self {
+        let network = SimNetwork::new(seed);
+        let clock = network.enter();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let tasks = Tasks::default();
+        let mut nodes = Vec::new();
+        let mut swarms = Vec::new();
+        for _ in 0..num_nodes {
+            let node = network.node();
+            nodes.push(node.node_id());
+
+            let mut secret = [0; 32];
+            rng.fill_bytes(&mut secret);
+            let secret = identity::ed25519::SecretKey::from_bytes(&mut secret)
+                .expect("Any 32 bytes are a valid secret key.");
+            let local_public_key = identity::Keypair::Ed25519(secret.into()).public();
+            let transport = node
+                .upgrade(upgrade::Version::V1)
+                .authenticate(plaintext::PlainText2Config {
+                    local_public_key: local_public_key.clone(),
+                })
+                .multiplex(yamux::YamuxConfig::default())
+                .boxed();
+            let tasks = tasks.clone();
+            let swarm = SwarmBuilder::new(
+                transport,
+                DummyBehaviour::with_keep_alive(keep_alive()),
+                local_public_key.into(),
+            )
+            .executor(Box::new(move |task| tasks.lock().unwrap().push(task)))
+            .build();
+            swarms.push(swarm);
+        }
+
+        Simulation {
+            network,
+            nodes,
+            swarms,
+            tasks,
+            _clock: clock,
+        }
+    }
+
+    fn peer_id(&self, node: usize) -> PeerId {
+        *self.swarms[node].local_peer_id()
+    }
+
+    fn listen(&mut self, node: usize) -> Multiaddr {
+        self.swarms[node]
+            .listen_on("/memory/0".parse().unwrap())
+            .unwrap();
+        self.run_until(|n, event| match event {
+            SwarmEvent::NewListenAddr { address, .. } if n == node => Some(address),
+            _ => None,
+        })
+    }
+
+    /// Polls all swarms until `f` returns [`Some`] for an event of one of
+    /// them, advancing the virtual clock whenever none can make progress.
+    fn run_until<T>(&mut self, mut f: impl FnMut(usize, SwarmEvent<Void, Void>) -> Option<T>) -> T {
+        let woken = Arc::new(Woken(AtomicBool::new(true)));
+        let waker = waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if !woken.0.swap(false, Ordering::SeqCst) {
+                assert!(
+                    self.network.advance_to_next_deadline(),
+                    "Simulation stalled at {:?}.",
+                    self.network.now()
+                );
+            }
+
+            let mut tasks = self.tasks.lock().unwrap();
+            while let Poll::Ready(Some(())) = tasks.poll_next_unpin(&mut cx) {}
+            drop(tasks);
+
+            for (node, swarm) in self.swarms.iter_mut().enumerate() {
+                while let Poll::Ready(Some(event)) = swarm.poll_next_unpin(&mut cx) {
+                    woken.0.store(true, Ordering::SeqCst);
+                    if let Some(t) = f(node, event) {
+                        return t;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Records whether any task of the [`Simulation`] was woken up.
+struct Woken(AtomicBool);
+
+impl ArcWake for Woken {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn connections_are_established_according_to_link_latency() {
+    let latency = Duration::from_millis(50);
+    let mut sim = Simulation::new(0, 3);
+    sim.network
+        .set_default_link(LinkConfig::default().with_latency(latency));
+    sim.network.set_link(
+        sim.nodes[0],
+        sim.nodes[2],
+        LinkConfig::default().with_latency(latency * 10),
+    );
+
+    let address = sim.listen(0);
+    sim.swarms[2].dial(address.clone()).unwrap();
+    sim.swarms[1].dial(address).unwrap();
+
+    let network = sim.network.clone();
+    let mut established = Vec::new();
+    sim.run_until(|node, event| match event {
+        SwarmEvent::ConnectionEstablished { peer_id, .. } if node == 0 => {
+            established.push((peer_id, network.now()));
+            (established.len() == 2).then(|| ())
+        }
+        SwarmEvent::ConnectionEstablished { .. } | SwarmEvent::IncomingConnection { .. } => None,
+        e => panic!("Unexpected network event: {:?}", e),
+    });
+
+    // Dialing alone takes a round trip, negotiating the connection at least
+    // another one.
+    assert_eq!(established[0].0, sim.peer_id(1));
+    assert!(established[0].1 >= latency * 4);
+    assert_eq!(established[1].0, sim.peer_id(2));
+    assert!(established[1].1 >= latency * 10 * 4);
+}
+
+#[test]
+fn partitioned_nodes_connect_once_healed() {
+    let mut sim = Simulation::new(0, 2);
+    let address = sim.listen(0);
+
+    sim.network.partition([[sim.nodes[0]], [sim.nodes[1]]]);
+    sim.swarms[1].dial(address.clone()).unwrap();
+    sim.run_until(|node, event| match event {
+        SwarmEvent::OutgoingConnectionError { .. } if node == 1 => Some(()),
+        e => panic!("Unexpected network event: {:?}", e),
+    });
+
+    sim.network.heal();
+    sim.swarms[1].dial(address).unwrap();
+    let listener = sim.peer_id(0);
+    sim.run_until(|node, event| match event {
+        SwarmEvent::ConnectionEstablished { peer_id, .. } if node == 1 => {
+            assert_eq!(peer_id, listener);
+            Some(())
+        }
+        SwarmEvent::ConnectionEstablished { .. } | SwarmEvent::IncomingConnection { .. } => None,
+        e => panic!("Unexpected network event: {:?}", e),
+    });
+}
+
+#[test]
+fn identities_are_derived_from_the_seed() {
+    let peer_ids = |seed| {
+        let sim = Simulation::new(seed, 2);
+        (sim.peer_id(0), sim.peer_id(1))
+    };
+
+    assert_eq!(peer_ids(0), peer_ids(0));
+    assert_ne!(peer_ids(0).0, peer_ids(0).1);
+    assert_ne!(peer_ids(0), peer_ids(1));
+}
+
+#[test]
+fn idle_connections_are_closed_according_to_virtual_clock() {
+    let idle_timeout = Duration::from_secs(3600);
+    let mut sim =
+        Simulation::with_keep_alive(0, 2, || KeepAlive::Until(time::now() + idle_timeout));
+    let start = Instant::now();
+
+    let address = sim.listen(0);
+    sim.swarms[1].dial(address).unwrap();
+    let network = sim.network.clone();
+    let closed_at = sim.run_until(|node, event| match event {
+        SwarmEvent::ConnectionClosed { .. } if node == 1 => Some(network.now()),
+        SwarmEvent::ConnectionEstablished { .. }
+        | SwarmEvent::ConnectionClosed { .. }
+        | SwarmEvent::IncomingConnection { .. } => None,
+        e => panic!("Unexpected network event: {:?}", e),
+    });
+
+    assert!(closed_at >= idle_timeout);
+    assert!(start.elapsed() < idle_timeout);
+}
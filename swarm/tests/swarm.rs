@@ -0,0 +1,2018 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::executor::block_on;
+use futures::future::poll_fn;
+use futures::future::Either;
+use futures::stream::FusedStream;
+use futures::{executor, future, ready, AsyncRead, StreamExt};
+use libp2p::core::{identity, multiaddr, transport, upgrade};
+use libp2p::plaintext;
+use libp2p::yamux;
+use libp2p_core::multiaddr::multiaddr;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::transport::ListenerEvent;
+use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr, NegotiatedProtocols, PeerId, Transport};
+use libp2p_swarm::behaviour::toggle::Toggle;
+use libp2p_swarm::dial_opts::{DialOpts, DialPriority};
+use libp2p_swarm::handler::DummyConnectionHandler;
+use libp2p_swarm::peer_store::AddressSource;
+use libp2p_swarm::*;
+use libp2p_swarm_test::behaviour::{CallTraceBehaviour, MockBehaviour};
+use quickcheck::{quickcheck, Arbitrary, Gen, QuickCheck};
+use rand::prelude::SliceRandom;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::io;
+use std::num::{NonZeroU8, NonZeroUsize};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use void::Void;
+
+// Test execution state.
+// Connection => Disconnecting => Connecting.
+enum State {
+    Connecting,
+    Disconnecting,
+}
+
+fn new_test_swarm<T, O>(handler_proto: T) -> SwarmBuilder<CallTraceBehaviour<MockBehaviour<T, O>>>
+where
+    T: ConnectionHandler + Clone,
+    T::OutEvent: Clone,
+    O: Send + 'static,
+{
+    new_test_swarm_with_behaviour(CallTraceBehaviour::new(MockBehaviour::new(handler_proto)))
+}
+
+fn new_test_swarm_with_behaviour<B: NetworkBehaviour>(behaviour: B) -> SwarmBuilder<B> {
+    let id_keys = identity::Keypair::generate_ed25519();
+    let local_public_key = id_keys.public();
+    let transport = transport::MemoryTransport::default()
+        .upgrade(upgrade::Version::V1)
+        .authenticate(plaintext::PlainText2Config {
+            local_public_key: local_public_key.clone(),
+        })
+        .multiplex(yamux::YamuxConfig::default())
+        .boxed();
+    SwarmBuilder::new(transport, behaviour, local_public_key.into())
+}
+
+fn swarms_connected<TBehaviour>(
+    swarm1: &Swarm<CallTraceBehaviour<TBehaviour>>,
+    swarm2: &Swarm<CallTraceBehaviour<TBehaviour>>,
+    num_connections: usize,
+) -> bool
+where
+    TBehaviour: NetworkBehaviour,
+    <<TBehaviour::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent: Clone,
+{
+    swarm1
+        .behaviour()
+        .num_connections_to_peer(*swarm2.local_peer_id())
+        == num_connections
+        && swarm2
+            .behaviour()
+            .num_connections_to_peer(*swarm1.local_peer_id())
+            == num_connections
+        && swarm1.is_connected(swarm2.local_peer_id())
+        && swarm2.is_connected(swarm1.local_peer_id())
+}
+
+fn swarms_disconnected<TBehaviour: NetworkBehaviour>(
+    swarm1: &Swarm<CallTraceBehaviour<TBehaviour>>,
+    swarm2: &Swarm<CallTraceBehaviour<TBehaviour>>,
+) -> bool
+where
+    TBehaviour: NetworkBehaviour,
+    <<TBehaviour::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent: Clone
+{
+    swarm1
+        .behaviour()
+        .num_connections_to_peer(*swarm2.local_peer_id())
+        == 0
+        && swarm2
+            .behaviour()
+            .num_connections_to_peer(*swarm1.local_peer_id())
+            == 0
+        && !swarm1.is_connected(swarm2.local_peer_id())
+        && !swarm2.is_connected(swarm1.local_peer_id())
+}
+
+/// Establishes multiple connections between two peers,
+/// after which one peer bans the other.
+///
+/// The test expects both behaviours to be notified via pairs of
+/// inject_connected / inject_disconnected as well as
+/// inject_connection_established / inject_connection_closed calls
+/// while unbanned.
+///
+/// While the ban is in effect, further dials occur. For these connections no
+/// `inject_connected`, `inject_connection_established`, `inject_disconnected`,
+/// `inject_connection_closed` calls should be registered.
+#[test]
+fn test_connect_disconnect_ban() {
+    // Since the test does not try to open any substreams, we can
+    // use the dummy protocols handler.
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+
+    let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
+
+    let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+    let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+
+    swarm1.listen_on(addr1.clone()).unwrap();
+    swarm2.listen_on(addr2.clone()).unwrap();
+
+    let swarm1_id = *swarm1.local_peer_id();
+
+    enum Stage {
+        /// Waiting for the peers to connect. Banning has not occurred.
+        Connecting,
+        /// Ban occurred.
+        Banned,
+        // Ban is in place and a dial is ongoing.
+        BannedDial,
+        // Mid-ban dial was registered and the peer was unbanned.
+        Unbanned,
+        // There are dial attempts ongoing for the no longer banned peers.
+        Reconnecting,
+    }
+
+    let num_connections = 10;
+
+    for _ in 0..num_connections {
+        swarm1.dial(addr2.clone()).unwrap();
+    }
+
+    let mut s1_expected_conns = num_connections;
+    let mut s2_expected_conns = num_connections;
+
+    let mut stage = Stage::Connecting;
+
+    executor::block_on(future::poll_fn(move |cx| loop {
+        let poll1 = swarm1.poll_next_unpin(cx);
+        let poll2 = swarm2.poll_next_unpin(cx);
+        match stage {
+            Stage::Connecting => {
+                if swarm1.behaviour().assert_connected(s1_expected_conns, 1)
+                    && swarm2.behaviour().assert_connected(s2_expected_conns, 1)
+                {
+                    // Setup to test that already established connections are correctly closed
+                    // and reported as such after the peer is banned.
+                    swarm2.ban_peer_id(swarm1_id);
+                    stage = Stage::Banned;
+                }
+            }
+            Stage::Banned => {
+                if swarm1.behaviour().assert_disconnected(s1_expected_conns, 1)
+                    && swarm2.behaviour().assert_disconnected(s2_expected_conns, 1)
+                {
+                    // Setup to test that new connections of banned peers are not reported.
+                    swarm1.dial(addr2.clone()).unwrap();
+                    s1_expected_conns += 1;
+                    stage = Stage::BannedDial;
+                }
+            }
+            Stage::BannedDial => {
+                if swarm2.network_info().num_peers() == 1 {
+                    // The banned connection was established. Check that it was not reported to
+                    // the behaviour of the banning swarm.
+                    assert_eq!(
+                        swarm2.behaviour().inject_connection_established.len(),
+                        s2_expected_conns,
+                        "No additional closed connections should be reported for the banned peer"
+                    );
+
+                    // Setup to test that the banned connection is not reported upon closing
+                    // even if the peer is unbanned.
+                    swarm2.unban_peer_id(swarm1_id);
+                    stage = Stage::Unbanned;
+                }
+            }
+            Stage::Unbanned => {
+                if swarm2.network_info().num_peers() == 0 {
+                    // The banned connection has closed. Check that it was not reported.
+                    assert_eq!(
+                        swarm2.behaviour().inject_connection_closed.len(),
+                        s2_expected_conns,
+                        "No additional closed connections should be reported for the banned peer"
+                    );
+
+                    // Setup to test that a ban lifted does not affect future connections.
+                    for _ in 0..num_connections {
+                        swarm1.dial(addr2.clone()).unwrap();
+                    }
+                    s1_expected_conns += num_connections;
+                    s2_expected_conns += num_connections;
+                    stage = Stage::Reconnecting;
+                }
+            }
+            Stage::Reconnecting => {
+                if swarm1.behaviour().inject_connection_established.len() == s1_expected_conns
+                    && swarm2.behaviour().assert_connected(s2_expected_conns, 2)
+                {
+                    return Poll::Ready(());
+                }
+            }
+        }
+
+        if poll1.is_pending() && poll2.is_pending() {
+            return Poll::Pending;
+        }
+    }))
+}
+
+/// Establishes multiple connections between two peers,
+/// after which one peer disconnects the other using [`Swarm::disconnect_peer_id`].
+///
+/// The test expects both behaviours to be notified via pairs of
+/// inject_connected / inject_disconnected as well as
+/// inject_connection_established / inject_connection_closed calls.
+#[test]
+fn test_swarm_disconnect() {
+    // Since the test does not try to open any substreams, we can
+    // use the dummy protocols handler.
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+
+    let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
+
+    let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+    let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+
+    swarm1.listen_on(addr1.clone()).unwrap();
+    swarm2.listen_on(addr2.clone()).unwrap();
+
+    let swarm1_id = *swarm1.local_peer_id();
+
+    let mut reconnected = false;
+    let num_connections = 10;
+
+    for _ in 0..num_connections {
+        swarm1.dial(addr2.clone()).unwrap();
+    }
+    let mut state = State::Connecting;
+
+    executor::block_on(future::poll_fn(move |cx| loop {
+        let poll1 = swarm1.poll_next_unpin(cx);
+        let poll2 = swarm2.poll_next_unpin(cx);
+        match state {
+            State::Connecting => {
+                if swarms_connected(&swarm1, &swarm2, num_connections) {
+                    if reconnected {
+                        return Poll::Ready(());
+                    }
+                    swarm2
+                        .disconnect_peer_id(swarm1_id)
+                        .expect("Error disconnecting");
+                    state = State::Disconnecting;
+                }
+            }
+            State::Disconnecting => {
+                if swarms_disconnected(&swarm1, &swarm2) {
+                    if reconnected {
+                        return Poll::Ready(());
+                    }
+                    reconnected = true;
+                    for _ in 0..num_connections {
+                        swarm2.dial(addr1.clone()).unwrap();
+                    }
+                    state = State::Connecting;
+                }
+            }
+        }
+
+        if poll1.is_pending() && poll2.is_pending() {
+            return Poll::Pending;
+        }
+    }))
+}
+
+/// Establishes multiple connections between two peers,
+/// after which one peer disconnects the other
+/// using [`NetworkBehaviourAction::CloseConnection`] returned by a [`NetworkBehaviour`].
+///
+/// The test expects both behaviours to be notified via pairs of
+/// inject_connected / inject_disconnected as well as
+/// inject_connection_established / inject_connection_closed calls.
+#[test]
+fn test_behaviour_disconnect_all() {
+    // Since the test does not try to open any substreams, we can
+    // use the dummy protocols handler.
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+
+    let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
+
+    let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+    let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+
+    swarm1.listen_on(addr1.clone()).unwrap();
+    swarm2.listen_on(addr2.clone()).unwrap();
+
+    let swarm1_id = *swarm1.local_peer_id();
+
+    let mut reconnected = false;
+    let num_connections = 10;
+
+    for _ in 0..num_connections {
+        swarm1.dial(addr2.clone()).unwrap();
+    }
+    let mut state = State::Connecting;
+
+    executor::block_on(future::poll_fn(move |cx| loop {
+        let poll1 = swarm1.poll_next_unpin(cx);
+        let poll2 = swarm2.poll_next_unpin(cx);
+        match state {
+            State::Connecting => {
+                if swarms_connected(&swarm1, &swarm2, num_connections) {
+                    if reconnected {
+                        return Poll::Ready(());
+                    }
+                    swarm2.behaviour_mut().inner().next_action.replace(
+                        NetworkBehaviourAction::CloseConnection {
+                            peer_id: swarm1_id,
+                            connection: CloseConnection::All,
+                        },
+                    );
+                    state = State::Disconnecting;
+                    continue;
+                }
+            }
+            State::Disconnecting => {
+                if swarms_disconnected(&swarm1, &swarm2) {
+                    reconnected = true;
+                    for _ in 0..num_connections {
+                        swarm2.dial(addr1.clone()).unwrap();
+                    }
+                    state = State::Connecting;
+                    continue;
+                }
+            }
+        }
+
+        if poll1.is_pending() && poll2.is_pending() {
+            return Poll::Pending;
+        }
+    }))
+}
+
+/// Establishes multiple connections between two peers,
+/// after which one peer closes a single connection
+/// using [`NetworkBehaviourAction::CloseConnection`] returned by a [`NetworkBehaviour`].
+///
+/// The test expects both behaviours to be notified via pairs of
+/// inject_connected / inject_disconnected as well as
+/// inject_connection_established / inject_connection_closed calls.
+#[test]
+fn test_behaviour_disconnect_one() {
+    // Since the test does not try to open any substreams, we can
+    // use the dummy protocols handler.
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+
+    let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
+
+    let addr1: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+    let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+
+    swarm1.listen_on(addr1.clone()).unwrap();
+    swarm2.listen_on(addr2.clone()).unwrap();
+
+    let swarm1_id = *swarm1.local_peer_id();
+
+    let num_connections = 10;
+
+    for _ in 0..num_connections {
+        swarm1.dial(addr2.clone()).unwrap();
+    }
+    let mut state = State::Connecting;
+    let mut disconnected_conn_id = None;
+
+    executor::block_on(future::poll_fn(move |cx| loop {
+        let poll1 = swarm1.poll_next_unpin(cx);
+        let poll2 = swarm2.poll_next_unpin(cx);
+        match state {
+            State::Connecting => {
+                if swarms_connected(&swarm1, &swarm2, num_connections) {
+                    disconnected_conn_id = {
+                        let conn_id =
+                            swarm2.behaviour().inject_connection_established[num_connections / 2].1;
+                        swarm2.behaviour_mut().inner().next_action.replace(
+                            NetworkBehaviourAction::CloseConnection {
+                                peer_id: swarm1_id,
+                                connection: CloseConnection::One(conn_id),
+                            },
+                        );
+                        Some(conn_id)
+                    };
+                    state = State::Disconnecting;
+                }
+            }
+            State::Disconnecting => {
+                for s in &[&swarm1, &swarm2] {
+                    assert!(s
+                        .behaviour()
+                        .inject_connection_closed
+                        .iter()
+                        .all(|(.., remaining_conns)| *remaining_conns > 0));
+                    assert_eq!(
+                        s.behaviour().inject_connection_established.len(),
+                        num_connections
+                    );
+                    s.behaviour().assert_connected(num_connections, 1);
+                }
+                if [&swarm1, &swarm2]
+                    .iter()
+                    .all(|s| s.behaviour().inject_connection_closed.len() == 1)
+                {
+                    let conn_id = swarm2.behaviour().inject_connection_closed[0].1;
+                    assert_eq!(Some(conn_id), disconnected_conn_id);
+                    return Poll::Ready(());
+                }
+            }
+        }
+
+        if poll1.is_pending() && poll2.is_pending() {
+            return Poll::Pending;
+        }
+    }))
+}
+
+#[test]
+fn concurrent_dialing() {
+    #[derive(Clone, Debug)]
+    struct DialConcurrencyFactor(NonZeroU8);
+
+    impl Arbitrary for DialConcurrencyFactor {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self(NonZeroU8::new(g.gen_range(1, 11)).unwrap())
+        }
+    }
+
+    fn prop(concurrency_factor: DialConcurrencyFactor) {
+        block_on(async {
+            let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler {
+                keep_alive: KeepAlive::Yes,
+            })
+            .dial_concurrency_factor(concurrency_factor.0)
+            .build();
+
+            // Listen on `concurrency_factor + 1` addresses.
+            //
+            // `+ 2` to ensure a subset of addresses is dialed by network_2.
+            let num_listen_addrs = concurrency_factor.0.get() + 2;
+            let mut listen_addresses = Vec::new();
+            let mut listeners = Vec::new();
+            for _ in 0..num_listen_addrs {
+                let mut listener = transport::MemoryTransport {}
+                    .listen_on("/memory/0".parse().unwrap())
+                    .unwrap();
+
+                match listener.next().await.unwrap().unwrap() {
+                    ListenerEvent::NewAddress(address) => {
+                        listen_addresses.push(address);
+                    }
+                    _ => panic!("Expected `NewListenAddr` event."),
+                }
+
+                listeners.push(listener);
+            }
+
+            // Have swarm dial each listener and wait for each listener to receive the incoming
+            // connections.
+            swarm
+                .dial(
+                    DialOpts::peer_id(PeerId::random())
+                        .addresses(listen_addresses.into())
+                        .build(),
+                )
+                .unwrap();
+            for mut listener in listeners.into_iter() {
+                loop {
+                    match futures::future::select(listener.next(), swarm.next()).await {
+                        Either::Left((Some(Ok(ListenerEvent::Upgrade { .. })), _)) => {
+                            break;
+                        }
+                        Either::Left(_) => {
+                            panic!("Unexpected listener event.")
+                        }
+                        Either::Right((e, _)) => {
+                            panic!("Expect swarm to not emit any event {:?}", e)
+                        }
+                    }
+                }
+            }
+
+            match swarm.next().await.unwrap() {
+                SwarmEvent::OutgoingConnectionError { .. } => {}
+                e => panic!("Unexpected swarm event {:?}", e),
+            }
+        })
+    }
+
+    QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _);
+}
+
+#[test]
+fn max_outgoing() {
+    use rand::Rng;
+
+    let outgoing_limit = rand::thread_rng().gen_range(1, 10);
+
+    let limits = ConnectionLimits::default().with_max_pending_outgoing(Some(outgoing_limit));
+    let mut network = new_test_swarm::<_, ()>(DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    })
+    .connection_limits(limits)
+    .build();
+
+    let addr: Multiaddr = "/memory/1234".parse().unwrap();
+
+    let target = PeerId::random();
+    for _ in 0..outgoing_limit {
+        network
+            .dial(
+                DialOpts::peer_id(target)
+                    .addresses(vec![addr.clone()])
+                    .build(),
+            )
+            .ok()
+            .expect("Unexpected connection limit.");
+    }
+
+    match network
+        .dial(
+            DialOpts::peer_id(target)
+                .addresses(vec![addr.clone()])
+                .build(),
+        )
+        .expect_err("Unexpected dialing success.")
+    {
+        DialError::ConnectionLimit(limit) => {
+            assert_eq!(limit.current, outgoing_limit);
+            assert_eq!(limit.limit, outgoing_limit);
+        }
+        e => panic!("Unexpected error: {:?}", e),
+    }
+
+    let info = network.network_info();
+    assert_eq!(info.num_peers(), 0);
+    assert_eq!(
+        info.connection_counters().num_pending_outgoing(),
+        outgoing_limit
+    );
+}
+
+#[test]
+fn max_established_incoming() {
+    use rand::Rng;
+
+    #[derive(Debug, Clone)]
+    struct Limit(u32);
+
+    impl Arbitrary for Limit {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self(g.gen_range(1, 10))
+        }
+    }
+
+    fn limits(limit: u32) -> ConnectionLimits {
+        ConnectionLimits::default().with_max_established_incoming(Some(limit))
+    }
+
+    fn prop(limit: Limit) {
+        let limit = limit.0;
+
+        let mut network1 = new_test_swarm::<_, ()>(DummyConnectionHandler {
+            keep_alive: KeepAlive::Yes,
+        })
+        .connection_limits(limits(limit))
+        .build();
+        let mut network2 = new_test_swarm::<_, ()>(DummyConnectionHandler {
+            keep_alive: KeepAlive::Yes,
+        })
+        .connection_limits(limits(limit))
+        .build();
+
+        let _ = network1.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        let listen_addr = async_std::task::block_on(poll_fn(|cx| {
+            match ready!(network1.poll_next_unpin(cx)).unwrap() {
+                SwarmEvent::NewListenAddr { address, .. } => Poll::Ready(address),
+                e => panic!("Unexpected network event: {:?}", e),
+            }
+        }));
+
+        // Spawn and block on the dialer.
+        async_std::task::block_on({
+            let mut n = 0;
+            let _ = network2.dial(listen_addr.clone()).unwrap();
+
+            let mut expected_closed = false;
+            let mut network_1_established = false;
+            let mut network_2_established = false;
+            let mut network_1_limit_reached = false;
+            let mut network_2_limit_reached = false;
+            poll_fn(move |cx| {
+                loop {
+                    let mut network_1_pending = false;
+                    let mut network_2_pending = false;
+
+                    match network1.poll_next_unpin(cx) {
+                        Poll::Ready(Some(SwarmEvent::IncomingConnection { .. })) => {}
+                        Poll::Ready(Some(SwarmEvent::ConnectionEstablished { .. })) => {
+                            network_1_established = true;
+                        }
+                        Poll::Ready(Some(SwarmEvent::IncomingConnectionError {
+                            error: PendingConnectionError::ConnectionLimit(err),
+                            ..
+                        })) => {
+                            assert_eq!(err.limit, limit);
+                            assert_eq!(err.limit, err.current);
+                            let info = network1.network_info();
+                            let counters = info.connection_counters();
+                            assert_eq!(counters.num_established_incoming(), limit);
+                            assert_eq!(counters.num_established(), limit);
+                            network_1_limit_reached = true;
+                        }
+                        Poll::Pending => {
+                            network_1_pending = true;
+                        }
+                        e => panic!("Unexpected network event: {:?}", e),
+                    }
+
+                    match network2.poll_next_unpin(cx) {
+                        Poll::Ready(Some(SwarmEvent::ConnectionEstablished { .. })) => {
+                            network_2_established = true;
+                        }
+                        Poll::Ready(Some(SwarmEvent::ConnectionClosed { .. })) => {
+                            assert!(expected_closed);
+                            let info = network2.network_info();
+                            let counters = info.connection_counters();
+                            assert_eq!(counters.num_established_outgoing(), limit);
+                            assert_eq!(counters.num_established(), limit);
+                            network_2_limit_reached = true;
+                        }
+                        Poll::Pending => {
+                            network_2_pending = true;
+                        }
+                        e => panic!("Unexpected network event: {:?}", e),
+                    }
+
+                    if network_1_pending && network_2_pending {
+                        return Poll::Pending;
+                    }
+
+                    if network_1_established && network_2_established {
+                        network_1_established = false;
+                        network_2_established = false;
+
+                        if n <= limit {
+                            // Dial again until the limit is exceeded.
+                            n += 1;
+                            network2.dial(listen_addr.clone()).unwrap();
+
+                            if n == limit {
+                                // The the next dialing attempt exceeds the limit, this
+                                // is the connection we expected to get closed.
+                                expected_closed = true;
+                            }
+                        } else {
+                            panic!("Expect networks not to establish connections beyond the limit.")
+                        }
+                    }
+
+                    if network_1_limit_reached && network_2_limit_reached {
+                        return Poll::Ready(());
+                    }
+                }
+            })
+        });
+    }
+
+    quickcheck(prop as fn(_));
+}
+
+#[test]
+fn invalid_peer_id() {
+    // Checks whether dialing an address containing the wrong peer id raises an error
+    // for the expected peer id instead of the obtained peer id.
+
+    let mut swarm1 = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+
+    swarm1.listen_on("/memory/0".parse().unwrap()).unwrap();
+
+    let address =
+        futures::executor::block_on(future::poll_fn(|cx| match swarm1.poll_next_unpin(cx) {
+            Poll::Ready(Some(SwarmEvent::NewListenAddr { address, .. })) => Poll::Ready(address),
+            Poll::Pending => Poll::Pending,
+            _ => panic!("Was expecting the listen address to be reported"),
+        }));
+
+    let other_id = PeerId::random();
+    let other_addr = address.with(Protocol::P2p(other_id.into()));
+
+    swarm2.dial(other_addr.clone()).unwrap();
+
+    let (peer_id, error) = futures::executor::block_on(future::poll_fn(|cx| {
+        if let Poll::Ready(Some(SwarmEvent::IncomingConnection { .. })) = swarm1.poll_next_unpin(cx)
+        {
+        }
+
+        match swarm2.poll_next_unpin(cx) {
+            Poll::Ready(Some(SwarmEvent::OutgoingConnectionError { peer_id, error, .. })) => {
+                Poll::Ready((peer_id, error))
+            }
+            Poll::Ready(x) => panic!("unexpected {:?}", x),
+            Poll::Pending => Poll::Pending,
+        }
+    }));
+    assert_eq!(peer_id.unwrap(), other_id);
+    match error {
+        DialError::WrongPeerId { obtained, endpoint } => {
+            assert_eq!(obtained, *swarm1.local_peer_id());
+            assert_eq!(
+                endpoint,
+                ConnectedPoint::Dialer {
+                    address: other_addr,
+                    role_override: Endpoint::Dialer,
+                }
+            );
+        }
+        x => panic!("wrong error {:?}", x),
+    }
+}
+
+#[test]
+fn dial_self() {
+    // Check whether dialing ourselves correctly fails.
+    //
+    // Dialing the same address we're listening should result in three events:
+    //
+    // - The incoming connection notification (before we know the incoming peer ID).
+    // - The connection error for the dialing endpoint (once we've determined that it's our own ID).
+    // - The connection error for the listening endpoint (once we've determined that it's our own ID).
+    //
+    // The last two can happen in any order.
+
+    let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    swarm.listen_on("/memory/0".parse().unwrap()).unwrap();
+
+    let local_address =
+        futures::executor::block_on(future::poll_fn(|cx| match swarm.poll_next_unpin(cx) {
+            Poll::Ready(Some(SwarmEvent::NewListenAddr { address, .. })) => Poll::Ready(address),
+            Poll::Pending => Poll::Pending,
+            _ => panic!("Was expecting the listen address to be reported"),
+        }));
+
+    swarm.dial(local_address.clone()).unwrap();
+
+    let mut got_dial_err = false;
+    let mut got_inc_err = false;
+    futures::executor::block_on(future::poll_fn(|cx| -> Poll<Result<(), io::Error>> {
+        loop {
+            match swarm.poll_next_unpin(cx) {
+                Poll::Ready(Some(SwarmEvent::OutgoingConnectionError {
+                    peer_id,
+                    error: DialError::WrongPeerId { .. },
+                    ..
+                })) => {
+                    assert_eq!(&peer_id.unwrap(), swarm.local_peer_id());
+                    assert!(!got_dial_err);
+                    got_dial_err = true;
+                    if got_inc_err {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Poll::Ready(Some(SwarmEvent::IncomingConnectionError { local_addr, .. })) => {
+                    assert!(!got_inc_err);
+                    assert_eq!(local_addr, local_address);
+                    got_inc_err = true;
+                    if got_dial_err {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Poll::Ready(Some(SwarmEvent::IncomingConnection { local_addr, .. })) => {
+                    assert_eq!(local_addr, local_address);
+                }
+                Poll::Ready(ev) => {
+                    panic!("Unexpected event: {:?}", ev)
+                }
+                Poll::Pending => break Poll::Pending,
+            }
+        }
+    }))
+    .unwrap();
+}
+
+#[test]
+fn dial_self_by_id() {
+    // Trying to dial self by passing the same `PeerId` shouldn't even be possible in the first
+    // place.
+    let swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    let peer_id = *swarm.local_peer_id();
+    assert!(!swarm.is_connected(&peer_id));
+}
+
+#[test]
+fn multiple_addresses_err() {
+    // Tries dialing multiple addresses, and makes sure there's one dialing error per address.
+
+    let target = PeerId::random();
+
+    let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+
+    let mut addresses = Vec::new();
+    for _ in 0..3 {
+        addresses.push(multiaddr![Ip4([0, 0, 0, 0]), Tcp(rand::random::<u16>())]);
+    }
+    for _ in 0..5 {
+        addresses.push(multiaddr![Udp(rand::random::<u16>())]);
+    }
+    addresses.shuffle(&mut rand::thread_rng());
+
+    swarm
+        .dial(
+            DialOpts::peer_id(target)
+                .addresses(addresses.clone())
+                .build(),
+        )
+        .unwrap();
+
+    futures::executor::block_on(future::poll_fn(|cx| -> Poll<Result<(), io::Error>> {
+        loop {
+            match swarm.poll_next_unpin(cx) {
+                Poll::Ready(Some(SwarmEvent::OutgoingConnectionError {
+                    peer_id,
+                    // multiaddr,
+                    error: DialError::Transport(errors),
+                })) => {
+                    assert_eq!(peer_id.unwrap(), target);
+
+                    // Addresses are dialed in the order given by the address ranking.
+                    let failed_addresses =
+                        errors.into_iter().map(|(addr, _)| addr).collect::<Vec<_>>();
+                    assert_eq!(
+                        failed_addresses,
+                        DefaultAddressRanking::default()
+                            .rank(addresses.clone(), &[])
+                            .into_iter()
+                            .map(|(addr, _)| addr.with(Protocol::P2p(target.into())))
+                            .collect::<Vec<_>>()
+                    );
+
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Ready(_) => unreachable!(),
+                Poll::Pending => break Poll::Pending,
+            }
+        }
+    }))
+    .unwrap();
+}
+
+#[test]
+fn gater_denies_dial() {
+    struct DenyMemory;
+
+    impl ConnectionGater for DenyMemory {
+        fn intercept_addr_dial(&mut self, _: Option<&PeerId>, addr: &Multiaddr) -> bool {
+            !matches!(addr.iter().next(), Some(Protocol::Memory(_)))
+        }
+    }
+
+    let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default())
+        .connection_gater(DenyMemory)
+        .build();
+
+    match swarm
+        .dial(
+            DialOpts::peer_id(PeerId::random())
+                .addresses(vec![multiaddr![Memory(1u64)], multiaddr![Memory(2u64)]])
+                .build(),
+        )
+        .expect_err("Unexpected dialing success.")
+    {
+        DialError::DeniedDial => {}
+        e => panic!("Unexpected error: {:?}", e),
+    }
+    assert_eq!(swarm.network_info().connection_counters().num_pending(), 0);
+}
+
+#[test]
+fn failed_addresses_are_backed_off() {
+    let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default())
+        .dial_backoff(DialBackoffConfig::default())
+        .build();
+    let peer_id = PeerId::random();
+    let address = multiaddr![Memory(rand::random::<u64>())];
+
+    swarm
+        .dial(
+            DialOpts::peer_id(peer_id)
+                .addresses(vec![address.clone()])
+                .build(),
+        )
+        .unwrap();
+    match block_on(swarm.next()).unwrap() {
+        SwarmEvent::OutgoingConnectionError {
+            error: DialError::Transport(_),
+            ..
+        } => {}
+        e => panic!("Unexpected network event: {:?}", e),
+    }
+
+    match swarm
+        .dial(
+            DialOpts::peer_id(peer_id)
+                .addresses(vec![address.clone()])
+                .build(),
+        )
+        .expect_err("Unexpected dialing success.")
+    {
+        DialError::Backoff { until } => assert!(until > Instant::now()),
+        e => panic!("Unexpected error: {:?}", e),
+    }
+
+    swarm
+        .dial(
+            DialOpts::peer_id(peer_id)
+                .addresses(vec![address])
+                .bypass_backoff()
+                .build(),
+        )
+        .unwrap();
+}
+
+#[test]
+fn gater_denies_secured_peer() {
+    struct DenyPeer(PeerId);
+
+    impl ConnectionGater for DenyPeer {
+        fn intercept_secured(&mut self, peer: &PeerId, _: &ConnectedPoint) -> bool {
+            peer != &self.0
+        }
+    }
+
+    let mut dialer = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    let gater = SharedConnectionGater::new(DenyPeer(*dialer.local_peer_id()));
+    let id_keys = identity::Keypair::generate_ed25519();
+    let local_public_key = id_keys.public();
+    let transport = transport::MemoryTransport::default()
+        .upgrade(upgrade::Version::V1)
+        .authenticate(plaintext::PlainText2Config {
+            local_public_key: local_public_key.clone(),
+        })
+        .intercept(gater.intercept_secured())
+        .multiplex(yamux::YamuxConfig::default())
+        .boxed();
+    let behaviour = CallTraceBehaviour::new(MockBehaviour::<_, ()>::new(
+        DummyConnectionHandler::default(),
+    ));
+    let mut listener = SwarmBuilder::new(transport, behaviour, local_public_key.into())
+        .connection_gater(gater)
+        .build();
+
+    listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+    let listener_address = match block_on(listener.next()).unwrap() {
+        SwarmEvent::NewListenAddr { address, .. } => address,
+        e => panic!("Unexpected network event: {:?}", e),
+    };
+
+    dialer.dial(listener_address).unwrap();
+
+    let dialer_id = *dialer.local_peer_id();
+    block_on(future::poll_fn(|cx| {
+        let _ = dialer.poll_next_unpin(cx);
+        match ready!(listener.poll_next_unpin(cx)).unwrap() {
+            SwarmEvent::IncomingConnection { .. } => Poll::Pending,
+            SwarmEvent::IncomingConnectionError {
+                error: PendingConnectionError::DeniedSecured { peer_id, .. },
+                ..
+            } => {
+                assert_eq!(peer_id, dialer_id);
+                Poll::Ready(())
+            }
+            e => panic!("Unexpected network event: {:?}", e),
+        }
+    }));
+    assert!(listener
+        .behaviour()
+        .inject_connection_established
+        .is_empty());
+}
+
+#[test]
+fn gater_denies_secured_peer_without_transport_interception() {
+    struct DenyPeer(PeerId);
+
+    impl ConnectionGater for DenyPeer {
+        fn intercept_secured(&mut self, peer: &PeerId, _: &ConnectedPoint) -> bool {
+            peer != &self.0
+        }
+    }
+
+    let mut dialer = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    // The transport of the listener does not consult the gater, thus the pool does.
+    let mut listener = new_test_swarm::<_, ()>(DummyConnectionHandler::default())
+        .connection_gater(DenyPeer(*dialer.local_peer_id()))
+        .build();
+
+    listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+    let listener_address = match block_on(listener.next()).unwrap() {
+        SwarmEvent::NewListenAddr { address, .. } => address,
+        e => panic!("Unexpected network event: {:?}", e),
+    };
+
+    dialer.dial(listener_address).unwrap();
+
+    let dialer_id = *dialer.local_peer_id();
+    block_on(future::poll_fn(|cx| {
+        let _ = dialer.poll_next_unpin(cx);
+        match ready!(listener.poll_next_unpin(cx)).unwrap() {
+            SwarmEvent::IncomingConnection { .. } => Poll::Pending,
+            SwarmEvent::IncomingConnectionError {
+                error: PendingConnectionError::DeniedSecured { peer_id, .. },
+                ..
+            } => {
+                assert_eq!(peer_id, dialer_id);
+                Poll::Ready(())
+            }
+            e => panic!("Unexpected network event: {:?}", e),
+        }
+    }));
+    assert!(listener
+        .behaviour()
+        .inject_connection_established
+        .is_empty());
+}
+
+#[test]
+fn dial_uses_peer_store_addresses() {
+    let mut dialer = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    let mut listener = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+
+    let listener_peer_id = *listener.local_peer_id();
+    listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+    let listener_address = match block_on(listener.next()).unwrap() {
+        SwarmEvent::NewListenAddr { address, .. } => address,
+        e => panic!("Unexpected network event: {:?}", e),
+    };
+
+    dialer.peer_store_mut().add_address(
+        listener_peer_id,
+        listener_address.clone(),
+        AddressSource::Manual,
+        None,
+    );
+    dialer.dial(listener_peer_id).unwrap();
+
+    block_on(future::poll_fn(|cx| {
+        let _ = listener.poll_next_unpin(cx);
+        match ready!(dialer.poll_next_unpin(cx)).unwrap() {
+            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                assert_eq!(peer_id, listener_peer_id);
+                Poll::Ready(())
+            }
+            e => panic!("Unexpected network event: {:?}", e),
+        }
+    }));
+
+    let info = dialer.peer_store().get(&listener_peer_id).unwrap();
+    assert!(info.last_seen().is_some());
+    let entries = info.addresses().collect::<Vec<_>>();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].addr(), &listener_address);
+    assert_eq!(entries[0].source(), AddressSource::Connection);
+}
+
+#[test]
+fn connection_manager_trims_above_high_watermark() {
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+
+    let mut dialer = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut listener = new_test_swarm::<_, ()>(handler_proto)
+        .connection_manager(ConnectionManagerConfig::new(0, 0).with_grace_period(Duration::ZERO))
+        .build();
+
+    listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+    let listener_address = match block_on(listener.next()).unwrap() {
+        SwarmEvent::NewListenAddr { address, .. } => address,
+        e => panic!("Unexpected network event: {:?}", e),
+    };
+
+    dialer.dial(listener_address).unwrap();
+
+    let dialer_id = *dialer.local_peer_id();
+    block_on(future::poll_fn(|cx| {
+        let _ = dialer.poll_next_unpin(cx);
+        loop {
+            match ready!(listener.poll_next_unpin(cx)).unwrap() {
+                SwarmEvent::IncomingConnection { .. }
+                | SwarmEvent::ConnectionEstablished { .. } => {}
+                SwarmEvent::ConnectionTrimmed {
+                    peer_id, reason, ..
+                } => {
+                    assert_eq!(peer_id, dialer_id);
+                    assert_eq!(
+                        reason,
+                        TrimReason::HighWatermark {
+                            established: 1,
+                            high_watermark: 0
+                        }
+                    );
+                    return Poll::Ready(());
+                }
+                e => panic!("Unexpected network event: {:?}", e),
+            }
+        }
+    }));
+}
+
+#[test]
+fn aborting_pending_connection_surfaces_error() {
+    let _ = env_logger::try_init();
+
+    let mut dialer = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    let mut listener = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+
+    let listener_peer_id = *listener.local_peer_id();
+    listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+    let listener_address = match block_on(listener.next()).unwrap() {
+        SwarmEvent::NewListenAddr { address, .. } => address,
+        e => panic!("Unexpected network event: {:?}", e),
+    };
+
+    dialer
+        .dial(
+            DialOpts::peer_id(listener_peer_id)
+                .addresses(vec![listener_address])
+                .build(),
+        )
+        .unwrap();
+
+    dialer
+        .disconnect_peer_id(listener_peer_id)
+        .expect_err("Expect peer to not yet be connected.");
+
+    match block_on(dialer.next()).unwrap() {
+        SwarmEvent::OutgoingConnectionError {
+            error: DialError::Aborted,
+            ..
+        } => {}
+        e => panic!("Unexpected swarm event {:?}.", e),
+    }
+}
+
+#[test]
+fn close_drains_connections_and_terminates() {
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+
+    let mut dialer = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut listener = new_test_swarm::<_, ()>(handler_proto).build();
+
+    listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+    let listener_address = match block_on(listener.next()).unwrap() {
+        SwarmEvent::NewListenAddr { address, .. } => address,
+        e => panic!("Unexpected network event: {:?}", e),
+    };
+
+    dialer.dial(listener_address.clone()).unwrap();
+
+    block_on(future::poll_fn(|cx| {
+        let _ = dialer.poll_next_unpin(cx);
+        loop {
+            match ready!(listener.poll_next_unpin(cx)).unwrap() {
+                SwarmEvent::IncomingConnection { .. } => {}
+                SwarmEvent::ConnectionEstablished { .. } => return Poll::Ready(()),
+                e => panic!("Unexpected network event: {:?}", e),
+            }
+        }
+    }));
+
+    // The handlers keep the connection alive, thus it is closed once the
+    // drain timeout elapsed.
+    listener.close(Duration::from_millis(100));
+    assert_eq!(listener.behaviour().inject_shutdown, 1);
+    assert!(matches!(
+        listener.dial(listener_address),
+        Err(DialError::Aborted)
+    ));
+
+    let mut listener_closed = false;
+    let mut connection_closed = false;
+    block_on(future::poll_fn(|cx| {
+        let _ = dialer.poll_next_unpin(cx);
+        loop {
+            match ready!(listener.poll_next_unpin(cx)) {
+                Some(SwarmEvent::ListenerClosed { reason, .. }) => {
+                    assert!(reason.is_ok());
+                    listener_closed = true;
+                }
+                Some(SwarmEvent::ExpiredListenAddr { .. }) => {}
+                Some(SwarmEvent::ConnectionClosed { cause, .. }) => {
+                    assert!(cause.is_none());
+                    connection_closed = true;
+                }
+                Some(e) => panic!("Unexpected network event: {:?}", e),
+                None => return Poll::Ready(()),
+            }
+        }
+    }));
+
+    assert!(listener_closed);
+    assert!(connection_closed);
+    assert!(listener.is_terminated());
+    assert_eq!(listener.behaviour().inject_connection_closed.len(), 1);
+}
+
+#[test]
+fn poll_close_starts_shutdown() {
+    let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    })
+    .build();
+
+    swarm.listen_on(multiaddr![Memory(0u64)]).unwrap();
+    match block_on(swarm.next()).unwrap() {
+        SwarmEvent::NewListenAddr { .. } => {}
+        e => panic!("Unexpected network event: {:?}", e),
+    }
+
+    let mut listener_closed = false;
+    block_on(future::poll_fn(|cx| loop {
+        match ready!(swarm.poll_close(cx)) {
+            Some(SwarmEvent::ListenerClosed { reason, .. }) => {
+                assert!(reason.is_ok());
+                listener_closed = true;
+            }
+            Some(SwarmEvent::ExpiredListenAddr { .. }) => {}
+            Some(e) => panic!("Unexpected network event: {:?}", e),
+            None => return Poll::Ready(()),
+        }
+    }));
+
+    assert!(listener_closed);
+    assert!(swarm.is_terminated());
+    assert_eq!(swarm.behaviour().inject_shutdown, 1);
+}
+
+#[test]
+fn confirmed_external_address_expires() {
+    let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default())
+        .external_addr_config(ExternalAddrConfig::default().with_ttl(Duration::from_millis(100)))
+        .build();
+    let address: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+
+    swarm.behaviour_mut().inner().next_action = Some(NetworkBehaviourAction::ConfirmExternalAddr {
+        address: address.clone(),
+    });
+    block_on(future::poll_fn(|cx| {
+        let _ = swarm.poll_next_unpin(cx);
+        if swarm.behaviour().inject_external_addr_confirmed.is_empty() {
+            return Poll::Pending;
+        }
+        Poll::Ready(())
+    }));
+    assert_eq!(
+        swarm.behaviour().inject_new_external_addr_candidate,
+        vec![address.clone()]
+    );
+    assert_eq!(
+        swarm.behaviour().inject_new_external_addr,
+        vec![address.clone()]
+    );
+    assert_eq!(
+        swarm.external_addresses().next().map(|r| &r.addr),
+        Some(&address)
+    );
+
+    block_on(future::poll_fn(|cx| {
+        let _ = swarm.poll_next_unpin(cx);
+        if swarm.behaviour().inject_external_addr_expired.is_empty() {
+            return Poll::Pending;
+        }
+        Poll::Ready(())
+    }));
+    assert_eq!(
+        swarm.behaviour().inject_expired_external_addr,
+        vec![address]
+    );
+    assert_eq!(swarm.external_addresses().count(), 0);
+}
+
+#[test]
+fn queued_dials_start_by_priority() {
+    let mut dialer = new_test_swarm::<_, ()>(DummyConnectionHandler::default())
+        .max_concurrent_dials(NonZeroUsize::new(1).unwrap())
+        .build();
+    let mut listeners = (0..3)
+        .map(|_| new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build())
+        .collect::<Vec<_>>();
+
+    let mut addresses = Vec::new();
+    for listener in listeners.iter_mut() {
+        listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        match block_on(listener.next()).unwrap() {
+            SwarmEvent::NewListenAddr { address, .. } => addresses.push(address),
+            e => panic!("Unexpected network event: {:?}", e),
+        }
+    }
+    let peer_ids = listeners
+        .iter()
+        .map(|l| *l.local_peer_id())
+        .collect::<Vec<_>>();
+
+    for (i, priority) in [DialPriority::Low, DialPriority::Low, DialPriority::High]
+        .into_iter()
+        .enumerate()
+    {
+        dialer
+            .dial(
+                DialOpts::peer_id(peer_ids[i])
+                    .addresses(vec![addresses[i].clone()])
+                    .priority(priority)
+                    .build(),
+            )
+            .unwrap();
+    }
+    assert_eq!(dialer.network_info().num_queued_dials(), 2);
+    assert_eq!(
+        dialer
+            .network_info()
+            .connection_counters()
+            .num_pending_outgoing(),
+        1
+    );
+
+    let mut established = Vec::new();
+    block_on(future::poll_fn(|cx| {
+        for listener in listeners.iter_mut() {
+            while let Poll::Ready(Some(_)) = listener.poll_next_unpin(cx) {}
+        }
+        loop {
+            match ready!(dialer.poll_next_unpin(cx)).unwrap() {
+                SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                    established.push(peer_id);
+                    if established.len() == 3 {
+                        return Poll::Ready(());
+                    }
+                }
+                SwarmEvent::Dialing(_) | SwarmEvent::ConnectionClosed { .. } => {}
+                e => panic!("Unexpected network event: {:?}", e),
+            }
+        }
+    }));
+
+    assert_eq!(established, vec![peer_ids[0], peer_ids[2], peer_ids[1]]);
+    assert_eq!(dialer.network_info().num_queued_dials(), 0);
+}
+
+#[test]
+fn banned_ips_are_not_dialed_until_ban_expires() {
+    let mut swarm = new_test_swarm::<_, ()>(DummyConnectionHandler::default()).build();
+    let ip_net: IpNet = "192.0.2.0/24".parse().unwrap();
+    let address: Multiaddr = "/ip4/192.0.2.7/tcp/4001".parse().unwrap();
+
+    swarm.ban_ip_for(ip_net, Duration::from_millis(10));
+    assert_eq!(
+        swarm.banned_ips().map(|(n, _)| *n).collect::<Vec<_>>(),
+        vec![ip_net]
+    );
+    match swarm.dial(address.clone()) {
+        Err(DialError::Banned) => {}
+        e => panic!("Unexpected dial result: {:?}", e),
+    }
+
+    match block_on(swarm.next()).unwrap() {
+        SwarmEvent::BanExpired { target } => assert_eq!(target, BanTarget::Ip(ip_net)),
+        e => panic!("Unexpected network event: {:?}", e),
+    }
+    assert_eq!(swarm.banned_ips().count(), 0);
+    assert!(swarm.dial(address).is_ok());
+}
+
+#[test]
+fn simultaneous_dials_keep_the_same_connection() {
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+    let config = DuplicateConnectionConfig::default().with_drain_timeout(Duration::ZERO);
+    let mut swarms = (0..2)
+        .map(|_| {
+            new_test_swarm::<_, ()>(handler_proto.clone())
+                .duplicate_connections(config.clone())
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let mut addresses = Vec::new();
+    for swarm in swarms.iter_mut() {
+        swarm.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        match block_on(swarm.next()).unwrap() {
+            SwarmEvent::NewListenAddr { address, .. } => addresses.push(address),
+            e => panic!("Unexpected network event: {:?}", e),
+        }
+    }
+    swarms[0].dial(addresses[1].clone()).unwrap();
+    swarms[1].dial(addresses[0].clone()).unwrap();
+
+    // Whether the connection closed by each swarm was dialed by that swarm,
+    // and whether it was closed as a duplicate. A swarm may see the duplicate
+    // closed by the remote before having established the other connection.
+    let mut closed = [None, None];
+    block_on(future::poll_fn(|cx| {
+        for (swarm, closed) in swarms.iter_mut().zip(closed.iter_mut()) {
+            while let Poll::Ready(Some(event)) = swarm.poll_next_unpin(cx) {
+                match event {
+                    SwarmEvent::ConnectionClosed {
+                        endpoint, cause, ..
+                    } => {
+                        let duplicate = matches!(cause, Some(ConnectionError::Duplicate));
+                        assert!(closed.replace((endpoint.is_dialer(), duplicate)).is_none());
+                    }
+                    SwarmEvent::ConnectionEstablished { .. }
+                    | SwarmEvent::IncomingConnection { .. }
+                    | SwarmEvent::Dialing(_) => {}
+                    e => panic!("Unexpected network event: {:?}", e),
+                }
+            }
+        }
+        let established = swarms
+            .iter()
+            .all(|s| s.network_info().connection_counters().num_established() == 1);
+        if closed.iter().all(Option::is_some) && established {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    // Both swarms closed the same connection, dialed by one of them.
+    let [(dialed_0, duplicate_0), (dialed_1, duplicate_1)] = closed.map(Option::unwrap);
+    assert_ne!(dialed_0, dialed_1);
+    assert!(duplicate_0 || duplicate_1);
+}
+
+#[test]
+fn repeated_dials_keep_the_same_connection() {
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+    let config = DuplicateConnectionConfig::default().with_drain_timeout(Duration::ZERO);
+    let mut swarms = (0..2)
+        .map(|_| {
+            new_test_swarm::<_, ()>(handler_proto.clone())
+                .duplicate_connections(config.clone())
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    swarms[1].listen_on(multiaddr![Memory(0u64)]).unwrap();
+    let address = match block_on(swarms[1].next()).unwrap() {
+        SwarmEvent::NewListenAddr { address, .. } => address,
+        e => panic!("Unexpected network event: {:?}", e),
+    };
+    // Only the peer with the smaller peer ID closes one of the connections
+    // as a duplicate, the other one sees it closed by the remote.
+    let smaller = if swarms[0].local_peer_id() < swarms[1].local_peer_id() {
+        0
+    } else {
+        1
+    };
+
+    swarms[0].dial(address.clone()).unwrap();
+    swarms[0].dial(address).unwrap();
+
+    let mut closed = [None, None];
+    block_on(future::poll_fn(|cx| {
+        for (swarm, closed) in swarms.iter_mut().zip(closed.iter_mut()) {
+            while let Poll::Ready(Some(event)) = swarm.poll_next_unpin(cx) {
+                match event {
+                    SwarmEvent::ConnectionClosed { cause, .. } => {
+                        let duplicate = matches!(cause, Some(ConnectionError::Duplicate));
+                        assert!(closed.replace(duplicate).is_none());
+                    }
+                    SwarmEvent::ConnectionEstablished { .. }
+                    | SwarmEvent::IncomingConnection { .. }
+                    | SwarmEvent::Dialing(_) => {}
+                    e => panic!("Unexpected network event: {:?}", e),
+                }
+            }
+        }
+        let established = swarms
+            .iter()
+            .all(|s| s.network_info().connection_counters().num_established() == 1);
+        if closed.iter().all(Option::is_some) && established {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    assert_eq!(closed[smaller], Some(true));
+    assert_eq!(closed[1 - smaller], Some(false));
+}
+
+/// A [`ConnectionHandler`] reporting a sequence of remote protocol sets.
+#[derive(Clone)]
+struct ReportingConnectionHandler {
+    reports: VecDeque<Vec<String>>,
+}
+
+impl ConnectionHandler for ReportingConnectionHandler {
+    type InEvent = Void;
+    type OutEvent = Void;
+    type Error = Void;
+    type InboundProtocol = upgrade::DeniedUpgrade;
+    type OutboundProtocol = upgrade::DeniedUpgrade;
+    type OutboundOpenInfo = Void;
+    type InboundOpenInfo = ();
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(upgrade::DeniedUpgrade, ())
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, v: Void, _: ()) {
+        void::unreachable(v)
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, v: Void, _: Void) {
+        void::unreachable(v)
+    }
+
+    fn inject_event(&mut self, v: Void) {
+        void::unreachable(v)
+    }
+
+    fn inject_dial_upgrade_error(&mut self, v: Void, _: ConnectionHandlerUpgrErr<Void>) {
+        void::unreachable(v)
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        KeepAlive::Yes
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut Context<'_>,
+    ) -> Poll<ConnectionHandlerEvent<upgrade::DeniedUpgrade, Void, Void, Void>> {
+        match self.reports.pop_front() {
+            Some(protocols) => {
+                Poll::Ready(ConnectionHandlerEvent::ReportRemoteProtocols(protocols))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[test]
+fn remote_protocols_changes_are_reported_to_behaviour() {
+    let protocols = |p: &[&str]| p.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    let handler_proto = ReportingConnectionHandler {
+        reports: VecDeque::from(vec![protocols(&["/a", "/b"]), protocols(&["/b", "/c"])]),
+    };
+    let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
+
+    let addr2: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+    swarm2.listen_on(addr2.clone()).unwrap();
+    swarm1.dial(addr2).unwrap();
+
+    block_on(future::poll_fn(|cx| {
+        while let Poll::Ready(Some(_)) = swarm1.poll_next_unpin(cx) {}
+        while let Poll::Ready(Some(_)) = swarm2.poll_next_unpin(cx) {}
+        if swarm1.behaviour().inject_remote_protocols_change.len() == 3 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    let peer2 = *swarm2.local_peer_id();
+    let changes = swarm1
+        .behaviour()
+        .inject_remote_protocols_change
+        .iter()
+        .map(|(peer, _, change)| {
+            assert_eq!(*peer, peer2);
+            change.clone()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            ProtocolsChange::Added(protocols(&["/a", "/b"])),
+            ProtocolsChange::Added(protocols(&["/c"])),
+            ProtocolsChange::Removed(protocols(&["/a"])),
+        ]
+    );
+    // The dummy listen protocol does not advertise any protocol.
+    assert!(swarm1.behaviour().inject_local_protocols_change.is_empty());
+}
+
+type NamedUpgrade = upgrade::FromFnUpgrade<
+    &'static str,
+    fn(NegotiatedSubstream, Endpoint) -> future::Ready<Result<(), Void>>,
+>;
+
+/// A [`ConnectionHandler`] listening on a sequence of protocols, switching to
+/// the next one on each poll.
+#[derive(Clone)]
+struct SwitchingConnectionHandler {
+    protocols: VecDeque<&'static str>,
+}
+
+impl ConnectionHandler for SwitchingConnectionHandler {
+    type InEvent = Void;
+    type OutEvent = Void;
+    type Error = Void;
+    type InboundProtocol = NamedUpgrade;
+    type OutboundProtocol = upgrade::DeniedUpgrade;
+    type OutboundOpenInfo = Void;
+    type InboundOpenInfo = ();
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        let accept: fn(NegotiatedSubstream, Endpoint) -> future::Ready<Result<(), Void>> =
+            |_, _| future::ready(Ok(()));
+        SubstreamProtocol::new(upgrade::from_fn(self.protocols[0], accept), ())
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, _: (), _: ()) {}
+
+    fn inject_fully_negotiated_outbound(&mut self, v: Void, _: Void) {
+        void::unreachable(v)
+    }
+
+    fn inject_event(&mut self, v: Void) {
+        void::unreachable(v)
+    }
+
+    fn inject_dial_upgrade_error(&mut self, v: Void, _: ConnectionHandlerUpgrErr<Void>) {
+        void::unreachable(v)
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        KeepAlive::Yes
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut Context<'_>,
+    ) -> Poll<ConnectionHandlerEvent<upgrade::DeniedUpgrade, Void, Void, Void>> {
+        if self.protocols.len() > 1 {
+            self.protocols.pop_front();
+            return Poll::Ready(ConnectionHandlerEvent::ReportLocalProtocolsChange);
+        }
+        Poll::Pending
+    }
+}
+
+#[test]
+fn local_protocols_changes_are_reported_to_behaviour() {
+    let handler_proto = SwitchingConnectionHandler {
+        protocols: VecDeque::from(vec!["/a", "/b"]),
+    };
+    let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
+
+    let addr2: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+    swarm2.listen_on(addr2.clone()).unwrap();
+    swarm1.dial(addr2).unwrap();
+
+    block_on(future::poll_fn(|cx| {
+        while let Poll::Ready(Some(_)) = swarm1.poll_next_unpin(cx) {}
+        while let Poll::Ready(Some(_)) = swarm2.poll_next_unpin(cx) {}
+        if swarm1.behaviour().inject_local_protocols_change.len() == 3 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    let changes = swarm1
+        .behaviour()
+        .inject_local_protocols_change
+        .iter()
+        .map(|(_, _, change)| change.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            ProtocolsChange::Added(vec!["/a".to_string()]),
+            ProtocolsChange::Added(vec!["/b".to_string()]),
+            ProtocolsChange::Removed(vec!["/a".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn disabling_toggle_is_reported_as_local_protocols_change() {
+    let new_swarm = || {
+        let handler_proto = SwitchingConnectionHandler {
+            protocols: VecDeque::from(vec!["/a"]),
+        };
+        let toggle = Toggle::from(Some(MockBehaviour::<_, ()>::new(handler_proto)));
+        new_test_swarm_with_behaviour(CallTraceBehaviour::new(toggle)).build()
+    };
+    let mut swarm1 = new_swarm();
+    let mut swarm2 = new_swarm();
+
+    let addr2: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+    swarm2.listen_on(addr2.clone()).unwrap();
+    swarm1.dial(addr2).unwrap();
+
+    block_on(future::poll_fn(|cx| {
+        while let Poll::Ready(Some(_)) = swarm1.poll_next_unpin(cx) {}
+        while let Poll::Ready(Some(_)) = swarm2.poll_next_unpin(cx) {}
+        if swarm1.behaviour().inject_local_protocols_change.len() == 1 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    swarm1.behaviour_mut().inner().set_enabled(false);
+    block_on(future::poll_fn(|cx| {
+        while let Poll::Ready(Some(_)) = swarm1.poll_next_unpin(cx) {}
+        while let Poll::Ready(Some(_)) = swarm2.poll_next_unpin(cx) {}
+        if swarm1.behaviour().inject_local_protocols_change.len() == 2 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    assert!(!swarm1.behaviour_mut().inner().is_enabled());
+    let changes = swarm1
+        .behaviour()
+        .inject_local_protocols_change
+        .iter()
+        .map(|(_, _, change)| change.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            ProtocolsChange::Added(vec!["/a".to_string()]),
+            ProtocolsChange::Removed(vec!["/a".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn negotiated_protocols_are_reported_to_behaviour() {
+    let handler_proto = DummyConnectionHandler {
+        keep_alive: KeepAlive::Yes,
+    };
+    let mut swarm1 = new_test_swarm::<_, ()>(handler_proto.clone()).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(handler_proto).build();
+
+    let addr2: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+    swarm2.listen_on(addr2.clone()).unwrap();
+    swarm1.dial(addr2).unwrap();
+
+    block_on(future::poll_fn(|cx| {
+        while let Poll::Ready(Some(_)) = swarm1.poll_next_unpin(cx) {}
+        while let Poll::Ready(Some(_)) = swarm2.poll_next_unpin(cx) {}
+        if swarms_connected(&swarm1, &swarm2, 1) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    let expected = NegotiatedProtocols {
+        security: Some("/plaintext/2.0.0".to_string()),
+        muxer: Some("/yamux/1.0.0".to_string()),
+    };
+    for swarm in [&swarm1, &swarm2] {
+        let (_, _, _, negotiated_protocols, _) =
+            &swarm.behaviour().inject_connection_established[0];
+        assert_eq!(negotiated_protocols, &expected);
+    }
+}
+
+type StreamUpgrade = upgrade::FromFnUpgrade<
+    &'static str,
+    fn(NegotiatedSubstream, Endpoint) -> future::Ready<Result<NegotiatedSubstream, Void>>,
+>;
+
+const LIMITED_PROTOCOL: &str = "/limited/1.0.0";
+
+fn stream_upgrade() -> StreamUpgrade {
+    let accept: fn(
+        NegotiatedSubstream,
+        Endpoint,
+    ) -> future::Ready<Result<NegotiatedSubstream, Void>> = |s, _| future::ready(Ok(s));
+    upgrade::from_fn(LIMITED_PROTOCOL, accept)
+}
+
+/// What a [`StreamsConnectionHandler`] observed.
+#[derive(Debug, Clone, PartialEq)]
+enum StreamsEvent {
+    /// An outbound substream failed to negotiate or was closed by the remote.
+    OutboundReset,
+    /// A limit reported via [`ConnectionHandler::inject_listen_upgrade_error`].
+    InboundLimitExceeded(InboundStreamLimit),
+}
+
+/// A [`ConnectionHandler`] opening a number of outbound substreams and keeping
+/// all substreams open.
+struct StreamsConnectionHandler {
+    to_open: usize,
+    inbound: Vec<NegotiatedSubstream>,
+    outbound: Vec<NegotiatedSubstream>,
+    events: VecDeque<StreamsEvent>,
+}
+
+impl StreamsConnectionHandler {
+    fn new(to_open: usize) -> Self {
+        StreamsConnectionHandler {
+            to_open,
+            inbound: Vec::new(),
+            outbound: Vec::new(),
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl Clone for StreamsConnectionHandler {
+    fn clone(&self) -> Self {
+        StreamsConnectionHandler::new(self.to_open)
+    }
+}
+
+impl ConnectionHandler for StreamsConnectionHandler {
+    type InEvent = Void;
+    type OutEvent = StreamsEvent;
+    type Error = Void;
+    type InboundProtocol = StreamUpgrade;
+    type OutboundProtocol = StreamUpgrade;
+    type OutboundOpenInfo = ();
+    type InboundOpenInfo = ();
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(stream_upgrade(), ())
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, stream: NegotiatedSubstream, _: ()) {
+        self.inbound.push(stream);
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, stream: NegotiatedSubstream, _: ()) {
+        self.outbound.push(stream);
+    }
+
+    fn inject_event(&mut self, v: Void) {
+        void::unreachable(v)
+    }
+
+    fn inject_dial_upgrade_error(&mut self, _: (), _: ConnectionHandlerUpgrErr<Void>) {
+        // The remote may reset the substream before confirming the protocol.
+        self.events.push_back(StreamsEvent::OutboundReset);
+    }
+
+    fn inject_listen_upgrade_error(&mut self, _: (), error: ConnectionHandlerUpgrErr<Void>) {
+        if let ConnectionHandlerUpgrErr::LimitExceeded(limit) = error {
+            self.events
+                .push_back(StreamsEvent::InboundLimitExceeded(limit));
+        }
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        KeepAlive::Yes
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ConnectionHandlerEvent<StreamUpgrade, (), StreamsEvent, Void>> {
+        if self.to_open > 0 {
+            self.to_open -= 1;
+            return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(stream_upgrade(), ()),
+            });
+        }
+
+        // The remote never writes, thus a read only completes once the
+        // substream is reset or closed.
+        let events = &mut self.events;
+        self.outbound
+            .retain_mut(|stream| match Pin::new(stream).poll_read(cx, &mut [0; 1]) {
+                Poll::Ready(_) => {
+                    events.push_back(StreamsEvent::OutboundReset);
+                    false
+                }
+                Poll::Pending => true,
+            });
+
+        match self.events.pop_front() {
+            Some(event) => Poll::Ready(ConnectionHandlerEvent::Custom(event)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[test]
+fn inbound_substream_exceeding_limit_is_reset_and_reported() {
+    let mut swarm1 = new_test_swarm::<_, ()>(StreamsConnectionHandler::new(2)).build();
+    let mut swarm2 = new_test_swarm::<_, ()>(StreamsConnectionHandler::new(0))
+        .inbound_stream_limits(
+            InboundStreamLimits::default().with_protocol_max_open(LIMITED_PROTOCOL, 1),
+        )
+        .build();
+
+    let addr2: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+    swarm2.listen_on(addr2.clone()).unwrap();
+    swarm1.dial(addr2).unwrap();
+
+    let peer1 = *swarm1.local_peer_id();
+    let mut limits_exceeded = Vec::new();
+    block_on(future::poll_fn(|cx| {
+        while let Poll::Ready(Some(_)) = swarm1.poll_next_unpin(cx) {}
+        while let Poll::Ready(Some(event)) = swarm2.poll_next_unpin(cx) {
+            if let SwarmEvent::InboundStreamLimitExceeded {
+                peer_id,
+                protocol,
+                limit,
+                ..
+            } = event
+            {
+                assert_eq!(peer_id, peer1);
+                limits_exceeded.push((protocol, limit));
+            }
+        }
+        if !swarm1.behaviour().inject_event.is_empty()
+            && !swarm2.behaviour().inject_event.is_empty()
+            && !limits_exceeded.is_empty()
+        {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    // The excess substream was reset, while the one within the limit remains open.
+    let events1 = swarm1
+        .behaviour()
+        .inject_event
+        .iter()
+        .map(|(_, _, event)| event.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(events1, vec![StreamsEvent::OutboundReset]);
+    assert_eq!(
+        limits_exceeded,
+        vec![(
+            Some(LIMITED_PROTOCOL.to_string()),
+            InboundStreamLimit::OpenPerProtocol(1)
+        )]
+    );
+    let events2 = swarm2
+        .behaviour()
+        .inject_event
+        .iter()
+        .map(|(peer, _, event)| {
+            assert_eq!(*peer, peer1);
+            event.clone()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events2,
+        vec![StreamsEvent::InboundLimitExceeded(
+            InboundStreamLimit::OpenPerProtocol(1)
+        )]
+    );
+}
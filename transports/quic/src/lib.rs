@@ -0,0 +1,628 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the libp2p `Transport` trait for QUIC.
+//!
+//! QUIC connections are secured with TLS 1.3 and multiplex streams natively.
+//! Thus, contrary to e.g. TCP, connections need no further upgrade: the
+//! transport yields the [`PeerId`] of the remote, authenticated by the
+//! [libp2p TLS handshake](https://github.com/libp2p/specs/blob/master/tls/tls.md),
+//! together with a [`StreamMuxerBox`].
+//!
+//! # Usage
+//!
+//! This crate provides a `QuicConfig` and `TokioQuicConfig`, depending on
+//! the enabled features, which implement the `Transport` trait for use as a
+//! transport with `libp2p-core` or `libp2p-swarm`. Addresses are of the form
+//! `/ip4/1.2.3.4/udp/4242/quic`.
+//!
+//! The transport can be combined with an upgraded TCP transport, e.g.:
+//!
+//! ```ignore
+//! let quic = QuicConfig::new(&keypair);
+//! let tcp = TcpConfig::new()
+//!     .upgrade(upgrade::Version::V1)
+//!     .authenticate(noise)
+//!     .multiplex(yamux::YamuxConfig::default());
+//! let transport = quic
+//!     .or_transport(tcp)
+//!     .map(|either_output, _| match either_output {
+//!         EitherOutput::First((peer_id, muxer)) => (peer_id, muxer),
+//!         EitherOutput::Second((peer_id, muxer)) => (peer_id, muxer),
+//!     })
+//!     .boxed();
+//! ```
+//!
+//! # Port reuse
+//!
+//! Outgoing connections are dialed from the UDP socket of a listener, if
+//! any, such that the remote observes the listening port of the local node.
+//! Clones of a transport share their sockets for this purpose. Otherwise a
+//! dedicated socket bound to an ephemeral port is used, in which case
+//! observed addresses are not translated, as their port is not the listening
+//! port.
+
+mod muxer;
+mod provider;
+
+pub use muxer::{QuicMuxer, Substream};
+pub use provider::Provider;
+
+#[cfg(feature = "async-std")]
+pub use provider::async_std;
+
+/// The type of a [`GenQuicConfig`] using the `async-std` implementation.
+#[cfg(feature = "async-std")]
+pub type QuicConfig = GenQuicConfig<async_std::AsyncStd>;
+
+#[cfg(feature = "tokio")]
+pub use provider::tokio;
+
+/// The type of a [`GenQuicConfig`] using the `tokio` implementation.
+#[cfg(feature = "tokio")]
+pub type TokioQuicConfig = GenQuicConfig<tokio::Tokio>;
+
+use futures::{
+    future::{self, BoxFuture, Either},
+    prelude::*,
+    ready,
+};
+use futures_timer::Delay;
+use libp2p_core::{
+    connection::NegotiatedProtocols,
+    identity,
+    multiaddr::{Multiaddr, Protocol},
+    muxing::StreamMuxerBox,
+    transport::{ListenerEvent, Transport, TransportError},
+    PeerId,
+};
+use libp2p_tls::certificate::ParseError;
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    io,
+    marker::PhantomData,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// The name of the security protocol reported for QUIC connections.
+const SECURITY_PROTOCOL: &str = "/tls/1.0.0";
+
+/// The server name sent in the client hello, see [`libp2p_tls`].
+const SERVER_NAME: &str = "l";
+
+/// The name of the stream multiplexer reported for QUIC connections.
+const MUXER_PROTOCOL: &str = "/quic";
+
+/// The configuration for a QUIC transport capability for libp2p.
+///
+/// A [`GenQuicConfig`] implements the [`Transport`] interface and thus
+/// is consumed on [`Transport::listen_on`] and [`Transport::dial`].
+/// However, the config can be cheaply cloned to perform multiple such
+/// operations with the same config. Clones share the UDP sockets of
+/// their listeners and dialers.
+#[derive(Clone)]
+pub struct GenQuicConfig<P> {
+    /// The TLS configuration for dialing, authenticating the local node.
+    client_tls: Arc<rustls::ClientConfig>,
+    /// The TLS configuration for listening, authenticating the local node.
+    server_tls: Arc<rustls::ServerConfig>,
+    /// Timeout for the establishment of a connection, including the handshake.
+    handshake_timeout: Duration,
+    /// Duration of inactivity after which a connection is closed.
+    max_idle_timeout: Duration,
+    /// Interval at which keep-alive packets are sent on idle connections.
+    keep_alive_interval: Duration,
+    /// The maximum number of concurrent inbound streams per connection.
+    max_concurrent_stream_limit: u32,
+    /// The endpoints of all listeners and dialers.
+    endpoints: Arc<Mutex<Endpoints>>,
+    _provider: PhantomData<P>,
+}
+
+impl<P> GenQuicConfig<P>
+where
+    P: Provider,
+{
+    /// Creates a new configuration for a QUIC transport, authenticating the
+    /// local node with the given identity.
+    ///
+    /// The configuration has the following defaults:
+    ///
+    ///   * A handshake timeout of 10 seconds.
+    ///   * A maximum idle timeout of 30 seconds.
+    ///   * A keep-alive interval of 15 seconds.
+    ///   * A limit of 256 concurrent inbound streams per connection.
+    pub fn new(keypair: &identity::Keypair) -> Self {
+        Self {
+            client_tls: Arc::new(
+                libp2p_tls::make_client_config(keypair, None)
+                    .expect("Generating a self-signed certificate for a valid keypair succeeds."),
+            ),
+            server_tls: Arc::new(
+                libp2p_tls::make_server_config(keypair)
+                    .expect("Generating a self-signed certificate for a valid keypair succeeds."),
+            ),
+            handshake_timeout: Duration::from_secs(10),
+            max_idle_timeout: Duration::from_secs(30),
+            keep_alive_interval: Duration::from_secs(15),
+            max_concurrent_stream_limit: 256,
+            endpoints: Default::default(),
+            _provider: PhantomData,
+        }
+    }
+
+    /// Configures the timeout for the establishment of a connection,
+    /// including the TLS handshake.
+    pub fn handshake_timeout(mut self, value: Duration) -> Self {
+        self.handshake_timeout = value;
+        self
+    }
+
+    /// Configures the duration of inactivity after which a connection is
+    /// closed.
+    ///
+    /// The effective timeout is the minimum of the timeouts of both peers.
+    pub fn max_idle_timeout(mut self, value: Duration) -> Self {
+        self.max_idle_timeout = value;
+        self
+    }
+
+    /// Configures the interval at which keep-alive packets are sent on
+    /// idle connections, which must be shorter than the idle timeout.
+    pub fn keep_alive_interval(mut self, value: Duration) -> Self {
+        self.keep_alive_interval = value;
+        self
+    }
+
+    /// Configures the maximum number of concurrent streams the remote may
+    /// open on a connection.
+    pub fn max_concurrent_stream_limit(mut self, value: u32) -> Self {
+        self.max_concurrent_stream_limit = value;
+        self
+    }
+
+    fn transport_config(&self) -> Arc<quinn::TransportConfig> {
+        let mut config = quinn::TransportConfig::default();
+        // Only bidirectional streams are used as substreams.
+        config
+            .max_concurrent_uni_streams(0u32.into())
+            .max_concurrent_bidi_streams(self.max_concurrent_stream_limit.into())
+            .max_idle_timeout(quinn::IdleTimeout::try_from(self.max_idle_timeout).ok())
+            .keep_alive_interval(Some(self.keep_alive_interval));
+        Arc::new(config)
+    }
+
+    fn server_config(&self) -> quinn::ServerConfig {
+        let mut config = quinn::ServerConfig::with_crypto(self.server_tls.clone());
+        config.transport_config(self.transport_config());
+        config
+    }
+
+    fn client_config(&self) -> quinn::ClientConfig {
+        let mut config = quinn::ClientConfig::new(self.client_tls.clone());
+        config.transport_config(self.transport_config());
+        config
+    }
+
+    /// Returns the endpoint to dial the given address from.
+    ///
+    /// Prefers the endpoint of a listener, such that the remote observes the
+    /// listening port, e.g. for hole punching. Otherwise a dedicated endpoint
+    /// is created on first use.
+    fn dialer(&self, remote_addr: &SocketAddr) -> io::Result<quinn::Endpoint> {
+        let mut endpoints = self.endpoints.lock();
+
+        if let Some((_, _, endpoint)) = endpoints.dialing_listener(remote_addr) {
+            return Ok(endpoint.clone());
+        }
+
+        let (dialer, unspecified) = if remote_addr.is_ipv4() {
+            (&mut endpoints.dialer_v4, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+        } else {
+            (&mut endpoints.dialer_v6, IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+        };
+        if let Some(endpoint) = dialer {
+            return Ok(endpoint.clone());
+        }
+
+        let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+        let endpoint =
+            quinn::Endpoint::new(quinn::EndpointConfig::default(), None, socket, P::runtime())?;
+        *dialer = Some(endpoint.clone());
+        Ok(endpoint)
+    }
+
+    fn do_dial(self, addr: Multiaddr) -> Result<<Self as Transport>::Dial, TransportError<Error>> {
+        let (socket_addr, remote_peer_id) = match multiaddr_to_socketaddr(&addr) {
+            Some((socket_addr, peer_id))
+                if socket_addr.port() != 0 && !socket_addr.ip().is_unspecified() =>
+            {
+                (socket_addr, peer_id)
+            }
+            _ => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+        log::debug!("dialing {}", socket_addr);
+
+        let endpoint = self
+            .dialer(&socket_addr)
+            .map_err(|e| TransportError::Other(Error::Io(e)))?;
+        let connecting = endpoint
+            .connect_with(self.client_config(), socket_addr, SERVER_NAME)
+            .map_err(|e| TransportError::Other(Error::Connect(e)))?;
+
+        Ok(upgrade(connecting, self.handshake_timeout, remote_peer_id))
+    }
+}
+
+impl<P> Transport for GenQuicConfig<P>
+where
+    P: Provider,
+{
+    type Output = (PeerId, StreamMuxerBox);
+    type Error = Error;
+    type Listener = Listener;
+    type ListenerUpgrade = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let socket_addr = match multiaddr_to_socketaddr(&addr) {
+            Some((socket_addr, None)) => socket_addr,
+            _ => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        let listener = (|| {
+            let socket = UdpSocket::bind(socket_addr)?;
+            let local_addr = socket.local_addr()?;
+            let endpoint = quinn::Endpoint::new(
+                quinn::EndpointConfig::default(),
+                Some(self.server_config()),
+                socket,
+                P::runtime(),
+            )?;
+
+            // Report the addresses of all interfaces when listening on an
+            // unspecified address.
+            let listen_addrs = if local_addr.ip().is_unspecified() {
+                if_addrs::get_if_addrs()?
+                    .into_iter()
+                    .map(|iface| SocketAddr::new(iface.ip(), local_addr.port()))
+                    .filter(|addr| addr.is_ipv4() == local_addr.is_ipv4())
+                    .collect()
+            } else {
+                vec![local_addr]
+            };
+            log::debug!("listening on {}", local_addr);
+
+            let id = self
+                .endpoints
+                .lock()
+                .add_listener(local_addr, endpoint.clone());
+
+            Ok::<_, io::Error>(Listener {
+                id,
+                accept: accept(endpoint.clone()),
+                endpoint,
+                endpoints: self.endpoints.clone(),
+                local_addr,
+                pending_addrs: listen_addrs.iter().map(socketaddr_to_multiaddr).collect(),
+                handshake_timeout: self.handshake_timeout,
+            })
+        })()
+        .map_err(|e| TransportError::Other(Error::Io(e)))?;
+
+        Ok(listener)
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr)
+    }
+
+    /// QUIC has no notion of the role of the dialer beyond the handshake.
+    /// When used for hole punching, both peers dial each other from their
+    /// listening sockets, thus this is equivalent to [`Transport::dial`].
+    fn dial_as_listener(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr)
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        let (listen_addr, _) = multiaddr_to_socketaddr(listen)?;
+        let (observed_addr, _) = multiaddr_to_socketaddr(observed)?;
+
+        // The observed port is the listening port only if outgoing connections
+        // are dialed from the socket of that listener. Connections dialed from
+        // a dedicated socket are observed with an ephemeral port instead.
+        let endpoints = self.endpoints.lock();
+        let (_, local_addr, _) = endpoints.dialing_listener(&observed_addr)?;
+        if local_addr.port() != listen_addr.port() {
+            return None;
+        }
+        Some(observed.clone())
+    }
+}
+
+/// Establishes a connection, i.e. performs the QUIC and TLS handshakes.
+///
+/// If `expected_peer_id` is given, the connection is closed unless the remote
+/// authenticated as that peer.
+fn upgrade(
+    connecting: quinn::Connecting,
+    timeout: Duration,
+    expected_peer_id: Option<PeerId>,
+) -> BoxFuture<'static, Result<(PeerId, StreamMuxerBox), Error>> {
+    async move {
+        let connection = match future::select(connecting, Delay::new(timeout)).await {
+            Either::Left((connection, _)) => connection?,
+            Either::Right(_) => return Err(Error::HandshakeTimedOut),
+        };
+
+        let certificates = connection
+            .peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<rustls::Certificate>>().ok())
+            .map(|certificates| *certificates)
+            .unwrap_or_default();
+        let peer_id = match certificates.as_slice() {
+            [certificate] => libp2p_tls::certificate::parse(certificate)?.peer_id(),
+            _ => return Err(ParseError::NumberOfCertificates(certificates.len()).into()),
+        };
+
+        if let Some(expected) = expected_peer_id {
+            if expected != peer_id {
+                log::debug!(
+                    "expected {}, but remote authenticated as {}",
+                    expected,
+                    peer_id
+                );
+                connection.close(0u32.into(), &[]);
+                return Err(Error::UnexpectedPeerId);
+            }
+        }
+
+        let muxer = StreamMuxerBox::new(muxer::QuicMuxer::new(connection))
+            .with_negotiated_protocols(NegotiatedProtocols {
+                security: Some(SECURITY_PROTOCOL.to_string()),
+                muxer: Some(MUXER_PROTOCOL.to_string()),
+            });
+
+        Ok((peer_id, muxer))
+    }
+    .boxed()
+}
+
+fn accept(endpoint: quinn::Endpoint) -> BoxFuture<'static, Option<quinn::Connecting>> {
+    async move { endpoint.accept().await }.boxed()
+}
+
+/// The endpoints of a [`GenQuicConfig`] and its clones.
+#[derive(Default)]
+struct Endpoints {
+    /// The endpoints of the active listeners, with their local addresses.
+    listeners: Vec<(u64, SocketAddr, quinn::Endpoint)>,
+    /// The identifier of the next listener.
+    next_listener_id: u64,
+    /// The endpoint for dialing IPv4 addresses when there is no listener.
+    dialer_v4: Option<quinn::Endpoint>,
+    /// The endpoint for dialing IPv6 addresses when there is no listener.
+    dialer_v6: Option<quinn::Endpoint>,
+}
+
+impl Endpoints {
+    fn add_listener(&mut self, local_addr: SocketAddr, endpoint: quinn::Endpoint) -> u64 {
+        let id = self.next_listener_id;
+        self.next_listener_id += 1;
+        self.listeners.push((id, local_addr, endpoint));
+        id
+    }
+
+    /// Returns the listener whose socket is used for dialing the given
+    /// address, if any.
+    fn dialing_listener(
+        &self,
+        remote_addr: &SocketAddr,
+    ) -> Option<&(u64, SocketAddr, quinn::Endpoint)> {
+        self.listeners.iter().find(|(_, local_addr, _)| {
+            local_addr.is_ipv4() == remote_addr.is_ipv4()
+                && (local_addr.ip().is_unspecified()
+                    || local_addr.ip().is_loopback() == remote_addr.ip().is_loopback())
+        })
+    }
+}
+
+/// A QUIC listener, accepting connections on a UDP socket.
+///
+/// Dropping the listener stops accepting new connections. Connections
+/// established on its socket remain open.
+pub struct Listener {
+    id: u64,
+    endpoint: quinn::Endpoint,
+    endpoints: Arc<Mutex<Endpoints>>,
+    /// The next incoming connection.
+    accept: BoxFuture<'static, Option<quinn::Connecting>>,
+    /// The local address of the socket.
+    local_addr: SocketAddr,
+    /// The listen addresses yet to be reported.
+    pending_addrs: VecDeque<Multiaddr>,
+    handshake_timeout: Duration,
+}
+
+impl Stream for Listener {
+    type Item = Result<
+        ListenerEvent<BoxFuture<'static, Result<(PeerId, StreamMuxerBox), Error>>, Error>,
+        Error,
+    >;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(addr) = self.pending_addrs.pop_front() {
+            return Poll::Ready(Some(Ok(ListenerEvent::NewAddress(addr))));
+        }
+
+        let connecting = match ready!(self.accept.poll_unpin(cx)) {
+            Some(connecting) => connecting,
+            None => return Poll::Ready(None),
+        };
+        self.accept = accept(self.endpoint.clone());
+
+        let local_addr = connecting
+            .local_ip()
+            .map(|ip| SocketAddr::new(ip, self.local_addr.port()))
+            .unwrap_or(self.local_addr);
+        let remote_addr = connecting.remote_address();
+        log::debug!("incoming connection from {}", remote_addr);
+
+        Poll::Ready(Some(Ok(ListenerEvent::Upgrade {
+            upgrade: upgrade(connecting, self.handshake_timeout, None),
+            local_addr: socketaddr_to_multiaddr(&local_addr),
+            remote_addr: socketaddr_to_multiaddr(&remote_addr),
+        })))
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.endpoints
+            .lock()
+            .listeners
+            .retain(|(id, _, _)| *id != self.id);
+        // Refuse new connections, while keeping established ones.
+        self.endpoint.set_server_config(None);
+    }
+}
+
+/// An error of a [`GenQuicConfig`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// An I/O error of a UDP socket.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// A connection could not be initiated.
+    #[error(transparent)]
+    Connect(#[from] quinn::ConnectError),
+    /// A connection failed, e.g. during the handshake.
+    #[error(transparent)]
+    Connection(#[from] quinn::ConnectionError),
+    /// The remote did not present a valid libp2p certificate.
+    #[error(transparent)]
+    Certificate(#[from] ParseError),
+    /// The remote authenticated as a different peer than the one dialed.
+    #[error("Remote authenticated as an unexpected peer")]
+    UnexpectedPeerId,
+    /// The handshake did not complete in time.
+    #[error("Handshake with the remote timed out")]
+    HandshakeTimedOut,
+}
+
+/// Extracts the socket address of a QUIC multiaddress, together with the
+/// [`PeerId`] of a trailing `/p2p` component, if any.
+fn multiaddr_to_socketaddr(addr: &Multiaddr) -> Option<(SocketAddr, Option<PeerId>)> {
+    let mut iter = addr.iter();
+    let ip = match iter.next()? {
+        Protocol::Ip4(ip) => IpAddr::from(ip),
+        Protocol::Ip6(ip) => IpAddr::from(ip),
+        _ => return None,
+    };
+    let port = match iter.next()? {
+        Protocol::Udp(port) => port,
+        _ => return None,
+    };
+    match iter.next()? {
+        Protocol::Quic => {}
+        _ => return None,
+    }
+    let peer_id = match iter.next() {
+        Some(Protocol::P2p(hash)) => Some(PeerId::from_multihash(hash).ok()?),
+        None => None,
+        Some(_) => return None,
+    };
+    if iter.next().is_some() {
+        return None;
+    }
+
+    Some((SocketAddr::new(ip, port), peer_id))
+}
+
+/// Creates a QUIC multiaddress from a socket address.
+fn socketaddr_to_multiaddr(socket_addr: &SocketAddr) -> Multiaddr {
+    Multiaddr::empty()
+        .with(socket_addr.ip().into())
+        .with(Protocol::Udp(socket_addr.port()))
+        .with(Protocol::Quic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiaddr_to_socketaddr_conversion() {
+        assert!(
+            multiaddr_to_socketaddr(&"/ip4/127.0.0.1/udp/1234".parse::<Multiaddr>().unwrap())
+                .is_none()
+        );
+        assert!(multiaddr_to_socketaddr(
+            &"/ip4/127.0.0.1/tcp/1234/quic".parse::<Multiaddr>().unwrap()
+        )
+        .is_none());
+        assert!(multiaddr_to_socketaddr(
+            &"/ip4/127.0.0.1/udp/1234/quic/ws"
+                .parse::<Multiaddr>()
+                .unwrap()
+        )
+        .is_none());
+
+        assert_eq!(
+            multiaddr_to_socketaddr(&"/ip4/127.0.0.1/udp/1234/quic".parse::<Multiaddr>().unwrap()),
+            Some((
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1234),
+                None
+            ))
+        );
+        assert_eq!(
+            multiaddr_to_socketaddr(&"/ip6/::1/udp/1234/quic".parse::<Multiaddr>().unwrap()),
+            Some((SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 1234), None))
+        );
+
+        let peer_id = PeerId::random();
+        assert_eq!(
+            multiaddr_to_socketaddr(
+                &format!("/ip4/1.2.3.4/udp/1234/quic/p2p/{}", peer_id)
+                    .parse::<Multiaddr>()
+                    .unwrap()
+            ),
+            Some((
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 1234),
+                Some(peer_id)
+            ))
+        );
+    }
+
+    #[test]
+    fn socketaddr_to_multiaddr_roundtrip() {
+        let socket_addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 4242);
+        let addr = socketaddr_to_multiaddr(&socket_addr);
+
+        assert_eq!(addr, "/ip6/::1/udp/4242/quic".parse::<Multiaddr>().unwrap());
+        assert_eq!(multiaddr_to_socketaddr(&addr), Some((socket_addr, None)));
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::socketaddr_to_multiaddr;
+use futures::{
+    future::BoxFuture,
+    io::{AsyncRead, AsyncWrite},
+    FutureExt,
+};
+use libp2p_core::muxing::{StreamMuxer, StreamMuxerEvent};
+use parking_lot::Mutex;
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A future resolving to a bidirectional stream of a QUIC connection.
+type StreamFuture = BoxFuture<'static, Result<(quinn::SendStream, quinn::RecvStream), io::Error>>;
+
+/// The [`StreamMuxer`] of an established QUIC connection.
+///
+/// Substreams are bidirectional QUIC streams, which are multiplexed natively
+/// by the connection.
+pub struct QuicMuxer {
+    connection: quinn::Connection,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    /// The next inbound stream accepted from the remote.
+    incoming: StreamFuture,
+    /// The last known address of the remote.
+    remote_addr: SocketAddr,
+}
+
+/// A substream of a [`QuicMuxer`].
+pub struct Substream {
+    send: quinn::SendStream,
+    recv: quinn::RecvStream,
+}
+
+impl QuicMuxer {
+    pub(crate) fn new(connection: quinn::Connection) -> Self {
+        let inner = Inner {
+            incoming: accept_bi(connection.clone()),
+            remote_addr: connection.remote_address(),
+        };
+        QuicMuxer {
+            connection,
+            inner: Mutex::new(inner),
+        }
+    }
+}
+
+fn accept_bi(connection: quinn::Connection) -> StreamFuture {
+    async move { connection.accept_bi().await.map_err(io::Error::from) }.boxed()
+}
+
+impl StreamMuxer for QuicMuxer {
+    type Substream = Substream;
+    type OutboundSubstream = StreamFuture;
+    type Error = io::Error;
+
+    fn poll_event(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent<Self::Substream>, Self::Error>> {
+        let mut inner = self.inner.lock();
+
+        // The remote may migrate to a different address, e.g. after a NAT rebinding.
+        let remote_addr = self.connection.remote_address();
+        if remote_addr != inner.remote_addr {
+            inner.remote_addr = remote_addr;
+            return Poll::Ready(Ok(StreamMuxerEvent::AddressChange(
+                socketaddr_to_multiaddr(&remote_addr),
+            )));
+        }
+
+        let (send, recv) = futures::ready!(inner.incoming.poll_unpin(cx))?;
+        inner.incoming = accept_bi(self.connection.clone());
+        Poll::Ready(Ok(StreamMuxerEvent::InboundSubstream(Substream {
+            send,
+            recv,
+        })))
+    }
+
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        let connection = self.connection.clone();
+        async move { connection.open_bi().await.map_err(io::Error::from) }.boxed()
+    }
+
+    fn poll_outbound(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::OutboundSubstream,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        s.poll_unpin(cx)
+            .map_ok(|(send, recv)| Substream { send, recv })
+    }
+
+    fn destroy_outbound(&self, _: Self::OutboundSubstream) {}
+
+    fn read_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        Pin::new(&mut s.recv).poll_read(cx, buf)
+    }
+
+    fn write_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Self::Error>> {
+        Pin::new(&mut s.send).poll_write(cx, buf)
+    }
+
+    fn flush_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut s.send).poll_flush(cx)
+    }
+
+    fn shutdown_substream(
+        &self,
+        cx: &mut Context<'_>,
+        s: &mut Self::Substream,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut s.send).poll_close(cx)
+    }
+
+    fn destroy_substream(&self, _: Self::Substream) {}
+
+    fn close(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Closing is completed in the background by the endpoint, which
+        // informs the remote and retransmits the close frame if necessary.
+        self.connection.close(From::from(0u32), &[]);
+        Poll::Ready(Ok(()))
+    }
+
+    fn flush_all(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Data written to QUIC streams is transmitted by the endpoint without
+        // further flushing.
+        Poll::Ready(Ok(()))
+    }
+}
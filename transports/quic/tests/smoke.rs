@@ -0,0 +1,146 @@
+use futures::{future, prelude::*};
+use libp2p_core::{
+    identity,
+    multiaddr::{Multiaddr, Protocol},
+    muxing::{self, StreamMuxerBox},
+    transport::{ListenerEvent, Transport},
+    PeerId,
+};
+use libp2p_quic::QuicConfig;
+use std::sync::Arc;
+
+fn new_transport() -> (PeerId, QuicConfig) {
+    let keypair = identity::Keypair::generate_ed25519();
+    (keypair.public().to_peer_id(), QuicConfig::new(&keypair))
+}
+
+/// Listens on a random loopback port and returns the listener together with
+/// its listen address.
+async fn start_listening(
+    transport: QuicConfig,
+) -> (<QuicConfig as Transport>::Listener, Multiaddr) {
+    let mut listener = transport
+        .listen_on("/ip4/127.0.0.1/udp/0/quic".parse().unwrap())
+        .unwrap();
+    match listener.next().await.unwrap().unwrap() {
+        ListenerEvent::NewAddress(addr) => (listener, addr),
+        _ => panic!("Expected a new listen address"),
+    }
+}
+
+/// Accepts the next connection of the given listener.
+async fn accept(
+    listener: &mut <QuicConfig as Transport>::Listener,
+) -> (Multiaddr, PeerId, StreamMuxerBox) {
+    loop {
+        match listener.next().await.unwrap().unwrap() {
+            ListenerEvent::Upgrade {
+                upgrade,
+                remote_addr,
+                ..
+            } => {
+                let (peer_id, muxer) = upgrade.await.unwrap();
+                return (remote_addr, peer_id, muxer);
+            }
+            ListenerEvent::NewAddress(_) => {}
+            e => panic!("Unexpected listener event: {:?}", e.map(|_| ())),
+        }
+    }
+}
+
+#[async_std::test]
+async fn smoke() {
+    let (listener_id, listener_transport) = new_transport();
+    let (dialer_id, dialer_transport) = new_transport();
+
+    let (mut listener, addr) = start_listening(listener_transport).await;
+
+    let (dialed, (_, accepted_id, accepted)) =
+        future::join(dialer_transport.dial(addr).unwrap(), accept(&mut listener)).await;
+    let (dialed_id, dialed) = dialed.unwrap();
+    assert_eq!(dialed_id, listener_id);
+    assert_eq!(accepted_id, dialer_id);
+    assert_eq!(
+        dialed.negotiated_protocols().security.as_deref(),
+        Some("/tls/1.0.0")
+    );
+
+    let dialed = Arc::new(dialed);
+    let accepted = Arc::new(accepted);
+
+    let client = async {
+        let mut substream = muxing::outbound_from_ref_and_wrap(dialed.clone())
+            .await
+            .unwrap();
+        substream.write_all(b"ping").await.unwrap();
+        substream.close().await.unwrap();
+        let mut pong = Vec::new();
+        substream.read_to_end(&mut pong).await.unwrap();
+        pong
+    };
+    let server = async {
+        let mut substream = muxing::event_from_ref_and_wrap(accepted.clone())
+            .await
+            .unwrap()
+            .into_inbound_substream()
+            .unwrap();
+        let mut ping = Vec::new();
+        substream.read_to_end(&mut ping).await.unwrap();
+        substream.write_all(b"pong").await.unwrap();
+        substream.close().await.unwrap();
+        ping
+    };
+
+    let (pong, ping) = future::join(client, server).await;
+    assert_eq!(ping, b"ping");
+    assert_eq!(pong, b"pong");
+}
+
+#[async_std::test]
+async fn dial_with_wrong_peer_id_fails() {
+    let (_, listener_transport) = new_transport();
+    let (_, dialer_transport) = new_transport();
+
+    let (mut listener, addr) = start_listening(listener_transport).await;
+    async_std::task::spawn(async move { while listener.next().await.is_some() {} });
+
+    let addr = addr.with(Protocol::P2p(PeerId::random().into()));
+    assert!(dialer_transport.dial(addr).unwrap().await.is_err());
+}
+
+#[async_std::test]
+async fn dials_from_listening_port() {
+    let (_, listener_transport) = new_transport();
+    let (_, dialer_transport) = new_transport();
+
+    let (mut listener, addr) = start_listening(listener_transport).await;
+    let (_dialer_listener, dialer_addr) = start_listening(dialer_transport.clone()).await;
+
+    let (dialed, (remote_addr, _, _)) =
+        future::join(dialer_transport.dial(addr).unwrap(), accept(&mut listener)).await;
+    dialed.unwrap();
+
+    // The listener observes the listening port of the dialer.
+    assert_eq!(remote_addr, dialer_addr);
+}
+
+#[async_std::test]
+async fn observed_addresses_are_translated_only_for_listening_sockets() {
+    let (_, listener_transport) = new_transport();
+    let (_, dialer_transport) = new_transport();
+
+    let (mut listener, addr) = start_listening(listener_transport).await;
+    async_std::task::spawn(async move { while listener.next().await.is_some() {} });
+
+    // Without a listener, outgoing connections use an ephemeral port.
+    let observed: Multiaddr = "/ip4/127.0.0.1/udp/4242/quic".parse().unwrap();
+    assert_eq!(dialer_transport.address_translation(&addr, &observed), None);
+
+    let (_dialer_listener, dialer_addr) = start_listening(dialer_transport.clone()).await;
+    assert_eq!(
+        dialer_transport.address_translation(&dialer_addr, &observed),
+        Some(observed.clone())
+    );
+    // The observed address is not translated for other listen addresses.
+    assert_eq!(dialer_transport.address_translation(&addr, &observed), None);
+}
@@ -0,0 +1,354 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Generation and parsing of the self-signed certificates carrying the libp2p
+//! public key extension.
+
+use libp2p_core::identity::{self, error::DecodingError, error::SigningError};
+use libp2p_core::PeerId;
+use x509_parser::{oid_registry, prelude::*};
+
+/// The OID of the libp2p public key extension, registered by Protocol Labs.
+const P2P_EXT_OID: [u64; 9] = [1, 3, 6, 1, 4, 1, 53594, 1, 1];
+
+/// The prefix of the message signed with the libp2p identity key, which is
+/// followed by the DER-encoded `SubjectPublicKeyInfo` of the certificate.
+const P2P_SIGNING_PREFIX: [u8; 21] = *b"libp2p-tls-handshake:";
+
+/// The signature algorithm of the certificate key.
+///
+/// The specification allows any algorithm supported by TLS 1.3, ECDSA with
+/// the P-256 curve is supported by all implementations.
+static P2P_SIGNATURE_ALGORITHM: &rcgen::SignatureAlgorithm = &rcgen::PKCS_ECDSA_P256_SHA256;
+
+/// Generates a self-signed certificate with a fresh certificate key, carrying
+/// the libp2p public key extension signed by the given identity.
+pub fn generate(
+    identity_keypair: &identity::Keypair,
+) -> Result<(rustls::Certificate, rustls::PrivateKey), GenError> {
+    // The certificate key is deliberately not derived from the identity key.
+    let certificate_keypair = rcgen::KeyPair::generate(P2P_SIGNATURE_ALGORITHM)?;
+    let rustls_key = rustls::PrivateKey(certificate_keypair.serialize_der());
+
+    let certificate = {
+        let mut params = rcgen::CertificateParams::new(vec![]);
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        params.custom_extensions.push(make_libp2p_extension(
+            identity_keypair,
+            &certificate_keypair,
+        )?);
+        params.alg = P2P_SIGNATURE_ALGORITHM;
+        params.key_pair = Some(certificate_keypair);
+        rcgen::Certificate::from_params(params)?
+    };
+
+    let rustls_certificate = rustls::Certificate(certificate.serialize_der()?);
+
+    Ok((rustls_certificate, rustls_key))
+}
+
+/// Parses and verifies a certificate presented by a remote.
+///
+/// Verifies the validity period and self-signature of the certificate as well
+/// as the signature of the libp2p public key extension.
+pub fn parse(certificate: &rustls::Certificate) -> Result<P2pCertificate<'_>, ParseError> {
+    let certificate = parse_unverified(certificate.as_ref())?;
+
+    certificate.verify()?;
+
+    Ok(certificate)
+}
+
+/// A certificate carrying the libp2p public key extension.
+pub struct P2pCertificate<'a> {
+    certificate: X509Certificate<'a>,
+    extension: P2pExtension,
+}
+
+/// The content of the libp2p public key extension.
+struct P2pExtension {
+    /// The libp2p identity of the remote.
+    public_key: identity::PublicKey,
+    /// The signature of the certificate key with the identity key.
+    signature: Vec<u8>,
+}
+
+impl P2pCertificate<'_> {
+    /// The [`PeerId`] of the remote authenticated by the certificate.
+    pub fn peer_id(&self) -> PeerId {
+        self.extension.public_key.to_peer_id()
+    }
+
+    /// Verifies the signature of `message` with the certificate key.
+    pub fn verify_signature(
+        &self,
+        signature_scheme: rustls::SignatureScheme,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), VerificationError> {
+        use ring::signature;
+        use rustls::SignatureScheme::*;
+
+        let algorithm: &dyn signature::VerificationAlgorithm = match signature_scheme {
+            RSA_PKCS1_SHA256 => &signature::RSA_PKCS1_2048_8192_SHA256,
+            RSA_PKCS1_SHA384 => &signature::RSA_PKCS1_2048_8192_SHA384,
+            RSA_PKCS1_SHA512 => &signature::RSA_PKCS1_2048_8192_SHA512,
+            ECDSA_NISTP256_SHA256 => &signature::ECDSA_P256_SHA256_ASN1,
+            ECDSA_NISTP384_SHA384 => &signature::ECDSA_P384_SHA384_ASN1,
+            RSA_PSS_SHA256 => &signature::RSA_PSS_2048_8192_SHA256,
+            RSA_PSS_SHA384 => &signature::RSA_PSS_2048_8192_SHA384,
+            RSA_PSS_SHA512 => &signature::RSA_PSS_2048_8192_SHA512,
+            ED25519 => &signature::ED25519,
+            scheme => return Err(VerificationError::UnsupportedSignatureScheme(scheme)),
+        };
+        let public_key = &self.certificate.public_key().subject_public_key;
+
+        signature::UnparsedPublicKey::new(algorithm, public_key.data.as_ref())
+            .verify(message, signature)
+            .map_err(|_| VerificationError::InvalidSignature)
+    }
+
+    /// Verifies the validity period, the self-signature and the signature of
+    /// the libp2p public key extension.
+    fn verify(&self) -> Result<(), VerificationError> {
+        if !self.certificate.validity().is_valid() {
+            return Err(VerificationError::Expired);
+        }
+
+        self.verify_signature(
+            self.signature_scheme()?,
+            self.certificate.tbs_certificate.as_ref(),
+            self.certificate.signature_value.data.as_ref(),
+        )?;
+
+        let mut msg = P2P_SIGNING_PREFIX.to_vec();
+        msg.extend(self.certificate.public_key().raw);
+        if !self
+            .extension
+            .public_key
+            .verify(&msg, &self.extension.signature)
+        {
+            return Err(VerificationError::InvalidExtensionSignature);
+        }
+
+        Ok(())
+    }
+
+    /// The signature scheme of the self-signature of the certificate.
+    fn signature_scheme(&self) -> Result<rustls::SignatureScheme, VerificationError> {
+        use oid_registry::*;
+        use rustls::SignatureScheme::*;
+
+        let algorithm = &self.certificate.signature_algorithm.algorithm;
+        let scheme = if *algorithm == OID_SIG_ECDSA_WITH_SHA256 {
+            ECDSA_NISTP256_SHA256
+        } else if *algorithm == OID_SIG_ECDSA_WITH_SHA384 {
+            ECDSA_NISTP384_SHA384
+        } else if *algorithm == OID_SIG_ED25519 {
+            ED25519
+        } else if *algorithm == OID_PKCS1_SHA256WITHRSA {
+            RSA_PKCS1_SHA256
+        } else if *algorithm == OID_PKCS1_SHA384WITHRSA {
+            RSA_PKCS1_SHA384
+        } else if *algorithm == OID_PKCS1_SHA512WITHRSA {
+            RSA_PKCS1_SHA512
+        } else {
+            return Err(VerificationError::UnsupportedSignatureAlgorithm(
+                algorithm.to_id_string(),
+            ));
+        };
+
+        Ok(scheme)
+    }
+}
+
+fn make_libp2p_extension(
+    identity_keypair: &identity::Keypair,
+    certificate_keypair: &rcgen::KeyPair,
+) -> Result<rcgen::CustomExtension, GenError> {
+    let signature = {
+        let mut msg = P2P_SIGNING_PREFIX.to_vec();
+        msg.extend(certificate_keypair.public_key_der());
+
+        identity_keypair.sign(&msg)?
+    };
+
+    // SignedKey ::= SEQUENCE {
+    //    publicKey OCTET STRING,
+    //    signature OCTET STRING
+    // }
+    let extension_content = {
+        let serialized_public_key = identity_keypair.public().to_protobuf_encoding();
+        yasna::encode_der(&(serialized_public_key, signature))
+    };
+
+    // The extension must be marked critical, such that implementations not
+    // aware of it reject the certificate.
+    let mut extension = rcgen::CustomExtension::from_oid_content(&P2P_EXT_OID, extension_content);
+    extension.set_criticality(true);
+
+    Ok(extension)
+}
+
+fn parse_unverified(der_input: &[u8]) -> Result<P2pCertificate<'_>, ParseError> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(der_input)?;
+
+    let p2p_ext_oid = x509_parser::der_parser::oid::Oid::from(&P2P_EXT_OID)
+        .expect("The OID is a valid sequence of arcs; qed");
+
+    let mut libp2p_extension = None;
+
+    for ext in certificate.extensions() {
+        if ext.oid == p2p_ext_oid {
+            if libp2p_extension.is_some() {
+                return Err(ParseError::DuplicateExtension);
+            }
+
+            let (public_key, signature): (Vec<u8>, Vec<u8>) =
+                yasna::decode_der(ext.value).map_err(ParseError::InvalidExtension)?;
+            let public_key = identity::PublicKey::from_protobuf_encoding(&public_key)
+                .map_err(ParseError::InvalidPublicKey)?;
+            libp2p_extension = Some(P2pExtension {
+                public_key,
+                signature,
+            });
+            continue;
+        }
+
+        if ext.critical {
+            // Critical extensions other than the libp2p one must be
+            // understood, which this implementation does not.
+            return Err(ParseError::UnsupportedCriticalExtension(
+                ext.oid.to_id_string(),
+            ));
+        }
+    }
+
+    let extension = libp2p_extension.ok_or(ParseError::MissingExtension)?;
+
+    Ok(P2pCertificate {
+        certificate,
+        extension,
+    })
+}
+
+/// An error that can happen when generating a certificate.
+#[derive(Debug, thiserror::Error)]
+pub enum GenError {
+    #[error("Failed to generate certificate")]
+    Certificate(#[from] rcgen::RcgenError),
+    #[error("Failed to sign the certificate key with the identity key")]
+    Signing(#[from] SigningError),
+}
+
+/// An error that can happen when parsing the certificate of a remote.
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("Invalid DER encoding of the certificate")]
+    Der(#[from] x509_parser::nom::Err<X509Error>),
+    #[error("Expected exactly one certificate, got {0}")]
+    NumberOfCertificates(usize),
+    #[error("Missing libp2p public key extension")]
+    MissingExtension,
+    #[error("Duplicate libp2p public key extension")]
+    DuplicateExtension,
+    #[error("Unsupported critical extension {0}")]
+    UnsupportedCriticalExtension(String),
+    #[error("Invalid encoding of the libp2p public key extension")]
+    InvalidExtension(#[source] yasna::ASN1Error),
+    #[error("Invalid public key in the libp2p public key extension")]
+    InvalidPublicKey(#[source] DecodingError),
+    #[error("Certificate verification failed")]
+    Verification(#[from] VerificationError),
+}
+
+/// An error that can happen when verifying the certificate of a remote or a
+/// signature made with its certificate key.
+#[derive(Debug, thiserror::Error)]
+pub enum VerificationError {
+    #[error("Certificate is not valid at the current time")]
+    Expired,
+    #[error("Unsupported signature scheme {0:?}")]
+    UnsupportedSignatureScheme(rustls::SignatureScheme),
+    #[error("Unsupported signature algorithm {0}")]
+    UnsupportedSignatureAlgorithm(String),
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Invalid signature of the libp2p public key extension")]
+    InvalidExtensionSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypairs() -> Vec<identity::Keypair> {
+        let mut rsa = include_bytes!("../../../core/src/identity/test/rsa-2048.pk8").to_vec();
+        vec![
+            identity::Keypair::generate_ed25519(),
+            identity::Keypair::generate_secp256k1(),
+            identity::Keypair::generate_ecdsa(),
+            identity::Keypair::rsa_from_pkcs8(&mut rsa).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn generated_certificates_verify() {
+        for keypair in keypairs() {
+            let (certificate, _) = generate(&keypair).unwrap();
+            let parsed = parse(&certificate).unwrap();
+
+            assert_eq!(parsed.peer_id(), keypair.public().to_peer_id());
+        }
+    }
+
+    #[test]
+    fn tampered_certificates_are_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let (certificate, _) = generate(&keypair).unwrap();
+
+        // Flip a bit in the middle of the certificate, which either breaks the
+        // encoding or invalidates one of the signatures.
+        let mut der = certificate.0;
+        let i = der.len() / 2;
+        der[i] ^= 0x01;
+
+        assert!(parse(&rustls::Certificate(der)).is_err());
+    }
+
+    #[test]
+    fn foreign_extension_signature_is_rejected() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let (certificate, _) = generate(&keypair).unwrap();
+        let other = identity::Keypair::generate_ed25519();
+        let (other_certificate, _) = generate(&other).unwrap();
+
+        // The extension of one certificate is not valid for the key of another.
+        let mut parsed = parse_unverified(certificate.as_ref()).unwrap();
+        parsed.extension = parse_unverified(other_certificate.as_ref())
+            .unwrap()
+            .extension;
+
+        assert!(matches!(
+            parsed.verify(),
+            Err(VerificationError::InvalidExtensionSignature)
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the [libp2p TLS handshake](https://github.com/libp2p/specs/blob/master/tls/tls.md).
+//!
+//! Peers authenticate each other with self-signed X.509 certificates
+//! carrying a libp2p public key extension. The extension contains the public
+//! key of the libp2p identity of the peer, together with a signature of the
+//! certificate key made with the identity key.
+//!
+//! [`TlsConfig`] implements the [`InboundUpgrade`](libp2p_core::InboundUpgrade)
+//! and [`OutboundUpgrade`](libp2p_core::OutboundUpgrade) traits, yielding the
+//! [`PeerId`] of the remote and a [`TlsStream`], e.g.:
+//!
+//! ```
+//! use libp2p_core::{identity, transport::MemoryTransport, upgrade, Transport};
+//! use libp2p_tls::TlsConfig;
+//!
+//! # fn main() {
+//! let id_keys = identity::Keypair::generate_ed25519();
+//! let tls = TlsConfig::new(&id_keys).unwrap();
+//! let builder = MemoryTransport
+//!     .upgrade(upgrade::Version::V1)
+//!     .authenticate(tls);
+//! // let transport = builder.multiplex(...);
+//! # }
+//! ```
+//!
+//! The TLS configurations created by [`make_client_config`] and
+//! [`make_server_config`] can be used by transports with a built-in TLS
+//! handshake, e.g. QUIC.
+
+pub mod certificate;
+mod upgrade;
+mod verifier;
+
+pub use futures_rustls::TlsStream;
+pub use upgrade::{TlsConfig, UpgradeError};
+
+use certificate::GenError;
+use libp2p_core::{identity, PeerId};
+use std::sync::Arc;
+
+/// The server name sent in the client hello.
+///
+/// The libp2p TLS specification does not make use of SNI, the identity of the
+/// remote is authenticated via the certificate extension only.
+const SERVER_NAME: &str = "l";
+
+/// Creates the TLS configuration of a dialer, authenticating as the given
+/// identity.
+///
+/// If `remote_peer_id` is given, the handshake fails unless the remote
+/// authenticates as that peer.
+pub fn make_client_config(
+    keypair: &identity::Keypair,
+    remote_peer_id: Option<PeerId>,
+) -> Result<rustls::ClientConfig, GenError> {
+    let (certificate, private_key) = certificate::generate(keypair)?;
+
+    let crypto = rustls::ClientConfig::builder()
+        .with_cipher_suites(verifier::CIPHERSUITES)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(verifier::PROTOCOL_VERSIONS)
+        .expect("Cipher suites and kx groups are configured; qed")
+        .with_custom_certificate_verifier(Arc::new(
+            verifier::Libp2pCertificateVerifier::with_remote_peer_id(remote_peer_id),
+        ))
+        .with_single_cert(vec![certificate], private_key)
+        .expect("Client cert key DER is valid; qed");
+
+    Ok(crypto)
+}
+
+/// Creates the TLS configuration of a listener, authenticating as the given
+/// identity.
+pub fn make_server_config(keypair: &identity::Keypair) -> Result<rustls::ServerConfig, GenError> {
+    let (certificate, private_key) = certificate::generate(keypair)?;
+
+    let crypto = rustls::ServerConfig::builder()
+        .with_cipher_suites(verifier::CIPHERSUITES)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(verifier::PROTOCOL_VERSIONS)
+        .expect("Cipher suites and kx groups are configured; qed")
+        .with_client_cert_verifier(Arc::new(verifier::Libp2pCertificateVerifier::new()))
+        .with_single_cert(vec![certificate], private_key)
+        .expect("Server cert key DER is valid; qed");
+
+    Ok(crypto)
+}
@@ -0,0 +1,134 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::certificate::{self, GenError, ParseError};
+use futures::future::BoxFuture;
+use futures::{AsyncRead, AsyncWrite, FutureExt};
+use futures_rustls::TlsStream;
+use libp2p_core::{identity, InboundUpgrade, OutboundUpgrade, PeerId, UpgradeInfo};
+use rustls::{CommonState, ServerName};
+use std::{io, iter, sync::Arc};
+
+/// Errors that can occur during the TLS upgrade.
+#[derive(Debug, thiserror::Error)]
+pub enum UpgradeError {
+    #[error("Failed to upgrade server connection: {0}")]
+    ServerUpgrade(io::Error),
+    #[error("Failed to upgrade client connection: {0}")]
+    ClientUpgrade(io::Error),
+    #[error("Failed to parse certificate: {0}")]
+    BadCertificate(#[from] ParseError),
+}
+
+/// Upgrade securing a connection with TLS 1.3 and authenticating both
+/// endpoints via their libp2p identity.
+#[derive(Clone)]
+pub struct TlsConfig {
+    server: Arc<rustls::ServerConfig>,
+    client: Arc<rustls::ClientConfig>,
+}
+
+impl TlsConfig {
+    /// Creates a new upgrade authenticating as the given identity.
+    ///
+    /// The certificate presented to remotes is generated once and shared by
+    /// all connections.
+    pub fn new(keypair: &identity::Keypair) -> Result<Self, GenError> {
+        Ok(Self {
+            server: Arc::new(crate::make_server_config(keypair)?),
+            client: Arc::new(crate::make_client_config(keypair, None)?),
+        })
+    }
+}
+
+impl UpgradeInfo for TlsConfig {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/tls/1.0.0")
+    }
+}
+
+impl<C> InboundUpgrade<C> for TlsConfig
+where
+    C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = (PeerId, TlsStream<C>);
+    type Error = UpgradeError;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: C, _: Self::Info) -> Self::Future {
+        async move {
+            let stream = futures_rustls::TlsAcceptor::from(self.server)
+                .accept(socket)
+                .await
+                .map_err(UpgradeError::ServerUpgrade)?;
+
+            let peer_id = extract_single_certificate(stream.get_ref().1)?.peer_id();
+
+            Ok((peer_id, stream.into()))
+        }
+        .boxed()
+    }
+}
+
+impl<C> OutboundUpgrade<C> for TlsConfig
+where
+    C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = (PeerId, TlsStream<C>);
+    type Error = UpgradeError;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_outbound(self, socket: C, _: Self::Info) -> Self::Future {
+        async move {
+            let name = ServerName::try_from(crate::SERVER_NAME)
+                .expect("The server name is a valid DNS name; qed");
+
+            let stream = futures_rustls::TlsConnector::from(self.client)
+                .connect(name, socket)
+                .await
+                .map_err(UpgradeError::ClientUpgrade)?;
+
+            let peer_id = extract_single_certificate(stream.get_ref().1)?.peer_id();
+
+            Ok((peer_id, stream.into()))
+        }
+        .boxed()
+    }
+}
+
+/// Extracts the libp2p certificate of the remote from the handshake state.
+///
+/// The certificate has already been verified by our
+/// [`Libp2pCertificateVerifier`](crate::verifier::Libp2pCertificateVerifier),
+/// thus parsing it again cannot fail unless the remote presented more than one
+/// certificate.
+fn extract_single_certificate(
+    state: &CommonState,
+) -> Result<certificate::P2pCertificate<'_>, ParseError> {
+    let certificates = state.peer_certificates().unwrap_or_default();
+
+    match certificates {
+        [certificate] => certificate::parse(certificate),
+        _ => Err(ParseError::NumberOfCertificates(certificates.len())),
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2022 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::{future, AsyncReadExt, AsyncWriteExt, StreamExt};
+use libp2p_core::{
+    identity,
+    multiaddr::Protocol,
+    transport::{MemoryTransport, Transport},
+    upgrade, PeerId,
+};
+use libp2p_tls::TlsConfig;
+
+fn rsa_keypair() -> identity::Keypair {
+    let mut pkcs8 = include_bytes!("../../../core/src/identity/test/rsa-2048.pk8").to_vec();
+    identity::Keypair::rsa_from_pkcs8(&mut pkcs8).unwrap()
+}
+
+async fn handshake(listener_keys: identity::Keypair, dialer_keys: identity::Keypair) {
+    let listener_id = PeerId::from(listener_keys.public());
+    let dialer_id = PeerId::from(dialer_keys.public());
+
+    let listener_config = TlsConfig::new(&listener_keys).unwrap();
+    let dialer_config = TlsConfig::new(&dialer_keys).unwrap();
+
+    let transport = MemoryTransport;
+    let mut listener = transport
+        .listen_on(Protocol::Memory(rand::random::<u64>().max(1)).into())
+        .unwrap();
+    let addr = listener
+        .next()
+        .await
+        .unwrap()
+        .unwrap()
+        .into_new_address()
+        .unwrap();
+
+    let server = async move {
+        let (upgrade, _) = listener
+            .next()
+            .await
+            .unwrap()
+            .unwrap()
+            .into_upgrade()
+            .unwrap();
+        let socket = upgrade.await.unwrap();
+        let (remote, mut stream) = upgrade::apply_inbound(socket, listener_config)
+            .await
+            .unwrap();
+        assert_eq!(remote, dialer_id);
+
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        stream.write_all(b"pong").await.unwrap();
+        stream.close().await.unwrap();
+    };
+
+    let client = async move {
+        let socket = transport.dial(addr).unwrap().await.unwrap();
+        let (remote, mut stream) =
+            upgrade::apply_outbound(socket, dialer_config, upgrade::Version::V1)
+                .await
+                .unwrap();
+        assert_eq!(remote, listener_id);
+
+        stream.write_all(b"ping").await.unwrap();
+        stream.flush().await.unwrap();
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+    };
+
+    future::join(server, client).await;
+}
+
+#[async_std::test]
+async fn ed25519() {
+    handshake(
+        identity::Keypair::generate_ed25519(),
+        identity::Keypair::generate_ed25519(),
+    )
+    .await;
+}
+
+#[async_std::test]
+async fn secp256k1() {
+    handshake(
+        identity::Keypair::generate_secp256k1(),
+        identity::Keypair::generate_secp256k1(),
+    )
+    .await;
+}
+
+#[async_std::test]
+async fn ecdsa() {
+    handshake(
+        identity::Keypair::generate_ecdsa(),
+        identity::Keypair::generate_ecdsa(),
+    )
+    .await;
+}
+
+#[async_std::test]
+async fn rsa() {
+    handshake(rsa_keypair(), identity::Keypair::generate_ed25519()).await;
+}
+
+#[async_std::test]
+async fn mixed_key_types() {
+    handshake(
+        identity::Keypair::generate_secp256k1(),
+        identity::Keypair::generate_ecdsa(),
+    )
+    .await;
+}